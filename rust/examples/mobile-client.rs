@@ -8,16 +8,7 @@ use xaynet_client::mobile_client::{
     participant::{AggregationConfig, ParticipantSettings},
     MobileClient,
 };
-use xaynet_core::mask::{
-    BoundType,
-    DataType,
-    FromPrimitives,
-    GroupType,
-    IntoPrimitives,
-    MaskConfig,
-    Model,
-    ModelType,
-};
+use xaynet_core::mask::{FromPrimitives, IntoPrimitives, Model};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "Test Drive")]
@@ -46,13 +37,8 @@ fn get_participant_settings() -> ParticipantSettings {
     ParticipantSettings {
         secret_key,
         aggregation_config: AggregationConfig {
-            mask: MaskConfig {
-                group_type: GroupType::Prime,
-                data_type: DataType::F32,
-                bound_type: BoundType::B0,
-                model_type: ModelType::M3,
-            },
             scalar: 1_f64,
+            privacy: None,
         },
     }
 }
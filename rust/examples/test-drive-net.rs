@@ -1,11 +1,13 @@
 #[macro_use]
 extern crate tracing;
 
+use std::time::Duration;
+
 use structopt::StructOpt;
-use tokio::signal;
+use tokio::{signal, task::JoinHandle};
 use tracing_subscriber::*;
 use xaynet_client::{
-    api::{HttpApiClient, HttpApiClientError},
+    api::{ApiClient, HttpApiClient, HttpApiClientError, SseApiClient},
     Client,
     ClientError,
 };
@@ -30,6 +32,11 @@ struct Opt {
     period: u64,
     #[structopt(default_value = "10", short, help = "The number of clients")]
     nb_client: u32,
+    #[structopt(
+        long,
+        help = "Subscribe to the coordinator events instead of only polling for service data"
+    )]
+    events: bool,
 }
 
 /// Test-drive script of a (local, but networked) federated
@@ -50,16 +57,15 @@ async fn main() -> Result<(), ClientError<HttpApiClientError>> {
 
     let mut clients = Vec::with_capacity(opt.nb_client as usize);
     for id in 0..opt.nb_client {
-        let mut client = Client::new(opt.period, id, HttpApiClient::new(&opt.url))?;
-        client.local_model = Some(model.clone());
-        let join_hdl = tokio::spawn(async move {
-            tokio::select! {
-                _ = signal::ctrl_c() => {}
-                result = client.start() => {
-                    error!("{:?}", result);
-                }
-            }
-        });
+        let join_hdl = if opt.events {
+            let api = SseApiClient::new(&opt.url, Duration::from_secs(opt.period));
+            spawn_client(Client::new(opt.period, id, api, None)?, model.clone())
+        } else {
+            spawn_client(
+                Client::new(opt.period, id, HttpApiClient::new(&opt.url), None)?,
+                model.clone(),
+            )
+        };
         clients.push(join_hdl);
     }
 
@@ -70,3 +76,20 @@ async fn main() -> Result<(), ClientError<HttpApiClientError>> {
 
     Ok(())
 }
+
+/// Runs the client with the given local model until it fails or ctrl-c is pressed.
+fn spawn_client<C>(mut client: Client<C>, model: Model) -> JoinHandle<()>
+where
+    C: ApiClient + Send + Sync + 'static,
+    C::Error: Send,
+{
+    client.local_model = Some(model);
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = signal::ctrl_c() => {}
+            result = client.start() => {
+                error!("{:?}", result);
+            }
+        }
+    })
+}
@@ -2,7 +2,7 @@ use crate::api::ApiClient;
 use reqwest::{self, Client, Response, StatusCode};
 use thiserror::Error;
 use xaynet_core::{
-    common::RoundParameters,
+    common::{MessageError, RoundParameters},
     crypto::ByteObject,
     mask::Model,
     SumDict,
@@ -43,6 +43,17 @@ pub enum HttpApiClientError {
 
     #[error("Unexpected response from the coordinator: {:?}", .0)]
     UnexpectedResponse(Response),
+
+    #[error("the coordinator rejected the message ({status}): {}", .error.message)]
+    MessageRejected {
+        /// The status code of the response
+        status: StatusCode,
+        /// The reason why the message was rejected
+        error: MessageError,
+    },
+
+    #[error("Unexpected status code from the coordinator: {0}")]
+    UnexpectedStatus(StatusCode),
 }
 
 impl From<bincode::Error> for HttpApiClientError {
@@ -133,12 +144,32 @@ impl ApiClient for HttpApiClient {
 
     async fn send_message(&mut self, msg: Vec<u8>) -> Result<(), Self::Error> {
         let url = format!("{}/message", self.address);
-        self.client
-            .post(&url)
-            .body(msg)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(())
+        let resp = self.client.post(&url).body(msg).send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        // the coordinator describes why it rejected the message in a
+        // JSON encoded `MessageError`
+        let body = resp.bytes().await?;
+        match serde_json::from_slice(&body[..]) {
+            Ok(error) => Err(HttpApiClientError::MessageRejected { status, error }),
+            Err(_) => Err(HttpApiClientError::UnexpectedStatus(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xaynet_core::common::MessageErrorKind;
+
+    #[test]
+    fn deserialize_message_error() {
+        let body =
+            br#"{"error":"not_sum_eligible","message":"participant is not eligible for sum task"}"#;
+        let error: MessageError = serde_json::from_slice(&body[..]).unwrap();
+        assert_eq!(error.kind, MessageErrorKind::NotSumEligible);
+        assert_eq!(error.message, "participant is not eligible for sum task");
     }
 }
@@ -3,6 +3,9 @@
 mod http;
 pub use self::http::{HttpApiClient, HttpApiClientError};
 
+mod sse;
+pub use self::sse::SseApiClient;
+
 use xaynet_core::{
     common::RoundParameters,
     mask::Model,
@@ -0,0 +1,342 @@
+use std::time::Duration;
+
+use bytes::{Buf, BytesMut};
+use reqwest::{self, Client, Response, StatusCode};
+use tokio::time::{self, Instant};
+use xaynet_core::{
+    common::RoundParameters,
+    mask::Model,
+    SumDict,
+    SumParticipantPublicKey,
+    UpdateSeedDict,
+};
+
+use crate::api::{ApiClient, HttpApiClient, HttpApiClientError};
+
+#[derive(Debug)]
+/// A client that communicates with the coordinator's API via HTTP(S)
+/// and is notified about new data via Server-Sent Events
+///
+/// Instead of returning immediately if the requested data is not
+/// available yet, the client waits until the coordinator announces
+/// the data on its `/events` stream and fetches it again. Hence, the
+/// data is usually fetched within milliseconds after it became
+/// available. If the stream is not available, the client behaves
+/// like an [`HttpApiClient`].
+pub struct SseApiClient {
+    /// HTTP client for fetching the data
+    http: HttpApiClient,
+    /// Subscription to the coordinator events
+    events: EventSubscription,
+    /// The round parameters which were fetched last
+    round_params: Option<RoundParameters>,
+}
+
+impl SseApiClient {
+    /// Creates a new client for the coordinator at the given address.
+    ///
+    /// * `timeout`: the maximum time to wait for an event, after
+    ///   which a request returns without the new data.
+    pub fn new<S>(address: S, timeout: Duration) -> Self
+    where
+        S: Into<String>,
+    {
+        let address = address.into();
+        Self {
+            http: HttpApiClient::new(address.clone()),
+            events: EventSubscription::new(address, timeout),
+            round_params: None,
+        }
+    }
+}
+
+#[async_trait]
+impl ApiClient for SseApiClient {
+    type Error = HttpApiClientError;
+
+    async fn get_round_params(&mut self) -> Result<RoundParameters, Self::Error> {
+        self.events.skip_pending().await;
+        let mut round_params = self.http.get_round_params().await?;
+        // the round parameters are always available, hence wait if
+        // they didn't change since the last request
+        if self.round_params.as_ref() == Some(&round_params) {
+            self.events.wait_for("params").await;
+            round_params = self.http.get_round_params().await?;
+        }
+        self.round_params = Some(round_params.clone());
+        Ok(round_params)
+    }
+
+    async fn get_sums(&mut self) -> Result<Option<SumDict>, Self::Error> {
+        self.events.skip_pending().await;
+        if let Some(sums) = self.http.get_sums().await? {
+            return Ok(Some(sums));
+        }
+        self.events.wait_for("sums").await;
+        self.http.get_sums().await
+    }
+
+    async fn get_seeds(
+        &mut self,
+        pk: SumParticipantPublicKey,
+    ) -> Result<Option<UpdateSeedDict>, Self::Error> {
+        self.events.skip_pending().await;
+        if let Some(seeds) = self.http.get_seeds(pk).await? {
+            return Ok(Some(seeds));
+        }
+        self.events.wait_for("seeds").await;
+        self.http.get_seeds(pk).await
+    }
+
+    async fn get_mask_length(&mut self) -> Result<Option<u64>, Self::Error> {
+        self.events.skip_pending().await;
+        if let Some(length) = self.http.get_mask_length().await? {
+            return Ok(Some(length));
+        }
+        self.events.wait_for("length").await;
+        self.http.get_mask_length().await
+    }
+
+    async fn get_model(&mut self) -> Result<Option<Model>, Self::Error> {
+        // don't wait for a new model, since the client is only
+        // interested in the latest one
+        self.http.get_model().await
+    }
+
+    async fn send_message(&mut self, msg: Vec<u8>) -> Result<(), Self::Error> {
+        self.http.send_message(msg).await
+    }
+}
+
+#[derive(Debug)]
+/// A subscription to the coordinator's `/events` stream
+///
+/// The subscription is (re-)established lazily whenever events are
+/// awaited. After a failed attempt, the events are not awaited until
+/// the timeout elapsed.
+struct EventSubscription {
+    /// HTTP client
+    client: Client,
+    /// Coordinator URL
+    address: String,
+    /// The maximum time to wait for an event
+    timeout: Duration,
+    /// The streaming response, if subscribed
+    response: Option<Response>,
+    /// The earliest time to subscribe again after a failed attempt
+    resubscribe_at: Instant,
+    /// Received data which doesn't form a complete event yet
+    buffer: BytesMut,
+    /// Whether the announcement of the current round parameters, with
+    /// which the coordinator starts a new event stream, is still to be
+    /// skipped
+    skip_params_snapshot: bool,
+}
+
+impl EventSubscription {
+    fn new(address: String, timeout: Duration) -> Self {
+        Self {
+            client: Client::new(),
+            address,
+            timeout,
+            response: None,
+            resubscribe_at: Instant::now(),
+            buffer: BytesMut::new(),
+            skip_params_snapshot: false,
+        }
+    }
+
+    /// Discards the events which were already received.
+    ///
+    /// Data which is fetched afterwards is at least as recent as
+    /// these events, hence waiting only considers subsequent events.
+    /// If the event stream is opened here, the announcement of the
+    /// current round parameters at its start is discarded as well,
+    /// since it announces the round parameters which are fetched
+    /// afterwards.
+    async fn skip_pending(&mut self) {
+        if self.response.is_none() && Instant::now() >= self.resubscribe_at {
+            self.open().await;
+            self.skip_params_snapshot = self.response.is_some();
+        }
+        while self.next_event(Instant::now()).await.is_some() {}
+    }
+
+    /// Waits until an event with the given name is received or the
+    /// timeout elapses.
+    async fn wait_for(&mut self, name: &str) {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match self.next_event(deadline).await {
+                Some(Some(event)) if event == name => return,
+                Some(_) => continue,
+                None => return,
+            }
+        }
+    }
+
+    /// Receives data from the event stream until the deadline.
+    ///
+    /// Returns the name of the next complete event, `Some(None)` if
+    /// the received data doesn't contain a named event, or `None` if
+    /// nothing was received.
+    async fn next_event(&mut self, deadline: Instant) -> Option<Option<String>> {
+        if let Some(event) = self.take_event() {
+            return Some(event);
+        }
+        if self.response.is_none() && Instant::now() >= self.resubscribe_at {
+            self.open().await;
+            self.skip_params_snapshot = false;
+        }
+        let response = self.response.as_mut()?;
+        match time::timeout_at(deadline, response.chunk()).await {
+            Ok(Ok(Some(chunk))) => {
+                self.buffer.extend_from_slice(chunk.bytes());
+                Some(self.take_event().flatten())
+            }
+            Ok(Ok(None)) | Ok(Err(_)) => {
+                debug!("the coordinator closed the event stream");
+                self.response = None;
+                self.buffer.clear();
+                None
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Takes the first complete event from the buffer and returns its
+    /// name, see [`take_event`]. The skipped announcement of the round
+    /// parameters yields `Some(None)`.
+    fn take_event(&mut self) -> Option<Option<String>> {
+        let name = take_event(&mut self.buffer)?;
+        if self.skip_params_snapshot && name.as_deref() == Some("params") {
+            self.skip_params_snapshot = false;
+            return Some(None);
+        }
+        Some(name)
+    }
+
+    /// Opens the event stream. After a failed attempt, the stream is
+    /// not opened again until the timeout elapsed.
+    async fn open(&mut self) {
+        self.response = self.subscribe().await;
+        if self.response.is_none() {
+            self.resubscribe_at = Instant::now() + self.timeout;
+        }
+    }
+
+    /// Subscribes to the event stream.
+    async fn subscribe(&self) -> Option<Response> {
+        let url = format!("{}/events", self.address);
+        let response = self
+            .client
+            .get(&url)
+            .header("Accept", "text/event-stream")
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status() == StatusCode::OK => Some(response),
+            Ok(response) => {
+                warn!(
+                    "failed to subscribe to the coordinator events: {}",
+                    response.status()
+                );
+                None
+            }
+            Err(err) => {
+                warn!("failed to subscribe to the coordinator events: {}", err);
+                None
+            }
+        }
+    }
+}
+
+/// Takes the first complete event from the buffer and returns its
+/// name.
+///
+/// Events are separated by an empty line, where lines end with
+/// `\r\n`, `\n` or `\r`. Events without a name, like keep-alive
+/// comments, yield `Some(None)`.
+fn take_event(buffer: &mut BytesMut) -> Option<Option<String>> {
+    let end = event_end(buffer)?;
+    let event = buffer.split_to(end);
+    let name = String::from_utf8_lossy(&event)
+        .split(['\r', '\n'].as_ref())
+        .find_map(|line| line.strip_prefix("event:"))
+        .map(|name| name.trim().to_string());
+    Some(name)
+}
+
+/// Gets the end of the first complete event in the buffer, i.e. the
+/// position after the line terminator of its empty line.
+fn event_end(buffer: &[u8]) -> Option<usize> {
+    let mut line_is_empty = true;
+    let mut pos = 0;
+    while pos < buffer.len() {
+        pos += match (buffer[pos], buffer.get(pos + 1)) {
+            // the line feed of a `\r\n` terminator may not be received yet
+            (b'\r', None) => return None,
+            (b'\r', Some(b'\n')) => 2,
+            (b'\r', _) | (b'\n', _) => 1,
+            _ => {
+                line_is_empty = false;
+                pos += 1;
+                continue;
+            }
+        };
+        if line_is_empty {
+            return Some(pos);
+        }
+        line_is_empty = true;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_events_from_buffer() {
+        let mut buffer = BytesMut::from(
+            &b"event:phase\ndata:{\"round_id\":1,\"phase\":\"Sum\"}\n\n:\n\nevent:sums\nda"[..],
+        );
+        assert_eq!(take_event(&mut buffer), Some(Some("phase".to_string())));
+        assert_eq!(take_event(&mut buffer), Some(None));
+        assert_eq!(take_event(&mut buffer), None);
+
+        buffer.extend_from_slice(b"ta:{\"round_id\":1}\n\n");
+        assert_eq!(take_event(&mut buffer), Some(Some("sums".to_string())));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn take_events_with_any_line_terminator() {
+        let mut buffer = BytesMut::from(
+            &b"event:params\r\ndata:{\"round_id\":1}\r\n\r\nevent:sums\rdata:{}\r\revent:seeds\r"[..],
+        );
+        assert_eq!(take_event(&mut buffer), Some(Some("params".to_string())));
+        assert_eq!(take_event(&mut buffer), Some(Some("sums".to_string())));
+        assert_eq!(take_event(&mut buffer), None);
+
+        buffer.extend_from_slice(b"\ndata:{}\r");
+        assert_eq!(take_event(&mut buffer), None);
+        buffer.extend_from_slice(b"\n\r\n");
+        assert_eq!(take_event(&mut buffer), Some(Some("seeds".to_string())));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn skip_params_snapshot() {
+        let mut events =
+            EventSubscription::new("http://localhost:8081".to_string(), Duration::from_secs(1));
+        events.skip_params_snapshot = true;
+        events.buffer.extend_from_slice(
+            b"event:phase\ndata:{}\n\nevent:params\ndata:{}\n\nevent:params\ndata:{}\n\n",
+        );
+        assert_eq!(events.take_event(), Some(Some("phase".to_string())));
+        assert_eq!(events.take_event(), Some(None));
+        assert_eq!(events.take_event(), Some(Some("params".to_string())));
+        assert_eq!(events.take_event(), None);
+    }
+}
@@ -4,7 +4,7 @@
 //!
 //! * Abiding by (the underlying [`Participant`]'s side of) the PET protocol.
 //! * Handling the network communication with the XayNet service, including
-//!   polling of service data.
+//!   polling of service data or subscribing to the service events.
 //!
 //! # Participant
 //! In any given round of federated learning, each [`Participant`] of the
@@ -13,7 +13,8 @@
 //!
 //! Participants selected to `Update` are responsible for sending masked model
 //! updates in the form of PET messages constructed with
-//! [`compose_update_message`].
+//! [`compose_update_message`]. Their local model can be protected by local
+//! differential privacy beforehand, see the [`privacy`] module.
 //!
 //! Participants selected to `Sum` are responsible for sending ephemeral keys
 //! and global masks in PET messages constructed respectively with
@@ -45,20 +46,23 @@ extern crate serde;
 #[macro_use]
 extern crate tracing;
 
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use thiserror::Error;
 use tokio::time;
 
 use xaynet_core::{crypto::ByteObject, mask::Model, CoordinatorPublicKey, InitError};
 
+use crate::privacy::{InvalidPrivacyConfig, PrivacyConfig};
+
 #[doc(hidden)]
 pub mod mobile_client;
 
 pub mod api;
+pub mod privacy;
 
 mod participant;
-pub use participant::{Participant, Task};
+pub use participant::{Participant, Task, MAX_CHUNK_SIZE};
 
 #[derive(Clone, Debug)]
 /// A primitive model cached on the heap.
@@ -87,6 +91,10 @@ pub enum ClientError<E: ::std::error::Error + ::std::fmt::Debug + 'static> {
     /// Failed to initialise participant.
     ParticipantInitErr(InitError),
 
+    #[error("invalid privacy configuration: {0}")]
+    /// Invalid privacy configuration.
+    Privacy(InvalidPrivacyConfig),
+
     #[error("an API request failed: {0}")]
     /// Failed to handle PET message.
     Api(#[from] E),
@@ -144,13 +152,25 @@ where
     /// * `period`: time period at which to poll for service data, in seconds.
     /// * `id`: an ID to assign to the [`Client`].
     /// * `addr`: service address to connect to.
+    /// * `privacy`: local differential privacy applied to the local model, if
+    ///   enabled.
     ///
     /// # Errors
     /// Returns a `ParticipantInitErr` if the underlying [`Participant`] is
-    /// unable to initialize.
-    pub fn new(period: u64, id: u32, api: C) -> Result<Self, ClientError<C::Error>> {
+    /// unable to initialize and a `Privacy` error if the privacy configuration
+    /// is invalid.
+    pub fn new(
+        period: u64,
+        id: u32,
+        api: C,
+        privacy: Option<PrivacyConfig>,
+    ) -> Result<Self, ClientError<C::Error>> {
+        let mut participant = Participant::new().map_err(ClientError::ParticipantInitErr)?;
+        participant
+            .set_privacy(privacy)
+            .map_err(ClientError::Privacy)?;
         Ok(Self {
-            participant: Participant::new().map_err(ClientError::ParticipantInitErr)?,
+            participant,
             interval: time::interval(Duration::from_secs(period)),
             coordinator_pk: CoordinatorPublicKey::zeroed(),
             has_new_coord_pk_since_last_check: false,
@@ -206,12 +226,15 @@ where
                 self.coordinator_pk = round_params.pk;
                 let round_seed = round_params.seed.as_slice();
                 self.participant.compute_signatures(round_seed);
+                self.participant.mask_config = round_params.mask_config;
+                self.participant.noise = round_params.noise;
                 let (sum_frac, upd_frac) = (round_params.sum, round_params.update);
 
                 // update the flag only after everthing else is done such that the client can learn
                 // via the API that a new round has started once all parameters are available
                 let task = self.participant.check_task(sum_frac, upd_frac);
                 self.has_new_coord_pk_since_last_check = true;
+                self.wait_for_start(round_params.start_time).await;
                 return match task {
                     Task::Sum => self.summer().await,
                     Task::Update => self.updater().await,
@@ -226,6 +249,18 @@ where
         }
     }
 
+    /// Waits until the scheduled `start_time` (a Unix timestamp in seconds) of the round.
+    async fn wait_for_start(&self, start_time: u64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        if start_time > now {
+            debug!(client_id = %self.id, "waiting {} seconds for the round to start", start_time - now);
+            time::delay_for(Duration::from_secs(start_time - now)).await;
+        }
+    }
+
     /// Work flow for unselected [`Client`]s.
     async fn unselected(&mut self) -> Result<Task, ClientError<C::Error>> {
         debug!(client_id = %self.id, "not selected");
@@ -236,9 +271,9 @@ where
     async fn summer(&mut self) -> Result<Task, ClientError<C::Error>> {
         info!(client_id = %self.id, "selected to sum");
         let msg = self.participant.compose_sum_message(self.coordinator_pk);
-        let sealed_msg = self.participant.seal_message(&self.coordinator_pk, &msg);
-
-        self.client.send_message(sealed_msg).await?;
+        for sealed_msg in self.participant.seal_message(&self.coordinator_pk, msg) {
+            self.client.send_message(sealed_msg).await?;
+        }
 
         debug!(client_id = %self.id, "polling for model/mask length");
         let length = loop {
@@ -264,8 +299,9 @@ where
                         error!("failed to compose sum2 message with seeds: {:?}", &seeds);
                         ClientError::ParticipantErr(e)
                     })?;
-                let sealed_msg = self.participant.seal_message(&self.coordinator_pk, &msg);
-                self.client.send_message(sealed_msg).await?;
+                for sealed_msg in self.participant.seal_message(&self.coordinator_pk, msg) {
+                    self.client.send_message(sealed_msg).await?;
+                }
 
                 info!(client_id = %self.id, "sum participant completed a round");
                 break Ok(Task::Sum);
@@ -294,14 +330,13 @@ where
         loop {
             if let Some(sums) = self.client.get_sums().await? {
                 debug!(client_id = %self.id, "sum dict received, sending update message.");
-                let msg = self.participant.compose_update_message(
-                    self.coordinator_pk,
-                    &sums,
-                    self.scalar,
-                    model,
-                );
-                let sealed_msg = self.participant.seal_message(&self.coordinator_pk, &msg);
-                self.client.send_message(sealed_msg).await?;
+                let msg = self
+                    .participant
+                    .compose_update_message(self.coordinator_pk, &sums, self.scalar, model)
+                    .map_err(ClientError::ParticipantErr)?;
+                for sealed_msg in self.participant.seal_message(&self.coordinator_pk, msg) {
+                    self.client.send_message(sealed_msg).await?;
+                }
 
                 info!(client_id = %self.id, "update participant completed a round");
                 break Ok(Task::Update);
@@ -56,19 +56,19 @@ impl ClientState<Awaiting> {
         }
     }
 
-    async fn next<T: ApiClient>(mut self, api: &mut T) -> ClientStateMachine {
+    async fn next<T: ApiClient>(mut self, api: &mut T) -> Transition<T::Error> {
         info!("awaiting task");
         let new_round_param = match api.get_round_params().await {
             Ok(new_round_param) => new_round_param,
             Err(err) => {
                 error!("{:?}", err);
-                return self.reset().into();
+                return Err((self.reset().into(), err));
             }
         };
 
         if new_round_param == self.round_params {
             debug!("still same round");
-            return self.into();
+            return Ok(self.into());
         } else {
             self.round_params = new_round_param;
         }
@@ -78,20 +78,24 @@ impl ClientState<Awaiting> {
             round_params,
         } = self;
 
-        match participant.determine_role(
-            round_params.seed.as_slice(),
-            round_params.sum,
-            round_params.update,
-        ) {
-            Role::Unselected(participant) => {
-                info!("unselected");
-                ClientState::<Awaiting>::new(participant.reset(), round_params).into()
-            }
-            Role::Summer(participant) => ClientState::<Sum>::new(participant, round_params).into(),
-            Role::Updater(participant) => {
-                ClientState::<Update>::new(participant, round_params).into()
-            }
-        }
+        Ok(
+            match participant.determine_role(
+                round_params.seed.as_slice(),
+                round_params.sum,
+                round_params.update,
+            ) {
+                Role::Unselected(participant) => {
+                    info!("unselected");
+                    ClientState::<Awaiting>::new(participant.reset(), round_params).into()
+                }
+                Role::Summer(participant) => {
+                    ClientState::<Sum>::new(participant, round_params).into()
+                }
+                Role::Updater(participant) => {
+                    ClientState::<Update>::new(participant, round_params).into()
+                }
+            },
+        )
     }
 }
 
@@ -103,15 +107,19 @@ impl ClientState<Sum> {
         }
     }
 
-    async fn next<T: ApiClient>(mut self, api: &mut T) -> ClientStateMachine {
+    async fn next<T: ApiClient>(mut self, api: &mut T) -> Transition<T::Error> {
         info!("selected to sum");
 
         match self.run(api).await {
-            Ok(_) => self.into_sum2().into(),
-            Err(ClientError::RoundOutdated) => self.reset().into(),
+            Ok(_) => Ok(self.into_sum2().into()),
+            Err(ClientError::RoundOutdated) => Ok(self.reset().into()),
+            Err(ClientError::Api(err)) => {
+                error!("{:?}", err);
+                Err((self.into(), err))
+            }
             Err(err) => {
                 error!("{:?}", err);
-                self.into()
+                Ok(self.into())
             }
         }
     }
@@ -120,12 +128,14 @@ impl ClientState<Sum> {
         self.check_round_freshness(api).await?;
 
         let sum_msg = self.participant.compose_sum_message(self.round_params.pk);
-        let sealed_msg = self
+        let sealed_msgs = self
             .participant
-            .seal_message(&self.round_params.pk, &sum_msg);
+            .seal_message(&self.round_params.pk, sum_msg);
 
         debug!("sending sum message");
-        api.send_message(sealed_msg).await?;
+        for sealed_msg in sealed_msgs {
+            api.send_message(sealed_msg).await?;
+        }
         debug!("sum message sent");
         Ok(())
     }
@@ -147,14 +157,18 @@ impl ClientState<Update> {
         mut self,
         api: &mut T,
         local_model: &mut L,
-    ) -> ClientStateMachine {
+    ) -> Transition<T::Error> {
         info!("selected to update");
 
         match self.run(api, local_model).await {
-            Ok(_) | Err(ClientError::RoundOutdated) => self.reset().into(),
+            Ok(_) | Err(ClientError::RoundOutdated) => Ok(self.reset().into()),
+            Err(ClientError::Api(err)) => {
+                error!("{:?}", err);
+                Err((self.into(), err))
+            }
             Err(err) => {
                 error!("{:?}", err);
-                self.into()
+                Ok(self.into())
             }
         }
     }
@@ -178,15 +192,24 @@ impl ClientState<Update> {
             .await?
             .ok_or(ClientError::TooEarly("sum dict"))?;
 
-        let upd_msg =
-            self.participant
-                .compose_update_message(self.round_params.pk, &sums, local_model);
-        let sealed_msg = self
+        let upd_msg = self
             .participant
-            .seal_message(&self.round_params.pk, &upd_msg);
+            .compose_update_message(
+                self.round_params.pk,
+                &sums,
+                local_model,
+                self.round_params.mask_config,
+                self.round_params.noise,
+            )
+            .map_err(ClientError::ParticipantErr)?;
+        let sealed_msgs = self
+            .participant
+            .seal_message(&self.round_params.pk, upd_msg);
 
         debug!("sending update message");
-        api.send_message(sealed_msg).await?;
+        for sealed_msg in sealed_msgs {
+            api.send_message(sealed_msg).await?;
+        }
         info!("update participant completed a round");
         Ok(())
     }
@@ -200,14 +223,18 @@ impl ClientState<Sum2> {
         }
     }
 
-    async fn next<T: ApiClient>(mut self, api: &mut T) -> ClientStateMachine {
+    async fn next<T: ApiClient>(mut self, api: &mut T) -> Transition<T::Error> {
         info!("selected to sum2");
 
         match self.run(api).await {
-            Ok(_) | Err(ClientError::RoundOutdated) => self.reset().into(),
+            Ok(_) | Err(ClientError::RoundOutdated) => Ok(self.reset().into()),
+            Err(ClientError::Api(err)) => {
+                error!("{:?}", err);
+                Err((self.into(), err))
+            }
             Err(err) => {
                 error!("{:?}", err);
-                self.into()
+                Ok(self.into())
             }
         }
     }
@@ -232,22 +259,35 @@ impl ClientState<Sum2> {
 
         let sum2_msg = self
             .participant
-            .compose_sum2_message(self.round_params.pk, &seeds, length as usize)
+            .compose_sum2_message(
+                self.round_params.pk,
+                &seeds,
+                length as usize,
+                self.round_params.mask_config,
+            )
             .map_err(|e| {
                 error!("failed to compose sum2 message with seeds: {:?}", &seeds);
                 ClientError::ParticipantErr(e)
             })?;
-        let sealed_msg = self
+        let sealed_msgs = self
             .participant
-            .seal_message(&self.round_params.pk, &sum2_msg);
+            .seal_message(&self.round_params.pk, sum2_msg);
 
         debug!("sending sum2 message");
-        api.send_message(sealed_msg).await?;
+        for sealed_msg in sealed_msgs {
+            api.send_message(sealed_msg).await?;
+        }
         info!("sum participant completed a round");
         Ok(())
     }
 }
 
+/// The result of a state transition.
+///
+/// If an API request failed, the error is returned along with the next state
+/// of the client.
+pub type Transition<E> = Result<ClientStateMachine, (ClientStateMachine, E)>;
+
 #[derive(From, Serialize, Deserialize)]
 pub enum ClientStateMachine {
     Awaiting(ClientState<Awaiting>),
@@ -268,7 +308,11 @@ impl ClientStateMachine {
         .into())
     }
 
-    pub async fn next<L: LocalModel, T: ApiClient>(self, api: &mut T, local_model: &mut L) -> Self {
+    pub async fn next<L: LocalModel, T: ApiClient>(
+        self,
+        api: &mut T,
+        local_model: &mut L,
+    ) -> Transition<T::Error> {
         match self {
             ClientStateMachine::Awaiting(state) => state.next(api).await,
             ClientStateMachine::Sum(state) => state.next(api).await,
@@ -276,4 +320,15 @@ impl ClientStateMachine {
             ClientStateMachine::Sum2(state) => state.next(api).await,
         }
     }
+
+    /// Gets the privacy budget spent by the last update message, if local
+    /// differential privacy is enabled.
+    pub fn epsilon_spent(&self) -> Option<f64> {
+        match self {
+            ClientStateMachine::Awaiting(state) => state.participant.epsilon_spent(),
+            ClientStateMachine::Sum(state) => state.participant.epsilon_spent(),
+            ClientStateMachine::Update(state) => state.participant.epsilon_spent(),
+            ClientStateMachine::Sum2(state) => state.participant.epsilon_spent(),
+        }
+    }
 }
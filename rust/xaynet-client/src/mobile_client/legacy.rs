@@ -0,0 +1,176 @@
+//! Migration of the unversioned state of a mobile client.
+//!
+//! The first releases serialized the client state machine without a version. The round
+//! parameters and the aggregation config have changed since then, hence such a state can't be
+//! deserialized into the current types anymore. The credentials and the scalar of the participant
+//! are kept, while the task of the current round is dropped, such that the client waits for the
+//! next round.
+use xaynet_core::{
+    common::RoundSeed,
+    crypto::SigningKeyPair,
+    mask::MaskConfig,
+    CoordinatorPublicKey,
+    ParticipantTaskSignature,
+    SumParticipantEphemeralPublicKey,
+    SumParticipantEphemeralSecretKey,
+};
+
+use crate::mobile_client::participant::{AggregationConfig, ParticipantSettings};
+
+#[derive(Serialize, Deserialize)]
+struct RoundParameters {
+    pk: CoordinatorPublicKey,
+    sum: f64,
+    update: f64,
+    seed: RoundSeed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LegacyAggregationConfig {
+    mask: MaskConfig,
+    scalar: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ParticipantState {
+    keys: SigningKeyPair,
+    aggregation_config: LegacyAggregationConfig,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Participant<Task> {
+    inner: Task,
+    state: ParticipantState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClientState<Task> {
+    participant: Participant<Task>,
+    round_params: RoundParameters,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Awaiting;
+
+#[derive(Serialize, Deserialize)]
+struct Sum {
+    ephm_pk: SumParticipantEphemeralPublicKey,
+    ephm_sk: SumParticipantEphemeralSecretKey,
+    sum_signature: ParticipantTaskSignature,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Update {
+    sum_signature: ParticipantTaskSignature,
+    update_signature: ParticipantTaskSignature,
+}
+
+#[derive(Serialize, Deserialize)]
+enum LegacyClientStateMachine {
+    Awaiting(ClientState<Awaiting>),
+    Sum(ClientState<Sum>),
+    Update(ClientState<Update>),
+    // the sum2 task has the same fields as the sum task
+    Sum2(ClientState<Sum>),
+}
+
+impl LegacyClientStateMachine {
+    fn into_state(self) -> ParticipantState {
+        match self {
+            Self::Awaiting(state) => state.participant.state,
+            Self::Sum(state) | Self::Sum2(state) => state.participant.state,
+            Self::Update(state) => state.participant.state,
+        }
+    }
+}
+
+/// Migrates an unversioned state to the settings of a participant which awaits the next round.
+///
+/// # Errors
+///
+/// Fails if the state is corrupted.
+pub(crate) fn migrate(bytes: &[u8]) -> Result<ParticipantSettings, bincode::Error> {
+    let state = bincode::deserialize::<LegacyClientStateMachine>(bytes)?.into_state();
+    warn!("migrating an unversioned client state: the task of the current round is dropped");
+    Ok(ParticipantSettings {
+        secret_key: state.keys.secret,
+        aggregation_config: AggregationConfig {
+            scalar: state.aggregation_config.scalar,
+            privacy: None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use xaynet_core::{
+        crypto::{ByteObject, EncryptKeyPair},
+        mask::{BoundType, DataType, GroupType, ModelType},
+    };
+
+    use super::*;
+    use crate::mobile_client::{MobileClient, MobileClientError};
+
+    fn legacy_state(keys: &SigningKeyPair) -> LegacyClientStateMachine {
+        let aggregation_config = LegacyAggregationConfig {
+            mask: MaskConfig {
+                group_type: GroupType::Prime,
+                data_type: DataType::F32,
+                bound_type: BoundType::B0,
+                model_type: ModelType::M3,
+            },
+            scalar: 0.5,
+        };
+        let ephm_keys = EncryptKeyPair::generate();
+        LegacyClientStateMachine::Sum(ClientState {
+            participant: Participant {
+                inner: Sum {
+                    ephm_pk: ephm_keys.public,
+                    ephm_sk: ephm_keys.secret,
+                    sum_signature: ParticipantTaskSignature::zeroed(),
+                },
+                state: ParticipantState {
+                    keys: keys.clone(),
+                    aggregation_config,
+                },
+            },
+            round_params: RoundParameters {
+                pk: CoordinatorPublicKey::zeroed(),
+                sum: 0.1,
+                update: 0.2,
+                seed: RoundSeed::generate(),
+            },
+        })
+    }
+
+    #[test]
+    fn unversioned_state_is_migrated() {
+        sodiumoxide::init().unwrap();
+        let keys = SigningKeyPair::generate();
+        let bytes = bincode::serialize(&legacy_state(&keys)).unwrap();
+
+        let client = MobileClient::restore("http://localhost:8081", &bytes).unwrap();
+        let expected = MobileClient::init(
+            "http://localhost:8081",
+            ParticipantSettings {
+                secret_key: keys.secret,
+                aggregation_config: AggregationConfig {
+                    scalar: 0.5,
+                    privacy: None,
+                },
+            },
+        )
+        .unwrap();
+        assert_eq!(client.serialize(), expected.serialize());
+    }
+
+    #[test]
+    fn corrupted_state_is_rejected() {
+        assert!(MobileClient::restore("http://localhost:8081", &[1, 2, 3]).is_err());
+        assert!(MobileClient::restore("http://localhost:8081", b"XNCS").is_err());
+        assert!(matches!(
+            MobileClient::restore("http://localhost:8081", b"XNCS\x02"),
+            Err(MobileClientError::UnsupportedVersion(2))
+        ));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod client;
+mod legacy;
 pub mod participant;
 
 use crate::{
@@ -7,6 +8,7 @@ use crate::{
         client::{ClientStateMachine, LocalModel},
         participant::ParticipantSettings,
     },
+    privacy::InvalidPrivacyConfig,
 };
 use thiserror::Error;
 use xaynet_core::{
@@ -30,8 +32,24 @@ pub enum MobileClientError {
     #[error("API request failed: {0}")]
     /// API request failed.
     Api(#[from] HttpApiClientError),
+    #[error("invalid privacy configuration: {0}")]
+    /// Invalid privacy configuration.
+    Privacy(#[from] InvalidPrivacyConfig),
+    #[error("unsupported version of the serialized state: {0}")]
+    /// Unsupported version of the serialized state.
+    UnsupportedVersion(u8),
 }
 
+/// The magic bytes which precede the version of a serialized client state.
+///
+/// The unversioned state of the first releases starts with the index of the state as a little
+/// endian `u32`, hence it never starts with these bytes.
+const STATE_MAGIC: &[u8] = b"XNCS";
+
+/// The version of the serialized client state. It must be incremented whenever the serialized
+/// types change.
+const STATE_VERSION: u8 = 1;
+
 pub struct MobileClient {
     api: HttpApiClient,
     local_model: LocalModelCache,
@@ -46,11 +64,15 @@ impl MobileClient {
     ///
     /// # Errors
     ///
-    /// Fails if the crypto module cannot be initialized.
+    /// Fails if the crypto module cannot be initialized or if the privacy configuration is
+    /// invalid.
     pub fn init(
         url: &str,
         participant_settings: ParticipantSettings,
     ) -> Result<Self, MobileClientError> {
+        if let Some(ref privacy) = participant_settings.aggregation_config.privacy {
+            privacy.validate()?;
+        }
         // It is critical that the initialization of sodiumoxide is successful.
         // We'd better not run the client than having a broken crypto.
         //
@@ -63,12 +85,26 @@ impl MobileClient {
 
     /// Restores a client from its serialized state.
     ///
+    /// An unversioned state of an earlier release is migrated to a client which
+    /// keeps its credentials and waits for the next round.
+    ///
     /// # Errors
     ///
-    /// Fails if the serialized state is corrupted and the client cannot be restored
-    /// or if the crypto module cannot be initialized.
+    /// Fails if the serialized state is corrupted or of an unsupported version and the
+    /// client cannot be restored or if the crypto module cannot be initialized.
     pub fn restore(url: &str, bytes: &[u8]) -> Result<Self, MobileClientError> {
-        let client_state: ClientStateMachine = bincode::deserialize(bytes)?;
+        let client_state = if bytes.starts_with(STATE_MAGIC) {
+            match bytes[STATE_MAGIC.len()..].split_first() {
+                Some((&STATE_VERSION, state)) => bincode::deserialize(state)?,
+                Some((&version, _)) => return Err(MobileClientError::UnsupportedVersion(version)),
+                None => {
+                    let err = bincode::ErrorKind::Custom("missing state version".to_string());
+                    return Err(MobileClientError::Deserialize(Box::new(err)));
+                }
+            }
+        } else {
+            ClientStateMachine::new(legacy::migrate(bytes)?)?
+        };
         Ok(Self::new(url, client_state))
     }
 
@@ -84,6 +120,9 @@ impl MobileClient {
 
     /// Serializes the current state of the client.
     ///
+    /// The state is preceded by its version, such that it can still be restored
+    /// after its types have changed.
+    ///
     /// # Note
     ///
     /// The serialized state is **not encrypted** and contains sensitive data such as the
@@ -100,7 +139,10 @@ impl MobileClient {
         // - https://github.com/servo/bincode/issues/293
         // - https://github.com/servo/bincode/issues/255
         // - https://github.com/servo/bincode/issues/130#issuecomment-284641263
-        bincode::serialize(&self.client_state).unwrap()
+        let mut bytes = STATE_MAGIC.to_vec();
+        bytes.push(STATE_VERSION);
+        bincode::serialize_into(&mut bytes, &self.client_state).unwrap();
+        bytes
     }
 
     /// Fetches and returns the latest global model from the coordinator.
@@ -123,6 +165,10 @@ impl MobileClient {
     /// Fails if the runtime cannot be initialized.
     /// In this case the state of the client remains unchanged and is returned
     /// along with the error.
+    ///
+    /// Fails if an API request has failed, for example because the coordinator rejected
+    /// a message (see [`HttpApiClientError::MessageRejected`]). In this case the new state
+    /// of the client is returned along with the error.
     pub fn try_to_proceed(self) -> Result<Self, (Self, MobileClientError)> {
         let mut runtime = match Self::runtime() {
             Ok(runtime) => runtime,
@@ -137,14 +183,21 @@ impl MobileClient {
             client_state,
         } = self;
 
-        let client_state =
-            runtime.block_on(async { client_state.next(&mut api, &mut local_model).await });
-
-        Ok(Self {
-            api,
-            local_model,
-            client_state,
-        })
+        match runtime.block_on(async { client_state.next(&mut api, &mut local_model).await }) {
+            Ok(client_state) => Ok(Self {
+                api,
+                local_model,
+                client_state,
+            }),
+            Err((client_state, err)) => Err((
+                Self {
+                    api,
+                    local_model,
+                    client_state,
+                },
+                err.into(),
+            )),
+        }
     }
 
     /// Returns the current state of the client.
@@ -157,6 +210,12 @@ impl MobileClient {
         }
     }
 
+    /// Returns the privacy budget spent by the last update message of the client, if local
+    /// differential privacy is enabled.
+    pub fn get_epsilon_spent(&self) -> Option<f64> {
+        self.client_state.epsilon_spent()
+    }
+
     /// Sets the local model.
     ///
     /// The local model is only sent if the client has been selected as an update client.
@@ -6,12 +6,16 @@
 use derive_more::From;
 use xaynet_core::{
     crypto::SigningKeyPair,
-    mask::MaskConfig,
     message::Message,
     CoordinatorPublicKey,
     ParticipantSecretKey,
 };
 
+use crate::{
+    participant::{seal_message, MAX_CHUNK_SIZE},
+    privacy::PrivacyConfig,
+};
+
 pub mod awaiting;
 pub mod sum;
 pub mod sum2;
@@ -19,18 +23,23 @@ pub mod update;
 
 pub use self::{awaiting::Awaiting, sum::Sum, sum2::Sum2, update::Update};
 
+// the masking configuration is not part of the aggregation config, since it is
+// taken from the round parameters of the coordinator in each round
 #[derive(Serialize, Deserialize)]
 pub struct AggregationConfig {
-    pub mask: MaskConfig,
     pub scalar: f64,
+    // local differential privacy applied to the local model, if enabled
+    pub privacy: Option<PrivacyConfig>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct ParticipantState {
     // credentials
     pub keys: SigningKeyPair,
-    // Mask config
+    // aggregation config
     pub aggregation_config: AggregationConfig,
+    // privacy budget spent by the last update message, if local differential privacy is enabled
+    pub epsilon_spent: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -52,6 +61,7 @@ impl From<ParticipantSettings> for ParticipantState {
                 secret: secret_key,
             },
             aggregation_config,
+            epsilon_spent: None,
         }
     }
 }
@@ -74,11 +84,17 @@ impl<Task> Participant<Task> {
     ///
     /// The message is signed with the participant secret signing
     /// key. `pk` is the coordinator public key, used to encrypt the
-    /// final message
-    pub fn seal_message(&self, pk: &CoordinatorPublicKey, message: &Message) -> Vec<u8> {
-        let mut buf = vec![0; message.buffer_length()];
-        message.to_bytes(&mut buf, &self.state.keys.secret);
-        pk.encrypt(&buf[..])
+    /// final message. Messages with a payload larger than
+    /// [`MAX_CHUNK_SIZE`] are split into a multipart message, whose
+    /// chunks are sealed separately and must be sent in order.
+    pub fn seal_message(&self, pk: &CoordinatorPublicKey, message: Message) -> Vec<Vec<u8>> {
+        seal_message(message, &self.state.keys.secret, pk, MAX_CHUNK_SIZE)
+    }
+
+    /// Gets the privacy budget spent by the last update message, if local
+    /// differential privacy is enabled.
+    pub fn epsilon_spent(&self) -> Option<f64> {
+        self.state.epsilon_spent
     }
 
     /// Resets the client.
@@ -1,6 +1,6 @@
 use super::{Participant, ParticipantState};
 use xaynet_core::{
-    mask::{Aggregation, MaskObject, MaskSeed},
+    mask::{Aggregation, MaskConfigPair, MaskObject, MaskSeed},
     message::{Message, Sum2 as Sum2Message},
     CoordinatorPublicKey,
     ParticipantPublicKey,
@@ -36,8 +36,8 @@ impl Participant<Sum2> {
         }
     }
 
-    /// Compose a sum2 message given the coordinator public key, seed dictionary
-    /// and mask length.
+    /// Compose a sum2 message given the coordinator public key, seed dictionary,
+    /// mask length and the masking configuration of the round.
     ///
     /// # Errors
     ///
@@ -48,9 +48,11 @@ impl Participant<Sum2> {
         coordinator_pk: CoordinatorPublicKey,
         seed_dict: &UpdateSeedDict,
         mask_len: usize,
+        mask_config: MaskConfigPair,
     ) -> Result<Message, PetError> {
         let mask_seeds = self.get_seeds(seed_dict)?;
-        let (model_mask, scalar_mask) = self.compute_global_mask(mask_seeds, mask_len)?;
+        let (model_mask, scalar_mask) =
+            self.compute_global_mask(mask_seeds, mask_len, mask_config)?;
         let payload = Sum2Message {
             sum_signature: self.inner.sum_signature,
             model_mask,
@@ -80,16 +82,16 @@ impl Participant<Sum2> {
         &self,
         mask_seeds: Vec<MaskSeed>,
         mask_len: usize,
+        mask_config: MaskConfigPair,
     ) -> Result<(MaskObject, MaskObject), PetError> {
         if mask_seeds.is_empty() {
             return Err(PetError::InvalidMask);
         }
 
-        let mut model_mask_agg = Aggregation::new(self.state.aggregation_config.mask, mask_len);
-        let mut scalar_mask_agg = Aggregation::new(self.state.aggregation_config.mask, 1);
+        let mut model_mask_agg = Aggregation::new(mask_config.model, mask_len);
+        let mut scalar_mask_agg = Aggregation::new(mask_config.scalar, 1);
         for seed in mask_seeds.into_iter() {
-            let (model_mask, scalar_mask) =
-                seed.derive_mask(mask_len, self.state.aggregation_config.mask);
+            let (model_mask, scalar_mask) = seed.derive_mask(mask_len, mask_config);
 
             model_mask_agg
                 .validate_aggregation(&model_mask)
@@ -113,7 +115,6 @@ mod tests {
     use std::{collections::HashSet, iter};
     use xaynet_core::{
         crypto::{ByteObject, EncryptKeyPair, Signature, SigningKeyPair},
-        mask::{BoundType, DataType, GroupType, MaskConfig, ModelType},
         UpdateParticipantPublicKey,
     };
 
@@ -121,18 +122,13 @@ mod tests {
         sodiumoxide::init().unwrap();
 
         let aggregation_config = AggregationConfig {
-            mask: MaskConfig {
-                group_type: GroupType::Prime,
-                data_type: DataType::F32,
-                bound_type: BoundType::B0,
-                model_type: ModelType::M3,
-            },
-
             scalar: 1_f64,
+            privacy: None,
         };
         ParticipantState {
             keys: SigningKeyPair::generate(),
             aggregation_config,
+            epsilon_spent: None,
         }
     }
 
@@ -1,6 +1,7 @@
 use super::{Participant, ParticipantState};
+use crate::PetError;
 use xaynet_core::{
-    mask::{MaskObject, MaskSeed, Masker, Model},
+    mask::{MaskConfigPair, MaskObject, MaskSeed, Masker, Model, NoiseParameters},
     message::{Message, Update as UpdateMessage},
     CoordinatorPublicKey,
     LocalSeedDict,
@@ -29,15 +30,35 @@ impl Participant<Update> {
     }
 
     /// Compose an update message given the coordinator public key, sum
-    /// dictionary, model scalar and local model update.
+    /// dictionary, local model update and the masking configuration and
+    /// noise parameters of the round.
+    ///
+    /// If local differential privacy is enabled, the local model is clipped
+    /// and noised before it is masked. If the round parameters contain noise
+    /// parameters, a noise share is added to the masked model.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PetError`] if the local model cannot be privatized.
     pub fn compose_update_message(
-        &self,
+        &mut self,
         coordinator_pk: CoordinatorPublicKey,
         sum_dict: &SumDict,
-
         local_model: Model,
-    ) -> Message {
-        let (mask_seed, masked_model, masked_scalar) = self.mask_model(local_model);
+        mask_config: MaskConfigPair,
+        noise: Option<NoiseParameters>,
+    ) -> Result<Message, PetError> {
+        let local_model = match self.state.aggregation_config.privacy {
+            Some(privacy) => {
+                let local_model = privacy.privatize(local_model)?;
+                info!("spent a privacy budget of epsilon {}", privacy.epsilon);
+                self.state.epsilon_spent = Some(privacy.epsilon);
+                local_model
+            }
+            None => local_model,
+        };
+        let (mask_seed, masked_model, masked_scalar) =
+            self.mask_model(local_model, mask_config, noise);
         let local_seed_dict = Self::create_local_seed_dict(sum_dict, &mask_seed);
         let payload = UpdateMessage {
             sum_signature: self.inner.sum_signature,
@@ -46,12 +67,22 @@ impl Participant<Update> {
             masked_scalar,
             local_seed_dict,
         };
-        Message::new_update(self.state.keys.public, coordinator_pk, payload)
+        Ok(Message::new_update(
+            self.state.keys.public,
+            coordinator_pk,
+            payload,
+        ))
     }
 
     /// Generate a mask seed and mask a local model.
-    fn mask_model(&self, local_model: Model) -> (MaskSeed, MaskObject, MaskObject) {
-        Masker::new(self.state.aggregation_config.mask)
+    fn mask_model(
+        &self,
+        local_model: Model,
+        mask_config: MaskConfigPair,
+        noise: Option<NoiseParameters>,
+    ) -> (MaskSeed, MaskObject, MaskObject) {
+        Masker::new(mask_config)
+            .with_noise(noise)
             .mask(self.state.aggregation_config.scalar, local_model)
     }
 
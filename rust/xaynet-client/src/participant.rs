@@ -6,6 +6,7 @@
 
 use std::default::Default;
 
+use sodiumoxide::randombytes::randombytes_uniform;
 use xaynet_core::{
     crypto::{ByteObject, EncryptKeyPair, SigningKeyPair},
     mask::{
@@ -14,11 +15,13 @@ use xaynet_core::{
         DataType,
         GroupType,
         MaskConfig,
+        MaskConfigPair,
         MaskObject,
         MaskSeed,
         Masker,
         Model,
         ModelType,
+        NoiseParameters,
     },
     message::{Message, Sum, Sum2, Update},
     CoordinatorPublicKey,
@@ -33,7 +36,15 @@ use xaynet_core::{
     UpdateSeedDict,
 };
 
-use crate::PetError;
+use crate::{
+    privacy::{InvalidPrivacyConfig, PrivacyConfig},
+    PetError,
+};
+
+/// Default maximum number of payload bytes in a single message sent
+/// to the coordinator. Messages with a larger payload are split into
+/// multipart messages.
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 /// Tasks of a participant.
@@ -56,6 +67,16 @@ pub struct Participant {
 
     // round parameters
     pub task: Task,
+    pub mask_config: MaskConfigPair,
+    pub noise: Option<NoiseParameters>,
+
+    /// Maximum number of payload bytes in a single message
+    pub max_chunk_size: usize,
+
+    /// Local differential privacy applied to the local model, if enabled
+    privacy: Option<PrivacyConfig>,
+    /// Privacy budget spent by the last update message, if local differential privacy is enabled
+    pub epsilon_spent: Option<f64>,
 }
 
 impl Default for Participant {
@@ -67,6 +88,7 @@ impl Default for Participant {
         let sum_signature = ParticipantTaskSignature::zeroed();
         let update_signature = ParticipantTaskSignature::zeroed();
         let task = Task::None;
+        let mask_config = dummy_config().into();
         Self {
             pk,
             sk,
@@ -75,6 +97,11 @@ impl Default for Participant {
             sum_signature,
             update_signature,
             task,
+            mask_config,
+            noise: None,
+            max_chunk_size: MAX_CHUNK_SIZE,
+            privacy: None,
+            epsilon_spent: None,
         }
     }
 }
@@ -98,6 +125,22 @@ impl Participant {
         })
     }
 
+    /// Enable or disable local differential privacy for the local model of
+    /// update messages.
+    ///
+    /// # Errors
+    /// Fails if the privacy configuration is invalid.
+    pub fn set_privacy(
+        &mut self,
+        privacy: Option<PrivacyConfig>,
+    ) -> Result<(), InvalidPrivacyConfig> {
+        if let Some(ref privacy) = privacy {
+            privacy.validate()?;
+        }
+        self.privacy = privacy;
+        Ok(())
+    }
+
     /// Compute the sum and update signatures for the given round seed.
     pub fn compute_signatures(&mut self, round_seed: &[u8]) {
         self.sum_signature = self.sk.sign_detached(&[round_seed, b"sum"].concat());
@@ -132,14 +175,31 @@ impl Participant {
 
     /// Compose an update message given the coordinator public key, sum
     /// dictionary, model scalar and local model update.
+    ///
+    /// If local differential privacy is enabled, the local model is clipped
+    /// and noised before it is masked. If the coordinator advertised noise
+    /// parameters for the round, a noise share is added to the masked model.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PetError`] if the local model cannot be privatized.
     pub fn compose_update_message(
-        &self,
+        &mut self,
         coordinator_pk: CoordinatorPublicKey,
         sum_dict: &SumDict,
         scalar: f64,
         local_model: Model,
-    ) -> Message {
-        let (mask_seed, masked_model, masked_scalar) = Self::mask_model(scalar, local_model);
+    ) -> Result<Message, PetError> {
+        let local_model = match self.privacy {
+            Some(privacy) => {
+                let local_model = privacy.privatize(local_model)?;
+                info!("spent a privacy budget of epsilon {}", privacy.epsilon);
+                self.epsilon_spent = Some(privacy.epsilon);
+                local_model
+            }
+            None => local_model,
+        };
+        let (mask_seed, masked_model, masked_scalar) = self.mask_model(scalar, local_model);
         let local_seed_dict = Self::create_local_seed_dict(sum_dict, &mask_seed);
         let payload = Update {
             sum_signature: self.sum_signature,
@@ -148,7 +208,7 @@ impl Participant {
             masked_scalar,
             local_seed_dict,
         };
-        Message::new_update(self.pk, coordinator_pk, payload)
+        Ok(Message::new_update(self.pk, coordinator_pk, payload))
     }
 
     /// Compose a sum2 message given the coordinator public key, seed dictionary
@@ -166,7 +226,7 @@ impl Participant {
     ) -> Result<Message, PetError> {
         let mask_seeds = self.get_seeds(seed_dict)?;
         let (model_mask, scalar_mask) =
-            self.compute_global_mask(mask_seeds, mask_len, dummy_config())?;
+            self.compute_global_mask(mask_seeds, mask_len, self.mask_config)?;
         let payload = Sum2 {
             sum_signature: self.sum_signature,
             model_mask,
@@ -177,10 +237,13 @@ impl Participant {
 
     /// Sign the given message with the participant secret key, and
     /// encrypt the signed message with the given public key.
-    pub fn seal_message(&self, pk: &CoordinatorPublicKey, message: &Message) -> Vec<u8> {
-        let mut buf = vec![0; message.buffer_length()];
-        message.to_bytes(&mut buf, &self.sk);
-        pk.encrypt(&buf[..])
+    ///
+    /// If the payload of the message is larger than
+    /// [`Participant::max_chunk_size`], the message is split into a
+    /// multipart message, and each chunk is signed and encrypted
+    /// separately. The chunks must be sent in the returned order.
+    pub fn seal_message(&self, pk: &CoordinatorPublicKey, message: Message) -> Vec<Vec<u8>> {
+        seal_message(message, &self.sk, pk, self.max_chunk_size)
     }
 
     /// Generate an ephemeral encryption key pair.
@@ -191,9 +254,10 @@ impl Participant {
     }
 
     /// Generate a mask seed and mask a local model.
-    fn mask_model(scalar: f64, local_model: Model) -> (MaskSeed, MaskObject, MaskObject) {
-        // TODO: use proper config
-        Masker::new(dummy_config()).mask(scalar, local_model)
+    fn mask_model(&self, scalar: f64, local_model: Model) -> (MaskSeed, MaskObject, MaskObject) {
+        Masker::new(self.mask_config)
+            .with_noise(self.noise)
+            .mask(scalar, local_model)
     }
 
     // Create a local seed dictionary from a sum dictionary.
@@ -220,14 +284,14 @@ impl Participant {
         &self,
         mask_seeds: Vec<MaskSeed>,
         mask_len: usize,
-        mask_config: MaskConfig,
+        mask_config: MaskConfigPair,
     ) -> Result<(MaskObject, MaskObject), PetError> {
         if mask_seeds.is_empty() {
             return Err(PetError::InvalidMask);
         }
 
-        let mut model_mask_agg = Aggregation::new(mask_config, mask_len);
-        let mut scalar_mask_agg = Aggregation::new(mask_config, 1);
+        let mut model_mask_agg = Aggregation::new(mask_config.model, mask_len);
+        let mut scalar_mask_agg = Aggregation::new(mask_config.scalar, 1);
         for seed in mask_seeds.into_iter() {
             let (model_mask, scalar_mask) = seed.derive_mask(mask_len, mask_config);
 
@@ -245,6 +309,30 @@ impl Participant {
     }
 }
 
+/// Split the given message into chunks of at most `max_chunk_size`
+/// payload bytes if necessary, sign the resulting messages with `sk`
+/// and encrypt them with `pk`.
+pub(crate) fn seal_message(
+    message: Message,
+    sk: &ParticipantSecretKey,
+    pk: &CoordinatorPublicKey,
+    max_chunk_size: usize,
+) -> Vec<Vec<u8>> {
+    // The message ID only needs to be unique among the multipart
+    // messages of this participant that are pending on the
+    // coordinator side.
+    let message_id = randombytes_uniform(u16::MAX as u32 + 1) as u16;
+    message
+        .into_chunks(message_id, max_chunk_size)
+        .iter()
+        .map(|message| {
+            let mut buf = vec![0; message.buffer_length()];
+            message.to_bytes(&mut buf, sk);
+            pk.encrypt(&buf[..])
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -255,7 +343,12 @@ mod tests {
     use sodiumoxide::randombytes::{randombytes, randombytes_uniform};
 
     use super::*;
-    use xaynet_core::{crypto::Signature, SumParticipantPublicKey, UpdateParticipantPublicKey};
+    use xaynet_core::{
+        crypto::Signature,
+        message::{MessageBuffer, Payload},
+        SumParticipantPublicKey,
+        UpdateParticipantPublicKey,
+    };
 
     #[test]
     fn test_participant() {
@@ -267,6 +360,46 @@ mod tests {
         assert_eq!(part.sum_signature, ParticipantTaskSignature::zeroed());
         assert_eq!(part.update_signature, ParticipantTaskSignature::zeroed());
         assert_eq!(part.task, Task::None);
+        assert_eq!(part.max_chunk_size, MAX_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_seal_message() {
+        let mut part = Participant::new().unwrap();
+        let EncryptKeyPair {
+            public: coord_pk,
+            secret: coord_sk,
+        } = EncryptKeyPair::generate();
+        let message = part.compose_sum_message(coord_pk);
+        let length = message.buffer_length();
+
+        // small enough message
+        let sealed = part.seal_message(&coord_pk, message.clone());
+        assert_eq!(sealed.len(), 1);
+        let bytes = coord_sk.decrypt(&sealed[0], &coord_pk).unwrap();
+        assert_eq!(bytes.len(), length);
+        let mut parsed = Message::from_bytes(&bytes).unwrap();
+        assert!(parsed.signature.take().is_some());
+        assert_eq!(parsed, message);
+
+        // multipart message
+        part.max_chunk_size = 10;
+        let sealed = part.seal_message(&coord_pk, message);
+        assert!(sealed.len() > 1);
+        for (i, sealed_chunk) in sealed.iter().enumerate() {
+            let bytes = coord_sk.decrypt(sealed_chunk, &coord_pk).unwrap();
+            let buffer = MessageBuffer::new(&bytes).unwrap();
+            assert!(buffer.as_ref().check_signature().is_ok());
+            let parsed = Message::from_bytes(&bytes).unwrap();
+            assert!(parsed.is_multipart);
+            match parsed.payload {
+                Payload::Chunk(chunk) => {
+                    assert_eq!(chunk.id as usize, i + 1);
+                    assert_eq!(chunk.last, i + 1 == sealed.len());
+                }
+                _ => panic!("expected a chunk payload"),
+            }
+        }
     }
 
     #[test]
@@ -0,0 +1,237 @@
+//! Local differential privacy for the model updates of the participants.
+//!
+//! Before a local model is masked, its L2 norm is clipped to a bound and calibrated noise is added
+//! to each weight. This bounds the influence of any single participant on the global model and
+//! protects the local model even from the aggregate. The noise is calibrated to the privacy
+//! budget `epsilon` that a participant spends in each round it sends an update.
+
+use std::f64::consts::PI;
+
+use sodiumoxide::randombytes::randombytes_into;
+use thiserror::Error;
+use xaynet_core::mask::{FromPrimitives, IntoPrimitives, Model};
+
+use crate::PetError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// The mechanism which adds noise to the clipped local model.
+pub enum NoiseMechanism {
+    /// Gaussian noise, which guarantees `(epsilon, delta)`-differential privacy.
+    ///
+    /// The classic calibration is used, which requires `epsilon < 1`.
+    Gaussian {
+        /// The probability with which the privacy guarantee may fail.
+        delta: f64,
+    },
+    /// Laplace noise, which guarantees `epsilon`-differential privacy.
+    ///
+    /// The noise is calibrated to the L1 norm bound implied by the L2 norm bound, which grows
+    /// with the square root of the model length.
+    Laplace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// The configuration of the local differential privacy.
+pub struct PrivacyConfig {
+    /// The bound of the L2 norm to which the local model is clipped.
+    pub clip_norm: f64,
+    /// The privacy budget spent per round.
+    pub epsilon: f64,
+    /// The noise mechanism.
+    pub mechanism: NoiseMechanism,
+}
+
+#[derive(Debug, Error)]
+/// Errors related to invalid privacy configurations.
+pub enum InvalidPrivacyConfig {
+    #[error("the clipping norm must be positive and finite")]
+    ClipNorm,
+    #[error("epsilon must be positive and finite, and less than 1 for Gaussian noise")]
+    Epsilon,
+    #[error("delta must be between 0 and 1")]
+    Delta,
+}
+
+impl PrivacyConfig {
+    /// Checks the privacy configuration.
+    ///
+    /// # Errors
+    /// Fails if a parameter is out of its range.
+    pub fn validate(&self) -> Result<(), InvalidPrivacyConfig> {
+        if !(self.clip_norm.is_finite() && self.clip_norm > 0.) {
+            return Err(InvalidPrivacyConfig::ClipNorm);
+        }
+        if !(self.epsilon.is_finite() && self.epsilon > 0.) {
+            return Err(InvalidPrivacyConfig::Epsilon);
+        }
+        if let NoiseMechanism::Gaussian { delta } = self.mechanism {
+            if self.epsilon >= 1. {
+                return Err(InvalidPrivacyConfig::Epsilon);
+            }
+            if !(0. < delta && delta < 1.) {
+                return Err(InvalidPrivacyConfig::Delta);
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the scale of the noise for a model of the given length.
+    ///
+    /// This is the standard deviation of Gaussian noise or the scale parameter of Laplace noise.
+    /// Since any two clipped models are at most twice the clipping norm apart, the sensitivity of
+    /// the local model is twice the clipping norm.
+    pub fn noise_scale(&self, model_len: usize) -> f64 {
+        let sensitivity = 2. * self.clip_norm;
+        match self.mechanism {
+            NoiseMechanism::Gaussian { delta } => {
+                sensitivity * (2. * (1.25 / delta).ln()).sqrt() / self.epsilon
+            }
+            NoiseMechanism::Laplace => sensitivity * (model_len as f64).sqrt() / self.epsilon,
+        }
+    }
+
+    /// Clips the L2 norm of the local model and adds noise to its weights.
+    ///
+    /// The privacy configuration must be valid.
+    ///
+    /// # Errors
+    /// Fails if a weight of the model can't be represented as a float.
+    pub fn privatize(&self, model: Model) -> Result<Model, PetError> {
+        let mut weights = model
+            .into_primitives()
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| PetError::InvalidModel)?;
+
+        let norm = weights
+            .iter()
+            .map(|weight| weight * weight)
+            .sum::<f64>()
+            .sqrt();
+        if norm > self.clip_norm {
+            debug!("clipping local model with L2 norm {}", norm);
+            let factor = self.clip_norm / norm;
+            weights.iter_mut().for_each(|weight| *weight *= factor);
+        }
+
+        let scale = self.noise_scale(weights.len());
+        for weight in weights.iter_mut() {
+            *weight += match self.mechanism {
+                NoiseMechanism::Gaussian { .. } => scale * standard_normal(),
+                NoiseMechanism::Laplace => scale * standard_laplace(),
+            };
+        }
+        Ok(Model::from_primitives_bounded(weights.into_iter()))
+    }
+}
+
+/// Samples a uniformly distributed number from the open interval `(0, 1)`.
+fn uniform() -> f64 {
+    let mut bytes = [0_u8; 8];
+    randombytes_into(&mut bytes);
+    // the 53 most significant bits fill the mantissa, the offset excludes both bounds
+    ((u64::from_le_bytes(bytes) >> 11) as f64 + 0.5) / (1_u64 << 53) as f64
+}
+
+/// Samples a normally distributed number with mean `0` and standard deviation `1`.
+fn standard_normal() -> f64 {
+    // Box-Muller transform
+    (-2. * uniform().ln()).sqrt() * (2. * PI * uniform()).cos()
+}
+
+/// Samples a Laplace distributed number with location `0` and scale `1`.
+fn standard_laplace() -> f64 {
+    let centered = uniform() - 0.5;
+    -centered.signum() * (1. - 2. * centered.abs()).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(clip_norm: f64, epsilon: f64, mechanism: NoiseMechanism) -> PrivacyConfig {
+        PrivacyConfig {
+            clip_norm,
+            epsilon,
+            mechanism,
+        }
+    }
+
+    #[test]
+    fn validate_privacy_config() {
+        let gaussian = NoiseMechanism::Gaussian { delta: 1e-5 };
+        assert!(config(1., 0.5, gaussian).validate().is_ok());
+        assert!(config(1., 2., NoiseMechanism::Laplace).validate().is_ok());
+
+        assert!(matches!(
+            config(0., 0.5, gaussian).validate(),
+            Err(InvalidPrivacyConfig::ClipNorm)
+        ));
+        assert!(matches!(
+            config(1., 2., gaussian).validate(),
+            Err(InvalidPrivacyConfig::Epsilon)
+        ));
+        assert!(matches!(
+            config(1., f64::INFINITY, NoiseMechanism::Laplace).validate(),
+            Err(InvalidPrivacyConfig::Epsilon)
+        ));
+        assert!(matches!(
+            config(1., 0.5, NoiseMechanism::Gaussian { delta: 0. }).validate(),
+            Err(InvalidPrivacyConfig::Delta)
+        ));
+    }
+
+    #[test]
+    fn noise_scale() {
+        let gaussian = config(0.5, 0.5, NoiseMechanism::Gaussian { delta: 1.25e-4 });
+        let expected = 2. * (2. * 1e4_f64.ln()).sqrt();
+        assert!((gaussian.noise_scale(100) - expected).abs() < 1e-12);
+
+        let laplace = config(0.5, 2., NoiseMechanism::Laplace);
+        assert_eq!(laplace.noise_scale(100), 5.);
+    }
+
+    #[test]
+    fn privatize_clips_and_adds_noise() {
+        sodiumoxide::init().unwrap();
+        let model = Model::from_primitives(vec![3_f64; 10_000].into_iter()).unwrap();
+        // with a tiny noise scale the model is just clipped
+        let privacy = config(1., 1e7, NoiseMechanism::Laplace);
+        let weights: Vec<f64> = privacy
+            .privatize(model.clone())
+            .unwrap()
+            .into_primitives_unchecked()
+            .collect();
+        let norm = weights.iter().map(|w| w * w).sum::<f64>().sqrt();
+        assert!((norm - 1.).abs() < 1e-3);
+
+        // the noise has the calibrated standard deviation
+        let privacy = config(1., 0.5, NoiseMechanism::Gaussian { delta: 1e-5 });
+        let weights: Vec<f64> = privacy
+            .privatize(model)
+            .unwrap()
+            .into_primitives_unchecked()
+            .map(|w: f64| w - 0.01)
+            .collect();
+        let std_dev = (weights.iter().map(|w| w * w).sum::<f64>() / weights.len() as f64).sqrt();
+        let scale = privacy.noise_scale(weights.len());
+        assert!((std_dev - scale).abs() < 0.05 * scale);
+    }
+
+    #[test]
+    fn samples_are_distributed() {
+        sodiumoxide::init().unwrap();
+        let n = 100_000;
+        let normal: Vec<f64> = (0..n).map(|_| standard_normal()).collect();
+        let mean = normal.iter().sum::<f64>() / n as f64;
+        let var = normal.iter().map(|x| x * x).sum::<f64>() / n as f64;
+        assert!(mean.abs() < 0.02);
+        assert!((var - 1.).abs() < 0.03);
+
+        // the variance of the standard Laplace distribution is 2
+        let laplace: Vec<f64> = (0..n).map(|_| standard_laplace()).collect();
+        let mean = laplace.iter().sum::<f64>() / n as f64;
+        let var = laplace.iter().map(|x| x * x).sum::<f64>() / n as f64;
+        assert!(mean.abs() < 0.03);
+        assert!((var - 2.).abs() < 0.1);
+    }
+}
@@ -1,6 +1,18 @@
 use sodiumoxide::{self, crypto::box_};
 
-use crate::{crypto::ByteObject, CoordinatorPublicKey};
+use crate::{
+    crypto::ByteObject,
+    mask::{
+        BoundType,
+        DataType,
+        GroupType,
+        MaskConfig,
+        MaskConfigPair,
+        ModelType,
+        NoiseParameters,
+    },
+    CoordinatorPublicKey,
+};
 
 /// The round parameters.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -13,6 +25,14 @@ pub struct RoundParameters {
     pub update: f64,
     /// The random round seed.
     pub seed: RoundSeed,
+    /// The Unix timestamp (in seconds) at which the round starts, i.e. from which on the
+    /// coordinator accepts sum messages.
+    pub start_time: u64,
+    /// The masking configurations of the model weights and of the scalar.
+    pub mask_config: MaskConfigPair,
+    /// The parameters of the noise which the update participants add to their masked models, if
+    /// distributed differential privacy is enabled.
+    pub noise: Option<NoiseParameters>,
 }
 
 impl Default for RoundParameters {
@@ -22,6 +42,15 @@ impl Default for RoundParameters {
             sum: 0.0,
             update: 0.0,
             seed: RoundSeed::zeroed(),
+            start_time: 0,
+            mask_config: MaskConfig {
+                group_type: GroupType::Prime,
+                data_type: DataType::F32,
+                bound_type: BoundType::B0,
+                model_type: ModelType::M3,
+            }
+            .into(),
+            noise: None,
         }
     }
 }
@@ -51,3 +80,59 @@ impl ByteObject for RoundSeed {
         self.0.as_ref()
     }
 }
+
+/// The reason why the coordinator rejected a PET message.
+///
+/// It is serialized as a `snake_case` string, so that clients can match on
+/// it without parsing the human readable error message. The C-API of the
+/// mobile client returns the index of the variant, hence new variants must be
+/// appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageErrorKind {
+    /// The message could not be decrypted with the coordinator secret key.
+    Decrypt,
+    /// The message could not be parsed.
+    Parsing,
+    /// The message signature is invalid.
+    InvalidMessageSignature,
+    /// The message was encrypted for another coordinator public key.
+    InvalidCoordinatorPublicKey,
+    /// The message was not expected in the current phase.
+    UnexpectedMessage,
+    /// The participant has too many incomplete multipart messages.
+    TooManyPendingMessages,
+    /// The multipart message exceeds the maximum message size.
+    MessageTooLarge,
+    /// The coordinator buffers too many incomplete multipart messages. The
+    /// message should be resent later.
+    MultipartBufferFull,
+    /// The participant is not on the allowlist of the coordinator.
+    ParticipantNotAllowed,
+    /// The participant is not eligible for the sum task.
+    NotSumEligible,
+    /// The participant is not eligible for the update task.
+    NotUpdateEligible,
+    /// The message was rejected by the state machine.
+    MessageRejected,
+    /// The model or scalar sent by the participant could not be aggregated.
+    AggregationFailed,
+    /// The seed dictionary sent by the participant is invalid.
+    InvalidLocalSeedDict,
+    /// The request queue of the coordinator is full. The message should be
+    /// resent later.
+    RequestQueueFull,
+    /// The message could not be processed due to an internal error.
+    InternalError,
+}
+
+/// The body of the response sent by the coordinator when it rejects a PET
+/// message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageError {
+    /// The reason why the message was rejected.
+    #[serde(rename = "error")]
+    pub kind: MessageErrorKind,
+    /// A human readable description of the error.
+    pub message: String,
+}
@@ -177,7 +177,7 @@ impl MaskConfig {
     ///
     /// # Panics
     /// Panics if the bytes per number can't be represented as usize.
-    pub(crate) fn bytes_per_number(&self) -> usize {
+    pub fn bytes_per_number(&self) -> usize {
         let max_number = self.order() - BigUint::from(1_u8);
         let bpn = (max_number.bits() + 7) / 8;
 
@@ -632,3 +632,25 @@ impl MaskConfig {
         BigUint::from_str_radix(order_str, 10).unwrap()
     }
 }
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// A pair of masking configurations.
+///
+/// The model weights and the scalar are masked, aggregated and unmasked independently of each
+/// other, hence the scalar may use a smaller group and a tighter bound than the model weights.
+pub struct MaskConfigPair {
+    /// The masking configuration of the model weights.
+    pub model: MaskConfig,
+    /// The masking configuration of the scalar.
+    pub scalar: MaskConfig,
+}
+
+impl From<MaskConfig> for MaskConfigPair {
+    /// Creates a pair which uses the same masking configuration for the model and the scalar.
+    fn from(config: MaskConfig) -> Self {
+        Self {
+            model: config,
+            scalar: config,
+        }
+    }
+}
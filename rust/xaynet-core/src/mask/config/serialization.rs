@@ -11,7 +11,7 @@ use anyhow::{anyhow, Context};
 use crate::{
     mask::config::MaskConfig,
     message::{
-        traits::{FromBytes, ToBytes},
+        traits::{take_bytes, FromBytes, ToBytes},
         DecodeError,
     },
 };
@@ -164,6 +164,13 @@ impl FromBytes for MaskConfig {
                 .context("invalid masking config")?,
         })
     }
+
+    fn from_byte_stream<I: Iterator<Item = u8> + ExactSizeIterator>(
+        iter: &mut I,
+    ) -> Result<Self, DecodeError> {
+        let bytes = take_bytes(iter, MASK_CONFIG_BUFFER_LEN)?;
+        Self::from_bytes(&bytes)
+    }
 }
 
 #[cfg(test)]
@@ -199,4 +206,21 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn deserialize_from_stream() {
+        let mut stream = vec![1, 1, 255, 9, 0xff].into_iter();
+        let config = MaskConfig::from_byte_stream(&mut stream).unwrap();
+        assert_eq!(
+            config,
+            MaskConfig {
+                group_type: GroupType::Prime,
+                data_type: DataType::F64,
+                bound_type: BoundType::Bmax,
+                model_type: ModelType::M9,
+            }
+        );
+        assert_eq!(stream.len(), 1);
+        assert!(MaskConfig::from_byte_stream(&mut stream).is_err());
+    }
 }
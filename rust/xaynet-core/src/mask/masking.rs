@@ -4,11 +4,10 @@
 //!
 //! [mask module]: ../index.html
 
-use std::iter::{self, Iterator};
-
 use num::{
-    bigint::{BigInt, BigUint, ToBigInt},
+    bigint::{BigInt, ToBigInt},
     clamp,
+    integer::Integer,
     rational::Ratio,
 };
 use rand::SeedableRng;
@@ -17,7 +16,13 @@ use thiserror::Error;
 
 use crate::{
     crypto::{prng::generate_integer, ByteObject},
-    mask::{config::MaskConfig, model::Model, object::MaskObject, seed::MaskSeed},
+    mask::{
+        config::{MaskConfig, MaskConfigPair},
+        model::Model,
+        noise::NoiseParameters,
+        object::MaskObject,
+        seed::MaskSeed,
+    },
 };
 
 #[derive(Debug, Error, Eq, PartialEq)]
@@ -34,6 +39,9 @@ pub enum UnmaskingError {
 
     #[error("the mask is invalid")]
     InvalidMask,
+
+    #[error("the unmasked values are out of bounds")]
+    OutOfBounds,
 }
 
 #[derive(Debug, Error)]
@@ -49,7 +57,7 @@ pub enum AggregationError {
     ModelMismatch,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// An aggregator for masks and masked models.
 pub struct Aggregation {
     nb_models: usize,
@@ -137,6 +145,45 @@ impl Aggregation {
         Ok(())
     }
 
+    /// Verifies that unmasking of the aggregated masked model with the given `mask` yields values
+    /// within the bounds of the masking configuration, without actually unmasking it.
+    ///
+    /// Unmasking with a wrong mask yields values which are spread over the whole finite group.
+    /// Hence, wrong masks are detected with high probability, unless the number of aggregated
+    /// models is close to the maximum number that the chosen masking configuration allows.
+    ///
+    /// # Errors
+    /// Fails if [`validate_unmasking()`] fails or if any of the unmasked values is out of bounds.
+    ///
+    /// [`validate_unmasking()`]: #method.validate_unmasking
+    pub fn verify_unmasking(&self, mask: &MaskObject) -> Result<(), UnmaskingError> {
+        self.validate_unmasking(mask)?;
+
+        // every masked model is shifted into the range [0, 2 * add_shift * exp_shift] before
+        // masking, hence the aggregated models are bounded by the sum of these ranges
+        let bound = self.object.config.add_shift()
+            * self.object.config.exp_shift()
+            * BigInt::from(2 * self.nb_models);
+        let order = self.object.config.order();
+        let in_bounds =
+            self.object
+                .data
+                .iter()
+                .zip(mask.data.iter())
+                .all(|(masked_weight, mask)| {
+                    // PANIC_SAFE: the mask is valid, see `unmask()`
+                    let n = (masked_weight + &order - mask) % &order;
+                    // UNWRAP_SAFE: to_bigint never fails for BigUint
+                    Ratio::<BigInt>::from(n.to_bigint().unwrap()) <= bound
+                });
+
+        if in_bounds {
+            Ok(())
+        } else {
+            Err(UnmaskingError::OutOfBounds)
+        }
+    }
+
     /// Unmasks the aggregated masked model with the given `mask`.
     ///
     /// It should be checked that [`validate_unmasking()`] succeeds before calling this, since
@@ -267,89 +314,135 @@ impl Aggregation {
 
 /// A masker for models.
 pub struct Masker {
-    config: MaskConfig,
+    config: MaskConfigPair,
     seed: MaskSeed,
+    noise: Option<NoiseParameters>,
 }
 
 impl Masker {
-    /// Creates a new masker with the given masking `config`uration with a randomly generated seed.
-    pub fn new(config: MaskConfig) -> Self {
+    /// Creates a new masker with the given masking `config`urations with a randomly generated
+    /// seed.
+    pub fn new(config: MaskConfigPair) -> Self {
         Self {
             config,
             seed: MaskSeed::generate(),
+            noise: None,
+        }
+    }
+
+    /// Creates a new masker with the given masking `config`urations and `seed`.
+    pub fn with_seed(config: MaskConfigPair, seed: MaskSeed) -> Self {
+        Self {
+            config,
+            seed,
+            noise: None,
         }
     }
 
-    /// Creates a new masker with the given masking `config`uration and `seed`.
-    pub fn with_seed(config: MaskConfig, seed: MaskSeed) -> Self {
-        Self { config, seed }
+    /// Sets the parameters of the noise share which is added to the masked model.
+    pub fn with_noise(self, noise: Option<NoiseParameters>) -> Self {
+        Self { noise, ..self }
     }
 }
 
 impl Masker {
-    /// Masks the given `model` wrt the masking configuration. Enforces bounds on the scalar and
+    /// Masks the given `model` wrt the masking configurations. Enforces bounds on the scalar and
     /// weights.
     ///
     /// The masking proceeds in the following steps:
-    /// - Clamp the scalar and the weights according to the masking configuration.
+    /// - Clamp the scalar according to the scalar masking configuration and the weights according
+    ///   to the model masking configuration.
     /// - Scale the weights by the scalar.
-    /// - Shift the weights into the non-negative reals.
-    /// - Shift the weights into the non-negative integers.
-    /// - Shift the weights into the finite group.
-    /// - Mask the weights with random elements from the finite group.
+    /// - If noise parameters are set, clip the L2 norm of the scaled weights.
+    /// - Shift the weights and the scalar into the non-negative reals.
+    /// - Shift the weights and the scalar into the non-negative integers.
+    /// - If noise parameters are set, add a noise share to each weight.
+    /// - Shift the weights and the scalar into their finite groups.
+    /// - Mask the weights and the scalar with random elements from their finite groups.
     ///
     /// The random elements are derived from a seeded PRNG. Unmasking as performed in [`unmask()`]
-    /// proceeds in reverse order.
+    /// proceeds in reverse order. The noise is derived from a separate, randomly seeded PRNG, since
+    /// the mask seed is known to the sum participants.
+    ///
+    /// The noise may push the aggregate of the weights out of the bounds of the masking
+    /// configuration, which wraps it around in the finite group and goes unnoticed when unmasking.
+    /// Hence, the noise parameters must leave enough headroom to the bounds, which is checked by
+    /// [`NoiseParameters::fits_bounds()`].
     ///
     /// [`unmask()`]: struct.Aggregation.html#method.unmask
+    /// [`NoiseParameters::fits_bounds()`]: struct.NoiseParameters.html#method.fits_bounds
     pub fn mask(self, scalar: f64, model: Model) -> (MaskSeed, MaskObject, MaskObject) {
-        let mut random_ints = self.random_ints();
-        let Self { seed, config } = self;
-
-        let exp_shift = config.exp_shift();
-        let add_shift = config.add_shift();
-        let order = config.order();
-        let higher_bound = &add_shift;
-        let lower_bound = -&add_shift;
+        let Self {
+            seed,
+            config,
+            noise,
+        } = self;
+        let mut prng = ChaCha20Rng::from_seed(seed.as_array());
 
+        let scalar_add_shift = config.scalar.add_shift();
         let scalar_ratio = crate::mask::model::float_to_ratio_bounded(scalar);
-        // HACK reuse upper bound for scaled weights for now, really should be tighter
-        // TODO give scalar its own config in later refactoring
         let zero = Ratio::<BigInt>::from_float(0_f64).unwrap();
-        let scalar_clamped = clamp(&scalar_ratio, &zero, higher_bound);
+        let scalar_clamped = clamp(&scalar_ratio, &zero, &scalar_add_shift);
 
-        let masked_weights = model
-            .into_iter()
-            .zip(&mut random_ints)
-            .map(|(weight, rand_int)| {
-                let scaled = scalar_clamped * &weight;
-                let scaled_clamped = clamp(&scaled, &lower_bound, higher_bound);
-                // PANIC_SAFE: shifted weight is guaranteed to be non-negative
-                let shifted = ((scaled_clamped + &add_shift) * &exp_shift)
-                    .to_integer()
-                    .to_biguint()
-                    .unwrap();
-                (shifted + rand_int) % &order
-            })
-            .collect();
-        let masked_model = MaskObject::new(config, masked_weights);
+        let exp_shift = config.model.exp_shift();
+        let add_shift = config.model.add_shift();
+        let order = config.model.order();
+        let higher_bound = &add_shift;
+        let lower_bound = -&add_shift;
+
+        let masked_weights = match noise {
+            None => model
+                .into_iter()
+                .map(|weight| {
+                    let scaled = scalar_clamped * &weight;
+                    let scaled_clamped = clamp(&scaled, &lower_bound, higher_bound);
+                    // PANIC_SAFE: shifted weight is guaranteed to be non-negative
+                    let shifted = ((scaled_clamped + &add_shift) * &exp_shift)
+                        .to_integer()
+                        .to_biguint()
+                        .unwrap();
+                    (shifted + generate_integer(&mut prng, &order)) % &order
+                })
+                .collect(),
+            Some(noise) => {
+                // the clipping needs the norm of all scaled weights
+                let mut scaled_weights = model
+                    .into_iter()
+                    .map(|weight| {
+                        let scaled = scalar_clamped * &weight;
+                        clamp(&scaled, &lower_bound, higher_bound).clone()
+                    })
+                    .collect::<Vec<_>>();
+                noise.clip(&mut scaled_weights);
+
+                let mut noise_prng = ChaCha20Rng::from_seed(MaskSeed::generate().as_array());
+                let order_int = BigInt::from(order.clone());
+                scaled_weights
+                    .into_iter()
+                    .map(|scaled_clamped| {
+                        let shifted = ((scaled_clamped + &add_shift) * &exp_shift).to_integer()
+                            + noise.sample(&exp_shift, &mut noise_prng);
+                        // PANIC_SAFE: the remainder is guaranteed to be non-negative
+                        let shifted = shifted.mod_floor(&order_int).to_biguint().unwrap();
+                        (shifted + generate_integer(&mut prng, &order)) % &order
+                    })
+                    .collect()
+            }
+        };
+        let masked_model = MaskObject::new(config.model, masked_weights);
 
-        let rand_int = random_ints.next().unwrap();
-        let shifted = ((scalar_clamped + &add_shift) * &exp_shift)
+        let scalar_order = config.scalar.order();
+        // PANIC_SAFE: shifted scalar is guaranteed to be non-negative
+        let shifted = ((scalar_clamped + &scalar_add_shift) * config.scalar.exp_shift())
             .to_integer()
             .to_biguint()
             .unwrap();
-        let masked_scalar = MaskObject::new(config, vec![(shifted + rand_int) % &order]);
+        let rand_int = generate_integer(&mut prng, &scalar_order);
+        let masked_scalar =
+            MaskObject::new(config.scalar, vec![(shifted + rand_int) % &scalar_order]);
 
         (seed, masked_model, masked_scalar)
     }
-
-    /// Creates an iterator that yields randomly generated integers wrt the masking configuration.
-    fn random_ints(&self) -> impl Iterator<Item = BigUint> {
-        let order = self.config.order();
-        let mut prng = ChaCha20Rng::from_seed(self.seed.as_array());
-        iter::from_fn(move || Some(generate_integer(&mut prng, &order)))
-    }
 }
 
 #[cfg(test)]
@@ -372,7 +465,7 @@ mod tests {
             MaskConfig,
             ModelType::M3,
         },
-        model::FromPrimitives,
+        model::{FromPrimitives, IntoPrimitives},
     };
 
     /// Generate tests for masking and unmasking of a single model:
@@ -425,13 +518,13 @@ mod tests {
                     // b. derive the mask corresponding to the seed used
                     // c. unmask the model and check it against the original one.
                     let (mask_seed, masked_model, masked_scalar) =
-                        Masker::new(config.clone()).mask(1_f64, model.clone());
+                        Masker::new(config.into()).mask(1_f64, model.clone());
                     assert_eq!(masked_model.data.len(), model.len());
                     assert!(masked_model.is_valid());
                     assert_eq!(masked_scalar.data.len(), 1);
                     assert!(masked_scalar.is_valid());
 
-                    let (mask, _scalar_mask) = mask_seed.derive_mask(model.len(), config);
+                    let (mask, _scalar_mask) = mask_seed.derive_mask(model.len(), config.into());
                     let aggregation = Aggregation::from(masked_model);
                     let unmasked_model = aggregation.unmask(mask);
 
@@ -729,8 +822,8 @@ mod tests {
                             });
 
                         let (mask_seed, masked_model, masked_scalar) =
-                            Masker::new(config).mask(scalar, model);
-                        let (mask, scalar_mask) = mask_seed.derive_mask($len as usize, config);
+                            Masker::new(config.into()).mask(scalar, model);
+                        let (mask, scalar_mask) = mask_seed.derive_mask($len as usize, config.into());
 
                         assert!(
                             aggregated_masked_model.validate_aggregation(&masked_model).is_ok()
@@ -747,7 +840,9 @@ mod tests {
                         aggregated_scalar_mask.aggregate(scalar_mask);
                     }
 
-                    let unmasked_model = aggregated_masked_model.unmask(aggregated_mask.into());
+                    let aggregated_mask: MaskObject = aggregated_mask.into();
+                    assert!(aggregated_masked_model.verify_unmasking(&aggregated_mask).is_ok());
+                    let unmasked_model = aggregated_masked_model.unmask(aggregated_mask);
                     let tolerance = Ratio::from_integer(BigInt::from($count as usize))
                         / Ratio::from_integer(config.exp_shift());
                     assert!(
@@ -837,4 +932,125 @@ mod tests {
     test_masking_and_aggregation!(pow_i64_b4, Power2, i64, 10_000, 10, 5);
     test_masking_and_aggregation!(pow_i64_b6, Power2, i64, 1_000_000, 10, 5);
     test_masking_and_aggregation!(pow_i64_bmax, Power2, i64, 10, 5);
+
+    #[test]
+    fn test_verify_unmasking_with_wrong_mask() {
+        let config = MaskConfig {
+            group_type: Prime,
+            data_type: F32,
+            bound_type: B0,
+            model_type: M3,
+        };
+        let model = Model::from_primitives(vec![0.5_f32; 10].into_iter()).unwrap();
+        let (_, masked_model, _) =
+            Masker::with_seed(config.into(), MaskSeed::from_slice_unchecked(&[1; 32]))
+                .mask(1_f64, model);
+        let aggregation = Aggregation::from(masked_model);
+
+        let (mask, _) = MaskSeed::from_slice_unchecked(&[1; 32]).derive_mask(10, config.into());
+        assert!(aggregation.verify_unmasking(&mask).is_ok());
+
+        let (wrong_mask, _) =
+            MaskSeed::from_slice_unchecked(&[2; 32]).derive_mask(10, config.into());
+        assert!(aggregation.validate_unmasking(&wrong_mask).is_ok());
+        assert_eq!(
+            aggregation.verify_unmasking(&wrong_mask).unwrap_err(),
+            UnmaskingError::OutOfBounds,
+        );
+    }
+
+    #[test]
+    fn test_masking_with_separate_scalar_config() {
+        let config = MaskConfigPair {
+            model: MaskConfig {
+                group_type: Prime,
+                data_type: F32,
+                bound_type: B2,
+                model_type: M3,
+            },
+            scalar: MaskConfig {
+                group_type: Power2,
+                data_type: F32,
+                bound_type: B0,
+                model_type: M3,
+            },
+        };
+        let mut model_agg = Aggregation::new(config.model, 10);
+        let mut scalar_agg = Aggregation::new(config.scalar, 1);
+        let mut model_mask_agg = Aggregation::new(config.model, 10);
+        let mut scalar_mask_agg = Aggregation::new(config.scalar, 1);
+        for weight in &[1_f32, 2_f32] {
+            let model = Model::from_primitives(vec![*weight; 10].into_iter()).unwrap();
+            let (seed, masked_model, masked_scalar) = Masker::new(config).mask(0.5, model);
+            assert_eq!(masked_model.config, config.model);
+            assert_eq!(masked_scalar.config, config.scalar);
+            assert!(model_agg.validate_aggregation(&masked_model).is_ok());
+            model_agg.aggregate(masked_model);
+            assert!(scalar_agg.validate_aggregation(&masked_scalar).is_ok());
+            scalar_agg.aggregate(masked_scalar);
+
+            let (model_mask, scalar_mask) = seed.derive_mask(10, config);
+            model_mask_agg.aggregate(model_mask);
+            scalar_mask_agg.aggregate(scalar_mask);
+        }
+
+        let model_mask = model_mask_agg.into();
+        let scalar_mask = scalar_mask_agg.into();
+        assert!(model_agg.verify_unmasking(&model_mask).is_ok());
+        assert!(scalar_agg.verify_unmasking(&scalar_mask).is_ok());
+        assert_eq!(
+            model_agg.unmask(model_mask),
+            Model::from_primitives(vec![1.5_f32; 10].into_iter()).unwrap(),
+        );
+        assert_eq!(
+            scalar_agg.unmask(scalar_mask),
+            Model::from_primitives(vec![1_f32].into_iter()).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_masking_with_noise() {
+        let config = MaskConfigPair::from(MaskConfig {
+            group_type: Prime,
+            data_type: F64,
+            bound_type: B2,
+            model_type: M3,
+        });
+        let unmask_noisy = |noise: NoiseParameters, weight: f64, len: usize| {
+            let mut model_agg = Aggregation::new(config.model, len);
+            let mut mask_agg = Aggregation::new(config.model, len);
+            for _ in 0..2 {
+                let model = Model::from_primitives(vec![weight; len].into_iter()).unwrap();
+                let (seed, masked_model, _) = Masker::new(config)
+                    .with_noise(Some(noise))
+                    .mask(1_f64, model);
+                model_agg.aggregate(masked_model);
+                mask_agg.aggregate(seed.derive_mask(len, config).0);
+            }
+            let mask = mask_agg.into();
+            assert!(model_agg.verify_unmasking(&mask).is_ok());
+            model_agg
+                .unmask(mask)
+                .into_primitives_unchecked()
+                .collect::<Vec<f64>>()
+        };
+
+        // without noise the models are just clipped
+        let noise = NoiseParameters {
+            clip_norm: 1.,
+            std_dev: 0.,
+        };
+        for weight in unmask_noisy(noise, 3., 4) {
+            assert!((weight - 1.).abs() < 1e-9);
+        }
+
+        // the noise shares add up
+        let noise = NoiseParameters {
+            clip_norm: 1.,
+            std_dev: 0.1,
+        };
+        let weights = unmask_noisy(noise, 0.01, 1_000);
+        let std_dev = (weights.iter().map(|w| (w - 0.02).powi(2)).sum::<f64>() / 1_000.).sqrt();
+        assert!((std_dev - 0.1 * 2_f64.sqrt()).abs() < 0.02);
+    }
 }
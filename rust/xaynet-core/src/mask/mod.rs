@@ -81,9 +81,11 @@
 //! personal information if the model is generalized enough.
 //!
 //! ## Masking
-//! A [`Model`] can be masked with a [`Masker`], which requires a [`MaskConfig`]. During the
-//! masking, the model weights are scaled, then embedded as elements of the chosen finite group and
-//! finally masked by randomly generated elements from that very same finite group. The scalar
+//! A [`Model`] can be masked with a [`Masker`], which requires a [`MaskConfigPair`] of masking
+//! configurations for the model weights and for the scalar. During the masking, the model weights
+//! are scaled, then embedded as elements of the chosen finite group and finally masked by randomly
+//! generated elements from that very same finite group. The scalar is masked likewise wrt its own
+//! masking configuration, which may use a smaller group and a tighter bound. The scalar
 //! provides the necessary means to perform different aggregation strategies, for example federated
 //! averaging. The masked model is returned as a [`MaskObject`] and the mask used to mask the model
 //! can be generated via the additionally returned [`MaskSeed`].
@@ -103,12 +105,12 @@
 //! };
 //!
 //! // mask the local models
-//! let (local_mask_seed_1, masked_local_model_1, masked_local_scalar_1) = Masker::new(config).mask(scalar, local_model_1);
-//! let (local_mask_seed_2, masked_local_model_2, masked_local_scalar_2) = Masker::new(config).mask(scalar, local_model_2);
+//! let (local_mask_seed_1, masked_local_model_1, masked_local_scalar_1) = Masker::new(config.into()).mask(scalar, local_model_1);
+//! let (local_mask_seed_2, masked_local_model_2, masked_local_scalar_2) = Masker::new(config.into()).mask(scalar, local_model_2);
 //!
 //! // derive the masks of the local masked models
-//! let local_mask_1 = local_mask_seed_1.derive_mask(number_weights, config);
-//! let local_mask_2 = local_mask_seed_2.derive_mask(number_weights, config);
+//! let local_mask_1 = local_mask_seed_1.derive_mask(number_weights, config.into());
+//! let local_mask_2 = local_mask_seed_2.derive_mask(number_weights, config.into());
 //! ```
 //!
 //! ## Aggregation
@@ -124,10 +126,10 @@
 //! # let local_model_1 = Model::from_primitives_bounded(vec![0_f32; number_weights].into_iter());
 //! # let local_model_2 = Model::from_primitives_bounded(vec![1_f32; number_weights].into_iter());
 //! # let config = MaskConfig { group_type: GroupType::Prime, data_type: DataType::F32, bound_type: BoundType::B0, model_type: ModelType::M3};
-//! # let (local_mask_seed_1, masked_local_model_1, masked_local_scalar_1) = Masker::new(config).mask(scalar, local_model_1);
-//! # let (local_mask_seed_2, masked_local_model_2, masked_local_scalar_2) = Masker::new(config).mask(scalar, local_model_2);
-//! # let (local_model_mask_1, local_scalar_mask_1) = local_mask_seed_1.derive_mask(number_weights, config);
-//! # let (local_model_mask_2, local_scalar_mask_2) = local_mask_seed_2.derive_mask(number_weights, config);
+//! # let (local_mask_seed_1, masked_local_model_1, masked_local_scalar_1) = Masker::new(config.into()).mask(scalar, local_model_1);
+//! # let (local_mask_seed_2, masked_local_model_2, masked_local_scalar_2) = Masker::new(config.into()).mask(scalar, local_model_2);
+//! # let (local_model_mask_1, local_scalar_mask_1) = local_mask_seed_1.derive_mask(number_weights, config.into());
+//! # let (local_model_mask_2, local_scalar_mask_2) = local_mask_seed_2.derive_mask(number_weights, config.into());
 //! // aggregate the local model masks (similarly for local scalar masks)
 //! let mut mask_aggregator = Aggregation::new(config, number_weights);
 //! if let Ok(_) = mask_aggregator.validate_aggregation(&local_model_mask_1) {
@@ -160,10 +162,10 @@
 //! # let local_model_1 = Model::from_primitives_bounded(vec![0_f32; number_weights].into_iter());
 //! # let local_model_2 = Model::from_primitives_bounded(vec![1_f32; number_weights].into_iter());
 //! # let config = MaskConfig { group_type: GroupType::Prime, data_type: DataType::F32, bound_type: BoundType::B0, model_type: ModelType::M3};
-//! # let (local_mask_seed_1, masked_local_model_1, masked_local_scalar_1) = Masker::new(config).mask(scalar, local_model_1);
-//! # let (local_mask_seed_2, masked_local_model_2, masked_local_scalar_2) = Masker::new(config).mask(scalar, local_model_2);
-//! # let (local_model_mask_1, local_scalar_mask_1) = local_mask_seed_1.derive_mask(number_weights, config);
-//! # let (local_model_mask_2, local_scalar_mask_2) = local_mask_seed_2.derive_mask(number_weights, config);
+//! # let (local_mask_seed_1, masked_local_model_1, masked_local_scalar_1) = Masker::new(config.into()).mask(scalar, local_model_1);
+//! # let (local_mask_seed_2, masked_local_model_2, masked_local_scalar_2) = Masker::new(config.into()).mask(scalar, local_model_2);
+//! # let (local_model_mask_1, local_scalar_mask_1) = local_mask_seed_1.derive_mask(number_weights, config.into());
+//! # let (local_model_mask_2, local_scalar_mask_2) = local_mask_seed_2.derive_mask(number_weights, config.into());
 //! # let mut mask_aggregator = Aggregation::new(config, number_weights);
 //! # if let Ok(_) = mask_aggregator.validate_aggregation(&local_model_mask_1) { mask_aggregator.aggregate(local_model_mask_1); };
 //! # if let Ok(_) = mask_aggregator.validate_aggregation(&local_model_mask_2) { mask_aggregator.aggregate(local_model_mask_2); };
@@ -184,6 +186,7 @@
 pub(crate) mod config;
 pub(crate) mod masking;
 pub(crate) mod model;
+pub(crate) mod noise;
 pub(crate) mod object;
 pub(crate) mod seed;
 
@@ -195,10 +198,12 @@ pub use self::{
         GroupType,
         InvalidMaskConfigError,
         MaskConfig,
+        MaskConfigPair,
         ModelType,
     },
     masking::{Aggregation, AggregationError, Masker, UnmaskingError},
     model::{FromPrimitives, IntoPrimitives, Model, ModelCastError, PrimitiveCastError},
+    noise::{NoiseParameters, NOISE_HEADROOM},
     object::{serialization::MaskObjectBuffer, InvalidMaskObjectError, MaskObject},
     seed::{EncryptedMaskSeed, MaskSeed},
 };
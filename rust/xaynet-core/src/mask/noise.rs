@@ -0,0 +1,192 @@
+//! Distributed differential privacy for masked models.
+//!
+//! See the [mask module] documentation since this is a private module anyways.
+//!
+//! [mask module]: ../index.html
+
+use std::f64::consts::PI;
+
+use num::{bigint::BigInt, rational::Ratio, traits::Zero};
+use rand::{distributions::OpenClosed01, Rng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::mask::{
+    config::MaskConfig,
+    model::{float_to_ratio_bounded, ratio_to_float},
+};
+
+/// The number of standard deviations of the aggregated noise which must fit into the headroom
+/// between the clipped weights and the bounds of a masking configuration.
+pub const NOISE_HEADROOM: f64 = 6.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// The parameters of the noise which each update participant adds to its masked model.
+///
+/// Each participant clips its scaled model and adds a share of Gaussian noise to it, which is
+/// rounded to the integers of the masking configuration. The shares of all participants add up in
+/// the aggregated model, such that the aggregate is differentially private even though each
+/// participant only adds a fraction of the noise. The noise of the individual participants is
+/// hidden by the masks.
+pub struct NoiseParameters {
+    /// The bound of the L2 norm to which the scaled model of a participant is clipped.
+    pub clip_norm: f64,
+    /// The standard deviation of the noise share which a participant adds to each scaled weight.
+    pub std_dev: f64,
+}
+
+impl NoiseParameters {
+    /// Calibrates the noise shares such that the aggregate of at least `min_count` models is
+    /// `(epsilon, delta)`-differentially private.
+    ///
+    /// The aggregate noise follows the classic Gaussian mechanism, which requires `epsilon < 1`.
+    /// Adding or removing a participant changes the aggregate by at most the `clip_norm`.
+    pub fn calibrate(clip_norm: f64, epsilon: f64, delta: f64, min_count: usize) -> Self {
+        let std_dev = clip_norm * (2. * (1.25 / delta).ln()).sqrt() / epsilon;
+        Self {
+            clip_norm,
+            std_dev: std_dev / (min_count.max(1) as f64).sqrt(),
+        }
+    }
+
+    /// Checks whether the aggregated noise of any number of at least `min_count` models stays
+    /// within the bounds of the masking configuration with overwhelming probability.
+    ///
+    /// Each clipped weight leaves a headroom of `add_shift - clip_norm` to the bounds, hence the
+    /// aggregate of `n` models leaves `n` times this headroom, while the aggregated noise only has
+    /// a standard deviation of `std_dev * sqrt(n)`. The headroom must cover [`NOISE_HEADROOM`]
+    /// standard deviations for the smallest `n`, since the aggregate would wrap around the finite
+    /// group otherwise, which corrupts the unmasked model.
+    pub fn fits_bounds(&self, config: &MaskConfig, min_count: usize) -> bool {
+        let add_shift = ratio_to_float::<f64>(&config.add_shift()).unwrap_or(f64::MAX);
+        let min_count = min_count.max(1) as f64;
+        add_shift - self.clip_norm >= NOISE_HEADROOM * self.std_dev / min_count.sqrt()
+    }
+
+    /// Clips the L2 norm of the scaled weights to the `clip_norm`.
+    pub(crate) fn clip(&self, weights: &mut [Ratio<BigInt>]) {
+        let floats = weights
+            .iter()
+            .map(|weight| ratio_to_float::<f64>(weight).unwrap_or(f64::MAX))
+            .collect::<Vec<_>>();
+        // the weights are normalized by their maximum to avoid an overflow
+        let max = floats
+            .iter()
+            .fold(0_f64, |max, weight| max.max(weight.abs()));
+        if max == 0. {
+            return;
+        }
+        let normalized_norm = floats
+            .iter()
+            .map(|weight| (weight / max).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        if max * normalized_norm > self.clip_norm {
+            let factor = float_to_ratio_bounded(self.clip_norm)
+                / (float_to_ratio_bounded(max) * float_to_ratio_bounded(normalized_norm));
+            weights.iter_mut().for_each(|weight| *weight *= &factor);
+        }
+    }
+
+    /// Samples a noise share for a weight in the integer domain of a masking configuration with
+    /// the given exponential shift.
+    pub(crate) fn sample(&self, exp_shift: &BigInt, prng: &mut ChaCha20Rng) -> BigInt {
+        // Box-Muller transform
+        let radius = (-2. * prng.sample::<f64, _>(OpenClosed01).ln()).sqrt();
+        let normal = radius * (2. * PI * prng.gen::<f64>()).cos();
+        let noise = float_to_ratio_bounded(self.std_dev * normal);
+        if noise.is_zero() {
+            return BigInt::zero();
+        }
+        (noise * exp_shift).round().to_integer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::traits::ToPrimitive;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn calibrate_noise() {
+        let noise = NoiseParameters::calibrate(2., 0.5, 1.25e-4, 4);
+        let expected = 2. * (2. * 1e4_f64.ln()).sqrt() / 0.5 / 2.;
+        assert_eq!(noise.clip_norm, 2.);
+        assert!((noise.std_dev - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn noise_fits_bounds() {
+        use crate::mask::config::{BoundType, DataType, GroupType, ModelType};
+
+        let config = |bound_type| MaskConfig {
+            group_type: GroupType::Prime,
+            data_type: DataType::F32,
+            bound_type,
+            model_type: ModelType::M3,
+        };
+        // the aggregated noise has a standard deviation of about 9.7
+        let noise = NoiseParameters::calibrate(1., 0.5, 1e-5, 3);
+        assert!(!noise.fits_bounds(&config(BoundType::B0), 3));
+        assert!(noise.fits_bounds(&config(BoundType::B2), 3));
+        let noise = NoiseParameters::calibrate(90., 0.5, 1e-5, 3);
+        assert!(!noise.fits_bounds(&config(BoundType::B2), 3));
+
+        // clipping to the bound leaves no headroom for any noise
+        let noise = NoiseParameters {
+            clip_norm: 1.,
+            std_dev: 0.,
+        };
+        assert!(noise.fits_bounds(&config(BoundType::B0), 3));
+        let noise = NoiseParameters {
+            clip_norm: 1.,
+            std_dev: 1e-9,
+        };
+        assert!(!noise.fits_bounds(&config(BoundType::B0), 3));
+    }
+
+    #[test]
+    fn clip_weights() {
+        let noise = NoiseParameters {
+            clip_norm: 1.,
+            std_dev: 0.,
+        };
+        let mut weights = vec![
+            float_to_ratio_bounded(3_f64),
+            float_to_ratio_bounded(-4_f64),
+        ];
+        noise.clip(&mut weights);
+        assert_eq!(weights[0], Ratio::new(BigInt::from(3), BigInt::from(5)));
+        assert_eq!(weights[1], Ratio::new(BigInt::from(-4), BigInt::from(5)));
+
+        // weights within the bound are kept
+        let mut weights = vec![float_to_ratio_bounded(0.5_f64); 2];
+        noise.clip(&mut weights);
+        assert_eq!(weights, vec![float_to_ratio_bounded(0.5_f64); 2]);
+
+        // huge weights don't overflow the norm
+        let mut weights = vec![float_to_ratio_bounded(f64::MAX); 2];
+        noise.clip(&mut weights);
+        let clipped = ratio_to_float::<f64>(&weights[0]).unwrap();
+        assert!((clipped - 0.5_f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sample_noise() {
+        let noise = NoiseParameters {
+            clip_norm: 1.,
+            std_dev: 0.5,
+        };
+        let exp_shift = BigInt::from(10_000_000_000_u64);
+        let mut prng = ChaCha20Rng::from_seed([0; 32]);
+        let n = 10_000;
+        let samples = (0..n)
+            .map(|_| noise.sample(&exp_shift, &mut prng).to_f64().unwrap() / 1e10)
+            .collect::<Vec<_>>();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let std_dev = (samples.iter().map(|x| x * x).sum::<f64>() / n as f64).sqrt();
+        assert!(mean.abs() < 0.02);
+        assert!((std_dev - 0.5).abs() < 0.02);
+    }
+}
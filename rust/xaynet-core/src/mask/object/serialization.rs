@@ -15,7 +15,7 @@ use crate::{
         object::MaskObject,
     },
     message::{
-        traits::{FromBytes, ToBytes},
+        traits::{take_bytes, FromBytes, ToBytes},
         utils::range,
         DecodeError,
     },
@@ -202,6 +202,40 @@ impl FromBytes for MaskObject {
 
         Ok(MaskObject { data, config })
     }
+
+    fn from_byte_stream<I: Iterator<Item = u8> + ExactSizeIterator>(
+        iter: &mut I,
+    ) -> Result<Self, DecodeError> {
+        let config = MaskConfig::from_byte_stream(iter).context("invalid MaskObject buffer")?;
+        let numbers_bytes = take_bytes(iter, NUMBERS_FIELD.end - NUMBERS_FIELD.start)?;
+        // UNWRAP SAFE: the slice is exactly 4 bytes long
+        let numbers = u32::from_be_bytes(numbers_bytes[..].try_into().unwrap()) as usize;
+
+        // check the length before allocating anything, since the
+        // numbers field could be arbitrarily large
+        let bytes_per_number = config.bytes_per_number();
+        let (data_length, overflows) = numbers.overflowing_mul(bytes_per_number);
+        if overflows {
+            return Err(anyhow!(
+                "invalid MaskObject buffer: invalid masking config or numbers field"
+            ));
+        }
+        if iter.len() < data_length {
+            return Err(anyhow!(
+                "invalid buffer length: expected {} bytes but stream has only {} bytes",
+                data_length,
+                iter.len()
+            ));
+        }
+
+        let mut data = Vec::with_capacity(numbers);
+        for _ in 0..numbers {
+            let bytes = take_bytes(iter, bytes_per_number)?;
+            data.push(BigUint::from_bytes_le(&bytes));
+        }
+
+        Ok(MaskObject { data, config })
+    }
 }
 #[cfg(test)]
 pub(crate) mod tests {
@@ -285,4 +319,22 @@ pub(crate) mod tests {
     fn deserialize_1() {
         assert_eq!(MaskObject::from_bytes(&bytes_1()).unwrap(), object_1());
     }
+
+    #[test]
+    fn deserialize_from_stream() {
+        let mut stream = [bytes(), bytes_1()].concat().into_iter();
+        assert_eq!(MaskObject::from_byte_stream(&mut stream).unwrap(), object());
+        assert_eq!(
+            MaskObject::from_byte_stream(&mut stream).unwrap(),
+            object_1()
+        );
+        assert_eq!(stream.len(), 0);
+    }
+
+    #[test]
+    fn deserialize_from_stream_exhausted() {
+        let mut bytes = bytes();
+        bytes.pop();
+        assert!(MaskObject::from_byte_stream(&mut bytes.into_iter()).is_err());
+    }
 }
@@ -14,7 +14,7 @@ use thiserror::Error;
 
 use crate::{
     crypto::{encrypt::SEALBYTES, prng::generate_integer, ByteObject},
-    mask::{config::MaskConfig, object::MaskObject},
+    mask::{config::MaskConfigPair, object::MaskObject},
     SumParticipantEphemeralPublicKey,
     SumParticipantEphemeralSecretKey,
 };
@@ -53,17 +53,18 @@ impl MaskSeed {
         EncryptedMaskSeed::from_slice_unchecked(pk.encrypt(self.as_slice()).as_slice())
     }
 
-    // TODO separate config for scalar mask - future refactoring
-    /// Derives a mask of given length from this seed wrt the masking configuration.
-    pub fn derive_mask(&self, len: usize, config: MaskConfig) -> (MaskObject, MaskObject) {
+    /// Derives a mask of given length from this seed wrt the masking configurations of the model
+    /// and the scalar.
+    pub fn derive_mask(&self, len: usize, config: MaskConfigPair) -> (MaskObject, MaskObject) {
         let mut prng = ChaCha20Rng::from_seed(self.as_array());
-        let rand_ints = iter::repeat_with(|| generate_integer(&mut prng, &config.order()))
+        let order = config.model.order();
+        let rand_ints = iter::repeat_with(|| generate_integer(&mut prng, &order))
             .take(len)
             .collect();
-        let model_mask = MaskObject::new(config, rand_ints);
+        let model_mask = MaskObject::new(config.model, rand_ints);
 
-        let rand_int = generate_integer(&mut prng, &config.order());
-        let scalar_mask = MaskObject::new(config, vec![rand_int]);
+        let rand_int = generate_integer(&mut prng, &config.scalar.order());
+        let scalar_mask = MaskObject::new(config.scalar, vec![rand_int]);
 
         (model_mask, scalar_mask)
     }
@@ -156,13 +157,27 @@ mod tests {
             bound_type: BoundType::B0,
             model_type: ModelType::M3,
         };
+        let scalar_config = MaskConfig {
+            group_type: GroupType::Power2,
+            data_type: DataType::F32,
+            bound_type: BoundType::B0,
+            model_type: ModelType::M3,
+        };
         let seed = MaskSeed::generate();
-        let (mask, scalar_mask) = seed.derive_mask(10, config);
+        let (mask, scalar_mask) = seed.derive_mask(
+            10,
+            MaskConfigPair {
+                model: config,
+                scalar: scalar_config,
+            },
+        );
         assert_eq!(mask.data.len(), 10);
+        assert_eq!(mask.config, config);
         assert!(mask.data.iter().all(|integer| integer < &config.order()));
 
         assert_eq!(scalar_mask.data.len(), 1);
-        // TODO check size later after future refactoring
+        assert_eq!(scalar_mask.config, scalar_config);
+        assert!(scalar_mask.data[0] < scalar_config.order());
     }
 
     #[test]
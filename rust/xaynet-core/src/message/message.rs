@@ -516,7 +516,7 @@ impl Message {
             participant_pk,
             coordinator_pk,
             is_multipart: false,
-            tag: Tag::Sum,
+            tag: Tag::Sum2,
             payload: message.into(),
         }
     }
@@ -533,7 +533,7 @@ impl Message {
             participant_pk,
             coordinator_pk,
             is_multipart: false,
-            tag: Tag::Sum,
+            tag: Tag::Update,
             payload: message.into(),
         }
     }
@@ -556,6 +556,44 @@ impl Message {
         }
     }
 
+    /// Split this message into multipart messages, each of them
+    /// carrying a [`Chunk`] of at most `max_chunk_size` bytes of the
+    /// serialized payload.
+    ///
+    /// Chunks are numbered starting from `1`, so that the ID of the
+    /// last chunk is also the number of chunks the message is made
+    /// of. If the payload doesn't fit in `u16::MAX` chunks of
+    /// `max_chunk_size` bytes, the chunk size is increased
+    /// accordingly. A message that is already a multipart message or
+    /// whose payload fits in a single chunk is returned as is.
+    ///
+    /// The signature of the resulting messages is not set, so that
+    /// each chunk gets signed when it is serialized.
+    pub fn into_chunks(self, message_id: u16, max_chunk_size: usize) -> Vec<Message> {
+        let payload_length = self.payload.buffer_length();
+        if self.is_multipart || payload_length <= max_chunk_size {
+            return vec![self];
+        }
+
+        let chunk_size = max_chunk_size.max(payload_length / u16::MAX as usize + 1);
+        let mut payload = vec![0; payload_length];
+        self.payload.to_bytes(&mut payload);
+        let chunks = payload.chunks(chunk_size);
+        let nb_chunks = chunks.len();
+        chunks
+            .enumerate()
+            .map(|(i, data)| {
+                let chunk = Chunk {
+                    id: (i + 1) as u16,
+                    message_id,
+                    last: i + 1 == nb_chunks,
+                    data: data.to_vec(),
+                };
+                Self::new_multipart(self.participant_pk, self.coordinator_pk, chunk, self.tag)
+            })
+            .collect()
+    }
+
     /// Parse the given message **without** verifying the
     /// signature. If you need to check the signature, call
     /// [`MessageBuffer.verify_signature`] before parsing the message.
@@ -718,4 +756,40 @@ pub(in crate::message) mod tests {
             .copy_from_slice(sum::tests::sum_bytes().as_slice());
         assert_eq!(bytes, expected);
     }
+
+    #[test]
+    fn into_chunks() {
+        let (_, message) = message();
+        let sum_bytes = sum::tests::sum_bytes();
+        let chunks = message.clone().into_chunks(42, 40);
+        assert_eq!(chunks.len(), 3);
+
+        let mut data = vec![];
+        for (i, chunk_message) in chunks.into_iter().enumerate() {
+            assert!(chunk_message.is_multipart);
+            assert!(chunk_message.signature.is_none());
+            assert_eq!(chunk_message.tag, Tag::Sum);
+            assert_eq!(chunk_message.participant_pk, message.participant_pk);
+            assert_eq!(chunk_message.coordinator_pk, message.coordinator_pk);
+            match chunk_message.payload {
+                Payload::Chunk(chunk) => {
+                    assert_eq!(chunk.id as usize, i + 1);
+                    assert_eq!(chunk.message_id, 42);
+                    assert_eq!(chunk.last, i == 2);
+                    data.extend(chunk.data);
+                }
+                _ => panic!("expected a chunk payload"),
+            }
+        }
+        assert_eq!(data, sum_bytes);
+    }
+
+    #[test]
+    fn into_chunks_single() {
+        let (_, message) = message();
+        let chunks = message
+            .clone()
+            .into_chunks(42, sum::tests::sum_bytes().len());
+        assert_eq!(chunks, vec![message]);
+    }
 }
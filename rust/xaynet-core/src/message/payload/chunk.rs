@@ -3,7 +3,7 @@ use std::convert::TryInto;
 use anyhow::{anyhow, Context};
 
 use crate::message::{
-    traits::{FromBytes, ToBytes},
+    traits::{take_bytes, FromBytes, ToBytes},
     DecodeError,
 };
 
@@ -188,6 +188,20 @@ impl FromBytes for Chunk {
             data: reader.payload().to_vec(),
         })
     }
+
+    fn from_byte_stream<I: Iterator<Item = u8> + ExactSizeIterator>(
+        iter: &mut I,
+    ) -> Result<Self, DecodeError> {
+        let header = take_bytes(iter, HEADER_LENGTH).context("Invalid chunk buffer")?;
+        let reader = ChunkBuffer::new_unchecked(&header);
+        Ok(Self {
+            last: reader.flags().contains(Flags::LAST_CHUNK),
+            id: reader.id(),
+            message_id: reader.message_id(),
+            // the data is the rest of the stream
+            data: iter.collect(),
+        })
+    }
 }
 
 impl ToBytes for Chunk {
@@ -271,4 +285,11 @@ mod tests {
         buffer.payload_mut().copy_from_slice(data().as_slice());
         assert_eq!(bytes, expected);
     }
+
+    #[test]
+    fn decode_from_stream() {
+        let (bytes, chunk) = chunk();
+        let parsed = Chunk::from_byte_stream(&mut bytes.into_iter()).unwrap();
+        assert_eq!(parsed, chunk);
+    }
 }
@@ -257,6 +257,21 @@ impl FromBytes for Update {
                 .context("invalid local seed dictionary")?,
         })
     }
+
+    fn from_byte_stream<I: Iterator<Item = u8> + ExactSizeIterator>(
+        iter: &mut I,
+    ) -> Result<Self, DecodeError> {
+        Ok(Self {
+            sum_signature: ParticipantTaskSignature::from_byte_stream(iter)
+                .context("invalid sum signature")?,
+            update_signature: ParticipantTaskSignature::from_byte_stream(iter)
+                .context("invalid update signature")?,
+            masked_model: MaskObject::from_byte_stream(iter).context("invalid masked model")?,
+            masked_scalar: MaskObject::from_byte_stream(iter).context("invalid masked scalar")?,
+            local_seed_dict: LocalSeedDict::from_byte_stream(iter)
+                .context("invalid local seed dictionary")?,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -386,6 +401,33 @@ pub(in crate::message) mod tests {
         assert_eq!(parsed, update);
     }
 
+    #[test]
+    fn decode_from_stream() {
+        let (update, bytes) = helpers::update();
+        let parsed = Update::from_byte_stream(&mut bytes.into_iter()).unwrap();
+        assert_eq!(parsed, update);
+    }
+
+    #[test]
+    fn decode_invalid_seed_dict_from_stream() {
+        let mut invalid = helpers::local_seed_dict().1;
+        // This truncates the last entry of the seed dictionary
+        invalid[3] = 0xe3;
+        let mut bytes = vec![];
+        bytes.extend(helpers::sum_signature().1);
+        bytes.extend(helpers::update_signature().1);
+        bytes.extend(helpers::masked_model().1);
+        bytes.extend(helpers::masked_scalar().1);
+        bytes.extend(invalid);
+
+        let e = Update::from_byte_stream(&mut bytes.into_iter()).unwrap_err();
+        let cause = e.source().unwrap().to_string();
+        assert_eq!(
+            cause,
+            "invalid local seed dictionary: trailing bytes".to_string()
+        );
+    }
+
     #[test]
     fn encode() {
         let (update, bytes) = helpers::update();
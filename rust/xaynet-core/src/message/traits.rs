@@ -46,6 +46,35 @@ pub trait FromBytes: Sized {
     /// # Errors
     /// May fail if certain parts of the deserialized buffer don't pass message validity checks.
     fn from_bytes<T: AsRef<[u8]>>(buffer: &T) -> Result<Self, DecodeError>;
+
+    /// Deserialize the type from the given stream of bytes.
+    ///
+    /// Contrary to [`from_bytes()`], the serialized data doesn't need to be held in a single
+    /// contiguous buffer, which allows to decode multipart messages without concatenating their
+    /// chunks first. Only the bytes that make up the type are consumed from the stream.
+    ///
+    /// # Errors
+    /// Fails if the stream is exhausted prematurely or if certain parts of the deserialized bytes
+    /// don't pass message validity checks.
+    ///
+    /// [`from_bytes()`]: #tymethod.from_bytes
+    fn from_byte_stream<I: Iterator<Item = u8> + ExactSizeIterator>(
+        iter: &mut I,
+    ) -> Result<Self, DecodeError>;
+}
+
+/// Reads exactly `n` bytes from the given stream.
+///
+/// # Errors
+/// Fails if the stream contains less than `n` bytes. In that case, nothing is consumed.
+pub(crate) fn take_bytes<I: Iterator<Item = u8> + ExactSizeIterator>(
+    iter: &mut I,
+    n: usize,
+) -> Result<Vec<u8>, DecodeError> {
+    if iter.len() < n {
+        return Err(anyhow!("stream exhausted: {} < {}", iter.len(), n));
+    }
+    Ok(iter.by_ref().take(n).collect())
 }
 
 impl<T> FromBytes for T
@@ -56,6 +85,13 @@ where
         Self::from_slice(buffer.as_ref())
             .ok_or_else(|| anyhow!("failed to deserialize byte object"))
     }
+
+    fn from_byte_stream<I: Iterator<Item = u8> + ExactSizeIterator>(
+        iter: &mut I,
+    ) -> Result<Self, DecodeError> {
+        let bytes = take_bytes(iter, Self::LENGTH)?;
+        Self::from_bytes(&bytes)
+    }
 }
 
 impl<T> ToBytes for T
@@ -301,6 +337,44 @@ impl FromBytes for LocalSeedDict {
         }
         Ok(dict)
     }
+
+    fn from_byte_stream<I: Iterator<Item = u8> + ExactSizeIterator>(
+        iter: &mut I,
+    ) -> Result<Self, DecodeError> {
+        let length_bytes = take_bytes(iter, LENGTH_FIELD.end)?;
+        // unwrap safe: the slice is exactly 4 bytes long
+        let length = u32::from_be_bytes(length_bytes[..].try_into().unwrap()) as usize;
+        if length < LENGTH_FIELD.end {
+            return Err(anyhow!(
+                "invalid length value: {} (should be >= {})",
+                length,
+                LENGTH_FIELD.end
+            ));
+        }
+        let value_length = length - LENGTH_FIELD.end;
+        let (nb_entries, trailing_bytes) =
+            (value_length / ENTRY_LENGTH, value_length % ENTRY_LENGTH);
+        if trailing_bytes != 0 {
+            return Err(anyhow!("invalid local seed dictionary: trailing bytes"));
+        }
+        if iter.len() < value_length {
+            return Err(anyhow!(
+                "invalid local seed dictionary length: {} < {}",
+                iter.len(),
+                value_length
+            ));
+        }
+
+        let mut dict = LocalSeedDict::new();
+        for _ in 0..nb_entries {
+            let key = SumParticipantPublicKey::from_byte_stream(iter)?;
+            let value = EncryptedMaskSeed::from_byte_stream(iter)?;
+            if dict.insert(key, value).is_some() {
+                return Err(anyhow!("invalid local seed dictionary: duplicated key"));
+            }
+        }
+        Ok(dict)
+    }
 }
 
 #[cfg(test)]
@@ -362,6 +436,43 @@ mod tests {
         assert_eq!(bytes, expected);
     }
 
+    #[test]
+    fn take_bytes_from_stream() {
+        let mut stream = vec![0x11, 0x22, 0x33].into_iter();
+        assert_eq!(take_bytes(&mut stream, 2).unwrap(), vec![0x11, 0x22]);
+        assert!(take_bytes(&mut stream, 2).is_err());
+        assert_eq!(take_bytes(&mut stream, 1).unwrap(), vec![0x33]);
+        assert!(take_bytes(&mut stream, 0).unwrap().is_empty());
+    }
+
+    fn local_seed_dict() -> (Vec<u8>, LocalSeedDict) {
+        let mut dict = LocalSeedDict::new();
+        dict.insert(
+            SumParticipantPublicKey::from_slice(&[0x11; SumParticipantPublicKey::LENGTH]).unwrap(),
+            EncryptedMaskSeed::from_slice(&[0xaa; EncryptedMaskSeed::LENGTH]).unwrap(),
+        );
+        let mut bytes = vec![0; dict.buffer_length()];
+        dict.to_bytes(&mut bytes);
+        (bytes, dict)
+    }
+
+    #[test]
+    fn decode_local_seed_dict_from_stream() {
+        let (mut bytes, dict) = local_seed_dict();
+        bytes.extend(vec![0xff; 3]);
+        let mut stream = bytes.into_iter();
+        assert_eq!(LocalSeedDict::from_byte_stream(&mut stream).unwrap(), dict);
+        // trailing bytes are not consumed
+        assert_eq!(stream.len(), 3);
+    }
+
+    #[test]
+    fn decode_local_seed_dict_from_stream_exhausted() {
+        let (mut bytes, _) = local_seed_dict();
+        bytes.pop();
+        assert!(LocalSeedDict::from_byte_stream(&mut bytes.into_iter()).is_err());
+    }
+
     #[test]
     fn encode_length_value_buffer_emty() {
         let mut bytes = vec![0xff; 5];
@@ -37,28 +37,34 @@ use std::{
     slice,
 };
 
-use xaynet_client::mobile_client::{
-    participant::{AggregationConfig, ParticipantSettings},
-    MobileClient,
+use xaynet_client::{
+    api::HttpApiClientError,
+    mobile_client::{
+        participant::{AggregationConfig, ParticipantSettings},
+        MobileClient,
+        MobileClientError,
+    },
+    privacy::{NoiseMechanism, PrivacyConfig},
 };
 use xaynet_core::{
     crypto::ByteObject,
-    mask::{
-        BoundType,
-        DataType,
-        FromPrimitives,
-        GroupType,
-        IntoPrimitives,
-        MaskConfig,
-        Model,
-        ModelType,
-    },
+    mask::{DataType, FromPrimitives, IntoPrimitives, Model},
     ParticipantSecretKey,
 };
 
 /// A Opaque type of MobileClient.
 /// see [FFI-C-OPAQUE](https://anssi-fr.github.io/rust-guide/07_ffi.html#recommendation-a-idffi-c-opaqueaffi-c-opaque)
-pub struct CMobileClient(MobileClient);
+pub struct CMobileClient {
+    client: MobileClient,
+    // the error of the last call of `xaynet_ffi_try_to_proceed_mobile_client`, if it failed
+    last_error: Option<MobileClientError>,
+}
+
+impl CMobileClient {
+    fn new(client: MobileClient, last_error: Option<MobileClientError>) -> *mut Self {
+        Box::into_raw(Box::new(Self { client, last_error }))
+    }
+}
 
 /// Initializes a fresh [`CMobileClient`]. This method only needs to be called once.
 ///
@@ -69,11 +75,17 @@ pub struct CMobileClient(MobileClient);
 ///
 /// - `url`: The URL fo the coordinator to which the [`MobileClient`] will try to connect to.
 /// - `secret_key`: The array that contains the secret key.
-/// - `group_type`: The [`GroupType`].
-/// - `data_type`: The [`DataType`].
-/// - `bound_type`: The [`BoundType`].
-/// - `model_type`: The [`ModelType`].
 /// - `scalar`: The scalar.
+/// - `privacy_mechanism`: The noise mechanism of the local differential privacy:
+///   - `0`: local differential privacy is disabled and the privacy parameters are ignored,
+///   - `1`: Gaussian noise,
+///   - `2`: Laplace noise, in which case `delta` is ignored.
+/// - `clip_norm`: The bound of the L2 norm to which the local model is clipped.
+/// - `epsilon`: The privacy budget spent per round.
+/// - `delta`: The probability with which the privacy guarantee of Gaussian noise may fail.
+///
+/// The masking configuration is not set by the client, since it is taken from the round
+/// parameters of the coordinator in each round.
 ///
 /// # Safety
 ///
@@ -93,10 +105,9 @@ pub struct CMobileClient(MobileClient);
 ///
 /// ## Returns `NULL` if:
 ///
-/// - a value of `group_type`, `data_type`, `bound_type` or `model_type` is not a valid value
-/// (see the module documentation of [`xaynet_core::mask`] for more information),
 /// - the pointer of `secret_key` or `url` points to `NULL`,
-/// - the `url` contains invalid UTF-8 characters.
+/// - the `url` contains invalid UTF-8 characters,
+/// - the `privacy_mechanism` is unknown or a privacy parameter is out of its range.
 ///
 /// [`MobileClient`]: xaynet_client::mobile_client::MobileClient
 #[allow(unused_unsafe)]
@@ -104,11 +115,11 @@ pub struct CMobileClient(MobileClient);
 pub unsafe extern "C" fn xaynet_ffi_init_mobile_client(
     url: FfiStr,
     secret_key: *const c_uchar,
-    group_type: c_uchar,
-    data_type: c_uchar,
-    bound_type: c_uchar,
-    model_type: c_uchar,
     scalar: c_double,
+    privacy_mechanism: c_int,
+    clip_norm: c_double,
+    epsilon: c_double,
+    delta: c_double,
 ) -> *mut CMobileClient {
     // we could return *const CMobileClient, however, the caller can ignore it
     // https://newrustacean.com/show_notes/e031/struct.script#strings
@@ -133,46 +144,28 @@ pub unsafe extern "C" fn xaynet_ffi_init_mobile_client(
         None => return ptr::null_mut(),
     };
 
-    let group_type = match GroupType::try_from(group_type) {
-        Ok(group_type) => group_type,
-        Err(_) => return ptr::null_mut(),
-    };
-
-    let data_type = match DataType::try_from(data_type) {
-        Ok(data_type) => data_type,
-        Err(_) => return ptr::null_mut(),
-    };
-
-    let bound_type = match BoundType::try_from(bound_type) {
-        Ok(bound_type) => bound_type,
-        Err(_) => return ptr::null_mut(),
-    };
-
-    let model_type = match ModelType::try_from(model_type) {
-        Ok(model_type) => model_type,
-        Err(_) => return ptr::null_mut(),
-    };
-
     let secret_key = unsafe { slice::from_raw_parts(secret_key, ParticipantSecretKey::LENGTH) };
     let secret_key = ParticipantSecretKey::from_slice_unchecked(secret_key);
 
-    let mask_config = MaskConfig {
-        group_type,
-        data_type,
-        bound_type,
-        model_type,
+    let mechanism = match privacy_mechanism {
+        0 => None,
+        1 => Some(NoiseMechanism::Gaussian { delta }),
+        2 => Some(NoiseMechanism::Laplace),
+        _ => return ptr::null_mut(),
     };
+    let privacy = mechanism.map(|mechanism| PrivacyConfig {
+        clip_norm,
+        epsilon,
+        mechanism,
+    });
 
     let participant_settings = ParticipantSettings {
         secret_key,
-        aggregation_config: AggregationConfig {
-            mask: mask_config,
-            scalar,
-        },
+        aggregation_config: AggregationConfig { scalar, privacy },
     };
 
     if let Ok(mobile_client) = MobileClient::init(url, participant_settings) {
-        Box::into_raw(Box::new(CMobileClient(mobile_client)))
+        CMobileClient::new(mobile_client, None)
     } else {
         ptr::null_mut()
     }
@@ -227,7 +220,7 @@ pub unsafe extern "C" fn xaynet_ffi_restore_mobile_client(
     let buffer = unsafe { slice::from_raw_parts(buffer, len as usize) };
 
     if let Ok(mobile_client) = MobileClient::restore(url, buffer) {
-        Box::into_raw(Box::new(CMobileClient(mobile_client)))
+        CMobileClient::new(mobile_client, None)
     } else {
         ptr::null_mut()
     }
@@ -262,7 +255,7 @@ pub unsafe extern "C" fn xaynet_ffi_serialize_mobile_client(
     client: *const CMobileClient,
 ) -> *mut BytesBuffer {
     let client = match unsafe { client.as_ref() } {
-        Some(client) => &client.0,
+        Some(client) => &client.client,
         None => return ptr::null_mut(),
     };
 
@@ -288,7 +281,8 @@ pub unsafe extern "C" fn xaynet_ffi_serialize_mobile_client(
 ///
 /// # Return Value
 ///
-/// Returns a new instance of [`CMobileClient`].
+/// Returns a new instance of [`CMobileClient`]. If the task failed, the error can be read with
+/// [`xaynet_ffi_get_last_error_mobile_client`].
 ///
 /// ## Returns `NULL` if:
 ///
@@ -304,15 +298,13 @@ pub unsafe extern "C" fn xaynet_ffi_try_to_proceed_mobile_client(
     };
 
     // access to the current mobile client
-    let CMobileClient(client) = unsafe { *Box::from_raw(client) };
+    let CMobileClient { client, .. } = unsafe { *Box::from_raw(client) };
 
     // perform the task (consumes the current client)
-    let client = match client.try_to_proceed() {
-        Ok(new_client) => new_client,
-        Err((old_client, _)) => old_client,
-    };
-
-    Box::into_raw(Box::new(CMobileClient(client)))
+    match client.try_to_proceed() {
+        Ok(new_client) => CMobileClient::new(new_client, None),
+        Err((old_client, err)) => CMobileClient::new(old_client, Some(err)),
+    }
 }
 
 /// Returns the current state of `client`.
@@ -344,13 +336,115 @@ pub unsafe extern "C" fn xaynet_ffi_get_current_state_mobile_client(
     client: *mut CMobileClient,
 ) -> c_int {
     let client = match unsafe { client.as_mut() } {
-        Some(client) => &mut (*client).0,
+        Some(client) => &mut (*client).client,
         None => return -1 as c_int,
     };
 
     (client.get_current_state() as u8) as c_int
 }
 
+/// Returns the error of the last call of [`xaynet_ffi_try_to_proceed_mobile_client`].
+///
+/// # Parameters
+///
+/// - `client`: A pointer that points to an instance of [`CMobileClient`].
+///
+/// # Safety
+///
+/// `client`:
+///
+/// The function only ensures null-safety. You must ensure that:
+/// - the pointer points to an initialized instance of [`CMobileClient`],
+/// - the data the pointer points to is properly aligned,
+/// - the memory of `client` is not mutated (from the outside of this function)
+/// for the duration of the execution of [`xaynet_ffi_get_last_error_mobile_client`].
+///
+/// # Return Value
+///
+/// - `-1`: the pointer of `client` points to `NULL`,
+/// - `0`: the task succeeded,
+/// - `1`: the runtime could not be initialized,
+/// - `2`: an API request failed,
+/// - `3`: the coordinator rejected a message (see [`xaynet_ffi_get_last_message_error_mobile_client`]),
+/// - `4`: another error occurred.
+#[allow(unused_unsafe)]
+#[no_mangle]
+pub unsafe extern "C" fn xaynet_ffi_get_last_error_mobile_client(
+    client: *const CMobileClient,
+) -> c_int {
+    let last_error = match unsafe { client.as_ref() } {
+        Some(client) => &client.last_error,
+        None => return -1 as c_int,
+    };
+
+    match last_error {
+        None => 0,
+        Some(MobileClientError::Runtime(_)) => 1,
+        Some(MobileClientError::Api(HttpApiClientError::MessageRejected { .. })) => 3,
+        Some(MobileClientError::Api(_)) => 2,
+        Some(_) => 4,
+    }
+}
+
+/// Returns the reason why the coordinator rejected a message in the last call of
+/// [`xaynet_ffi_try_to_proceed_mobile_client`].
+///
+/// # Parameters
+///
+/// - `client`: A pointer that points to an instance of [`CMobileClient`].
+///
+/// # Safety
+///
+/// `client`:
+///
+/// The function only ensures null-safety. You must ensure that:
+/// - the pointer points to an initialized instance of [`CMobileClient`],
+/// - the data the pointer points to is properly aligned,
+/// - the memory of `client` is not mutated (from the outside of this function)
+/// for the duration of the execution of [`xaynet_ffi_get_last_message_error_mobile_client`].
+///
+/// # Return Value
+///
+/// - `-1`: the pointer of `client` points to `NULL`,
+/// - `-2`: the coordinator didn't reject a message,
+/// - `0`: `Decrypt`,
+/// - `1`: `Parsing`,
+/// - `2`: `InvalidMessageSignature`,
+/// - `3`: `InvalidCoordinatorPublicKey`,
+/// - `4`: `UnexpectedMessage`,
+/// - `5`: `TooManyPendingMessages`,
+/// - `6`: `MessageTooLarge`,
+/// - `7`: `MultipartBufferFull`,
+/// - `8`: `ParticipantNotAllowed`,
+/// - `9`: `NotSumEligible`,
+/// - `10`: `NotUpdateEligible`,
+/// - `11`: `MessageRejected`,
+/// - `12`: `AggregationFailed`,
+/// - `13`: `InvalidLocalSeedDict`,
+/// - `14`: `RequestQueueFull`,
+/// - `15`: `InternalError`.
+///
+/// See [`MessageErrorKind`] for the meaning of the values.
+///
+/// [`MessageErrorKind`]: xaynet_core::common::MessageErrorKind
+#[allow(unused_unsafe)]
+#[no_mangle]
+pub unsafe extern "C" fn xaynet_ffi_get_last_message_error_mobile_client(
+    client: *const CMobileClient,
+) -> c_int {
+    let last_error = match unsafe { client.as_ref() } {
+        Some(client) => &client.last_error,
+        None => return -1 as c_int,
+    };
+
+    match last_error {
+        Some(MobileClientError::Api(HttpApiClientError::MessageRejected { error, .. })) => {
+            error.kind as c_int
+        }
+        _ => -2,
+    }
+}
+
 define_box_destructor!(CMobileClient, xaynet_ffi_destroy_mobile_client);
 
 /// Fetches and returns the latest global model from the coordinator.
@@ -405,7 +499,7 @@ pub unsafe extern "C" fn xaynet_ffi_get_global_model_mobile_client(
     len: c_uint,
 ) -> c_int {
     let client = match unsafe { client.as_mut() } {
-        Some(client) => &mut (*client).0,
+        Some(client) => &mut (*client).client,
         None => return -1 as c_int,
     };
 
@@ -543,7 +637,7 @@ pub unsafe extern "C" fn xaynet_ffi_set_local_model_mobile_client(
     len: c_uint,
 ) -> c_int {
     let client = match unsafe { client.as_mut() } {
-        Some(client) => &mut (*client).0,
+        Some(client) => &mut (*client).client,
         None => return -1 as c_int,
     };
 
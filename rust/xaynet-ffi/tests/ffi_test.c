@@ -33,21 +33,32 @@ static char *test_xaynet_ffi_init()
   xaynet_ffi_new_secret_key(secret_key);
   char *url = "http://localhost:8081";
 
-  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 0, 0, 0, 3, 1);
+  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 1, 0, 0, 0, 0);
   mu_assert("error, client == null", client != NULL);
 
   xaynet_ffi_destroy_mobile_client(client);
   return 0;
 }
 
-static char *test_xaynet_ffi_init_wrong_group_type()
+static char *test_xaynet_ffi_init_with_privacy()
 {
   unsigned char secret_key[64] = {0};
   xaynet_ffi_new_secret_key(secret_key);
   char *url = "http://localhost:8081";
 
-  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 0, 12, 0, 3, 1);
-  mu_assert("error, client == null", client == NULL);
+  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 1, 1, 1, 0.5, 1e-5);
+  mu_assert("error, gaussian client == null", client != NULL);
+  xaynet_ffi_destroy_mobile_client(client);
+
+  client = xaynet_ffi_init_mobile_client(url, secret_key, 1, 2, 1, 2, 0);
+  mu_assert("error, laplace client == null", client != NULL);
+  xaynet_ffi_destroy_mobile_client(client);
+
+  client = xaynet_ffi_init_mobile_client(url, secret_key, 1, 1, 1, 2, 1e-5);
+  mu_assert("error, invalid epsilon client != null", client == NULL);
+
+  client = xaynet_ffi_init_mobile_client(url, secret_key, 1, 3, 1, 0.5, 1e-5);
+  mu_assert("error, unknown mechanism client != null", client == NULL);
   return 0;
 }
 
@@ -57,7 +68,7 @@ static char *test_xaynet_ffi_serialize()
   xaynet_ffi_new_secret_key(secret_key);
   char *url = "http://localhost:8081";
 
-  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 0, 0, 0, 3, 1);
+  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 1, 0, 0, 0, 0);
 
   BytesBuffer *buffer = xaynet_ffi_serialize_mobile_client(client);
   mu_assert("error, byte buffer == null", client != NULL);
@@ -83,7 +94,7 @@ static char *test_xaynet_ffi_restore()
   xaynet_ffi_new_secret_key(secret_key);
   char *url = "http://localhost:8081";
 
-  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 0, 0, 0, 3, 1);
+  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 1, 0, 0, 0, 0);
 
   BytesBuffer *buffer = xaynet_ffi_serialize_mobile_client(client);
   unsigned int size_buffer = xaynet_ffi_get_len_of_byte_buffer(buffer);
@@ -108,13 +119,20 @@ static char *test_xaynet_ffi_try_to_proceed_mobile_client()
   xaynet_ffi_new_secret_key(secret_key);
   char *url = "http://localhost:8081";
 
-  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 0, 0, 0, 3, 1);
+  CMobileClient *client = xaynet_ffi_init_mobile_client(url, secret_key, 1, 0, 0, 0, 0);
   mu_assert("error, client == null", client != NULL);
 
+  mu_assert("error, last error != 0", xaynet_ffi_get_last_error_mobile_client(client) == 0);
+
   CMobileClient *next_client = xaynet_ffi_try_to_proceed_mobile_client(client);
   mu_assert("error, new_client == null", next_client != NULL);
   mu_assert("error, client == next_client", client != next_client);
 
+  // no coordinator is running
+  mu_assert("error, last error != 2", xaynet_ffi_get_last_error_mobile_client(next_client) == 2);
+  mu_assert("error, last message error != -2",
+            xaynet_ffi_get_last_message_error_mobile_client(next_client) == -2);
+
   xaynet_ffi_destroy_mobile_client(next_client);
   return 0;
 }
@@ -123,7 +141,7 @@ static char *all_tests()
 {
   mu_run_test(test_xaynet_ffi_new_secret_key);
   mu_run_test(test_xaynet_ffi_init);
-  mu_run_test(test_xaynet_ffi_init_wrong_group_type);
+  mu_run_test(test_xaynet_ffi_init_with_privacy);
   mu_run_test(test_xaynet_ffi_serialize);
   mu_run_test(test_xaynet_ffi_restore);
   mu_run_test(test_xaynet_ffi_try_to_proceed_mobile_client);
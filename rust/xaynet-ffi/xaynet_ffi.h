@@ -192,6 +192,79 @@ int xaynet_ffi_get_global_model_mobile_client(CMobileClient *client,
                                               void *buffer,
                                               unsigned int len);
 
+/**
+ * Returns the error of the last call of [`xaynet_ffi_try_to_proceed_mobile_client`].
+ *
+ * # Parameters
+ *
+ * - `client`: A pointer that points to an instance of [`CMobileClient`].
+ *
+ * # Safety
+ *
+ * `client`:
+ *
+ * The function only ensures null-safety. You must ensure that:
+ * - the pointer points to an initialized instance of [`CMobileClient`],
+ * - the data the pointer points to is properly aligned,
+ * - the memory of `client` is not mutated (from the outside of this function)
+ * for the duration of the execution of [`xaynet_ffi_get_last_error_mobile_client`].
+ *
+ * # Return Value
+ *
+ * - `-1`: the pointer of `client` points to `NULL`,
+ * - `0`: the task succeeded,
+ * - `1`: the runtime could not be initialized,
+ * - `2`: an API request failed,
+ * - `3`: the coordinator rejected a message (see [`xaynet_ffi_get_last_message_error_mobile_client`]),
+ * - `4`: another error occurred.
+ */
+int xaynet_ffi_get_last_error_mobile_client(const CMobileClient *client);
+
+/**
+ * Returns the reason why the coordinator rejected a message in the last call of
+ * [`xaynet_ffi_try_to_proceed_mobile_client`].
+ *
+ * # Parameters
+ *
+ * - `client`: A pointer that points to an instance of [`CMobileClient`].
+ *
+ * # Safety
+ *
+ * `client`:
+ *
+ * The function only ensures null-safety. You must ensure that:
+ * - the pointer points to an initialized instance of [`CMobileClient`],
+ * - the data the pointer points to is properly aligned,
+ * - the memory of `client` is not mutated (from the outside of this function)
+ * for the duration of the execution of [`xaynet_ffi_get_last_message_error_mobile_client`].
+ *
+ * # Return Value
+ *
+ * - `-1`: the pointer of `client` points to `NULL`,
+ * - `-2`: the coordinator didn't reject a message,
+ * - `0`: `Decrypt`,
+ * - `1`: `Parsing`,
+ * - `2`: `InvalidMessageSignature`,
+ * - `3`: `InvalidCoordinatorPublicKey`,
+ * - `4`: `UnexpectedMessage`,
+ * - `5`: `TooManyPendingMessages`,
+ * - `6`: `MessageTooLarge`,
+ * - `7`: `MultipartBufferFull`,
+ * - `8`: `ParticipantNotAllowed`,
+ * - `9`: `NotSumEligible`,
+ * - `10`: `NotUpdateEligible`,
+ * - `11`: `MessageRejected`,
+ * - `12`: `AggregationFailed`,
+ * - `13`: `InvalidLocalSeedDict`,
+ * - `14`: `RequestQueueFull`,
+ * - `15`: `InternalError`.
+ *
+ * See [`MessageErrorKind`] for the meaning of the values.
+ *
+ * [`MessageErrorKind`]: xaynet_core::common::MessageErrorKind
+ */
+int xaynet_ffi_get_last_message_error_mobile_client(const CMobileClient *client);
+
 /**
  * Returns the length of `buffer`.
  *
@@ -225,11 +298,17 @@ int xaynet_ffi_get_len_of_byte_buffer(const BytesBuffer *buffer);
  *
  * - `url`: The URL fo the coordinator to which the [`MobileClient`] will try to connect to.
  * - `secret_key`: The array that contains the secret key.
- * - `group_type`: The [`GroupType`].
- * - `data_type`: The [`DataType`].
- * - `bound_type`: The [`BoundType`].
- * - `model_type`: The [`ModelType`].
  * - `scalar`: The scalar.
+ * - `privacy_mechanism`: The noise mechanism of the local differential privacy:
+ *   - `0`: local differential privacy is disabled and the privacy parameters are ignored,
+ *   - `1`: Gaussian noise,
+ *   - `2`: Laplace noise, in which case `delta` is ignored.
+ * - `clip_norm`: The bound of the L2 norm to which the local model is clipped.
+ * - `epsilon`: The privacy budget spent per round.
+ * - `delta`: The probability with which the privacy guarantee of Gaussian noise may fail.
+ *
+ * The masking configuration is not set by the client, since it is taken from the round
+ * parameters of the coordinator in each round.
  *
  * # Safety
  *
@@ -249,20 +328,19 @@ int xaynet_ffi_get_len_of_byte_buffer(const BytesBuffer *buffer);
  *
  * ## Returns `NULL` if:
  *
- * - a value of `group_type`, `data_type`, `bound_type` or `model_type` is not a valid value
- * (see the module documentation of [`xaynet_core::mask`] for more information),
  * - the pointer of `secret_key` or `url` points to `NULL`,
- * - the `url` contains invalid UTF-8 characters.
+ * - the `url` contains invalid UTF-8 characters,
+ * - the `privacy_mechanism` is unknown or a privacy parameter is out of its range.
  *
  * [`MobileClient`]: xaynet_client::mobile_client::MobileClient
  */
 CMobileClient *xaynet_ffi_init_mobile_client(FfiStr url,
                                              const unsigned char *secret_key,
-                                             unsigned char group_type,
-                                             unsigned char data_type,
-                                             unsigned char bound_type,
-                                             unsigned char model_type,
-                                             double scalar);
+                                             double scalar,
+                                             int privacy_mechanism,
+                                             double clip_norm,
+                                             double epsilon,
+                                             double delta);
 
 /**
  * Creates a new participant secret key and writes it into `buffer`.
@@ -423,7 +501,8 @@ int xaynet_ffi_set_local_model_mobile_client(CMobileClient *client,
  *
  * # Return Value
  *
- * Returns a new instance of [`CMobileClient`].
+ * Returns a new instance of [`CMobileClient`]. If the task failed, the error can be read with
+ * [`xaynet_ffi_get_last_error_mobile_client`].
  *
  * ## Returns `NULL` if:
  *
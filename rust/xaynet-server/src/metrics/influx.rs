@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use influxdb::{Client, InfluxDbWriteable, Timestamp, WriteQuery};
+
+use super::{
+    models::{Metric, Value},
+    MetricsBackend,
+};
+
+/// A generic influx data point.
+struct DataPoint<T: Into<influxdb::Type>> {
+    pub time: DateTime<Utc>,
+    pub value: T,
+    pub round_id: Option<u64>,
+    pub phase: Option<u8>,
+}
+
+impl<T: Into<influxdb::Type>> InfluxDbWriteable for DataPoint<T> {
+    fn into_query<I: Into<String>>(self, name: I) -> influxdb::WriteQuery {
+        let timestamp: ::influxdb::Timestamp = self.time.into();
+        let mut query = timestamp.into_query(name);
+        query = query.add_field("value", self.value);
+        query = query.add_tag("round_id", self.round_id);
+        query = query.add_tag("phase", self.phase);
+        query
+    }
+}
+
+/// An `event` data point.
+#[derive(InfluxDbWriteable)]
+struct Event {
+    pub time: DateTime<Utc>,
+    pub title: String,
+    pub text: Option<String>,
+    pub tags: Option<String>,
+}
+
+impl From<&Metric> for WriteQuery {
+    fn from(metric: &Metric) -> Self {
+        let time = Timestamp::Now.into();
+        let name = metric.measurement.to_string();
+        let round_id = metric.round_id;
+        let phase = metric.phase.map(|phase| phase as u8);
+
+        match (&metric.measurement, &metric.value) {
+            (_, Value::Event { kind, title }) => Event {
+                time,
+                title: title.clone(),
+                text: None,
+                tags: Some(kind.to_string()),
+            }
+            .into_query(name),
+            (_, Value::Float(value)) => DataPoint {
+                time,
+                value: *value,
+                round_id,
+                phase,
+            }
+            .into_query(name),
+            (_, Value::Integer(value)) => DataPoint {
+                time,
+                value: *value,
+                round_id,
+                phase,
+            }
+            .into_query(name),
+        }
+    }
+}
+
+/// A metrics backend that writes the metrics to InfluxDB.
+pub struct InfluxBackend {
+    /// The InfluxDB client.
+    client: Client,
+}
+
+impl InfluxBackend {
+    /// Creates a new InfluxDB backend.
+    ///
+    /// - `url`: The url where InfluxDB is running (e.g. `http://127.0.0.1:8086`).
+    /// - `database`: The name of the database in which the metrics are to be written.
+    ///
+    /// Note:
+    /// It is assumed that the database already exists. If this is not the case, no metrics are
+    /// written in InfluxDB.
+    pub fn new(url: &str, database: &str) -> Self {
+        Self {
+            client: Client::new(url, database),
+        }
+    }
+
+    /// Similar to the [`InfluxBackend::new`] but with additional InfluxDB user credentials.
+    ///
+    /// - `username`: The username for InfluxDB.
+    /// - `password`: The password for that username.
+    pub fn new_with_auth(url: &str, database: &str, username: &str, password: &str) -> Self {
+        Self {
+            client: Client::new(url, database).with_auth(username, password),
+        }
+    }
+}
+
+#[async_trait]
+impl MetricsBackend for InfluxBackend {
+    async fn record(&mut self, metric: &Metric) {
+        let _ = self
+            .client
+            .query(&WriteQuery::from(metric))
+            .await
+            .map_err(|e| error!("{}", e));
+    }
+}
@@ -1,11 +1,21 @@
-//! Utilities for sending metrics to an InfluxDB instance.
+//! Utilities for recording metrics of the coordinator.
+//!
+//! Metrics are sent through a [`MetricsSender`] to the [`MetricsService`], which records them
+//! with each of its [`MetricsBackend`]s. Two backends are available:
+//!
+//! - [`InfluxBackend`] writes every metric as a data point to an InfluxDB instance.
+//! - [`PrometheusRegistry`] keeps the latest value of every metric in memory and exposes it on a
+//!   `/metrics` endpoint that can be scraped by Prometheus, see [`serve_prometheus`]. Counters
+//!   are labelled with the phase they were emitted in, but not with the round id (which is
+//!   exposed by the `xaynet_round_total_number` gauge instead) to keep the number of time series
+//!   bounded. For the same reason, events are labelled with their kind instead of their title.
 //!
 //! ## Basic usage:
 //!
 //! ```compile_fail
 //! async fn main() {
-//!     let (metrics_service, metrics_sender) =
-//!         MetricsService::new("http://127.0.0.1:8086", "metrics");
+//!     let influx = InfluxBackend::new("http://127.0.0.1:8086", "metrics");
+//!     let (metrics_service, metrics_sender) = MetricsService::new(vec![Box::new(influx)]);
 //!     let metrics_service_handle =
 //!         tokio::spawn(async { run_metric_service(metrics_service).await });
 //!
@@ -18,7 +28,15 @@
 //! }
 //! ```
 
+mod influx;
 mod models;
+mod prometheus;
+
+pub use self::{
+    influx::InfluxBackend,
+    models::Metric,
+    prometheus::{serve as serve_prometheus, PrometheusRegistry},
+};
 
 #[cfg(not(test))]
 pub(crate) mod service;
@@ -29,10 +47,18 @@ pub mod tests;
 #[cfg(test)]
 pub use self::tests::MetricsSender;
 
+/// A backend that records the metrics sent through a [`MetricsSender`].
+#[async_trait]
+pub trait MetricsBackend: Send {
+    /// Records a metric.
+    ///
+    /// Errors are logged and the metric is discarded.
+    async fn record(&mut self, metric: &Metric);
+}
+
 pub mod round_parameters {
-    use super::models::{DataPoint, Measurement};
+    use super::models::{Measurement, Metric};
     use crate::state_machine::phases::PhaseName;
-    use influxdb::{InfluxDbWriteable, Timestamp, WriteQuery};
     pub mod sum {
         use super::*;
 
@@ -49,14 +75,8 @@ pub mod round_parameters {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn update(sum: f64, round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: sum,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::RoundParamSum.to_string())
+        pub fn update(sum: f64, round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(Measurement::RoundParamSum, sum, round_id, phase)
         }
     }
 
@@ -76,22 +96,15 @@ pub mod round_parameters {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn update(update: f64, round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: update,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::RoundParamUpdate.to_string())
+        pub fn update(update: f64, round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(Measurement::RoundParamUpdate, update, round_id, phase)
         }
     }
 }
 
 pub mod phase {
-    use super::models::{DataPoint, Event, Measurement};
+    use super::models::{Measurement, Metric};
     use crate::state_machine::phases::{PhaseName, StateError};
-    use influxdb::{InfluxDbWriteable, Timestamp, WriteQuery};
     pub mod error {
         use super::*;
 
@@ -104,14 +117,10 @@ pub mod phase {
         /// | measurement | `event             `        |
         /// | field_key   | `title`                     |
         /// | field_value | value of `error.to_string()`|
-        pub fn emit(error: &StateError) -> WriteQuery {
-            Event {
-                time: Timestamp::Now.into(),
-                title: error.to_string(),
-                text: None,
-                tags: None,
-            }
-            .into_query(Measurement::Event.to_string())
+        /// | field_key   | `tags`                      |
+        /// | field_value | value of `error.kind()`     |
+        pub fn emit(error: &StateError) -> Metric {
+            Metric::new(Measurement::Event, (error.kind(), error.to_string()))
         }
     }
 
@@ -124,21 +133,14 @@ pub mod phase {
     /// | measurement | `phase`                  |
     /// | field_key   | `value`                  |
     /// | field_value | value of `phase` as `u8` |
-    pub fn update(phase: PhaseName) -> WriteQuery {
-        DataPoint {
-            time: Timestamp::Now.into(),
-            value: phase as u8,
-            round_id: None,
-            phase: None,
-        }
-        .into_query(Measurement::Phase.to_string())
+    pub fn update(phase: PhaseName) -> Metric {
+        Metric::new(Measurement::Phase, u64::from(phase as u8))
     }
 }
 
 pub mod masks {
-    use super::models::{DataPoint, Measurement};
+    use super::models::{Measurement, Metric};
     use crate::state_machine::phases::PhaseName;
-    use influxdb::{InfluxDbWriteable, Timestamp, WriteQuery};
     pub mod total_number {
         use super::*;
 
@@ -155,22 +157,20 @@ pub mod masks {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn update(total_number: usize, round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: total_number as u64,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::MasksTotalNumber.to_string())
+        pub fn update(total_number: usize, round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(
+                Measurement::MasksTotalNumber,
+                total_number as u64,
+                round_id,
+                phase,
+            )
         }
     }
 }
 
 pub mod round {
-    use super::models::{DataPoint, Measurement};
+    use super::models::{Measurement, Metric};
     use crate::state_machine::phases::PhaseName;
-    use influxdb::{InfluxDbWriteable, Timestamp, WriteQuery};
     pub mod total_number {
         use super::*;
 
@@ -183,14 +183,8 @@ pub mod round {
         /// | measurement | `round_total_number`     |
         /// | field_key   | `value`                  |
         /// | field_value | value of `total_number`  |
-        pub fn update(total_number: u64) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: total_number,
-                round_id: None,
-                phase: None,
-            }
-            .into_query(Measurement::RoundTotalNumber.to_string())
+        pub fn update(total_number: u64) -> Metric {
+            Metric::new(Measurement::RoundTotalNumber, total_number)
         }
     }
 
@@ -210,22 +204,15 @@ pub mod round {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn increment(round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: 1,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::RoundSuccessful.to_string())
+        pub fn increment(round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(Measurement::RoundSuccessful, 1_u64, round_id, phase)
         }
     }
 }
 
 pub mod message {
-    use super::models::{DataPoint, Measurement};
+    use super::models::{Measurement, Metric};
     use crate::state_machine::phases::PhaseName;
-    use influxdb::{InfluxDbWriteable, Timestamp, WriteQuery};
     pub mod sum {
         use super::*;
 
@@ -242,14 +229,8 @@ pub mod message {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn increment(round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: 1,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::MessageSum.to_string())
+        pub fn increment(round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(Measurement::MessageSum, 1_u64, round_id, phase)
         }
     }
 
@@ -269,14 +250,8 @@ pub mod message {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn increment(round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: 1,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::MessageUpdate.to_string())
+        pub fn increment(round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(Measurement::MessageUpdate, 1_u64, round_id, phase)
         }
     }
 
@@ -296,14 +271,8 @@ pub mod message {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn increment(round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: 1,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::MessageSum2.to_string())
+        pub fn increment(round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(Measurement::MessageSum2, 1_u64, round_id, phase)
         }
     }
 
@@ -323,14 +292,8 @@ pub mod message {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn increment(round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: 1,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::MessageDiscarded.to_string())
+        pub fn increment(round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(Measurement::MessageDiscarded, 1_u64, round_id, phase)
         }
     }
 
@@ -350,17 +313,31 @@ pub mod message {
         /// | tag_value   | value of `round_id`      |
         /// | tag_key     | `"phase"`                |
         /// | tag_value   | value of `phase` as `u8` |
-        pub fn increment(round_id: u64, phase: PhaseName) -> WriteQuery {
-            DataPoint {
-                time: Timestamp::Now.into(),
-                value: 1,
-                round_id: Some(round_id),
-                phase: Some(phase as u8),
-            }
-            .into_query(Measurement::MessageRejected.to_string())
+        pub fn increment(round_id: u64, phase: PhaseName) -> Metric {
+            Metric::with_tags(Measurement::MessageRejected, 1_u64, round_id, phase)
+        }
+    }
+}
+pub mod request_queue {
+    use super::models::{Measurement, Metric};
+    pub mod depth {
+        use super::*;
+
+        /// Updates the measurement `request_queue_depth` with the value of `depth`.
+        ///
+        /// Creates an influx data point with the following properties:
+        ///
+        /// | property    | value                    |
+        /// |-------------|--------------------------|
+        /// | measurement | `request_queue_depth`    |
+        /// | field_key   | `value`                  |
+        /// | field_value | value of `depth`         |
+        pub fn update(depth: usize) -> Metric {
+            Metric::new(Measurement::RequestQueueDepth, depth as u64)
         }
     }
 }
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -368,7 +345,7 @@ mod test {
         phases::{PhaseName, StateError},
         RoundFailed,
     };
-    use influxdb::Query;
+    use influxdb::{Query, WriteQuery};
 
     // The fields of the WriteQuery are private and there are no kinds of getters for the fields.
     // One way to get something is via `build`.
@@ -377,102 +354,115 @@ mod test {
 
     #[test]
     fn test_round_parameters_sum() {
-        let query = round_parameters::sum::update(0.6, 1, PhaseName::Sum).build();
+        let query =
+            WriteQuery::from(&round_parameters::sum::update(0.6, 1, PhaseName::Sum)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("round_param_sum,round_id=\\\"1\\\",phase=\\\"1\\\" value=0.6"));
     }
 
     #[test]
     fn test_round_parameters_update() {
-        let query = round_parameters::update::update(0.8, 1, PhaseName::Sum).build();
+        let query =
+            WriteQuery::from(&round_parameters::update::update(0.8, 1, PhaseName::Sum)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("round_param_update,round_id=\\\"1\\\",phase=\\\"1\\\" value=0.8"));
     }
 
     #[test]
     fn test_phase_name() {
-        let query = phase::update(PhaseName::Idle).build();
+        let query = WriteQuery::from(&phase::update(PhaseName::Idle)).build();
         assert!(format!("{:?}", query.unwrap()).contains("phase value=0"));
 
-        let query = phase::update(PhaseName::Sum).build();
+        let query = WriteQuery::from(&phase::update(PhaseName::Sum)).build();
         assert!(format!("{:?}", query.unwrap()).contains("phase value=1"));
 
-        let query = phase::update(PhaseName::Update).build();
+        let query = WriteQuery::from(&phase::update(PhaseName::Update)).build();
         assert!(format!("{:?}", query.unwrap()).contains("phase value=2"));
 
-        let query = phase::update(PhaseName::Sum2).build();
+        let query = WriteQuery::from(&phase::update(PhaseName::Sum2)).build();
         assert!(format!("{:?}", query.unwrap()).contains("phase value=3"));
 
-        let query = phase::update(PhaseName::Unmask).build();
+        let query = WriteQuery::from(&phase::update(PhaseName::Unmask)).build();
         assert!(format!("{:?}", query.unwrap()).contains("phase value=4"));
 
-        let query = phase::update(PhaseName::Error).build();
+        let query = WriteQuery::from(&phase::update(PhaseName::Error)).build();
         assert!(format!("{:?}", query.unwrap()).contains("phase value=5"));
 
-        let query = phase::update(PhaseName::Shutdown).build();
+        let query = WriteQuery::from(&phase::update(PhaseName::Shutdown)).build();
         assert!(format!("{:?}", query.unwrap()).contains("phase value=6"));
     }
 
     #[test]
     fn test_phase_error() {
-        let query = phase::error::emit(&StateError::RoundError(RoundFailed::NoMask)).build();
-        assert!(format!("{:?}", query.unwrap()).contains(
+        let query = WriteQuery::from(&phase::error::emit(&StateError::RoundError(
+            RoundFailed::NoMask,
+        )))
+        .build();
+        let query = format!("{:?}", query.unwrap());
+        assert!(query.contains(
             "event title=\\\"state\\\\ failed:\\\\ round\\\\ error:\\\\ no\\\\ mask\\\\ found\\\""
         ));
+        assert!(query.contains("tags=\\\"round\\\""));
     }
 
     #[test]
     fn test_masks_total_number() {
-        let query = masks::total_number::update(12, 1, PhaseName::Sum).build();
+        let query = WriteQuery::from(&masks::total_number::update(12, 1, PhaseName::Sum)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("masks_total_number,round_id=\\\"1\\\",phase=\\\"1\\\" value=12"));
     }
 
     #[test]
     fn test_round_total_number() {
-        let query = round::total_number::update(2).build();
+        let query = WriteQuery::from(&round::total_number::update(2)).build();
         assert!(format!("{:?}", query.unwrap()).contains("round_total_number value=2"));
     }
 
     #[test]
     fn test_round_successful() {
-        let query = round::successful::increment(1, PhaseName::Sum).build();
+        let query = WriteQuery::from(&round::successful::increment(1, PhaseName::Sum)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("round_successful,round_id=\\\"1\\\",phase=\\\"1\\\" value=1"));
     }
 
     #[test]
     fn test_message_sum() {
-        let query = message::sum::increment(1, PhaseName::Sum).build();
+        let query = WriteQuery::from(&message::sum::increment(1, PhaseName::Sum)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("message_sum,round_id=\\\"1\\\",phase=\\\"1\\\" value=1"));
     }
 
     #[test]
     fn test_message_update() {
-        let query = message::update::increment(1, PhaseName::Update).build();
+        let query = WriteQuery::from(&message::update::increment(1, PhaseName::Update)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("message_update,round_id=\\\"1\\\",phase=\\\"2\\\" value=1"));
     }
 
     #[test]
     fn test_message_sum2() {
-        let query = message::sum2::increment(1, PhaseName::Sum2).build();
+        let query = WriteQuery::from(&message::sum2::increment(1, PhaseName::Sum2)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("message_sum2,round_id=\\\"1\\\",phase=\\\"3\\\" value=1"));
     }
 
     #[test]
     fn test_message_discarded() {
-        let query = message::discarded::increment(1, PhaseName::Idle).build();
+        let query = WriteQuery::from(&message::discarded::increment(1, PhaseName::Idle)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("message_discarded,round_id=\\\"1\\\",phase=\\\"0\\\" value=1"));
     }
 
     #[test]
     fn test_message_rejected() {
-        let query = message::rejected::increment(1, PhaseName::Sum).build();
+        let query = WriteQuery::from(&message::rejected::increment(1, PhaseName::Sum)).build();
         assert!(format!("{:?}", query.unwrap())
             .contains("message_rejected,round_id=\\\"1\\\",phase=\\\"1\\\" value=1"));
     }
+
+    #[test]
+    fn test_request_queue_depth() {
+        let query = WriteQuery::from(&request_queue::depth::update(7)).build();
+        assert!(format!("{:?}", query.unwrap()).contains("request_queue_depth value=7"));
+    }
 }
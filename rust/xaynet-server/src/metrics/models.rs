@@ -1,7 +1,7 @@
-use chrono::{DateTime, Utc};
-use influxdb::InfluxDbWriteable;
+use crate::state_machine::phases::PhaseName;
 
 /// An enum that contains all supported measurements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(in crate::metrics) enum Measurement {
     RoundParamSum,
     RoundParamUpdate,
@@ -14,6 +14,7 @@ pub(in crate::metrics) enum Measurement {
     MessageSum2,
     MessageDiscarded,
     MessageRejected,
+    RequestQueueDepth,
     Event,
 }
 
@@ -31,6 +32,7 @@ impl From<&Measurement> for &'static str {
             Measurement::MessageSum2 => "message_sum2",
             Measurement::MessageDiscarded => "message_discarded",
             Measurement::MessageRejected => "message_rejected",
+            Measurement::RequestQueueDepth => "request_queue_depth",
             Measurement::Event => "event",
         }
     }
@@ -42,30 +44,68 @@ impl ToString for Measurement {
     }
 }
 
-/// A generic influx data point.
-pub(in crate::metrics) struct DataPoint<T: Into<influxdb::Type>> {
-    pub time: DateTime<Utc>,
-    pub value: T,
-    pub round_id: Option<u64>,
-    pub phase: Option<u8>,
+/// The value of a metric.
+#[derive(Debug, Clone, PartialEq)]
+pub(in crate::metrics) enum Value {
+    Float(f64),
+    Integer(u64),
+    /// An event with a fixed kind and a free text title.
+    Event {
+        kind: &'static str,
+        title: String,
+    },
 }
 
-impl<T: Into<influxdb::Type>> InfluxDbWriteable for DataPoint<T> {
-    fn into_query<I: Into<String>>(self, name: I) -> influxdb::WriteQuery {
-        let timestamp: ::influxdb::Timestamp = self.time.into();
-        let mut query = timestamp.into_query(name);
-        query = query.add_field("value", self.value);
-        query = query.add_tag("round_id", self.round_id);
-        query = query.add_tag("phase", self.phase);
-        query
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
     }
 }
 
-/// An `event` data point.
-#[derive(InfluxDbWriteable)]
-pub(in crate::metrics) struct Event {
-    pub time: DateTime<Utc>,
-    pub title: String,
-    pub text: Option<String>,
-    pub tags: Option<String>,
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<(&'static str, String)> for Value {
+    fn from((kind, title): (&'static str, String)) -> Self {
+        Self::Event { kind, title }
+    }
+}
+
+/// A single metric, independent of the backend it is recorded with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub(in crate::metrics) measurement: Measurement,
+    pub(in crate::metrics) value: Value,
+    pub(in crate::metrics) round_id: Option<u64>,
+    pub(in crate::metrics) phase: Option<PhaseName>,
+}
+
+impl Metric {
+    /// Creates a metric without any tags.
+    pub(in crate::metrics) fn new<V: Into<Value>>(measurement: Measurement, value: V) -> Self {
+        Self {
+            measurement,
+            value: value.into(),
+            round_id: None,
+            phase: None,
+        }
+    }
+
+    /// Creates a metric that is tagged with the round id and the phase it was emitted in.
+    pub(in crate::metrics) fn with_tags<V: Into<Value>>(
+        measurement: Measurement,
+        value: V,
+        round_id: u64,
+        phase: PhaseName,
+    ) -> Self {
+        Self {
+            measurement,
+            value: value.into(),
+            round_id: Some(round_id),
+            phase: Some(phase),
+        }
+    }
 }
@@ -0,0 +1,257 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use warp::Filter;
+
+use super::{
+    models::{Measurement, Metric, Value},
+    MetricsBackend,
+};
+
+/// The prefix of all metric names.
+const NAMESPACE: &str = "xaynet";
+
+/// The content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// The type of a Prometheus metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A value that is only ever incremented.
+    Counter,
+    /// A value that is replaced on every update.
+    Gauge,
+}
+
+/// Gets the type and help text of the Prometheus metric that corresponds to a measurement.
+fn describe(measurement: Measurement) -> (Kind, &'static str) {
+    match measurement {
+        Measurement::RoundParamSum => (Kind::Gauge, "The fraction of sum participants."),
+        Measurement::RoundParamUpdate => (Kind::Gauge, "The fraction of update participants."),
+        Measurement::Phase => (Kind::Gauge, "The current phase of the coordinator."),
+        Measurement::MasksTotalNumber => (Kind::Gauge, "The number of masks of the round."),
+        Measurement::RoundTotalNumber => (Kind::Gauge, "The number of the current round."),
+        Measurement::RoundSuccessful => (Kind::Counter, "The number of successful rounds."),
+        Measurement::MessageSum => (Kind::Counter, "The number of accepted sum messages."),
+        Measurement::MessageUpdate => (Kind::Counter, "The number of accepted update messages."),
+        Measurement::MessageSum2 => (Kind::Counter, "The number of accepted sum2 messages."),
+        Measurement::MessageDiscarded => (Kind::Counter, "The number of discarded messages."),
+        Measurement::MessageRejected => (Kind::Counter, "The number of rejected messages."),
+        Measurement::RequestQueueDepth => (Kind::Gauge, "The number of queued messages."),
+        Measurement::Event => (Kind::Counter, "The number of events, such as phase errors."),
+    }
+}
+
+/// Gets the name of the Prometheus metric that corresponds to a measurement.
+fn name(measurement: Measurement, kind: Kind) -> String {
+    match kind {
+        Kind::Counter => format!("{}_{}_total", NAMESPACE, measurement.to_string()),
+        Kind::Gauge => format!("{}_{}", NAMESPACE, measurement.to_string()),
+    }
+}
+
+/// Escapes a label value as required by the Prometheus text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The label names and values of a time series.
+type Labels = Vec<(&'static str, String)>;
+
+/// A metrics backend that keeps the current value of each metric in memory, so that it can be
+/// scraped by Prometheus.
+///
+/// The registry is cheap to clone: all clones share the same values.
+#[derive(Debug, Clone, Default)]
+pub struct PrometheusRegistry {
+    series: Arc<Mutex<BTreeMap<Measurement, BTreeMap<Labels, f64>>>>,
+}
+
+impl PrometheusRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the registry with a metric.
+    ///
+    /// Counters are incremented by the value of the metric, gauges are set to it.
+    fn update(&self, metric: &Metric) {
+        let (kind, _) = describe(metric.measurement);
+        let mut labels = Labels::new();
+        if let Some(phase) = metric.phase {
+            labels.push(("phase", format!("{:?}", phase).to_lowercase()));
+        }
+        let value = match &metric.value {
+            Value::Float(value) => *value,
+            Value::Integer(value) => *value as f64,
+            // the kind of an event is used as a label of the event counter, since the title is
+            // free text which would create a new time series for every distinct message
+            Value::Event { kind, .. } => {
+                labels.push(("kind", kind.to_string()));
+                1.0
+            }
+        };
+
+        let mut series = self.series.lock().unwrap();
+        let sample = series
+            .entry(metric.measurement)
+            .or_default()
+            .entry(labels)
+            .or_insert(0.0);
+        match kind {
+            Kind::Counter => *sample += value,
+            Kind::Gauge => *sample = value,
+        }
+    }
+
+    /// Renders the current values of all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let series = self.series.lock().unwrap();
+        let mut output = String::new();
+        for (measurement, samples) in series.iter() {
+            let (kind, help) = describe(*measurement);
+            let name = name(*measurement, kind);
+            let kind = match kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+            };
+            // writing to a string can't fail
+            let _ = writeln!(output, "# HELP {} {}", name, help);
+            let _ = writeln!(output, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples.iter() {
+                if labels.is_empty() {
+                    let _ = writeln!(output, "{} {}", name, value);
+                } else {
+                    let labels = labels
+                        .iter()
+                        .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    let _ = writeln!(output, "{}{{{}}} {}", name, labels, value);
+                }
+            }
+        }
+        output
+    }
+}
+
+#[async_trait]
+impl MetricsBackend for PrometheusRegistry {
+    async fn record(&mut self, metric: &Metric) {
+        self.update(metric);
+    }
+}
+
+/// Starts a HTTP server at the given address that serves the metrics of the registry on
+/// `GET /metrics`.
+pub async fn serve(addr: impl Into<SocketAddr> + 'static, registry: PrometheusRegistry) {
+    let metrics = route(registry).with(warp::log("http"));
+    warp::serve(metrics).run(addr).await
+}
+
+/// Responds to `GET /metrics` with the rendered metrics of the registry.
+fn route(
+    registry: PrometheusRegistry,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("metrics")
+        .and(warp::get())
+        .map(move || warp::reply::with_header(registry.render(), "Content-Type", CONTENT_TYPE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        metrics,
+        state_machine::{
+            phases::{PhaseName, StateError},
+            RoundFailed,
+        },
+    };
+
+    #[test]
+    fn test_gauges() {
+        let registry = PrometheusRegistry::new();
+        registry.update(&metrics::round::total_number::update(1));
+        registry.update(&metrics::round::total_number::update(2));
+        registry.update(&metrics::phase::update(PhaseName::Sum));
+        registry.update(&metrics::round_parameters::sum::update(
+            0.5,
+            2,
+            PhaseName::Idle,
+        ));
+
+        let output = registry.render();
+        assert!(output
+            .contains("# TYPE xaynet_round_total_number gauge\nxaynet_round_total_number 2\n"));
+        assert!(output.contains("# TYPE xaynet_phase gauge\nxaynet_phase 1\n"));
+        assert!(output.contains("xaynet_round_param_sum{phase=\"idle\"} 0.5\n"));
+    }
+
+    #[test]
+    fn test_counters() {
+        let registry = PrometheusRegistry::new();
+        registry.update(&metrics::message::sum::increment(1, PhaseName::Sum));
+        registry.update(&metrics::message::sum::increment(1, PhaseName::Sum));
+        registry.update(&metrics::message::sum::increment(2, PhaseName::Sum));
+        registry.update(&metrics::message::rejected::increment(2, PhaseName::Sum));
+        registry.update(&metrics::message::rejected::increment(2, PhaseName::Update));
+        registry.update(&metrics::message::discarded::increment(2, PhaseName::Idle));
+        registry.update(&metrics::masks::total_number::update(
+            3,
+            2,
+            PhaseName::Unmask,
+        ));
+
+        let output = registry.render();
+        assert!(output.contains("# TYPE xaynet_message_sum_total counter\n"));
+        assert!(output.contains("xaynet_message_sum_total{phase=\"sum\"} 3\n"));
+        assert!(output.contains("xaynet_message_rejected_total{phase=\"sum\"} 1\n"));
+        assert!(output.contains("xaynet_message_rejected_total{phase=\"update\"} 1\n"));
+        assert!(output.contains("xaynet_message_discarded_total{phase=\"idle\"} 1\n"));
+        assert!(output.contains("xaynet_masks_total_number{phase=\"unmask\"} 3\n"));
+    }
+
+    #[test]
+    fn test_events() {
+        let registry = PrometheusRegistry::new();
+        registry.update(&metrics::phase::error::emit(&StateError::RoundError(
+            RoundFailed::NoMask,
+        )));
+        registry.update(&metrics::phase::error::emit(&StateError::RoundError(
+            RoundFailed::NoQuorum,
+        )));
+        registry.update(&metrics::phase::error::emit(&StateError::Aborted));
+
+        let output = registry.render();
+        assert!(output.contains("xaynet_event_total{kind=\"round\"} 2\n"));
+        assert!(output.contains("xaynet_event_total{kind=\"aborted\"} 1\n"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[tokio::test]
+    async fn test_endpoint() {
+        let registry = PrometheusRegistry::new();
+        registry.update(&metrics::round::total_number::update(7));
+
+        let resp = warp::test::request()
+            .path("/metrics")
+            .reply(&route(registry))
+            .await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["content-type"], CONTENT_TYPE);
+        assert!(String::from_utf8_lossy(resp.body()).contains("xaynet_round_total_number 7\n"));
+    }
+}
@@ -1,8 +1,8 @@
-use influxdb::WriteQuery;
+use super::Metric;
 
 #[derive(Debug)]
 pub struct MetricsSender();
 
 impl MetricsSender {
-    pub fn send(&mut self, _query: WriteQuery) {}
+    pub fn send(&mut self, _metric: Metric) {}
 }
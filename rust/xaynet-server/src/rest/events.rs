@@ -0,0 +1,188 @@
+//! Notifications about coordinator events for the REST API.
+//!
+//! Instead of polling the data endpoints, clients can subscribe to `/events` and are notified via
+//! Server-Sent Events as soon as new data is available. The notifications only announce the data,
+//! which must still be fetched from the respective endpoint.
+
+use std::fmt;
+
+use futures::{future, stream, Stream, StreamExt};
+use serde::Serialize;
+
+use crate::state_machine::{
+    events::{DictionaryUpdate, EventSubscriber, MaskLengthUpdate, ModelUpdate},
+    phases::PhaseName,
+};
+
+/// The kind of a coordinator event.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EventKind {
+    /// New round parameters are available at `/params`.
+    Params,
+    /// The coordinator entered a new phase.
+    Phase,
+    /// The sum dictionary is available at `/sums`.
+    Sums,
+    /// The seed dictionaries are available at `/seeds`.
+    Seeds,
+    /// The mask length is available at `/length`.
+    Length,
+    /// A new global model is available at `/model`.
+    Model,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Params => "params",
+            Self::Phase => "phase",
+            Self::Sums => "sums",
+            Self::Seeds => "seeds",
+            Self::Length => "length",
+            Self::Model => "model",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A notification about a coordinator event.
+///
+/// The kind is sent as the name of the server-sent event and the notification itself as its JSON
+/// encoded data.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    #[serde(skip)]
+    pub kind: EventKind,
+    /// The round in which the event was emitted.
+    pub round_id: u64,
+    /// The new phase, if this is a phase event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<PhaseName>,
+}
+
+impl Notification {
+    fn new(kind: EventKind, round_id: u64) -> Self {
+        Self {
+            kind,
+            round_id,
+            phase: None,
+        }
+    }
+}
+
+/// Creates a stream of notifications about the coordinator events.
+///
+/// The stream starts with the notifications about the current state of the coordinator, followed
+/// by a notification for every subsequent event. Invalidated data is not announced.
+///
+/// The subscriber's own listeners are never polled, hence the listeners handed out by it yield
+/// the latest event first.
+pub fn notifications(
+    subscriber: &EventSubscriber,
+) -> impl Stream<Item = Notification> + Send + Unpin + 'static {
+    let params = subscriber
+        .params_listener()
+        .map(|event| Notification::new(EventKind::Params, event.round_id));
+    let phase = subscriber.phase_listener().map(|event| Notification {
+        phase: Some(event.event),
+        ..Notification::new(EventKind::Phase, event.round_id)
+    });
+    let sums = subscriber.sum_dict_listener().filter_map(|event| {
+        future::ready(match event.event {
+            DictionaryUpdate::New(_) => Some(Notification::new(EventKind::Sums, event.round_id)),
+            DictionaryUpdate::Invalidate => None,
+        })
+    });
+    let seeds = subscriber.seed_dict_listener().filter_map(|event| {
+        future::ready(match event.event {
+            DictionaryUpdate::New(_) => Some(Notification::new(EventKind::Seeds, event.round_id)),
+            DictionaryUpdate::Invalidate => None,
+        })
+    });
+    let length = subscriber.mask_length_listener().filter_map(|event| {
+        future::ready(match event.event {
+            MaskLengthUpdate::New(_) => Some(Notification::new(EventKind::Length, event.round_id)),
+            MaskLengthUpdate::Invalidate => None,
+        })
+    });
+    let model = subscriber.model_listener().filter_map(|event| {
+        future::ready(match event.event {
+            ModelUpdate::New(_) => Some(Notification::new(EventKind::Model, event.round_id)),
+            ModelUpdate::Invalidate => None,
+        })
+    });
+
+    stream::select_all(vec![
+        params.boxed(),
+        phase.boxed(),
+        sums.boxed(),
+        seeds.boxed(),
+        length.boxed(),
+        model.boxed(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio_test::{assert_pending, task};
+
+    use super::*;
+    use crate::services::tests::utils;
+
+    #[tokio::test]
+    async fn notifications_follow_the_events() {
+        let (mut publisher, subscriber) = utils::new_event_channels();
+        let mut task = task::spawn(notifications(&subscriber));
+
+        // the current state is announced first, without the invalidated data
+        let mut initial = vec![task.next().await.unwrap(), task.next().await.unwrap()];
+        initial.sort_by_key(|notification| notification.kind.to_string());
+        assert_eq!(
+            initial,
+            vec![
+                Notification::new(EventKind::Params, 0),
+                Notification {
+                    phase: Some(PhaseName::Idle),
+                    ..Notification::new(EventKind::Phase, 0)
+                },
+            ]
+        );
+        assert_pending!(task.poll_next());
+
+        publisher.set_round_id(1);
+        publisher.broadcast_phase(PhaseName::Sum);
+        assert_eq!(
+            task.next().await.unwrap(),
+            Notification {
+                phase: Some(PhaseName::Sum),
+                ..Notification::new(EventKind::Phase, 1)
+            }
+        );
+
+        publisher.broadcast_sum_dict(DictionaryUpdate::Invalidate);
+        assert_pending!(task.poll_next());
+        publisher.broadcast_sum_dict(DictionaryUpdate::New(Arc::new(Default::default())));
+        assert_eq!(
+            task.next().await.unwrap(),
+            Notification::new(EventKind::Sums, 1)
+        );
+    }
+
+    #[test]
+    fn notification_as_json() {
+        let notification = Notification {
+            phase: Some(PhaseName::Sum2),
+            ..Notification::new(EventKind::Phase, 3)
+        };
+        assert_eq!(
+            serde_json::to_string(&notification).unwrap(),
+            r#"{"round_id":3,"phase":"Sum2"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Notification::new(EventKind::Model, 3)).unwrap(),
+            r#"{"round_id":3}"#
+        );
+    }
+}
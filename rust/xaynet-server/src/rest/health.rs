@@ -0,0 +1,179 @@
+//! Health checks of the coordinator for the REST API.
+//!
+//! The coordinator is live as long as it serves requests at `/health/live`. It is ready at
+//! `/health/ready` if the state machine is running and accepts requests, if Redis responds and if
+//! the state machine is neither shutting down nor failing rounds repeatedly because of storage
+//! errors. Rounds which fail for other reasons, like a missing quorum of participants, don't
+//! affect the readiness, since participants couldn't reach an unready coordinator anymore.
+
+use serde::Serialize;
+use tokio::time::{timeout, Duration};
+
+use crate::{
+    state_machine::{events::EventSubscriber, phases::PhaseName, requests::RequestSender},
+    storage::redis::Client,
+};
+
+/// The maximum number of consecutive rounds which may fail because of storage errors while the
+/// coordinator is ready.
+const MAX_STORAGE_FAILURES: u32 = 3;
+
+/// The maximum time to wait for a response from Redis.
+const REDIS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The dependencies of the coordinator which are checked for its readiness.
+#[derive(Debug, Clone)]
+pub struct Health {
+    /// Subscriber for the phase of the state machine.
+    event_subscriber: EventSubscriber,
+    /// Sender half of the request channel of the state machine.
+    requests: RequestSender,
+    /// The Redis client, or `None` if the coordinator doesn't use Redis.
+    redis: Option<Client>,
+}
+
+/// The result of the readiness checks.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Readiness {
+    /// Whether the coordinator is ready.
+    pub ready: bool,
+    /// The current phase of the state machine.
+    pub phase: PhaseName,
+    /// Whether the state machine is running.
+    pub state_machine: bool,
+    /// Whether the state machine accepts requests.
+    pub requests: bool,
+    /// Whether Redis responds, if the coordinator uses Redis.
+    pub redis: Option<bool>,
+}
+
+impl Health {
+    /// Creates the health checks for the given dependencies.
+    pub fn new(
+        event_subscriber: EventSubscriber,
+        requests: RequestSender,
+        redis: Option<Client>,
+    ) -> Self {
+        Self {
+            event_subscriber,
+            requests,
+            redis,
+        }
+    }
+
+    /// Checks the readiness of the coordinator.
+    pub async fn readiness(&self) -> Readiness {
+        let phase_listener = self.event_subscriber.phase_listener();
+        let phase = phase_listener.get_latest().event;
+        let state_machine = !phase_listener.is_closed();
+        let requests = !self.requests.is_closed();
+        let redis = match self.redis {
+            Some(ref redis) => Some(ping(redis).await),
+            None => None,
+        };
+
+        let storage_failures = self
+            .event_subscriber
+            .storage_failures_listener()
+            .get_latest()
+            .event;
+        let phase_ok = phase != PhaseName::Shutdown && storage_failures <= MAX_STORAGE_FAILURES;
+
+        Readiness {
+            ready: phase_ok && state_machine && requests && redis.unwrap_or(true),
+            phase,
+            state_machine,
+            requests,
+            redis,
+        }
+    }
+}
+
+/// Checks whether Redis responds in time.
+async fn ping(redis: &Client) -> bool {
+    // the timeout includes waiting for a connection, which blocks if all connections are in use
+    match timeout(REDIS_TIMEOUT, async {
+        redis.connection().await.ping().await
+    })
+    .await
+    {
+        Ok(Ok(())) => true,
+        Ok(Err(err)) => {
+            warn!("readiness check failed: Redis ping failed: {}", err);
+            false
+        }
+        Err(_) => {
+            warn!("readiness check failed: Redis ping timed out");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use super::*;
+    use crate::{
+        services::tests::utils,
+        settings::RequestSettings,
+        state_machine::requests::RequestReceiver,
+    };
+
+    #[tokio::test]
+    async fn readiness() {
+        let (mut publisher, subscriber) = utils::new_event_channels();
+        let (receiver, sender) = RequestReceiver::new(RequestSettings::default());
+        let health = Health::new(subscriber, sender, None);
+
+        publisher.broadcast_phase(PhaseName::Sum);
+        assert_eq!(
+            health.readiness().await,
+            Readiness {
+                ready: true,
+                phase: PhaseName::Sum,
+                state_machine: true,
+                requests: true,
+                redis: None,
+            }
+        );
+
+        // the state machine may pass through the error phase
+        publisher.broadcast_phase(PhaseName::Error);
+        publisher.broadcast_storage_failures(MAX_STORAGE_FAILURES);
+        assert!(health.readiness().await.ready);
+        publisher.broadcast_storage_failures(MAX_STORAGE_FAILURES + 1);
+        assert!(!health.readiness().await.ready);
+        publisher.broadcast_storage_failures(0);
+        assert!(health.readiness().await.ready);
+
+        publisher.broadcast_phase(PhaseName::Shutdown);
+        assert!(!health.readiness().await.ready);
+
+        drop(receiver);
+        drop(publisher);
+        assert_eq!(
+            health.readiness().await,
+            Readiness {
+                ready: false,
+                phase: PhaseName::Shutdown,
+                state_machine: false,
+                requests: false,
+                redis: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn integration_readiness_with_redis() {
+        let (_publisher, subscriber) = utils::new_event_channels();
+        let (_receiver, sender) = RequestReceiver::new(RequestSettings::default());
+        let redis = Client::new("redis://127.0.0.1/", 1).await.unwrap();
+        let health = Health::new(subscriber, sender, Some(redis));
+
+        let readiness = health.readiness().await;
+        assert_eq!(readiness.redis, Some(true));
+        assert!(readiness.ready);
+    }
+}
@@ -0,0 +1,1584 @@
+//! A HTTP API for the PET protocol interactions.
+
+mod events;
+mod health;
+mod status;
+mod tls;
+
+pub use self::{
+    health::Health,
+    tls::{TlsConfig, TlsError},
+};
+
+use self::status::Status;
+use crate::{
+    services::{fetchers::Fetcher, messages::PetMessageHandler},
+    settings::ApiSettings,
+    state_machine::{
+        admin::{AdminCommand, AdminSender, UpdatePetSettingsError},
+        events::EventSubscriber,
+        phases::unix_time,
+    },
+    storage::{
+        allowlist::Allowlist,
+        history::{ModelHistory, ModelMetadata},
+    },
+};
+use bytes::{Buf, Bytes};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::Infallible, hash::Hash, net::SocketAddr, sync::Arc};
+use tokio::{net::TcpListener, sync::OwnedSemaphorePermit};
+use warp::{
+    http::{Method, Response, StatusCode},
+    Filter,
+    Reply,
+};
+use xaynet_core::{
+    common::{MessageError, RoundParameters},
+    crypto::ByteObject,
+    mask::{MaskConfigPair, Model, NoiseParameters},
+    ParticipantPublicKey,
+};
+
+/// Starts a HTTP server at the configured address, listening to GET requests for
+/// data and POST requests containing PET messages.
+///
+/// The data is encoded with bincode by default. Requests with an `Accept:
+/// application/json` header get a JSON response instead, in which keys and
+/// other byte objects are encoded as base64 strings.
+///
+/// Clients can subscribe to `/events` to be notified via Server-Sent Events
+/// as soon as new round parameters, dictionaries, mask lengths or models are
+/// available, instead of polling the data endpoints.
+///
+/// The status of the coordinator, including the progress of the current round
+/// and the outcome of the last round, is served as JSON at `/status`.
+///
+/// The liveness of the coordinator is probed at `/health/live` and its
+/// readiness at `/health/ready`, which responds with `503 Service Unavailable`
+/// if the coordinator is not ready.
+///
+/// Admin commands are accepted as POST requests to `/admin/<command>` if an
+/// admin token is configured. The requests must carry the token in an
+/// `Authorization: Bearer <token>` header. The admin API is served on its own
+/// address if an `admin_bind_address` is configured. Otherwise it is served
+/// next to the public API, such that everyone who can reach the coordinator can
+/// try the admin token. Either way, it is served over TLS if TLS is configured,
+/// such that the admin token is never sent in cleartext.
+///
+/// New PET settings are accepted as a PUT request to `/admin/settings/pet`
+/// with the `[pet]` section of the configuration as a JSON body. They are
+/// applied when the next round starts.
+///
+/// If an `allowlist` is set, it is managed through the admin API at
+/// `/admin/allowlist`. A GET request lists the base64 encoded public keys on
+/// the allowlist, while POST and DELETE requests add and remove the base64
+/// encoded public key in the request body.
+///
+/// If a `model_history` is set, the global model of an earlier round is
+/// served at `/model?round=<round_id>` and the metadata of all stored global
+/// models is listed at `/models`.
+///
+/// The server terminates TLS connections itself if a TLS configuration is
+/// given. The TLS configuration is loaded from the TLS settings beforehand, such
+/// that invalid certificates or keys are detected before the coordinator starts,
+/// and it is reloaded when its files change.
+///
+/// * `settings`: settings of the REST API.
+/// * `tls`: the TLS configuration or `None` to serve plain HTTP.
+/// * `fetcher`: fetcher for responding to data requests.
+/// * `event_subscriber`: subscriber for notifying clients about events.
+/// * `pet_message_handler`: handler for responding to PET messages.
+/// * `admin_sender`: sender for forwarding admin commands to the state machine.
+/// * `model_history`: history of the global models.
+/// * `allowlist`: allowlist of the participants.
+/// * `health`: dependencies to check for the readiness.
+#[allow(clippy::too_many_arguments)]
+pub async fn serve<F>(
+    settings: ApiSettings,
+    tls: Option<TlsConfig>,
+    fetcher: F,
+    event_subscriber: EventSubscriber,
+    pet_message_handler: PetMessageHandler,
+    admin_sender: AdminSender,
+    model_history: Option<ModelHistory>,
+    allowlist: Option<Allowlist>,
+    health: Health,
+) where
+    F: Fetcher + Sync + Send + 'static + Clone,
+{
+    let message = message(settings.max_body_size, pet_message_handler);
+
+    let sum_dict = warp::path!("sums")
+        .and(warp::get())
+        .and(format())
+        .and(with_fetcher(fetcher.clone()))
+        .and_then(handle_sums);
+
+    let seed_dict = warp::path!("seeds")
+        .and(warp::get())
+        .and(part_pk())
+        .and(format())
+        .and(with_fetcher(fetcher.clone()))
+        .and_then(handle_seeds);
+
+    let length = warp::path!("length")
+        .and(warp::get())
+        .and(format())
+        .and(with_fetcher(fetcher.clone()))
+        .and_then(handle_length);
+
+    let round_params = warp::path!("params")
+        .and(warp::get())
+        .and(format())
+        .and(with_fetcher(fetcher.clone()))
+        .and_then(handle_params);
+
+    let model = warp::path!("model")
+        .and(warp::get())
+        .and(warp::query::<ModelQuery>())
+        .and(format())
+        .and(with_fetcher(fetcher.clone()))
+        .and(with_model_history(model_history.clone()))
+        .and_then(handle_model);
+
+    let models = warp::path!("models")
+        .and(warp::get())
+        .and(format())
+        .and(with_model_history(model_history))
+        .and_then(handle_models);
+
+    let events = warp::path!("events")
+        .and(warp::get())
+        .and(with_event_subscriber(event_subscriber.clone()))
+        .and_then(handle_events);
+
+    let status = warp::path!("status")
+        .and(warp::get())
+        .and(with_event_subscriber(event_subscriber))
+        .and_then(handle_status);
+
+    let live = warp::path!("health" / "live")
+        .and(warp::get())
+        .map(|| StatusCode::OK);
+
+    let ready = warp::path!("health" / "ready")
+        .and(warp::get())
+        .and(with_health(health))
+        .and_then(handle_ready);
+
+    let admin = Admin {
+        token: settings.admin_token,
+        sender: admin_sender,
+        allowlist,
+    };
+
+    let admin_allowlist = warp::path!("admin" / "allowlist")
+        .and(warp::method())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::bytes())
+        .and(with_admin(admin.clone()))
+        .and_then(handle_allowlist);
+
+    let admin_pet_settings = warp::path!("admin" / "settings" / "pet")
+        .and(warp::put())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::bytes())
+        .and(with_admin(admin.clone()))
+        .and_then(handle_pet_settings);
+
+    let admin_command = warp::path!("admin" / String)
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(with_admin(admin))
+        .and_then(handle_admin);
+
+    let public_routes = message
+        .or(round_params)
+        .or(sum_dict)
+        .or(seed_dict)
+        .or(length)
+        .or(model)
+        .or(models)
+        .or(events)
+        .or(status)
+        .or(live)
+        .or(ready);
+    let admin_routes = admin_allowlist.or(admin_pet_settings).or(admin_command);
+
+    let tls = tls.map(Arc::new);
+    let reload_tls = {
+        let tls = tls.clone();
+        async move {
+            match tls {
+                Some(tls) => tls::reload_periodically(tls).await,
+                None => futures::future::pending().await,
+            }
+        }
+    };
+    let admin_bind_address = match settings.admin_bind_address {
+        Some(admin_bind_address) => admin_bind_address,
+        None => {
+            let routes = public_routes
+                .or(admin_routes)
+                .recover(handle_reject)
+                .with(warp::log("http"));
+            tokio::select! {
+                _ = serve_routes(routes, settings.bind_address, tls) => {}
+                _ = reload_tls => {}
+            }
+            return;
+        }
+    };
+    let public_routes = public_routes.recover(handle_reject).with(warp::log("http"));
+    let admin_routes = admin_routes.recover(handle_reject).with(warp::log("http"));
+    info!("serving the admin API on {}", admin_bind_address);
+    tokio::select! {
+        _ = serve_routes(public_routes, settings.bind_address, tls.clone()) => {}
+        _ = serve_routes(admin_routes, admin_bind_address, tls) => {}
+        _ = reload_tls => {}
+    }
+}
+
+/// Serves the routes at the given address, over TLS if a TLS configuration is
+/// given.
+async fn serve_routes<F, R>(routes: F, bind_address: SocketAddr, tls: Option<Arc<TlsConfig>>)
+where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let tls = match tls {
+        Some(tls) => tls,
+        None => return warp::serve(routes).run(bind_address).await,
+    };
+    let listener = match TcpListener::bind(bind_address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("failed to bind to {}: {}", bind_address, e);
+            return;
+        }
+    };
+    info!("serving over TLS on {}", bind_address);
+    warp::serve(routes)
+        .run_incoming(tls::incoming(listener, tls))
+        .await
+}
+
+/// Creates the route for PET messages.
+///
+/// The body of a message is only received once the message may be processed,
+/// such that the limit of concurrently processed messages also limits the
+/// memory of the buffered message bodies.
+fn message(
+    max_body_size: u64,
+    handler: PetMessageHandler,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    warp::path!("message")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(max_body_size))
+        .and(with_message_handler(handler))
+        .and_then(acquire_in_flight)
+        .untuple_one()
+        .and(warp::body::bytes())
+        .and_then(handle_message)
+}
+
+/// Waits until the message handler may process another message.
+async fn acquire_in_flight(
+    handler: PetMessageHandler,
+) -> Result<(PetMessageHandler, OwnedSemaphorePermit), Infallible> {
+    let permit = handler.acquire().await;
+    Ok((handler, permit))
+}
+
+/// Handles and responds to a PET message.
+///
+/// If the message is rejected, the response carries the status code of the
+/// `ServiceError` and a JSON encoded [`MessageError`] body. If the coordinator
+/// is overloaded, the response also carries a `Retry-After` header.
+async fn handle_message(
+    mut handler: PetMessageHandler,
+    _permit: OwnedSemaphorePermit,
+    body: Bytes,
+) -> Result<warp::reply::Response, Infallible> {
+    Ok(match handler.handle_message(body.to_vec()).await {
+        Ok(()) => warp::reply().into_response(),
+        Err(e) => {
+            warn!("failed to handle message: {:?}", e);
+            let body = warp::reply::json(&MessageError::from(&e));
+            let reply = warp::reply::with_status(body, e.status_code());
+            match e.retry_after() {
+                Some(retry_after) => {
+                    warp::reply::with_header(reply, "retry-after", retry_after.to_string())
+                        .into_response()
+                }
+                None => reply.into_response(),
+            }
+        }
+    })
+}
+
+/// Handles and responds to a request for the sum dictionary.
+async fn handle_sums<F: Fetcher>(
+    format: Format,
+    mut fetcher: F,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(match fetcher.sum_dict().await {
+        Err(e) => {
+            warn!("failed to handle sum dict request: {:?}", e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Vec::new())
+                .unwrap()
+        }
+        Ok(None) => Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Vec::new())
+            .unwrap(),
+        Ok(Some(dict)) => format.reply(dict.as_ref(), || json_dict(dict.as_ref())),
+    })
+}
+
+/// Handles and responds to a request for the seed dictionary.
+async fn handle_seeds<F: Fetcher>(
+    pk: ParticipantPublicKey,
+    format: Format,
+    mut fetcher: F,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(match fetcher.seed_dict().await {
+        Err(e) => {
+            warn!("failed to handle seed dict request: {:?}", e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Vec::new())
+                .unwrap()
+        }
+        Ok(Some(dict)) if dict.get(&pk).is_some() => {
+            let seeds = dict.as_ref().get(&pk).unwrap();
+            format.reply(seeds, || json_dict(seeds))
+        }
+        _ => Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Vec::new())
+            .unwrap(),
+    })
+}
+
+/// Handles and responds to a request for mask / model length.
+async fn handle_length<F: Fetcher>(
+    format: Format,
+    mut fetcher: F,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(match fetcher.mask_length().await {
+        // the length is sent as plain text, which happens to be valid JSON as well
+        Ok(Some(mask_length)) if format == Format::Json => Response::builder()
+            .header("Content-Type", "application/json")
+            .status(StatusCode::OK)
+            .body(mask_length.to_string())
+            .unwrap(),
+        Ok(Some(mask_length)) => Response::builder()
+            .status(StatusCode::OK)
+            .body(mask_length.to_string())
+            .unwrap(),
+        Ok(None) => Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(String::new())
+            .unwrap(),
+        Err(e) => {
+            warn!("failed to handle mask_length request: {:?}", e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap()
+        }
+    })
+}
+
+/// The query parameters of a request for the global model.
+#[derive(Debug, Deserialize)]
+struct ModelQuery {
+    /// The round of the requested global model, or `None` for the latest global model.
+    round: Option<u64>,
+}
+
+/// Handles and responds to a request for the global model.
+///
+/// The global model of an earlier round is taken from the model history. The
+/// response is `404 Not Found` if the history is disabled or doesn't contain
+/// the requested round.
+async fn handle_model<F: Fetcher>(
+    query: ModelQuery,
+    format: Format,
+    mut fetcher: F,
+    model_history: Option<ModelHistory>,
+) -> Result<impl warp::Reply, Infallible> {
+    if let Some(round_id) = query.round {
+        let history = match model_history {
+            Some(history) => history,
+            None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        };
+        return Ok(match history.get_model(round_id).await {
+            Ok(Some(model)) => format.reply(&model, || json_model(&model)).into_response(),
+            Ok(None) => StatusCode::NOT_FOUND.into_response(),
+            Err(e) => {
+                warn!("failed to handle model history request: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        });
+    }
+
+    Ok(match fetcher.model().await {
+        Ok(Some(model)) => format
+            .reply(model.as_ref(), || json_model(model.as_ref()))
+            .into_response(),
+        Ok(None) => Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(Vec::new())
+            .unwrap()
+            .into_response(),
+        Err(e) => {
+            warn!("failed to handle model request: {:?}", e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Vec::new())
+                .unwrap()
+                .into_response()
+        }
+    })
+}
+
+/// Handles and responds to a request for the metadata of the global models in
+/// the model history.
+///
+/// The response is `404 Not Found` if the history is disabled.
+async fn handle_models(
+    format: Format,
+    model_history: Option<ModelHistory>,
+) -> Result<impl warp::Reply, Infallible> {
+    let history = match model_history {
+        Some(history) => history,
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    Ok(match history.list_models().await {
+        Ok(models) => format
+            .reply(&models, || {
+                models
+                    .iter()
+                    .map(JsonModelMetadata::from)
+                    .collect::<Vec<_>>()
+            })
+            .into_response(),
+        Err(e) => {
+            warn!("failed to handle model history request: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    })
+}
+
+/// Handles and responds to a request for the round parameters.
+async fn handle_params<F: Fetcher>(
+    format: Format,
+    mut fetcher: F,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(match fetcher.round_params().await {
+        Ok(params) => format.reply(&params, || JsonRoundParameters::from(&params)),
+        Err(e) => {
+            warn!("failed to handle round parameters request: {:?}", e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Vec::new())
+                .unwrap()
+        }
+    })
+}
+
+/// The format of a response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// The bincode encoding expected by the SDK.
+    Bincode,
+    /// A JSON encoding where byte objects are represented as base64 strings.
+    Json,
+}
+
+impl Format {
+    /// Negotiates the format from the value of an `Accept` header.
+    ///
+    /// The supported media type with the highest quality value is picked. Bincode is
+    /// the default if the header is missing or doesn't contain any supported media type.
+    fn from_accept(accept: Option<&str>) -> Self {
+        let mut best: Option<(Self, f32)> = None;
+        for media_range in accept.unwrap_or_default().split(',') {
+            let mut params = media_range.split(';').map(str::trim);
+            let format = match params.next().map(str::to_ascii_lowercase).as_deref() {
+                Some("application/json") => Self::Json,
+                Some("application/octet-stream") | Some("application/*") | Some("*/*") => {
+                    Self::Bincode
+                }
+                _ => continue,
+            };
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let is_better = match best {
+                Some((_, best_quality)) => quality > best_quality,
+                None => quality > 0.0,
+            };
+            if is_better {
+                best = Some((format, quality));
+            }
+        }
+        best.map_or(Self::Bincode, |(format, _)| format)
+    }
+
+    /// Builds a successful response with the given data.
+    ///
+    /// The data is serialized with bincode, or the JSON representation returned
+    /// by `json` is serialized instead.
+    fn reply<B, J, F>(self, data: &B, json: F) -> Response<Vec<u8>>
+    where
+        B: Serialize + ?Sized,
+        J: Serialize,
+        F: FnOnce() -> J,
+    {
+        let (content_type, body) = match self {
+            Self::Bincode => (
+                "application/octet-stream",
+                bincode::serialize(data).unwrap(),
+            ),
+            Self::Json => ("application/json", serde_json::to_vec(&json()).unwrap()),
+        };
+        Response::builder()
+            .header("Content-Type", content_type)
+            .status(StatusCode::OK)
+            .body(body)
+            .unwrap()
+    }
+}
+
+/// The JSON representation of the round parameters.
+#[derive(Serialize)]
+struct JsonRoundParameters {
+    pk: String,
+    sum: f64,
+    update: f64,
+    seed: String,
+    start_time: u64,
+    mask_config: MaskConfigPair,
+    noise: Option<NoiseParameters>,
+}
+
+impl From<&RoundParameters> for JsonRoundParameters {
+    fn from(params: &RoundParameters) -> Self {
+        Self {
+            pk: base64::encode(params.pk.as_slice()),
+            sum: params.sum,
+            update: params.update,
+            seed: base64::encode(params.seed.as_slice()),
+            start_time: params.start_time,
+            mask_config: params.mask_config,
+            noise: params.noise,
+        }
+    }
+}
+
+/// The JSON representation of the metadata of a global model.
+#[derive(Serialize)]
+struct JsonModelMetadata {
+    round_id: u64,
+    round_seed_hash: String,
+    nb_sum: usize,
+    nb_update: usize,
+    nb_sum2: usize,
+}
+
+impl From<&ModelMetadata> for JsonModelMetadata {
+    fn from(metadata: &ModelMetadata) -> Self {
+        Self {
+            round_id: metadata.round_id,
+            round_seed_hash: base64::encode(metadata.round_seed_hash.as_slice()),
+            nb_sum: metadata.nb_sum,
+            nb_update: metadata.nb_update,
+            nb_sum2: metadata.nb_sum2,
+        }
+    }
+}
+
+/// Streams notifications about the coordinator events as Server-Sent Events.
+///
+/// Each notification is sent as an event named after its kind with the JSON
+/// encoded notification as data. Keep-alive comments are sent in between to
+/// hold the connection open.
+async fn handle_events(event_subscriber: EventSubscriber) -> Result<impl warp::Reply, Infallible> {
+    let notifications = events::notifications(&event_subscriber).map(|notification| {
+        Ok::<_, Infallible>((
+            warp::sse::event(notification.kind),
+            warp::sse::json(notification),
+        ))
+    });
+    Ok(warp::sse::reply(
+        warp::sse::keep_alive().stream(notifications),
+    ))
+}
+
+/// Handles and responds to a request for the coordinator status.
+async fn handle_status(event_subscriber: EventSubscriber) -> Result<impl warp::Reply, Infallible> {
+    let status = Status::new(&event_subscriber, unix_time());
+    Ok(warp::reply::json(&status))
+}
+
+/// Handles and responds to a readiness probe.
+async fn handle_ready(health: Health) -> Result<impl warp::Reply, Infallible> {
+    let readiness = health.readiness().await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&readiness),
+        status,
+    ))
+}
+
+/// Handles and responds to an admin command.
+///
+/// The response is `404 Not Found` if the admin API is disabled or the command
+/// is unknown, `401 Unauthorized` if the request doesn't carry the admin token
+/// and `202 Accepted` once the command has been forwarded to the state machine.
+async fn handle_admin(
+    command: String,
+    authorization: Option<String>,
+    admin: Admin,
+) -> Result<impl warp::Reply, Infallible> {
+    if let Some(response) = reject_unauthorized(&admin, authorization) {
+        return Ok(response);
+    }
+
+    let command = match command.parse::<AdminCommand>() {
+        Ok(command) => command,
+        Err(e) => {
+            warn!("{}", e);
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        }
+    };
+    Ok(match admin.sender.send(command) {
+        Ok(()) => {
+            info!("forwarded admin command: {:?}", command);
+            StatusCode::ACCEPTED.into_response()
+        }
+        Err(e) => {
+            warn!("failed to handle admin command: {}", e);
+            StatusCode::SERVICE_UNAVAILABLE.into_response()
+        }
+    })
+}
+
+/// Handles and responds to a request for updating the PET settings.
+///
+/// The response is `404 Not Found` if the admin API is disabled, `401
+/// Unauthorized` if the request doesn't carry the admin token, `400 Bad
+/// Request` if the body doesn't contain valid PET settings and `202 Accepted`
+/// once the settings have been forwarded to the state machine.
+async fn handle_pet_settings(
+    authorization: Option<String>,
+    body: Bytes,
+    admin: Admin,
+) -> Result<impl warp::Reply, Infallible> {
+    if let Some(response) = reject_unauthorized(&admin, authorization) {
+        return Ok(response);
+    }
+
+    let settings = match serde_json::from_slice(body.bytes()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            warn!("failed to parse PET settings: {}", e);
+            return Ok(StatusCode::BAD_REQUEST.into_response());
+        }
+    };
+    Ok(match admin.sender.update_pet_settings(settings) {
+        Ok(()) => {
+            info!("forwarded PET settings: {:?}", settings);
+            StatusCode::ACCEPTED.into_response()
+        }
+        Err(e @ UpdatePetSettingsError::Invalid(_)) => {
+            warn!("{}", e);
+            StatusCode::BAD_REQUEST.into_response()
+        }
+        Err(e @ UpdatePetSettingsError::Closed(_)) => {
+            warn!("failed to update PET settings: {}", e);
+            StatusCode::SERVICE_UNAVAILABLE.into_response()
+        }
+    })
+}
+
+/// Handles and responds to a request for managing the allowlist.
+///
+/// The response is `404 Not Found` if the admin API or the allowlist is
+/// disabled and `401 Unauthorized` if the request doesn't carry the admin
+/// token. Otherwise:
+/// - GET: `200 OK` with a JSON array of the base64 encoded public keys.
+/// - POST: `201 Created` if the base64 encoded public key in the body was
+///   added or `200 OK` if it was already on the allowlist.
+/// - DELETE: `204 No Content` if the base64 encoded public key in the body was
+///   removed or `404 Not Found` if it was not on the allowlist.
+///
+/// The response is `400 Bad Request` if the body of a POST or DELETE request
+/// is not a base64 encoded public key.
+async fn handle_allowlist(
+    method: Method,
+    authorization: Option<String>,
+    body: Bytes,
+    admin: Admin,
+) -> Result<impl warp::Reply, Infallible> {
+    if let Some(response) = reject_unauthorized(&admin, authorization) {
+        return Ok(response);
+    }
+    let allowlist = match admin.allowlist {
+        Some(allowlist) => allowlist,
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+
+    if method == Method::GET {
+        return Ok(match allowlist.list().await {
+            Ok(pks) => {
+                let mut pks = pks
+                    .iter()
+                    .map(|pk| base64::encode(pk.as_slice()))
+                    .collect::<Vec<_>>();
+                pks.sort_unstable();
+                warp::reply::json(&pks).into_response()
+            }
+            Err(e) => {
+                warn!("failed to handle allowlist request: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        });
+    }
+
+    let pk = std::str::from_utf8(body.bytes())
+        .ok()
+        .and_then(|body| base64::decode(body.trim()).ok())
+        .and_then(|bytes| ParticipantPublicKey::from_slice(&bytes));
+    let pk = match pk {
+        Some(pk) => pk,
+        None => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+    let result = match method {
+        Method::POST => allowlist.add(&pk).await.map(|added| {
+            if added {
+                info!("added participant to the allowlist");
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            }
+        }),
+        Method::DELETE => allowlist.remove(&pk).await.map(|removed| {
+            if removed {
+                info!("removed participant from the allowlist");
+                StatusCode::NO_CONTENT
+            } else {
+                StatusCode::NOT_FOUND
+            }
+        }),
+        _ => return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+    };
+    Ok(match result {
+        Ok(status) => status.into_response(),
+        Err(e) => {
+            warn!("failed to handle allowlist request: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    })
+}
+
+/// Checks that an admin request carries the admin token.
+///
+/// Returns the response to reply with if the admin API is disabled or the
+/// request is unauthorized.
+fn reject_unauthorized(
+    admin: &Admin,
+    authorization: Option<String>,
+) -> Option<warp::reply::Response> {
+    let token = match admin.token {
+        Some(ref token) => token,
+        None => return Some(StatusCode::NOT_FOUND.into_response()),
+    };
+    let expected = format!("Bearer {}", token);
+    let authorized = match authorization {
+        Some(authorization) => {
+            sodiumoxide::utils::memcmp(authorization.as_bytes(), expected.as_bytes())
+        }
+        None => false,
+    };
+    if authorized {
+        None
+    } else {
+        warn!("rejecting unauthorized admin request");
+        Some(
+            warp::reply::with_header(StatusCode::UNAUTHORIZED, "www-authenticate", "Bearer")
+                .into_response(),
+        )
+    }
+}
+
+/// The admin API.
+#[derive(Clone)]
+struct Admin {
+    /// The token which authenticates admin requests, or `None` if the admin
+    /// API is disabled.
+    token: Option<String>,
+    /// The sender for forwarding admin commands to the state machine.
+    sender: AdminSender,
+    /// The allowlist of the participants, or `None` if the allowlist is
+    /// disabled.
+    allowlist: Option<Allowlist>,
+}
+
+/// Gets the JSON representation of a dictionary, with base64 encoded keys and values.
+fn json_dict<K, V>(dict: &HashMap<K, V>) -> HashMap<String, String>
+where
+    K: ByteObject + Eq + Hash,
+    V: ByteObject,
+{
+    dict.iter()
+        .map(|(key, value)| {
+            (
+                base64::encode(key.as_slice()),
+                base64::encode(value.as_slice()),
+            )
+        })
+        .collect()
+}
+
+/// Gets the JSON representation of a model.
+///
+/// The weights are encoded as exact rational numbers, i.e. `"numerator/denominator"`.
+fn json_model(model: &Model) -> Vec<String> {
+    model.iter().map(ToString::to_string).collect()
+}
+
+/// Extracts the response format from the `Accept` header of a request.
+fn format() -> impl Filter<Extract = (Format,), Error = Infallible> + Clone {
+    warp::header::optional::<String>("accept")
+        .map(|accept: Option<String>| Format::from_accept(accept.as_deref()))
+        // the `Accept` header is optional, so the filter can't be rejected
+        .or(warp::any().map(|| Format::Bincode))
+        .unify()
+}
+
+/// Converts a PET message handler into a `warp` filter.
+fn with_message_handler(
+    handler: PetMessageHandler,
+) -> impl Filter<Extract = (PetMessageHandler,), Error = Infallible> + Clone {
+    warp::any().map(move || handler.clone())
+}
+
+/// Converts the admin API into a `warp` filter.
+fn with_admin(admin: Admin) -> impl Filter<Extract = (Admin,), Error = Infallible> + Clone {
+    warp::any().map(move || admin.clone())
+}
+
+/// Converts the model history into a `warp` filter.
+fn with_model_history(
+    model_history: Option<ModelHistory>,
+) -> impl Filter<Extract = (Option<ModelHistory>,), Error = Infallible> + Clone {
+    warp::any().map(move || model_history.clone())
+}
+
+/// Converts an event subscriber into a `warp` filter.
+fn with_event_subscriber(
+    event_subscriber: EventSubscriber,
+) -> impl Filter<Extract = (EventSubscriber,), Error = Infallible> + Clone {
+    warp::any().map(move || event_subscriber.clone())
+}
+
+/// Converts the health checks into a `warp` filter.
+fn with_health(health: Health) -> impl Filter<Extract = (Health,), Error = Infallible> + Clone {
+    warp::any().map(move || health.clone())
+}
+
+/// Converts a data fetcher into a `warp` filter.
+fn with_fetcher<F: Fetcher + Sync + Send + 'static + Clone>(
+    fetcher: F,
+) -> impl Filter<Extract = (F,), Error = Infallible> + Clone {
+    warp::any().map(move || fetcher.clone())
+}
+
+/// Extracts a participant public key from a request body
+fn part_pk() -> impl Filter<Extract = (ParticipantPublicKey,), Error = warp::Rejection> + Clone {
+    warp::body::bytes().and_then(|body: Bytes| async move {
+        if let Some(pk) = ParticipantPublicKey::from_slice(body.bytes()) {
+            Ok(pk)
+        } else {
+            Err(warp::reject::custom(InvalidPublicKey))
+        }
+    })
+}
+
+#[derive(Debug)]
+struct InvalidPublicKey;
+
+impl warp::reject::Reject for InvalidPublicKey {}
+
+/// Handles `warp` rejections of bad requests.
+async fn handle_reject(err: warp::Rejection) -> Result<impl warp::Reply, Infallible> {
+    let code = if err.is_not_found() {
+        StatusCode::NOT_FOUND
+    } else if let Some(InvalidPublicKey) = err.find() {
+        StatusCode::BAD_REQUEST
+    } else if err.find::<warp::reject::InvalidQuery>().is_some() {
+        StatusCode::BAD_REQUEST
+    } else if err.find::<warp::reject::LengthRequired>().is_some() {
+        StatusCode::LENGTH_REQUIRED
+    } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+        StatusCode::PAYLOAD_TOO_LARGE
+    } else {
+        error!("unhandled rejection: {:?}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    // reply with empty body; the status code is the interesting part
+    Ok(warp::reply::with_status(Vec::new(), code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        services::{
+            fetchers::{
+                FetchError,
+                MaskLengthResponse,
+                ModelResponse,
+                RoundParamsResponse,
+                SeedDictResponse,
+                SumDictResponse,
+            },
+            tests::utils,
+        },
+        settings::{MultipartSettings, ProcessingSettings, RequestSettings},
+        state_machine::{
+            admin::AdminReceiver,
+            events::EventPublisher,
+            phases::PhaseName,
+            requests::RequestReceiver,
+        },
+        storage::tests::utils::TempPath,
+    };
+    use num::{bigint::BigInt, rational::Ratio};
+    use std::{sync::Arc, time::Duration};
+    use tokio::time::timeout;
+    use xaynet_core::{
+        common::RoundSeed,
+        crypto::{EncryptKeyPair, Sha256},
+        mask::{BoundType, DataType, GroupType, MaskConfig, ModelType},
+        CoordinatorPublicKey,
+        SumDict,
+    };
+
+    #[derive(Clone)]
+    struct StaticFetcher {
+        params: RoundParameters,
+        sum_dict: Arc<SumDict>,
+        model: Arc<Model>,
+    }
+
+    #[async_trait]
+    impl Fetcher for StaticFetcher {
+        async fn round_params(&mut self) -> Result<RoundParamsResponse, FetchError> {
+            Ok(self.params.clone())
+        }
+
+        async fn mask_length(&mut self) -> Result<MaskLengthResponse, FetchError> {
+            Ok(Some(self.model.len()))
+        }
+
+        async fn model(&mut self) -> Result<ModelResponse, FetchError> {
+            Ok(Some(self.model.clone()))
+        }
+
+        async fn seed_dict(&mut self) -> Result<SeedDictResponse, FetchError> {
+            Ok(None)
+        }
+
+        async fn sum_dict(&mut self) -> Result<SumDictResponse, FetchError> {
+            Ok(Some(self.sum_dict.clone()))
+        }
+    }
+
+    fn fetcher() -> StaticFetcher {
+        let params = RoundParameters {
+            pk: CoordinatorPublicKey::fill_with(0x01),
+            sum: 0.1,
+            update: 0.5,
+            seed: RoundSeed::fill_with(0x02),
+            start_time: 1_600_000_000,
+            mask_config: MaskConfigPair {
+                model: MaskConfig {
+                    group_type: GroupType::Prime,
+                    data_type: DataType::F32,
+                    bound_type: BoundType::B2,
+                    model_type: ModelType::M3,
+                },
+                scalar: MaskConfig {
+                    group_type: GroupType::Power2,
+                    data_type: DataType::F32,
+                    bound_type: BoundType::B0,
+                    model_type: ModelType::M3,
+                },
+            },
+            noise: Some(NoiseParameters {
+                clip_norm: 1.,
+                std_dev: 0.5,
+            }),
+        };
+        let mut sum_dict = SumDict::new();
+        let EncryptKeyPair { public, .. } = EncryptKeyPair::generate();
+        sum_dict.insert(ParticipantPublicKey::fill_with(0x03), public);
+        let model = vec![
+            Ratio::new(BigInt::from(1), BigInt::from(2)),
+            Ratio::from(BigInt::from(3)),
+        ]
+        .into_iter()
+        .collect();
+        StaticFetcher {
+            params,
+            sum_dict: Arc::new(sum_dict),
+            model: Arc::new(model),
+        }
+    }
+
+    #[test]
+    fn format_from_accept() {
+        assert_eq!(Format::from_accept(None), Format::Bincode);
+        assert_eq!(Format::from_accept(Some("*/*")), Format::Bincode);
+        assert_eq!(Format::from_accept(Some("text/html")), Format::Bincode);
+        assert_eq!(Format::from_accept(Some("application/json")), Format::Json);
+        assert_eq!(
+            Format::from_accept(Some("text/html, application/json;charset=utf-8")),
+            Format::Json
+        );
+        assert_eq!(
+            Format::from_accept(Some("application/octet-stream;q=0.5, application/json")),
+            Format::Json
+        );
+        assert_eq!(
+            Format::from_accept(Some("application/json;q=0.2, */*;q=0.8")),
+            Format::Bincode
+        );
+        assert_eq!(
+            Format::from_accept(Some("application/json;q=0")),
+            Format::Bincode
+        );
+    }
+
+    #[tokio::test]
+    async fn params_as_bincode() {
+        let fetcher = fetcher();
+        let resp = warp::test::request()
+            .path("/params")
+            .reply(
+                &warp::path!("params")
+                    .and(format())
+                    .and(with_fetcher(fetcher.clone()))
+                    .and_then(handle_params),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let params: RoundParameters = bincode::deserialize(resp.body()).unwrap();
+        assert_eq!(params, fetcher.params);
+    }
+
+    #[tokio::test]
+    async fn params_as_json() {
+        let fetcher = fetcher();
+        let resp = warp::test::request()
+            .path("/params")
+            .header("accept", "application/json")
+            .reply(
+                &warp::path!("params")
+                    .and(format())
+                    .and(with_fetcher(fetcher))
+                    .and_then(handle_params),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        let params: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            params,
+            serde_json::json!({
+                "pk": base64::encode([0x01; 32]),
+                "sum": 0.1,
+                "update": 0.5,
+                "seed": base64::encode([0x02; 32]),
+                "start_time": 1_600_000_000,
+                "mask_config": {
+                    "model": {
+                        "group_type": "Prime",
+                        "data_type": "F32",
+                        "bound_type": "B2",
+                        "model_type": "M3",
+                    },
+                    "scalar": {
+                        "group_type": "Power2",
+                        "data_type": "F32",
+                        "bound_type": "B0",
+                        "model_type": "M3",
+                    },
+                },
+                "noise": {
+                    "clip_norm": 1.0,
+                    "std_dev": 0.5,
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn sums_as_json() {
+        let fetcher = fetcher();
+        let resp = warp::test::request()
+            .path("/sums")
+            .header("accept", "application/json")
+            .reply(
+                &warp::path!("sums")
+                    .and(format())
+                    .and(with_fetcher(fetcher.clone()))
+                    .and_then(handle_sums),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let sums: HashMap<String, String> = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(sums, json_dict(fetcher.sum_dict.as_ref()));
+        let ephm_pk = &sums[&base64::encode([0x03; 32])];
+        assert_eq!(base64::decode(ephm_pk).unwrap().len(), 32);
+    }
+
+    #[tokio::test]
+    async fn model_and_length_as_json() {
+        let fetcher = fetcher();
+        let resp = warp::test::request()
+            .path("/model")
+            .header("accept", "application/json")
+            .reply(
+                &warp::path!("model")
+                    .and(warp::query::<ModelQuery>())
+                    .and(format())
+                    .and(with_fetcher(fetcher.clone()))
+                    .and(with_model_history(None))
+                    .and_then(handle_model),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let model: Vec<String> = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(model, vec!["1/2", "3"]);
+
+        let resp = warp::test::request()
+            .path("/length")
+            .header("accept", "application/json")
+            .reply(
+                &warp::path!("length")
+                    .and(format())
+                    .and(with_fetcher(fetcher))
+                    .and_then(handle_length),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        let length: usize = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(length, 2);
+    }
+
+    async fn model_history_request(
+        path: &str,
+        fetcher: StaticFetcher,
+        model_history: Option<ModelHistory>,
+    ) -> Response<Bytes> {
+        let model = warp::path!("model")
+            .and(warp::query::<ModelQuery>())
+            .and(format())
+            .and(with_fetcher(fetcher))
+            .and(with_model_history(model_history.clone()))
+            .and_then(handle_model);
+        let models = warp::path!("models")
+            .and(format())
+            .and(with_model_history(model_history))
+            .and_then(handle_models);
+        warp::test::request()
+            .path(path)
+            .header("accept", "application/json")
+            .reply(&model.or(models).recover(handle_reject))
+            .await
+    }
+
+    #[tokio::test]
+    async fn model_history_by_round() {
+        let fetcher = fetcher();
+        let directory = TempPath::new();
+        let history = ModelHistory::Directory(directory.to_path_buf());
+        let metadata = ModelMetadata {
+            round_id: 7,
+            round_seed_hash: Sha256::hash(fetcher.params.seed.as_slice()),
+            nb_sum: 1,
+            nb_update: 3,
+            nb_sum2: 1,
+        };
+        let model = vec![Ratio::from(BigInt::from(-1))].into_iter().collect();
+        history.add_model(&metadata, &model).await.unwrap();
+
+        let resp =
+            model_history_request("/model?round=7", fetcher.clone(), Some(history.clone())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let model: Vec<String> = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(model, vec!["-1"]);
+
+        // the latest global model is served without a round
+        let resp = model_history_request("/model", fetcher.clone(), Some(history.clone())).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let model: Vec<String> = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(model, vec!["1/2", "3"]);
+
+        let resp =
+            model_history_request("/model?round=8", fetcher.clone(), Some(history.clone())).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp =
+            model_history_request("/model?round=latest", fetcher.clone(), Some(history)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = model_history_request("/model?round=7", fetcher, None).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    fn message_handler(
+        max_in_flight: usize,
+    ) -> (PetMessageHandler, EventPublisher, RequestReceiver) {
+        let (publisher, subscriber) = utils::new_event_channels();
+        let (receiver, sender) = RequestReceiver::new(RequestSettings::default());
+        let processing_settings = ProcessingSettings {
+            threads: 1,
+            max_in_flight,
+        };
+        let handler = PetMessageHandler::new(
+            &subscriber,
+            sender,
+            MultipartSettings::default(),
+            processing_settings,
+            None,
+        )
+        .unwrap();
+        (handler, publisher, receiver)
+    }
+
+    #[tokio::test]
+    async fn message_body_size_limit() {
+        let (handler, _publisher, _receiver) = message_handler(1);
+        let filter = message(4, handler).recover(handle_reject);
+
+        // the message is received, but it can't be decrypted
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/message")
+            .body([0; 4])
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/message")
+            .body([0; 5])
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn message_in_flight_limit() {
+        let (handler, _publisher, _receiver) = message_handler(1);
+        let filter = message(4, handler.clone()).recover(handle_reject);
+        let request = || {
+            warp::test::request()
+                .method("POST")
+                .path("/message")
+                .body([0; 4])
+                .reply(&filter)
+        };
+
+        // the message waits until the earlier message has been processed
+        let permit = handler.acquire().await;
+        assert!(timeout(Duration::from_millis(100), request())
+            .await
+            .is_err());
+        drop(permit);
+        assert_eq!(request().await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn readiness_probe() {
+        let (mut publisher, subscriber) = utils::new_event_channels();
+        let (_receiver, sender) = RequestReceiver::new(RequestSettings::default());
+        let filter = warp::path!("health" / "ready")
+            .and(warp::get())
+            .and(with_health(Health::new(subscriber, sender, None)))
+            .and_then(handle_ready);
+
+        let resp = warp::test::request()
+            .path("/health/ready")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["ready"], true);
+
+        publisher.broadcast_phase(PhaseName::Shutdown);
+        let resp = warp::test::request()
+            .path("/health/ready")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn model_history_listing() {
+        let fetcher = fetcher();
+        let resp = model_history_request("/models", fetcher.clone(), None).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let directory = TempPath::new();
+        let history = ModelHistory::Directory(directory.to_path_buf());
+        for round_id in &[2, 1] {
+            let metadata = ModelMetadata {
+                round_id: *round_id,
+                round_seed_hash: Sha256::fill_with(0x04),
+                nb_sum: 1,
+                nb_update: 3,
+                nb_sum2: 1,
+            };
+            history
+                .add_model(&metadata, fetcher.model.as_ref())
+                .await
+                .unwrap();
+        }
+
+        let resp = model_history_request("/models", fetcher, Some(history)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let models: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(
+            models,
+            serde_json::json!([
+                {
+                    "round_id": 1,
+                    "round_seed_hash": base64::encode([0x04; 32]),
+                    "nb_sum": 1,
+                    "nb_update": 3,
+                    "nb_sum2": 1,
+                },
+                {
+                    "round_id": 2,
+                    "round_seed_hash": base64::encode([0x04; 32]),
+                    "nb_sum": 1,
+                    "nb_update": 3,
+                    "nb_sum2": 1,
+                },
+            ])
+        );
+    }
+
+    const ADMIN_TOKEN: &str = "0123456789abcdef";
+
+    fn admin(token: Option<&str>) -> (Admin, AdminReceiver) {
+        let (receiver, sender) = AdminReceiver::new();
+        let admin = Admin {
+            token: token.map(ToString::to_string),
+            sender,
+            allowlist: None,
+        };
+        (admin, receiver)
+    }
+
+    async fn admin_request(admin: Admin, command: &str, authorization: Option<&str>) -> StatusCode {
+        let mut req = warp::test::request()
+            .method("POST")
+            .path(&format!("/admin/{}", command));
+        if let Some(authorization) = authorization {
+            req = req.header("authorization", authorization);
+        }
+        req.reply(
+            &warp::path!("admin" / String)
+                .and(warp::post())
+                .and(warp::header::optional::<String>("authorization"))
+                .and(with_admin(admin))
+                .and_then(handle_admin),
+        )
+        .await
+        .status()
+    }
+
+    #[tokio::test]
+    async fn admin_command_is_forwarded() {
+        let (admin, mut receiver) = admin(Some(ADMIN_TOKEN));
+        let authorization = format!("Bearer {}", ADMIN_TOKEN);
+        let status = admin_request(admin.clone(), "pause", Some(&authorization)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(receiver.recv().await, AdminCommand::Pause);
+
+        let status = admin_request(admin, "restart", Some(&authorization)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(receiver.recv().await, AdminCommand::Restart);
+    }
+
+    #[tokio::test]
+    async fn admin_command_requires_token() {
+        let (admin, _receiver) = admin(Some(ADMIN_TOKEN));
+        let status = admin_request(admin.clone(), "abort", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let status = admin_request(admin.clone(), "abort", Some(ADMIN_TOKEN)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let status = admin_request(admin, "abort", Some("Bearer fedcba9876543210")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn admin_api_is_disabled_without_token() {
+        let (admin, _receiver) = admin(None);
+        let status = admin_request(admin, "shutdown", Some("Bearer ")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn unknown_admin_command() {
+        let (admin, _receiver) = admin(Some(ADMIN_TOKEN));
+        let authorization = format!("Bearer {}", ADMIN_TOKEN);
+        let status = admin_request(admin, "stop", Some(&authorization)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn admin_command_after_shutdown() {
+        let (admin, receiver) = admin(Some(ADMIN_TOKEN));
+        drop(receiver);
+        let authorization = format!("Bearer {}", ADMIN_TOKEN);
+        let status = admin_request(admin, "resume", Some(&authorization)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    async fn pet_settings_request(admin: Admin, body: serde_json::Value) -> StatusCode {
+        warp::test::request()
+            .method("PUT")
+            .path("/admin/settings/pet")
+            .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+            .body(body.to_string())
+            .reply(
+                &warp::path!("admin" / "settings" / "pet")
+                    .and(warp::put())
+                    .and(warp::header::optional::<String>("authorization"))
+                    .and(warp::body::bytes())
+                    .and(with_admin(admin))
+                    .and_then(handle_pet_settings),
+            )
+            .await
+            .status()
+    }
+
+    #[tokio::test]
+    async fn pet_settings_are_forwarded() {
+        let (admin, mut receiver) = admin(Some(ADMIN_TOKEN));
+        let mut body = serde_json::json!({
+            "min_sum_count": 1,
+            "min_update_count": 5,
+            "min_sum_time": 5,
+            "min_update_time": 10,
+            "max_sum_time": 3600,
+            "max_update_time": 3600,
+            "min_idle_time": 0,
+            "round_period": 0,
+            "sum": 0.01,
+            "update": 0.1,
+            "adapt_fractions": false,
+            "fraction_headroom": 0.5,
+            "mask_quorum": 0.0,
+        });
+        let status = pet_settings_request(admin.clone(), body.clone()).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        match receiver.recv().await {
+            AdminCommand::UpdatePetSettings(settings) => {
+                assert_eq!(settings.min_update_count, 5);
+                assert_eq!(settings.max_rounds, None);
+            }
+            command => panic!("unexpected admin command: {:?}", command),
+        }
+
+        // settings which fail the validation are rejected
+        body["min_update_time"] = serde_json::json!(7200);
+        let status = pet_settings_request(admin.clone(), body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let status = pet_settings_request(admin, serde_json::json!({ "sum": 0.5 })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    async fn allowlist_request(
+        admin: Admin,
+        method: &str,
+        body: &[u8],
+        authorization: bool,
+    ) -> Response<Bytes> {
+        let mut req = warp::test::request()
+            .method(method)
+            .path("/admin/allowlist")
+            .body(body);
+        if authorization {
+            req = req.header("authorization", format!("Bearer {}", ADMIN_TOKEN));
+        }
+        req.reply(
+            &warp::path!("admin" / "allowlist")
+                .and(warp::method())
+                .and(warp::header::optional::<String>("authorization"))
+                .and(warp::body::bytes())
+                .and(with_admin(admin))
+                .and_then(handle_allowlist),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn admin_allowlist() {
+        let path = TempPath::new();
+        let (mut admin, _receiver) = admin(Some(ADMIN_TOKEN));
+        let resp = allowlist_request(admin.clone(), "GET", &[], true).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        admin.allowlist = Some(Allowlist::from_file(path.to_path_buf()).await.unwrap());
+        let pk = base64::encode([0x05; 32]);
+        let resp = allowlist_request(admin.clone(), "POST", pk.as_bytes(), false).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = allowlist_request(admin.clone(), "POST", pk.as_bytes(), true).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp =
+            allowlist_request(admin.clone(), "POST", format!("{}\n", pk).as_bytes(), true).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = allowlist_request(admin.clone(), "POST", &[0x05; 32], true).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let short_pk = base64::encode([0x05; 31]);
+        let resp = allowlist_request(admin.clone(), "POST", short_pk.as_bytes(), true).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = allowlist_request(admin.clone(), "GET", &[], true).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let pks: Vec<String> = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(pks, vec![pk.clone()]);
+
+        let resp = allowlist_request(admin.clone(), "DELETE", pk.as_bytes(), true).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = allowlist_request(admin.clone(), "DELETE", pk.as_bytes(), true).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = allowlist_request(admin, "PUT", pk.as_bytes(), true).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
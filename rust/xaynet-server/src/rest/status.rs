@@ -0,0 +1,149 @@
+//! The status of the coordinator for the REST API.
+//!
+//! The status is served at `/status` and describes the progress of the current round, so that
+//! operators don't have to consult the logs or the metrics.
+
+use serde::Serialize;
+
+use crate::state_machine::{
+    events::{EventSubscriber, MessageProgress, RoundOutcome},
+    phases::PhaseName,
+};
+
+/// The status of the coordinator.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Status {
+    /// The current round.
+    pub round_id: u64,
+    /// The current phase.
+    pub phase: PhaseName,
+    /// The time at which the current phase started, as a Unix timestamp in seconds.
+    pub phase_start_time: u64,
+    /// The progress of the accepted messages, if the current phase accepts messages.
+    pub messages: Option<MessagesStatus>,
+    /// The outcome of the last round, if a round ended already.
+    pub last_round: Option<LastRound>,
+}
+
+/// The progress of the messages accepted during the current phase.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessagesStatus {
+    /// The number of messages accepted during the phase.
+    pub count: usize,
+    /// The minimum number of messages the phase requires.
+    pub min_count: usize,
+    /// The time in seconds until the phase times out.
+    pub time_remaining: u64,
+}
+
+/// The outcome of the last round.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum LastRound {
+    /// The round completed with a new global model.
+    Completed { round_id: u64 },
+    /// The round failed for the given reason.
+    Failed { round_id: u64, reason: String },
+}
+
+impl Status {
+    /// Gets the status of the coordinator at the given Unix time.
+    pub fn new(subscriber: &EventSubscriber, now: u64) -> Self {
+        let phase = subscriber.phase_listener().get_latest();
+        let progress = subscriber.progress_listener().get_latest().event;
+        let outcome = subscriber.outcome_listener().get_latest();
+
+        let messages = progress.messages.map(
+            |MessageProgress {
+                 count,
+                 min_count,
+                 max_time,
+             }| MessagesStatus {
+                count,
+                min_count,
+                time_remaining: (progress.start_time + max_time).saturating_sub(now),
+            },
+        );
+        let round_id = outcome.round_id;
+        let last_round = outcome.event.map(|event| match event {
+            RoundOutcome::Completed => LastRound::Completed { round_id },
+            RoundOutcome::Failed(reason) => LastRound::Failed { round_id, reason },
+        });
+
+        Self {
+            round_id: phase.round_id,
+            phase: phase.event,
+            phase_start_time: progress.start_time,
+            messages,
+            last_round,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::services::tests::utils;
+
+    #[test]
+    fn status_of_the_current_round() {
+        let (mut publisher, subscriber) = utils::new_event_channels();
+        publisher.broadcast_round_outcome(RoundOutcome::Failed("no quorum".to_string()));
+
+        publisher.set_round_id(1);
+        publisher.broadcast_phase(PhaseName::Update);
+        let mut messages = MessageProgress {
+            count: 0,
+            min_count: 3,
+            max_time: 60,
+        };
+        publisher.broadcast_phase_start(1000, Some(messages));
+        messages.count = 2;
+        publisher.broadcast_message_progress(messages);
+
+        let status = Status::new(&subscriber, 1045);
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            json!({
+                "round_id": 1,
+                "phase": "Update",
+                "phase_start_time": 1000,
+                "messages": {
+                    "count": 2,
+                    "min_count": 3,
+                    "time_remaining": 15,
+                },
+                "last_round": {
+                    "outcome": "failed",
+                    "round_id": 0,
+                    "reason": "no quorum",
+                },
+            })
+        );
+
+        // the remaining time doesn't underflow if the phase is overdue
+        let status = Status::new(&subscriber, 1100);
+        assert_eq!(status.messages.unwrap().time_remaining, 0);
+
+        publisher.broadcast_round_outcome(RoundOutcome::Completed);
+        publisher.set_round_id(2);
+        publisher.broadcast_phase(PhaseName::Idle);
+        publisher.broadcast_phase_start(1200, None);
+        let status = Status::new(&subscriber, 1200);
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            json!({
+                "round_id": 2,
+                "phase": "Idle",
+                "phase_start_time": 1200,
+                "messages": null,
+                "last_round": {
+                    "outcome": "completed",
+                    "round_id": 1,
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! TLS termination for the REST API.
+//!
+//! The TLS configuration is built from the [`TlsSettings`] and rebuilt whenever one of the
+//! configured files changes, so that renewed certificates are used for new connections without
+//! restarting the coordinator. Established connections keep the configuration they were accepted
+//! with.
+//!
+//! [`TlsSettings`]: crate::settings::TlsSettings
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use futures::{Stream, StreamExt};
+use thiserror::Error;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time::{self, Duration},
+};
+use tokio_rustls::{
+    rustls::{
+        internal::pemfile,
+        AllowAnyAuthenticatedClient,
+        Certificate,
+        NoClientAuth,
+        PrivateKey,
+        RootCertStore,
+        ServerConfig,
+        TLSError,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+
+use crate::settings::TlsSettings;
+
+/// The interval in which the configured files are checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The maximum number of concurrent TLS handshakes.
+const MAX_HANDSHAKES: usize = 128;
+
+/// The time after which an incomplete TLS handshake is aborted.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors which can occur while loading the TLS configuration.
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("failed to read {0}: {1}")]
+    Io(PathBuf, #[source] io::Error),
+
+    #[error("no certificate found in {0}")]
+    NoCertificate(PathBuf),
+
+    #[error("no private key found in {0}")]
+    NoPrivateKey(PathBuf),
+
+    #[error("invalid client CA certificate in {0}")]
+    InvalidClientCa(PathBuf),
+
+    #[error("invalid certificate or private key: {0}")]
+    Rustls(#[from] TLSError),
+}
+
+/// A TLS configuration which is rebuilt when the configured files change.
+pub struct TlsConfig {
+    settings: TlsSettings,
+    current: RwLock<LoadedConfig>,
+}
+
+/// A TLS configuration together with the modification times of the files it was built from.
+struct LoadedConfig {
+    config: Arc<ServerConfig>,
+    modified: Vec<Option<SystemTime>>,
+}
+
+impl TlsConfig {
+    /// Loads the TLS configuration from the files of the settings.
+    ///
+    /// # Errors
+    /// Fails if a file cannot be read or doesn't contain a valid certificate or private key.
+    pub fn load(settings: TlsSettings) -> Result<Self, TlsError> {
+        let current = RwLock::new(LoadedConfig::load(&settings)?);
+        Ok(Self { settings, current })
+    }
+
+    /// Gets an acceptor for new connections with the current TLS configuration.
+    pub fn acceptor(&self) -> TlsAcceptor {
+        // Safe unwrap: the lock is never held across a panic
+        TlsAcceptor::from(self.current.read().unwrap().config.clone())
+    }
+
+    /// Reloads the TLS configuration if one of the configured files has been modified since it
+    /// was loaded. Returns whether the configuration has been reloaded.
+    ///
+    /// # Errors
+    /// Fails if the modified files are invalid, in which case the current configuration is kept.
+    pub fn reload_if_changed(&self) -> Result<bool, TlsError> {
+        // Safe unwrap: the lock is never held across a panic
+        if self.current.read().unwrap().modified == modification_times(&self.settings) {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// Reloads the TLS configuration.
+    ///
+    /// # Errors
+    /// Fails if the configured files are invalid, in which case the current configuration is
+    /// kept.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let loaded = LoadedConfig::load(&self.settings)?;
+        // Safe unwrap: the lock is never held across a panic
+        *self.current.write().unwrap() = loaded;
+        Ok(())
+    }
+}
+
+impl LoadedConfig {
+    fn load(settings: &TlsSettings) -> Result<Self, TlsError> {
+        // the modification times are taken first, so that a file which is modified while it is
+        // read is reloaded again
+        let modified = modification_times(settings);
+
+        let certificates = read_certificates(&settings.certificate)?;
+        let key = read_private_key(&settings.key)?;
+        let verifier = match &settings.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in read_certificates(path)? {
+                    roots
+                        .add(&certificate)
+                        .map_err(|_| TlsError::InvalidClientCa(path.clone()))?;
+                }
+                AllowAnyAuthenticatedClient::new(roots)
+            }
+            None => NoClientAuth::new(),
+        };
+        let mut config = ServerConfig::new(verifier);
+        config.set_single_cert(certificates, key)?;
+
+        Ok(Self {
+            config: Arc::new(config),
+            modified,
+        })
+    }
+}
+
+/// Gets the modification times of the configured files, or `None` for files which can't be
+/// accessed.
+fn modification_times(settings: &TlsSettings) -> Vec<Option<SystemTime>> {
+    let mut paths = vec![&settings.certificate, &settings.key];
+    paths.extend(settings.client_ca.as_ref());
+    paths
+        .into_iter()
+        .map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
+
+/// Reads the PEM encoded certificates from a file.
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>, TlsError> {
+    let pem = fs::read(path).map_err(|err| TlsError::Io(path.to_path_buf(), err))?;
+    match pemfile::certs(&mut pem.as_slice()) {
+        Ok(certificates) if !certificates.is_empty() => Ok(certificates),
+        _ => Err(TlsError::NoCertificate(path.to_path_buf())),
+    }
+}
+
+/// Reads a PEM encoded PKCS#8 or RSA private key from a file.
+fn read_private_key(path: &Path) -> Result<PrivateKey, TlsError> {
+    let pem = fs::read(path).map_err(|err| TlsError::Io(path.to_path_buf(), err))?;
+    pemfile::pkcs8_private_keys(&mut pem.as_slice())
+        .ok()
+        .and_then(|keys| keys.into_iter().next())
+        .or_else(|| {
+            pemfile::rsa_private_keys(&mut pem.as_slice())
+                .ok()
+                .and_then(|keys| keys.into_iter().next())
+        })
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_path_buf()))
+}
+
+/// Accepts TLS connections on the listener.
+///
+/// Up to [`MAX_HANDSHAKES`] TLS handshakes are performed concurrently, so that a slow client
+/// can't delay the connections of other clients. Connections whose handshake fails or doesn't
+/// complete within [`HANDSHAKE_TIMEOUT`] are dropped.
+pub fn incoming(
+    listener: TcpListener,
+    tls: Arc<TlsConfig>,
+) -> impl Stream<Item = Result<TlsStream<TcpStream>, io::Error>> + Send {
+    listener
+        .filter_map(|connection| async {
+            connection
+                .map_err(|err| warn!("failed to accept a connection: {}", err))
+                .ok()
+        })
+        .map(move |stream| time::timeout(HANDSHAKE_TIMEOUT, tls.acceptor().accept(stream)))
+        .buffer_unordered(MAX_HANDSHAKES)
+        .filter_map(|handshake| async {
+            match handshake {
+                Ok(Ok(stream)) => Some(Ok(stream)),
+                Ok(Err(err)) => {
+                    debug!("TLS handshake failed: {}", err);
+                    None
+                }
+                Err(_) => {
+                    debug!("TLS handshake timed out");
+                    None
+                }
+            }
+        })
+}
+
+/// Checks the configured files for changes and reloads the TLS configuration periodically.
+pub async fn reload_periodically(tls: Arc<TlsConfig>) {
+    let mut interval = time::interval(RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        match tls.reload_if_changed() {
+            Ok(true) => info!("reloaded the TLS configuration"),
+            Ok(false) => {}
+            Err(err) => warn!(
+                "failed to reload the TLS configuration, keeping the previous one: {}",
+                err
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The certificates in `testdata/` are issued for `localhost` by the test CA in `ca.pem` and
+    //! are valid until 2126.
+    use super::*;
+    use crate::storage::tests::utils::TempPath;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::{
+        rustls::{ClientConfig, Session},
+        webpki::DNSNameRef,
+        TlsConnector,
+    };
+    use warp::Filter;
+
+    const CA: &str = include_str!("testdata/ca.pem");
+    const SERVER_CERT: &str = include_str!("testdata/server.pem");
+    const SERVER_KEY: &str = include_str!("testdata/server.key");
+    const RENEWED_SERVER_CERT: &str = include_str!("testdata/server_renewed.pem");
+    const RENEWED_SERVER_KEY: &str = include_str!("testdata/server_renewed.key");
+    const CLIENT_CERT: &str = include_str!("testdata/client.pem");
+    const CLIENT_KEY: &str = include_str!("testdata/client.key");
+
+    /// A temporary directory with TLS files, which is removed when it's dropped.
+    struct TlsFiles(TempPath);
+
+    impl TlsFiles {
+        fn new(client_ca: bool) -> Self {
+            let files = Self(TempPath::new());
+            fs::create_dir(&*files.0).unwrap();
+            files.write("server.pem", SERVER_CERT);
+            files.write("server.key", SERVER_KEY);
+            if client_ca {
+                files.write("ca.pem", CA);
+            }
+            files
+        }
+
+        fn write(&self, name: &str, contents: &str) {
+            fs::write(self.0.join(name), contents).unwrap();
+        }
+
+        fn settings(&self) -> TlsSettings {
+            let client_ca = self.0.join("ca.pem");
+            TlsSettings {
+                certificate: self.0.join("server.pem"),
+                key: self.0.join("server.key"),
+                client_ca: if client_ca.exists() {
+                    Some(client_ca)
+                } else {
+                    None
+                },
+            }
+        }
+    }
+
+    /// Serves a single route over TLS and returns the address of the server.
+    async fn serve(tls: Arc<TlsConfig>) -> std::net::SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let listener = TcpListener::from_std(listener).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let routes = warp::path!("ping").map(|| "pong");
+        tokio::spawn(warp::serve(routes).run_incoming(incoming(listener, tls)));
+        addr
+    }
+
+    /// Sends a request over TLS and returns the response as well as the server certificate.
+    async fn request(
+        addr: std::net::SocketAddr,
+        client_cert: bool,
+    ) -> (io::Result<String>, Option<Certificate>) {
+        let mut config = ClientConfig::new();
+        config.root_store.add_pem_file(&mut CA.as_bytes()).unwrap();
+        if client_cert {
+            let certificates = pemfile::certs(&mut CLIENT_CERT.as_bytes()).unwrap();
+            let key = pemfile::pkcs8_private_keys(&mut CLIENT_KEY.as_bytes())
+                .unwrap()
+                .remove(0);
+            config.set_single_client_cert(certificates, key).unwrap();
+        }
+        let connector = TlsConnector::from(Arc::new(config));
+        let domain = DNSNameRef::try_from_ascii_str("localhost").unwrap();
+
+        let stream = TcpStream::from_std(std::net::TcpStream::connect(addr).unwrap()).unwrap();
+        let mut stream = match connector.connect(domain, stream).await {
+            Ok(stream) => stream,
+            Err(err) => return (Err(err), None),
+        };
+        let server_cert = stream
+            .get_ref()
+            .1
+            .get_peer_certificates()
+            .and_then(|certificates| certificates.into_iter().next());
+        let response = async {
+            stream
+                .write_all(b"GET /ping HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+                .await?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await?;
+            Ok(response)
+        };
+        (response.await, server_cert)
+    }
+
+    fn certificate(pem: &str) -> Certificate {
+        pemfile::certs(&mut pem.as_bytes()).unwrap().remove(0)
+    }
+
+    #[test]
+    fn load_invalid_tls_config() {
+        let files = TlsFiles::new(false);
+        files.write("server.key", SERVER_CERT);
+        assert!(matches!(
+            TlsConfig::load(files.settings()),
+            Err(TlsError::NoPrivateKey(_))
+        ));
+
+        files.write("server.pem", SERVER_KEY);
+        assert!(matches!(
+            TlsConfig::load(files.settings()),
+            Err(TlsError::NoCertificate(_))
+        ));
+
+        let mut settings = files.settings();
+        settings.certificate = files.0.join("missing.pem");
+        assert!(matches!(TlsConfig::load(settings), Err(TlsError::Io(_, _))));
+    }
+
+    #[tokio::test]
+    async fn serve_over_tls() {
+        let files = TlsFiles::new(false);
+        let tls = Arc::new(TlsConfig::load(files.settings()).unwrap());
+        let addr = serve(tls).await;
+
+        let (response, server_cert) = request(addr, false).await;
+        let response = response.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("pong"));
+        assert_eq!(server_cert, Some(certificate(SERVER_CERT)));
+    }
+
+    #[tokio::test]
+    async fn serve_over_tls_with_client_auth() {
+        let files = TlsFiles::new(true);
+        let tls = Arc::new(TlsConfig::load(files.settings()).unwrap());
+        let addr = serve(tls).await;
+
+        let (response, _) = request(addr, true).await;
+        assert!(response.unwrap().starts_with("HTTP/1.1 200 OK"));
+
+        let (response, _) = request(addr, false).await;
+        assert!(response.map_or(true, |response| response.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn reload_renewed_certificate() {
+        let files = TlsFiles::new(false);
+        let tls = Arc::new(TlsConfig::load(files.settings()).unwrap());
+        let addr = serve(tls.clone()).await;
+        assert!(!tls.reload_if_changed().unwrap());
+
+        // an invalid file is rejected and the previous configuration is kept
+        files.write("server.key", "");
+        assert!(tls.reload().is_err());
+        let (_, server_cert) = request(addr, false).await;
+        assert_eq!(server_cert, Some(certificate(SERVER_CERT)));
+
+        files.write("server.pem", RENEWED_SERVER_CERT);
+        files.write("server.key", RENEWED_SERVER_KEY);
+        tls.reload().unwrap();
+        let (response, server_cert) = request(addr, false).await;
+        assert!(response.unwrap().starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(server_cert, Some(certificate(RENEWED_SERVER_CERT)));
+        assert!(!tls.reload_if_changed().unwrap());
+    }
+}
@@ -0,0 +1,97 @@
+use std::task::Poll;
+
+use futures::{
+    future::{self, BoxFuture},
+    task::Context,
+    FutureExt,
+};
+use tower::Service;
+use xaynet_core::message::Message;
+
+use crate::{services::messages::ServiceError, storage::allowlist::Allowlist};
+
+/// A service that rejects messages from participants which are not on the
+/// allowlist.
+///
+/// If no allowlist is configured, all messages are accepted.
+#[derive(Clone, Debug)]
+pub struct AllowlistValidator {
+    allowlist: Option<Allowlist>,
+}
+
+impl AllowlistValidator {
+    pub fn new(allowlist: Option<Allowlist>) -> Self {
+        Self { allowlist }
+    }
+}
+
+impl Service<Message> for AllowlistValidator {
+    type Response = Message;
+    type Error = ServiceError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, message: Message) -> Self::Future {
+        let allowlist = match self.allowlist {
+            Some(ref allowlist) => allowlist.clone(),
+            None => return future::ready(Ok(message)).boxed(),
+        };
+        async move {
+            match allowlist.contains(&message.participant_pk).await {
+                Ok(true) => Ok(message),
+                Ok(false) => Err(ServiceError::ParticipantNotAllowed),
+                Err(e) => Err(ServiceError::InternalError(e.to_string())),
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::assert_ready;
+    use tower_test::mock::Spawn;
+
+    use crate::{services::tests::utils, storage::tests::utils::TempPath};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_allowed_participant() {
+        let path = TempPath::new();
+        let allowlist = Allowlist::from_file(path.to_path_buf()).await.unwrap();
+        let mut task = Spawn::new(AllowlistValidator::new(Some(allowlist.clone())));
+
+        let (_, subscriber) = utils::new_event_channels();
+        let round_params = subscriber.params_listener().get_latest().event;
+        let (message, _) = utils::new_sum_message(&round_params);
+
+        assert_ready!(task.poll_ready()).unwrap();
+        let err = task.call(message.clone()).await.unwrap_err();
+        match err {
+            ServiceError::ParticipantNotAllowed => {}
+            _ => panic!("expected ServiceError::ParticipantNotAllowed got {:?}", err),
+        }
+
+        allowlist.add(&message.participant_pk).await.unwrap();
+        assert_ready!(task.poll_ready()).unwrap();
+        let resp = task.call(message.clone()).await.unwrap();
+        assert_eq!(resp, message);
+    }
+
+    #[tokio::test]
+    async fn test_no_allowlist() {
+        let mut task = Spawn::new(AllowlistValidator::new(None));
+
+        let (_, subscriber) = utils::new_event_channels();
+        let round_params = subscriber.params_listener().get_latest().event;
+        let (message, _) = utils::new_sum_message(&round_params);
+
+        assert_ready!(task.poll_ready()).unwrap();
+        let resp = task.call(message.clone()).await.unwrap();
+        assert_eq!(resp, message);
+    }
+}
@@ -1,5 +1,9 @@
 use thiserror::Error;
-use xaynet_core::message::DecodeError;
+use warp::http::StatusCode;
+use xaynet_core::{
+    common::{MessageError, MessageErrorKind},
+    message::DecodeError,
+};
 
 use crate::state_machine::StateMachineError;
 
@@ -21,11 +25,21 @@ pub enum ServiceError {
     #[error("The message was not expected in the current phase")]
     UnexpectedMessage,
 
-    // FIXME: we need to refine the state machine errors and the
-    // conversion into a service error
+    #[error("Too many incomplete multipart messages for this participant")]
+    TooManyPendingMessages,
+
+    #[error("The multipart message exceeds the maximum message size")]
+    MessageTooLarge,
+
+    #[error("Too many incomplete multipart messages are buffered")]
+    MultipartBufferFull,
+
     #[error("the state machine failed to process the request: {0:?}")]
     StateMachine(StateMachineError),
 
+    #[error("participant is not on the allowlist")]
+    ParticipantNotAllowed,
+
     #[error("participant is not eligible for sum task")]
     NotSumEligible,
 
@@ -35,3 +49,135 @@ pub enum ServiceError {
     #[error("Internal error: {0}")]
     InternalError(String),
 }
+
+impl ServiceError {
+    /// Gets the machine readable kind of this error.
+    pub fn kind(&self) -> MessageErrorKind {
+        match self {
+            Self::Decrypt => MessageErrorKind::Decrypt,
+            Self::Parsing(_) => MessageErrorKind::Parsing,
+            Self::InvalidMessageSignature => MessageErrorKind::InvalidMessageSignature,
+            Self::InvalidCoordinatorPublicKey => MessageErrorKind::InvalidCoordinatorPublicKey,
+            Self::UnexpectedMessage => MessageErrorKind::UnexpectedMessage,
+            Self::TooManyPendingMessages => MessageErrorKind::TooManyPendingMessages,
+            Self::MessageTooLarge => MessageErrorKind::MessageTooLarge,
+            Self::MultipartBufferFull => MessageErrorKind::MultipartBufferFull,
+            Self::StateMachine(err) => err.kind(),
+            Self::ParticipantNotAllowed => MessageErrorKind::ParticipantNotAllowed,
+            Self::NotSumEligible => MessageErrorKind::NotSumEligible,
+            Self::NotUpdateEligible => MessageErrorKind::NotUpdateEligible,
+            Self::InternalError(_) => MessageErrorKind::InternalError,
+        }
+    }
+
+    /// Gets the HTTP status code the coordinator replies with for this error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Decrypt | Self::Parsing(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidMessageSignature => StatusCode::UNAUTHORIZED,
+            Self::InvalidCoordinatorPublicKey => StatusCode::MISDIRECTED_REQUEST,
+            Self::UnexpectedMessage => StatusCode::CONFLICT,
+            Self::TooManyPendingMessages => StatusCode::TOO_MANY_REQUESTS,
+            Self::MessageTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::MultipartBufferFull => StatusCode::SERVICE_UNAVAILABLE,
+            Self::StateMachine(err) => match err {
+                StateMachineError::MessageRejected => StatusCode::CONFLICT,
+                StateMachineError::AggregationFailed | StateMachineError::InvalidLocalSeedDict => {
+                    StatusCode::UNPROCESSABLE_ENTITY
+                }
+                StateMachineError::RequestQueueFull { .. } => StatusCode::SERVICE_UNAVAILABLE,
+                StateMachineError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Self::ParticipantNotAllowed | Self::NotSumEligible | Self::NotUpdateEligible => {
+                StatusCode::FORBIDDEN
+            }
+            Self::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl ServiceError {
+    /// Gets the time in seconds after which the message should be resent, if the message was
+    /// rejected because the coordinator is overloaded.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Self::StateMachine(StateMachineError::RequestQueueFull { retry_after }) => {
+                Some(*retry_after)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<&ServiceError> for MessageError {
+    fn from(err: &ServiceError) -> Self {
+        Self {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_machine::StateMachineError;
+
+    #[test]
+    fn status_codes() {
+        assert_eq!(ServiceError::Decrypt.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            ServiceError::InvalidMessageSignature.status_code(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            ServiceError::UnexpectedMessage.status_code(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            ServiceError::ParticipantNotAllowed.status_code(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            ServiceError::NotSumEligible.status_code(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            ServiceError::MultipartBufferFull.status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            ServiceError::StateMachine(StateMachineError::MessageRejected).status_code(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            ServiceError::StateMachine(StateMachineError::AggregationFailed).status_code(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            ServiceError::StateMachine(StateMachineError::InternalError).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            ServiceError::StateMachine(StateMachineError::RequestQueueFull { retry_after: 5 })
+                .status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn retry_after() {
+        let err =
+            ServiceError::StateMachine(StateMachineError::RequestQueueFull { retry_after: 5 });
+        assert_eq!(err.retry_after(), Some(5));
+        assert_eq!(ServiceError::NotSumEligible.retry_after(), None);
+    }
+
+    #[test]
+    fn message_error() {
+        let err = ServiceError::StateMachine(StateMachineError::InvalidLocalSeedDict);
+        let body = MessageError::from(&err);
+        assert_eq!(body.kind, MessageErrorKind::InvalidLocalSeedDict);
+        assert_eq!(body.message, err.to_string());
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! There are multiple such services and [`PetMessageHandler`]
 //! provides a single unifying interface for all of these.
+mod allowlist_validator;
 mod decryptor;
 mod error;
 mod message_parser;
@@ -10,8 +11,10 @@ mod state_machine;
 mod task_validator;
 pub use self::error::ServiceError;
 use self::{
+    allowlist_validator::AllowlistValidator,
     decryptor::Decryptor,
     message_parser::MessageParser,
+    multipart::MultipartHandler,
     state_machine::StateMachine,
     task_validator::TaskValidator,
 };
@@ -19,30 +22,53 @@ use self::{
 use std::sync::Arc;
 
 use futures::future::poll_fn;
-use rayon::ThreadPoolBuilder;
+use rayon::{ThreadPoolBuildError, ThreadPoolBuilder};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tower::Service;
 use xaynet_core::message::Message;
 
-use crate::state_machine::{events::EventSubscriber, requests::RequestSender};
+use crate::{
+    settings::{MultipartSettings, ProcessingSettings},
+    state_machine::{events::EventSubscriber, requests::RequestSender},
+    storage::allowlist::Allowlist,
+};
 
 impl PetMessageHandler {
-    pub fn new(event_subscriber: &EventSubscriber, requests_tx: RequestSender) -> Self {
-        // TODO: make this configurable. Users should be able to
-        // choose how many threads they want etc.
-        //
-        // TODO: don't unwrap
-        let thread_pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+    /// Creates a new message handler.
+    ///
+    /// # Errors
+    /// Fails if the thread pool for decrypting and parsing the
+    /// messages cannot be built.
+    pub fn new(
+        event_subscriber: &EventSubscriber,
+        requests_tx: RequestSender,
+        multipart_settings: MultipartSettings,
+        processing_settings: ProcessingSettings,
+        allowlist: Option<Allowlist>,
+    ) -> Result<Self, ThreadPoolBuildError> {
+        let thread_pool = Arc::new(
+            ThreadPoolBuilder::new()
+                .num_threads(processing_settings.threads)
+                .thread_name(|index| format!("message-processing-{}", index))
+                .build()?,
+        );
         let decryptor = Decryptor::new(event_subscriber, thread_pool.clone());
-        let message_parser = MessageParser::new(event_subscriber, thread_pool);
+        let message_parser = MessageParser::new(event_subscriber, thread_pool.clone());
+        let allowlist_validator = AllowlistValidator::new(allowlist);
+        let multipart_handler =
+            MultipartHandler::new(multipart_settings, event_subscriber, thread_pool);
         let task_validator = TaskValidator::new(event_subscriber);
         let state_machine = StateMachine::new(requests_tx);
 
-        Self {
+        Ok(Self {
+            in_flight: Arc::new(Semaphore::new(processing_settings.max_in_flight)),
             decryptor,
             message_parser,
+            allowlist_validator,
+            multipart_handler,
             task_validator,
             state_machine,
-        }
+        })
     }
     async fn decrypt(&mut self, enc_data: Vec<u8>) -> Result<Vec<u8>, ServiceError> {
         poll_fn(|cx| <Decryptor as Service<Vec<u8>>>::poll_ready(&mut self.decryptor, cx)).await?;
@@ -55,6 +81,19 @@ impl PetMessageHandler {
         self.message_parser.call(data).await
     }
 
+    async fn validate_participant(&mut self, message: Message) -> Result<Message, ServiceError> {
+        poll_fn(|cx| self.allowlist_validator.poll_ready(cx)).await?;
+        self.allowlist_validator.call(message).await
+    }
+
+    async fn handle_multipart(
+        &mut self,
+        message: Message,
+    ) -> Result<Option<Message>, ServiceError> {
+        poll_fn(|cx| self.multipart_handler.poll_ready(cx)).await?;
+        self.multipart_handler.call(message).await
+    }
+
     async fn validate_task(&mut self, message: Message) -> Result<Message, ServiceError> {
         poll_fn(|cx| self.task_validator.poll_ready(cx)).await?;
         self.task_validator.call(message).await
@@ -65,9 +104,32 @@ impl PetMessageHandler {
         self.state_machine.call(message).await
     }
 
+    /// Waits until another message may be processed concurrently.
+    ///
+    /// The permit must be held while the message is received and handled, such that the number
+    /// of buffered messages is limited as well.
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.in_flight.clone().acquire_owned().await
+    }
+
+    /// Handles an encrypted message.
+    ///
+    /// The number of messages which are handled concurrently is only limited if the caller holds
+    /// a permit from [`acquire()`].
+    ///
+    /// [`acquire()`]: PetMessageHandler::acquire
     pub async fn handle_message(&mut self, enc_data: Vec<u8>) -> Result<(), ServiceError> {
+        // shed the load before any work is spent on the message if the
+        // state machine can't keep up
+        poll_fn(|cx| self.state_machine.poll_ready(cx)).await?;
         let raw_message = self.decrypt(enc_data).await?;
         let message = self.parse(raw_message).await?;
+        let message = self.validate_participant(message).await?;
+        let message = match self.handle_multipart(message).await? {
+            Some(message) => message,
+            // The message is incomplete, wait for the next chunks
+            None => return Ok(()),
+        };
         let message = self.validate_task(message).await?;
         self.process(message).await
     }
@@ -76,21 +138,35 @@ impl PetMessageHandler {
 /// A service that processes requests from the beginning to the
 /// end.
 ///
-/// The processing is divided in three phases:
+/// The processing is divided in five phases:
 ///
 /// 1. The raw request (which is just a vector of bytes represented an
 ///    encrypted message) goes through the `MessageParser` service,
 ///    which decrypt the message, validates it, and parses it
 ///
-/// 2. The message is passed to the `TaskValidator`, which depending on
+/// 2. The message is passed to the `AllowlistValidator`, which rejects
+///    the message if an allowlist is configured and the participant
+///    is not on it
+///
+/// 3. If the message is a chunk of a multipart message, it is passed
+///    to the `MultipartHandler`, which buffers the chunks until the
+///    full message can be reassembled. Messages of participants that
+///    are not eligible for the task of the message are discarded as
+///    soon as their task signatures are complete
+///
+/// 4. The message is passed to the `TaskValidator`, which depending on
 ///    the message type performs some additional checks. The
 ///    `TaskValidator` may also discard the message
 ///
-/// 3. Finally, the message is handled by the `StateMachine` service.
+/// 5. Finally, the message is handled by the `StateMachine` service.
 #[derive(Clone)]
 pub struct PetMessageHandler {
+    /// Limits the number of messages which are processed concurrently.
+    in_flight: Arc<Semaphore>,
     decryptor: Decryptor,
     message_parser: MessageParser,
+    allowlist_validator: AllowlistValidator,
+    multipart_handler: MultipartHandler,
     task_validator: TaskValidator,
     state_machine: StateMachine,
 }
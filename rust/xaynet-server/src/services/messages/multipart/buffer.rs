@@ -32,6 +32,9 @@ impl From<BTreeMap<u16, Vec<u8>>> for MultipartBufferIterator {
 // currently increment a counter for every byte consumed, but we could
 // exploits the fact that IterVec implements ExactSizeIterator avoid
 // that.
+//
+// The chunks are consumed by value, so that the memory of a chunk is
+// released as soon as all its bytes have been read.
 impl Iterator for MultipartBufferIterator {
     type Item = u8;
 
@@ -56,12 +59,13 @@ impl Iterator for MultipartBufferIterator {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let lower_bound = self.initial_length - self.consumed;
-        let upper_bound = self.initial_length;
-        (lower_bound, Some(upper_bound))
+        let remaining = self.initial_length - self.consumed;
+        (remaining, Some(remaining))
     }
 }
 
+impl ExactSizeIterator for MultipartBufferIterator {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,6 +78,7 @@ mod tests {
         map.insert(3, vec![4, 5]);
 
         let mut iter = MultipartBufferIterator::from(map);
+        assert_eq!(iter.len(), 6);
         assert_eq!(iter.consumed, 0);
         assert_eq!(iter.initial_length, 6);
         assert!(iter.current_chunk.is_none());
@@ -107,5 +112,8 @@ mod tests {
         assert_eq!(iter.consumed, 6);
         assert_eq!(iter.initial_length, 6);
         assert!(iter.current_chunk.is_some());
+        assert_eq!(iter.len(), 0);
+
+        assert_eq!(iter.next(), None);
     }
 }
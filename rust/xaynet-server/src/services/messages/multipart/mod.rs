@@ -1,2 +1,4 @@
 mod buffer;
 mod service;
+
+pub use self::service::MultipartHandler;
@@ -1,19 +1,28 @@
 use std::{
     collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
     task::Poll,
+    time::{Duration, Instant},
 };
 
-use futures::{
-    future::{self, Ready},
-    task::Context,
-};
+use anyhow::anyhow;
+use futures::{future, task::Context};
+use rayon::ThreadPool;
+use tokio::sync::oneshot;
 use tower::Service;
 use xaynet_core::{
-    crypto::{PublicEncryptKey, PublicSigningKey},
+    common::RoundParameters,
+    crypto::{ByteObject, PublicEncryptKey, PublicSigningKey},
     message::{Chunk, DecodeError, FromBytes, Message, Payload, Sum, Sum2, Tag, Update},
+    ParticipantTaskSignature,
 };
 
-use crate::services::messages::ServiceError;
+use super::buffer::MultipartBufferIterator;
+use crate::{
+    services::messages::{task_validator::check_eligibility, BoxedServiceFuture, ServiceError},
+    settings::MultipartSettings,
+    state_machine::events::{EventListener, EventSubscriber},
+};
 
 /// A `MessageBuilder` stores chunks of a multipart message. Once it
 /// has all the chunks, it can be consumed and turned into a
@@ -32,6 +41,14 @@ pub struct MessageBuilder {
     last_chunk_id: Option<u16>,
     /// Chunks, ordered by ID
     data: BTreeMap<u16, Vec<u8>>,
+    /// Total size in bytes of the chunks received so far
+    size: usize,
+    /// Whether the task signatures at the start of the payload have
+    /// been checked
+    verified: bool,
+    /// Time at which the last chunk was received, along with a
+    /// sequence number that makes it unique
+    last_chunk_received: (Instant, u64),
 }
 
 impl MessageBuilder {
@@ -43,6 +60,9 @@ impl MessageBuilder {
             coordinator_pk,
             data: BTreeMap::new(),
             last_chunk_id: None,
+            size: 0,
+            verified: false,
+            last_chunk_received: (Instant::now(), 0),
         }
     }
 
@@ -54,36 +74,73 @@ impl MessageBuilder {
             .unwrap_or(false)
     }
 
-    /// Add a chunk.
+    /// Return `true` if the chunk with the given ID can be added.
+    ///
+    /// Until the task signatures have been checked, the chunks must
+    /// be received in order, such that the chunks received so far
+    /// always form the start of the payload.
+    fn accepts(&self, chunk_id: u16) -> bool {
+        self.verified || chunk_id as usize == self.data.len() + 1
+    }
+
+    /// Add a chunk. If a chunk with the same ID was already received,
+    /// it is replaced.
     fn add_chunk(&mut self, chunk: Chunk) {
         let Chunk { id, last, data, .. } = chunk;
         if last {
             self.last_chunk_id = Some(id);
         }
-        self.data.insert(id, data);
+        self.size += data.len();
+        if let Some(replaced) = self.data.insert(id, data) {
+            self.size -= replaced.len();
+        }
+    }
+
+    /// Return the task signatures at the start of the payload, or
+    /// `None` if they haven't been received completely yet.
+    ///
+    /// This method should only be called before the message is
+    /// verified, since the chunks are only guaranteed to form the
+    /// start of the payload until then.
+    fn task_signatures(
+        &self,
+    ) -> Option<(ParticipantTaskSignature, Option<ParticipantTaskSignature>)> {
+        let length = match self.tag {
+            Tag::Update => 2 * ParticipantTaskSignature::LENGTH,
+            Tag::Sum | Tag::Sum2 => ParticipantTaskSignature::LENGTH,
+        };
+        if self.size < length {
+            return None;
+        }
+        let bytes = self
+            .data
+            .values()
+            .flatten()
+            .take(length)
+            .copied()
+            .collect::<Vec<u8>>();
+        let (sum_signature, update_signature) = bytes.split_at(ParticipantTaskSignature::LENGTH);
+        // The slices have the length of a signature, so it's ok to
+        // unwrap.
+        Some((
+            ParticipantTaskSignature::from_slice(sum_signature).unwrap(),
+            ParticipantTaskSignature::from_slice(update_signature),
+        ))
     }
 
     /// Aggregate all the chunks. This method should only be called
     /// when all the chunks are here, otherwise the aggregated message
     /// will be invalid.
+    ///
+    /// The payload is parsed directly from the chunks, without
+    /// concatenating them first. Each chunk is freed as soon as it
+    /// has been consumed.
     fn into_message(self) -> Result<Message, DecodeError> {
-        // FIXME: this is highly inefficient. We end up allocating a
-        // potentially huge vector (dozens of MB is worst but realistic
-        // cases). In theory we could parse the message directly from the
-        // BTreeMap. But that would require parsing from a stream of bytes
-        // instead of an array, which xaynet_core doesn't support atm.
-        let bytes = self
-            .data
-            .into_iter()
-            .map(|(_, chunk)| chunk)
-            .fold(vec![], |mut acc, chunk| {
-                acc.extend(chunk);
-                acc
-            });
+        let mut stream = MultipartBufferIterator::from(self.data);
         let payload = match self.tag {
-            Tag::Sum => Sum::from_bytes(&&bytes[..]).map(Into::into)?,
-            Tag::Update => Update::from_bytes(&&bytes[..]).map(Into::into)?,
-            Tag::Sum2 => Sum2::from_bytes(&&bytes[..]).map(Into::into)?,
+            Tag::Sum => Sum::from_byte_stream(&mut stream).map(Into::into)?,
+            Tag::Update => Update::from_byte_stream(&mut stream).map(Into::into)?,
+            Tag::Sum2 => Sum2::from_byte_stream(&mut stream).map(Into::into)?,
         };
         let message = Message {
             signature: None,
@@ -106,16 +163,149 @@ pub struct MessageId {
     participant_pk: PublicSigningKey,
 }
 
+/// The partial messages, along with the indices needed to bound them
+/// without scanning all of them.
+#[derive(Debug, Default)]
+struct MessageBuilders {
+    builders: HashMap<MessageId, MessageBuilder>,
+    /// IDs of the partial messages, ordered by the time at which
+    /// they received their last chunk
+    by_last_chunk: BTreeMap<(Instant, u64), MessageId>,
+    /// Number of partial messages per participant
+    pending_messages: HashMap<PublicSigningKey, usize>,
+    /// Total size in bytes of the chunks of all partial messages
+    size: usize,
+    /// Sequence number of the last received chunk
+    sequence: u64,
+}
+
+impl MessageBuilders {
+    fn get(&self, id: &MessageId) -> Option<&MessageBuilder> {
+        self.builders.get(id)
+    }
+
+    fn pending_messages(&self, participant_pk: &PublicSigningKey) -> usize {
+        self.pending_messages
+            .get(participant_pk)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Add a chunk to a partial message, creating it if necessary.
+    fn add_chunk(
+        &mut self,
+        id: &MessageId,
+        tag: Tag,
+        coordinator_pk: PublicEncryptKey,
+        chunk: Chunk,
+    ) {
+        let builder = match self.builders.get_mut(id) {
+            Some(builder) => {
+                self.by_last_chunk.remove(&builder.last_chunk_received);
+                builder
+            }
+            None => {
+                *self.pending_messages.entry(id.participant_pk).or_default() += 1;
+                self.builders
+                    .entry(id.clone())
+                    .or_insert_with(|| MessageBuilder::new(tag, id.participant_pk, coordinator_pk))
+            }
+        };
+        self.size -= builder.size;
+        builder.add_chunk(chunk);
+        self.size += builder.size;
+        self.sequence += 1;
+        builder.last_chunk_received = (Instant::now(), self.sequence);
+        self.by_last_chunk
+            .insert(builder.last_chunk_received, id.clone());
+    }
+
+    /// Mark a partial message as verified.
+    fn verify(&mut self, id: &MessageId) {
+        if let Some(builder) = self.builders.get_mut(id) {
+            builder.verified = true;
+        }
+    }
+
+    /// Remove a partial message.
+    fn remove(&mut self, id: &MessageId) -> Option<MessageBuilder> {
+        let builder = self.builders.remove(id)?;
+        self.by_last_chunk.remove(&builder.last_chunk_received);
+        if let Some(pending_messages) = self.pending_messages.get_mut(&id.participant_pk) {
+            *pending_messages -= 1;
+            if *pending_messages == 0 {
+                self.pending_messages.remove(&id.participant_pk);
+            }
+        }
+        self.size -= builder.size;
+        Some(builder)
+    }
+
+    /// Discard the partial messages that didn't receive any chunk for
+    /// longer than `expiry`.
+    fn discard_expired(&mut self, expiry: Duration) {
+        while let Some(id) = self
+            .by_last_chunk
+            .iter()
+            .next()
+            .filter(|((last_chunk_received, _), _)| last_chunk_received.elapsed() > expiry)
+            .map(|(_, id)| id.clone())
+        {
+            debug!(
+                "discarding expired multipart message {} from {:?}",
+                id.message_id, id.participant_pk
+            );
+            self.remove(&id);
+        }
+    }
+}
+
 /// A service that handles multipart messages.
+///
+/// The partial messages are shared between the clones of this
+/// service, since the chunks of a message may be handled by
+/// different clones.
+///
+/// A partial message is only buffered as long as its participant is
+/// eligible for the task of the message. Therefore, the chunks must
+/// be sent in order until the task signatures at the start of the
+/// payload have been received, which are checked before any further
+/// chunk is accepted.
+///
+/// Since decoding a large message is a CPU-intensive task, a complete
+/// message is decoded on a `rayon` thread-pool after the partial
+/// messages have been unlocked.
+#[derive(Clone)]
 pub struct MultipartHandler {
-    message_builders: HashMap<MessageId, MessageBuilder>,
+    message_builders: Arc<Mutex<MessageBuilders>>,
+    params_listener: EventListener<RoundParameters>,
+    /// Maximum number of partial messages per participant
+    max_pending_messages: usize,
+    /// Maximum size in bytes of a message payload
+    max_message_size: usize,
+    /// Maximum size in bytes of all partial messages
+    max_buffer_size: usize,
+    /// Time after which a partial message that doesn't receive any
+    /// new chunk is discarded
+    expiry: Duration,
+    /// Thread-pool the decoding of complete messages is offloaded to.
+    thread_pool: Arc<ThreadPool>,
 }
 
 impl MultipartHandler {
-    #[allow(dead_code)]
-    pub fn new() -> Self {
+    pub fn new(
+        settings: MultipartSettings,
+        subscriber: &EventSubscriber,
+        thread_pool: Arc<ThreadPool>,
+    ) -> Self {
         Self {
-            message_builders: HashMap::new(),
+            message_builders: Arc::new(Mutex::new(MessageBuilders::default())),
+            params_listener: subscriber.params_listener(),
+            max_pending_messages: settings.max_pending_messages,
+            max_message_size: settings.max_message_size,
+            max_buffer_size: settings.max_buffer_size,
+            expiry: Duration::from_secs(settings.expiry),
+            thread_pool,
         }
     }
 }
@@ -123,7 +313,7 @@ impl MultipartHandler {
 impl Service<Message> for MultipartHandler {
     type Response = Option<Message>;
     type Error = ServiceError;
-    type Future = Ready<Result<Self::Response, Self::Error>>;
+    type Future = BoxedServiceFuture<Self::Response, Self::Error>;
 
     fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
@@ -144,31 +334,105 @@ impl Service<Message> for MultipartHandler {
             ..
         } = message
         {
+            // The lock is never held across an await point, and the
+            // map cannot be left in an inconsistent state if a panic
+            // occurs while holding it, so it's ok to unwrap.
+            let mut message_builders = self.message_builders.lock().unwrap();
+            message_builders.discard_expired(self.expiry);
+
             let id = MessageId {
                 message_id: chunk.message_id,
                 participant_pk,
             };
-            // If we don't have a partial message for this ID, create
-            // an empty one.
-            let mp_message = self
-                .message_builders
-                .entry(id.clone())
-                .or_insert_with(|| MessageBuilder::new(tag, participant_pk, coordinator_pk));
+            // If we don't have a partial message for this ID, a new
+            // one is created, unless the participant already has too
+            // many of them.
+            let accepts = match message_builders.get(&id) {
+                Some(builder) => builder.accepts(chunk.id),
+                None => {
+                    if message_builders.pending_messages(&participant_pk)
+                        >= self.max_pending_messages
+                    {
+                        warn!("too many pending multipart messages for participant");
+                        return ready_err(ServiceError::TooManyPendingMessages);
+                    }
+                    chunk.id == 1
+                }
+            };
+            if !accepts {
+                warn!("discarding multipart message chunk received out of order");
+                message_builders.remove(&id);
+                return ready_err(ServiceError::Parsing(anyhow!(
+                    "the chunks must be sent in order until the task signatures are complete"
+                )));
+            }
+            if message_builders.size + chunk.data.len() > self.max_buffer_size {
+                warn!("discarding multipart message chunk exceeding the buffer size");
+                return ready_err(ServiceError::MultipartBufferFull);
+            }
+
             // Add the chunk to the partial message
-            mp_message.add_chunk(chunk);
+            message_builders.add_chunk(&id, tag, coordinator_pk, chunk);
+            // This entry exists, per the line above, so it's ok to
+            // unwrap.
+            let mp_message = message_builders.get(&id).unwrap();
+
+            if mp_message.size > self.max_message_size {
+                warn!("discarding multipart message exceeding the maximum message size");
+                message_builders.remove(&id);
+                return ready_err(ServiceError::MessageTooLarge);
+            }
+
+            // Check the eligibility of the participant as soon as the
+            // task signatures are complete
+            if !mp_message.verified {
+                if let Some((sum_signature, update_signature)) = mp_message.task_signatures() {
+                    let params = self.params_listener.get_latest().event;
+                    if let Err(e) = check_eligibility(
+                        &params,
+                        &participant_pk,
+                        tag,
+                        &sum_signature,
+                        update_signature.as_ref(),
+                    ) {
+                        warn!("discarding multipart message: {}", e);
+                        message_builders.remove(&id);
+                        return ready_err(e);
+                    }
+                    message_builders.verify(&id);
+                }
+            }
 
             // Check if the message is complete, and if so parse it
             // and return it
-            if mp_message.has_all_chunks() {
-                // This entry exists, because `mp_message` above
-                // refers to it, so it's ok to unwrap.
-                match self.message_builders.remove(&id).unwrap().into_message() {
-                    Ok(message) => ready_ok(Some(message)),
-                    Err(e) => ready_err(ServiceError::Parsing(e)),
-                }
-            } else {
-                ready_ok(None)
+            let is_complete = message_builders
+                .get(&id)
+                .map(MessageBuilder::has_all_chunks)
+                .unwrap_or(false);
+            if !is_complete {
+                return ready_ok(None);
             }
+            // This entry exists, per the check above, so it's ok to
+            // unwrap.
+            let builder = message_builders.remove(&id).unwrap();
+            drop(message_builders);
+
+            let (tx, rx) = oneshot::channel::<Result<Self::Response, Self::Error>>();
+            trace!("spawning multipart message decoding task on threadpool");
+            self.thread_pool.spawn(move || {
+                let res = builder
+                    .into_message()
+                    .map(Some)
+                    .map_err(ServiceError::Parsing);
+                let _ = tx.send(res);
+            });
+            Box::pin(async move {
+                rx.await.unwrap_or_else(|_| {
+                    Err(ServiceError::InternalError(
+                        "failed to receive response from thread-pool".to_string(),
+                    ))
+                })
+            })
         } else {
             // This cannot happen, because parsing have fail
             panic!("multipart flag is set but payload is not a multipart message");
@@ -176,42 +440,87 @@ impl Service<Message> for MultipartHandler {
     }
 }
 
-fn ready_ok<T, E>(t: T) -> Ready<Result<T, E>> {
-    future::ready(Ok(t))
+fn ready_ok<T, E>(t: T) -> BoxedServiceFuture<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    Box::pin(future::ready(Ok(t)))
 }
 
-fn ready_err<T, E>(e: E) -> Ready<Result<T, E>> {
-    future::ready(Err(e))
+fn ready_err<T, E>(e: E) -> BoxedServiceFuture<T, E>
+where
+    T: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    Box::pin(future::ready(Err(e)))
 }
 
 #[cfg(test)]
 mod tests {
-    use std::iter;
-
+    use rayon::ThreadPoolBuilder;
     use tokio_test::assert_ready;
     use tower_test::mock::Spawn;
-    use xaynet_core::crypto::{ByteObject, PublicEncryptKey, Signature};
+    use xaynet_core::{
+        crypto::{PublicEncryptKey, SigningKeyPair},
+        message::ToBytes,
+    };
 
     use super::*;
+    use crate::{services::tests::utils, state_machine::events::EventPublisher};
+
+    /// Spawn the service with round parameters that make every
+    /// participant eligible for the sum task.
+    fn spawn_svc(settings: MultipartSettings) -> (EventPublisher, Spawn<MultipartHandler>) {
+        let (mut publisher, subscriber) = utils::new_event_channels();
+        let mut round_params = subscriber.params_listener().get_latest().event;
+        round_params.sum = 1.0;
+        publisher.broadcast_params(round_params);
+        let thread_pool = Arc::new(ThreadPoolBuilder::new().build().unwrap());
+        let task = Spawn::new(MultipartHandler::new(settings, &subscriber, thread_pool));
+        (publisher, task)
+    }
 
-    fn spawn_svc() -> Spawn<MultipartHandler> {
-        Spawn::new(MultipartHandler::new())
+    fn round_params(task: &Spawn<MultipartHandler>) -> RoundParameters {
+        task.get_ref().params_listener.get_latest().event
     }
 
-    fn sum() -> (Vec<u8>, Sum) {
-        let mut start_byte: u8 = 0xff;
-        let f = move || {
-            start_byte = start_byte.wrapping_add(1);
-            Some(start_byte)
-        };
-        let bytes: Vec<u8> = iter::from_fn(f)
-            .take(PublicEncryptKey::LENGTH + Signature::LENGTH)
-            .collect();
+    /// Number of partial messages held by the service
+    fn nb_messages(task: &Spawn<MultipartHandler>) -> usize {
+        task.get_ref()
+            .message_builders
+            .lock()
+            .unwrap()
+            .builders
+            .len()
+    }
+
+    /// Total size of the partial messages held by the service
+    fn buffer_size(task: &Spawn<MultipartHandler>) -> usize {
+        task.get_ref().message_builders.lock().unwrap().size
+    }
 
+    /// Number of chunks received for the given partial message
+    fn nb_chunks(task: &Spawn<MultipartHandler>, id: &MessageId) -> Option<usize> {
+        task.get_ref()
+            .message_builders
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|builder| builder.data.len())
+    }
+
+    /// A sum payload of 96 bytes, signed by the given participant for
+    /// the given round parameters.
+    fn sum(keys: &SigningKeyPair, round_params: &RoundParameters) -> (Vec<u8>, Sum) {
         let sum = Sum {
-            sum_signature: Signature::from_slice(&bytes[..Signature::LENGTH]).unwrap(),
-            ephm_pk: PublicEncryptKey::from_slice(&bytes[Signature::LENGTH..]).unwrap(),
+            sum_signature: keys
+                .secret
+                .sign_detached(&[round_params.seed.as_slice(), b"sum"].concat()),
+            ephm_pk: PublicEncryptKey::zeroed(),
         };
+        let mut bytes = vec![0; sum.buffer_length()];
+        sum.to_bytes(&mut bytes);
         (bytes, sum)
     }
 
@@ -223,80 +532,66 @@ mod tests {
     }
 
     fn chunks(mut data: Vec<u8>) -> (Chunk, Chunk, Chunk, Chunk, Chunk) {
-        // Chunk 1: 1 byte
-        // Chunk 2: 2 bytes
-        // Chunk 3: 3 bytes
-        // Chunk 4: 4 bytes
-        // Chunk 5: 96 - (1 + 2 + 3 + 4) = 86 bytes
+        // Chunk 1: 64 bytes, i.e. the sum signature
+        // Chunk 2: 8 bytes
+        // Chunk 3: 8 bytes
+        // Chunk 4: 8 bytes
+        // Chunk 5: 8 bytes
 
         assert_eq!(data.len(), 96);
 
-        // 96 - 10 = 86, remains 10
-        let data5 = data.split_off(10);
-        assert_eq!(data5.len(), 86);
-        assert_eq!(data.len(), 10);
-
-        // 10 - 6 = 4, remains 6
-        let data4 = data.split_off(6);
-        assert_eq!(data4.len(), 4);
-        assert_eq!(data.len(), 6);
-
-        // 6 - 3 = 3, remains 3
-        let data3 = data.split_off(3);
-        assert_eq!(data3.len(), 3);
-        assert_eq!(data.len(), 3);
+        let data5 = data.split_off(88);
+        let data4 = data.split_off(80);
+        let data3 = data.split_off(72);
+        let data2 = data.split_off(64);
+        assert_eq!(data.len(), 64);
 
-        // 3 - 1 = 2, remains 1
-        let data2 = data.split_off(1);
-        assert_eq!(data2.len(), 2);
-        assert_eq!(data.len(), 1);
-
-        let chunk1 = Chunk {
-            id: 1,
-            message_id: 1234,
-            last: false,
-            data: data,
-        };
-        let chunk2 = Chunk {
-            id: 2,
-            message_id: 1234,
-            last: false,
-            data: data2,
-        };
-        let chunk3 = Chunk {
-            id: 3,
-            message_id: 1234,
-            last: false,
-            data: data3,
-        };
-        let chunk4 = Chunk {
-            id: 4,
+        let chunk = |id, data| Chunk {
+            id,
             message_id: 1234,
-            last: false,
-            data: data4,
+            last: id == 5,
+            data,
         };
-        let chunk5 = Chunk {
-            id: 5,
-            message_id: 1234,
-            last: true,
-            data: data5,
+        (
+            chunk(1, data),
+            chunk(2, data2),
+            chunk(3, data3),
+            chunk(4, data4),
+            chunk(5, data5),
+        )
+    }
+
+    fn chunk_message(pk: PublicSigningKey, message_id: u16, chunk: &Chunk) -> Message {
+        let chunk = Chunk {
+            message_id,
+            ..chunk.clone()
         };
-        (chunk1, chunk2, chunk3, chunk4, chunk5)
+        Message::new_multipart(pk, PublicEncryptKey::zeroed(), chunk, Tag::Sum)
+    }
+
+    /// A participant along with the chunks of its sum message.
+    fn participant(task: &Spawn<MultipartHandler>) -> (PublicSigningKey, Sum, Vec<Chunk>) {
+        let keys = SigningKeyPair::generate();
+        let (data, sum) = sum(&keys, &round_params(task));
+        let (c1, c2, c3, c4, c5) = chunks(data);
+        (keys.public, sum, vec![c1, c2, c3, c4, c5])
     }
 
     #[test]
     fn test_message_builder_in_order() {
         let mut msg = message_builder();
-        let (data, sum) = sum();
+        let (data, sum) = sum(&SigningKeyPair::generate(), &RoundParameters::default());
         let (c1, c2, c3, c4, c5) = chunks(data.clone());
 
         assert!(msg.data.is_empty());
         assert!(msg.last_chunk_id.is_none());
+        assert!(msg.task_signatures().is_none());
 
         msg.add_chunk(c1);
         assert_eq!(msg.data.len(), 1);
         assert!(msg.last_chunk_id.is_none());
         assert!(!msg.has_all_chunks());
+        assert_eq!(msg.task_signatures(), Some((sum.sum_signature, None)));
 
         msg.add_chunk(c2);
         assert_eq!(msg.data.len(), 2);
@@ -328,7 +623,7 @@ mod tests {
     #[test]
     fn test_message_builder_out_of_order() {
         let mut msg = message_builder();
-        let (data, sum) = sum();
+        let (data, sum) = sum(&SigningKeyPair::generate(), &RoundParameters::default());
         let (c1, c2, c3, c4, c5) = chunks(data.clone());
 
         assert!(msg.data.is_empty());
@@ -368,76 +663,287 @@ mod tests {
 
     #[tokio::test]
     async fn message_handler() {
-        let mut task = spawn_svc();
+        let (_publisher, mut task) = spawn_svc(MultipartSettings::default());
         assert_ready!(task.poll_ready()).unwrap();
 
-        let coordinator_pk =
-            PublicEncryptKey::from_slice(&vec![0x00; PublicSigningKey::LENGTH]).unwrap();
-
-        // The payload of the message (and therefore the chunks) will
-        // be the same for the two participants. What must differ is
-        // the participant public key in the header.
-        let (data, sum) = sum();
-        let (c1, c2, c3, c4, c5) = chunks(data.clone());
-
-        // A signing key that identifies a first faked participant.
-        let pk1 = PublicSigningKey::from_slice(&vec![0x11; PublicSigningKey::LENGTH]).unwrap();
-        // message ID for the message from our fake participant identified by `pk1`
+        let (pk1, sum1, chunks1) = participant(&task);
+        let (pk2, sum2, chunks2) = participant(&task);
         let message_id1 = MessageId {
             message_id: 1234,
             participant_pk: pk1,
         };
-        // function that take a data chunk and create Chunk message
-        // with `pk1` as participant public key in the header
-        let make_message1 =
-            |chunk: &Chunk| Message::new_multipart(pk1, coordinator_pk, chunk.clone(), Tag::Sum);
-
-        // Do the same thing to fake a second participant: generate a
-        // public key, a message ID, and a function to create messages
-        // originating from that participant
-        let pk2 = PublicSigningKey::from_slice(&vec![0x22; PublicSigningKey::LENGTH]).unwrap();
         let message_id2 = MessageId {
             message_id: 1234,
             participant_pk: pk2,
         };
-        let make_message2 =
-            |chunk: &Chunk| Message::new_multipart(pk2, coordinator_pk, chunk.clone(), Tag::Sum);
 
-        // Start of the actual test. Notice that we send the chunks
-        // out of order.
+        // The first chunks contain the task signatures, the remaining
+        // chunks may be sent out of order.
+        for &i in &[0, 2, 4, 3] {
+            assert!(task
+                .call(chunk_message(pk1, 1234, &chunks1[i]))
+                .await
+                .unwrap()
+                .is_none());
+            assert!(task
+                .call(chunk_message(pk2, 1234, &chunks2[i]))
+                .await
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(nb_messages(&task), 2);
+        assert_eq!(nb_chunks(&task, &message_id1), Some(4));
+        assert_eq!(nb_chunks(&task, &message_id2), Some(4));
+        assert_eq!(buffer_size(&task), 2 * 88);
+
+        let res1 = task
+            .call(chunk_message(pk1, 1234, &chunks1[1]))
+            .await
+            .unwrap()
+            .unwrap();
+        let res2 = task
+            .call(chunk_message(pk2, 1234, &chunks2[1]))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(nb_chunks(&task, &message_id1).is_none());
+        assert!(nb_chunks(&task, &message_id2).is_none());
+        assert_eq!(buffer_size(&task), 0);
 
-        assert!(task.call(make_message1(&c3)).await.unwrap().is_none());
-        assert_eq!(task.get_ref().message_builders.len(), 1);
-        let builder = task.get_ref().message_builders.get(&message_id1).unwrap();
-        assert_eq!(builder.data.len(), 1);
+        let coordinator_pk = PublicEncryptKey::zeroed();
+        assert_eq!(res1, Message::new_sum(pk1, coordinator_pk, sum1));
+        assert_eq!(res2, Message::new_sum(pk2, coordinator_pk, sum2));
+    }
 
-        assert!(task.call(make_message2(&c3)).await.unwrap().is_none());
-        assert_eq!(task.get_ref().message_builders.len(), 2);
-        let builder = task.get_ref().message_builders.get(&message_id2).unwrap();
-        assert_eq!(builder.data.len(), 1);
+    #[tokio::test]
+    async fn message_handler_not_eligible() {
+        let (mut publisher, mut task) = spawn_svc(MultipartSettings::default());
+        assert_ready!(task.poll_ready()).unwrap();
 
-        assert!(task.call(make_message1(&c5)).await.unwrap().is_none());
-        assert!(task.call(make_message2(&c5)).await.unwrap().is_none());
+        let (pk, _, chunks) = participant(&task);
+        let mut round_params = round_params(&task);
+        round_params.sum = 0.0;
+        publisher.broadcast_params(round_params);
 
-        assert!(task.call(make_message1(&c1)).await.unwrap().is_none());
-        assert!(task.call(make_message2(&c1)).await.unwrap().is_none());
+        match task
+            .call(chunk_message(pk, 1, &chunks[0]))
+            .await
+            .unwrap_err()
+        {
+            ServiceError::NotSumEligible => {}
+            e => panic!("expected ServiceError::NotSumEligible got {:?}", e),
+        }
+        assert_eq!(nb_messages(&task), 0);
+        assert_eq!(buffer_size(&task), 0);
+    }
 
-        assert!(task.call(make_message1(&c4)).await.unwrap().is_none());
-        assert!(task.call(make_message2(&c4)).await.unwrap().is_none());
+    #[tokio::test]
+    async fn message_handler_chunks_out_of_order() {
+        let (_publisher, mut task) = spawn_svc(MultipartSettings::default());
+        assert_ready!(task.poll_ready()).unwrap();
 
-        let builder = task.get_ref().message_builders.get(&message_id1).unwrap();
-        assert_eq!(builder.data.len(), 4);
+        let (pk, _, chunks) = participant(&task);
 
-        let builder = task.get_ref().message_builders.get(&message_id2).unwrap();
-        assert_eq!(builder.data.len(), 4);
+        // the first chunk of a message must be sent first
+        assert!(matches!(
+            task.call(chunk_message(pk, 1, &chunks[1])).await,
+            Err(ServiceError::Parsing(_))
+        ));
+        assert_eq!(nb_messages(&task), 0);
 
-        let res1 = task.call(make_message1(&c2)).await.unwrap().unwrap();
-        let res2 = task.call(make_message2(&c2)).await.unwrap().unwrap();
+        // until the task signatures are complete, the chunks must be
+        // sent in order
+        let split = Chunk {
+            data: chunks[0].data[..32].to_vec(),
+            ..chunks[0].clone()
+        };
+        assert!(task
+            .call(chunk_message(pk, 1, &split))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            task.call(chunk_message(pk, 1, &chunks[2])).await,
+            Err(ServiceError::Parsing(_))
+        ));
+        assert_eq!(nb_messages(&task), 0);
+        assert_eq!(buffer_size(&task), 0);
+    }
+
+    #[tokio::test]
+    async fn message_handler_max_pending_messages() {
+        let (_publisher, mut task) = spawn_svc(MultipartSettings {
+            max_pending_messages: 2,
+            ..MultipartSettings::default()
+        });
+        assert_ready!(task.poll_ready()).unwrap();
 
-        assert!(task.get_ref().message_builders.get(&message_id1).is_none());
-        assert!(task.get_ref().message_builders.get(&message_id2).is_none());
+        let (pk, _, chunks) = participant(&task);
+        let (other_pk, _, other_chunks) = participant(&task);
+
+        assert!(task
+            .call(chunk_message(pk, 1, &chunks[0]))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(task
+            .call(chunk_message(pk, 2, &chunks[0]))
+            .await
+            .unwrap()
+            .is_none());
+        // a third message from the same participant is rejected
+        match task
+            .call(chunk_message(pk, 3, &chunks[0]))
+            .await
+            .unwrap_err()
+        {
+            ServiceError::TooManyPendingMessages => {}
+            e => panic!("expected ServiceError::TooManyPendingMessages got {:?}", e),
+        }
+        // but other participants are not affected
+        assert!(task
+            .call(chunk_message(other_pk, 3, &other_chunks[0]))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(nb_messages(&task), 3);
+
+        // chunks of the pending messages are still accepted
+        for chunk in &chunks[1..4] {
+            assert!(task
+                .call(chunk_message(pk, 1, chunk))
+                .await
+                .unwrap()
+                .is_none());
+        }
+        assert!(task
+            .call(chunk_message(pk, 1, &chunks[4]))
+            .await
+            .unwrap()
+            .is_some());
+
+        // once a message is complete, a new one can be started
+        assert!(task
+            .call(chunk_message(pk, 3, &chunks[0]))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn message_handler_max_message_size() {
+        let (_publisher, mut task) = spawn_svc(MultipartSettings {
+            max_message_size: 88,
+            ..MultipartSettings::default()
+        });
+        assert_ready!(task.poll_ready()).unwrap();
+
+        let (pk, _, chunks) = participant(&task);
+        let id = MessageId {
+            message_id: 1,
+            participant_pk: pk,
+        };
+
+        // 64 + 8 + 8 + 8 = 88 bytes
+        for chunk in &chunks[..4] {
+            assert!(task
+                .call(chunk_message(pk, 1, chunk))
+                .await
+                .unwrap()
+                .is_none());
+        }
+        // sending a chunk twice doesn't count twice
+        assert!(task
+            .call(chunk_message(pk, 1, &chunks[3]))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(nb_chunks(&task, &id), Some(4));
+        assert_eq!(buffer_size(&task), 88);
+
+        match task
+            .call(chunk_message(pk, 1, &chunks[4]))
+            .await
+            .unwrap_err()
+        {
+            ServiceError::MessageTooLarge => {}
+            e => panic!("expected ServiceError::MessageTooLarge got {:?}", e),
+        }
+        assert!(nb_chunks(&task, &id).is_none());
+        assert_eq!(buffer_size(&task), 0);
+    }
+
+    #[tokio::test]
+    async fn message_handler_max_buffer_size() {
+        let (_publisher, mut task) = spawn_svc(MultipartSettings {
+            max_buffer_size: 100,
+            ..MultipartSettings::default()
+        });
+        assert_ready!(task.poll_ready()).unwrap();
+
+        let (pk1, _, chunks1) = participant(&task);
+        let (pk2, _, chunks2) = participant(&task);
+
+        assert!(task
+            .call(chunk_message(pk1, 1, &chunks1[0]))
+            .await
+            .unwrap()
+            .is_none());
+        // the buffer is shared by all participants
+        match task
+            .call(chunk_message(pk2, 1, &chunks2[0]))
+            .await
+            .unwrap_err()
+        {
+            ServiceError::MultipartBufferFull => {}
+            e => panic!("expected ServiceError::MultipartBufferFull got {:?}", e),
+        }
+        assert_eq!(nb_messages(&task), 1);
+        assert_eq!(buffer_size(&task), 64);
+    }
+
+    #[tokio::test]
+    async fn message_handler_expiry() {
+        let (_publisher, mut task) = spawn_svc(MultipartSettings {
+            expiry: 0,
+            ..MultipartSettings::default()
+        });
+        assert_ready!(task.poll_ready()).unwrap();
+
+        let (pk1, _, chunks1) = participant(&task);
+        let (pk2, _, chunks2) = participant(&task);
+        let id1 = MessageId {
+            message_id: 1,
+            participant_pk: pk1,
+        };
+        let id2 = MessageId {
+            message_id: 1,
+            participant_pk: pk2,
+        };
 
-        assert_eq!(res1, Message::new_sum(pk1, coordinator_pk, sum.clone()));
-        assert_eq!(res2, Message::new_sum(pk2, coordinator_pk, sum.clone()));
+        assert!(task
+            .call(chunk_message(pk1, 1, &chunks1[0]))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(nb_chunks(&task, &id1), Some(1));
+        tokio::time::delay_for(Duration::from_millis(10)).await;
+
+        // the partial message of the first participant expired and
+        // is discarded when the next chunk is handled
+        assert!(task
+            .call(chunk_message(pk2, 1, &chunks2[0]))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(nb_chunks(&task, &id1).is_none());
+        assert_eq!(nb_chunks(&task, &id2), Some(1));
+        assert_eq!(buffer_size(&task), 64);
+
+        let message_builders = task.get_ref().message_builders.lock().unwrap();
+        assert_eq!(message_builders.by_last_chunk.len(), 1);
+        assert_eq!(message_builders.pending_messages(&pk1), 0);
+        assert_eq!(message_builders.pending_messages(&pk2), 1);
     }
 }
@@ -37,8 +37,20 @@ impl Service<Message> for StateMachine {
     type Error = ServiceError;
     type Future = BoxedServiceFuture<Self::Response, Self::Error>;
 
+    /// Reports the saturation of the request queue of the state machine.
+    ///
+    /// Requests are rejected instead of waiting for the queue to drain, so that the load is shed
+    /// before the messages are buffered by the coordinator.
     fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
+        if self.handle.is_full() {
+            Poll::Ready(Err(ServiceError::StateMachine(
+                StateMachineError::RequestQueueFull {
+                    retry_after: self.handle.retry_after(),
+                },
+            )))
+        } else {
+            Poll::Ready(Ok(()))
+        }
     }
 
     fn call(&mut self, req: Message) -> Self::Future {
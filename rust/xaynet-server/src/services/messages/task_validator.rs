@@ -4,8 +4,9 @@ use futures::{future, task::Context};
 use tower::Service;
 use xaynet_core::{
     common::RoundParameters,
-    crypto::ByteObject,
-    message::{Message, Payload},
+    crypto::{ByteObject, PublicSigningKey},
+    message::{Message, Payload, Tag},
+    ParticipantTaskSignature,
 };
 
 use crate::{
@@ -38,52 +39,62 @@ impl Service<Message> for TaskValidator {
     }
 
     fn call(&mut self, message: Message) -> Self::Future {
-        let (sum_signature, update_signature) = match message.payload {
-            Payload::Sum(ref sum) => (sum.sum_signature, None),
-            Payload::Update(ref update) => (update.sum_signature, Some(update.update_signature)),
-            Payload::Sum2(ref sum2) => (sum2.sum_signature, None),
+        let (tag, sum_signature, update_signature) = match message.payload {
+            Payload::Sum(ref sum) => (Tag::Sum, sum.sum_signature, None),
+            Payload::Update(ref update) => (
+                Tag::Update,
+                update.sum_signature,
+                Some(update.update_signature),
+            ),
+            Payload::Sum2(ref sum2) => (Tag::Sum2, sum2.sum_signature, None),
             _ => return future::ready(Err(ServiceError::UnexpectedMessage)),
         };
         let params = self.params_listener.get_latest().event;
-        let seed = params.seed.as_slice();
-
-        // Check whether the participant is eligible for the sum task
-        let has_valid_sum_signature = message
-            .participant_pk
-            .verify_detached(&sum_signature, &[seed, b"sum"].concat());
-        let is_summer = has_valid_sum_signature && sum_signature.is_eligible(params.sum);
-
-        // Check whether the participant is eligible for the update task
-        let has_valid_update_signature = update_signature
-            .map(|sig| {
-                message
-                    .participant_pk
-                    .verify_detached(&sig, &[seed, b"update"].concat())
-            })
+        future::ready(
+            check_eligibility(
+                &params,
+                &message.participant_pk,
+                tag,
+                &sum_signature,
+                update_signature.as_ref(),
+            )
+            .map(|_| message),
+        )
+    }
+}
+
+/// Checks whether a participant is eligible for the task of a message
+/// with the given tag, based on the task signatures of the
+/// participant for the current round.
+pub(super) fn check_eligibility(
+    params: &RoundParameters,
+    participant_pk: &PublicSigningKey,
+    tag: Tag,
+    sum_signature: &ParticipantTaskSignature,
+    update_signature: Option<&ParticipantTaskSignature>,
+) -> Result<(), ServiceError> {
+    let seed = params.seed.as_slice();
+
+    // Check whether the participant is eligible for the sum task
+    let has_valid_sum_signature =
+        participant_pk.verify_detached(sum_signature, &[seed, b"sum"].concat());
+    let is_summer = has_valid_sum_signature && sum_signature.is_eligible(params.sum);
+
+    // Check whether the participant is eligible for the update task
+    let has_valid_update_signature = update_signature
+        .map(|sig| participant_pk.verify_detached(sig, &[seed, b"update"].concat()))
+        .unwrap_or(false);
+    let is_updater = !is_summer
+        && has_valid_update_signature
+        && update_signature
+            .map(|sig| sig.is_eligible(params.update))
             .unwrap_or(false);
-        let is_updater = !is_summer
-            && has_valid_update_signature
-            && update_signature
-                .map(|sig| sig.is_eligible(params.update))
-                .unwrap_or(false);
-
-        match message.payload {
-            Payload::Sum(_) | Payload::Sum2(_) => {
-                if is_summer {
-                    future::ready(Ok(message))
-                } else {
-                    future::ready(Err(ServiceError::NotSumEligible))
-                }
-            }
-            Payload::Update(_) => {
-                if is_updater {
-                    future::ready(Ok(message))
-                } else {
-                    future::ready(Err(ServiceError::NotUpdateEligible))
-                }
-            }
-            _ => future::ready(Err(ServiceError::UnexpectedMessage)),
-        }
+
+    match tag {
+        Tag::Sum | Tag::Sum2 if is_summer => Ok(()),
+        Tag::Sum | Tag::Sum2 => Err(ServiceError::NotSumEligible),
+        Tag::Update if is_updater => Ok(()),
+        Tag::Update => Err(ServiceError::NotUpdateEligible),
     }
 }
 
@@ -11,4 +11,4 @@ pub mod fetchers;
 pub mod messages;
 
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
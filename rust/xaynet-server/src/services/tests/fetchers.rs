@@ -89,6 +89,8 @@ async fn test_round_params_svc() {
         sum: 0.42,
         update: 0.42,
         seed: RoundSeed::fill_with(0x11),
+        start_time: 0,
+        ..Default::default()
     };
     publisher.broadcast_params(params.clone());
     assert_ready!(task.poll_ready()).unwrap();
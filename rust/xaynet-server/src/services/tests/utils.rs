@@ -19,6 +19,8 @@ pub fn new_event_channels() -> (EventPublisher, EventSubscriber) {
         sum: 0.0,
         update: 0.0,
         seed: RoundSeed::generate(),
+        start_time: 0,
+        ..Default::default()
     };
     let phase = PhaseName::Idle;
     let round_id = 0;
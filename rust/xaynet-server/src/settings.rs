@@ -12,7 +12,18 @@ use thiserror::Error;
 use tracing_subscriber::filter::EnvFilter;
 use validator::{Validate, ValidationError, ValidationErrors};
 
-use xaynet_core::mask::{BoundType, DataType, GroupType, MaskConfig, ModelType};
+use xaynet_core::mask::{
+    BoundType,
+    DataType,
+    GroupType,
+    MaskConfig,
+    MaskConfigPair,
+    ModelType,
+    NoiseParameters,
+};
+
+/// The placeholder for secrets in the debug output of the settings.
+const REDACTED: &str = "<redacted>";
 
 #[derive(Error, Debug)]
 /// An error related to loading and validation of settings.
@@ -24,6 +35,7 @@ pub enum SettingsError {
 }
 
 #[derive(Debug, Validate, Deserialize)]
+#[validate(schema(function = "validate_settings"))]
 /// The combined settings.
 ///
 /// Each section in the configuration file corresponds to the identically named settings field.
@@ -32,12 +44,23 @@ pub struct Settings {
     pub api: ApiSettings,
     #[validate]
     pub pet: PetSettings,
+    #[validate]
+    pub multipart: MultipartSettings,
+    #[validate]
+    pub requests: RequestSettings,
+    #[validate]
+    pub processing: ProcessingSettings,
     pub mask: MaskSettings,
     pub log: LoggingSettings,
+    #[validate]
     pub model: ModelSettings,
     #[validate]
     pub metrics: MetricsSettings,
-    pub redis: RedisSettings,
+    /// Settings for Redis. The coordinator state is only kept in memory and is lost on a restart
+    /// if the section is missing.
+    pub redis: Option<RedisSettings>,
+    #[validate]
+    pub allowlist: Option<AllowlistSettings>,
 }
 
 impl Settings {
@@ -51,7 +74,11 @@ impl Settings {
         Ok(settings)
     }
 
-    fn load(path: PathBuf) -> Result<Self, ConfigError> {
+    /// Loads the settings via a configuration file without validating them.
+    ///
+    /// # Errors
+    /// Fails when the loading of the configuration file failed.
+    pub fn load(path: PathBuf) -> Result<Self, ConfigError> {
         let mut config = Config::new();
         config.merge(config::File::from(path))?;
         config.merge(Environment::with_prefix("xaynet").separator("__"))?;
@@ -59,7 +86,33 @@ impl Settings {
     }
 }
 
-#[derive(Debug, Validate, Deserialize, Clone, Copy)]
+/// Checks the settings which depend on each other across sections.
+fn validate_settings(s: &Settings) -> Result<(), ValidationError> {
+    s.pet
+        .validate_dp_bounds(&MaskConfigPair::from(s.mask).model)?;
+    validate_redis_stores(s)
+}
+
+/// Checks that Redis is configured if the model history or the allowlist are stored in Redis.
+fn validate_redis_stores(s: &Settings) -> Result<(), ValidationError> {
+    let history_in_redis = matches!(
+        &s.model.history,
+        Some(history) if history.store == ModelHistoryStore::Redis
+    );
+    let allowlist_in_redis = matches!(
+        &s.allowlist,
+        Some(allowlist) if allowlist.store == AllowlistStore::Redis
+    );
+    if s.redis.is_none() && (history_in_redis || allowlist_in_redis) {
+        Err(ValidationError::new(
+            "missing redis settings for a redis store",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Validate, Deserialize, Clone, Copy, PartialEq)]
 #[validate(schema(function = "validate_pet"))]
 /// PET protocol settings.
 pub struct PetSettings {
@@ -195,6 +248,66 @@ pub struct PetSettings {
     /// ```
     pub max_update_time: u64,
 
+    /// The minimum amount of time reserved for the `idle` phase, in seconds.
+    ///
+    /// Defaults to 0 i.e. a new round starts *as soon as* the previous round ended. Set this
+    /// higher to give the participants time to learn about the new round before it starts.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [pet]
+    /// min_idle_time = 60
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_PET__MIN_IDLE_TIME=60
+    /// ```
+    pub min_idle_time: u64,
+
+    /// The period of the round schedule, in seconds.
+    ///
+    /// Defaults to 0 i.e. rounds are not scheduled. Otherwise, rounds only start at wall-clock
+    /// times which are multiples of the period since the Unix epoch, e.g. `3600` starts the rounds
+    /// at full hours. A round starts at the first such time after the
+    /// [`PetSettings::min_idle_time`] has passed.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [pet]
+    /// round_period = 3600
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_PET__ROUND_PERIOD=3600
+    /// ```
+    pub round_period: u64,
+
+    #[validate(range(min = 1))]
+    /// The maximum number of completed rounds after which the coordinator shuts down. Failed
+    /// rounds don't count towards the maximum.
+    ///
+    /// Defaults to no maximum i.e. the coordinator runs rounds until it is stopped.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [pet]
+    /// max_rounds = 100
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_PET__MAX_ROUNDS=100
+    /// ```
+    pub max_rounds: Option<u64>,
+
     /// The expected fraction of participants selected for computing the unmasking sum. The value
     /// must be between `0` and `1` (i.e. `0 < sum < 1`).
     ///
@@ -234,6 +347,80 @@ pub struct PetSettings {
     /// XAYNET_PET__UPDATE=0.01
     /// ```
     pub update: f64,
+
+    /// Whether the fractions [`PetSettings::sum`] and [`PetSettings::update`] are adapted between
+    /// rounds.
+    ///
+    /// If enabled, the fractions are adapted at the start of each round from the numbers of sum
+    /// and update messages of the previous round, such that the expected numbers of messages
+    /// reach [`PetSettings::min_sum_count`] and [`PetSettings::min_update_count`] plus the
+    /// [`PetSettings::fraction_headroom`]. The configured fractions are used for the first round.
+    /// The adapted fractions stay within a factor of 4 of the configured fractions and are kept
+    /// after a round without any messages.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [pet]
+    /// adapt_fractions = true
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_PET__ADAPT_FRACTIONS=true
+    /// ```
+    pub adapt_fractions: bool,
+
+    #[validate(range(min = 0.0))]
+    /// The headroom on top of the minimum numbers of messages targeted by adapted fractions. The
+    /// value must be greater or equal to `0` (i.e. `fraction_headroom >= 0`).
+    ///
+    /// For example, `0.5` targets 50% more messages than [`PetSettings::min_sum_count`] and
+    /// [`PetSettings::min_update_count`]. This is only used if
+    /// [`PetSettings::adapt_fractions`] is enabled.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [pet]
+    /// fraction_headroom = 0.5
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_PET__FRACTION_HEADROOM=0.5
+    /// ```
+    pub fraction_headroom: f64,
+
+    /// The fraction of sum2 participants which must agree on the masks for them to be considered
+    /// for unmasking. The value must be between `0` and `1` (i.e. `0 <= mask_quorum < 1`).
+    ///
+    /// The model mask and the scalar mask of a sum participant are considered as a pair. The
+    /// masks are only considered if the pair was submitted by strictly more than this fraction of
+    /// the sum2 participants, hence `0.5` demands a strict majority and `0` considers all
+    /// submitted masks. The considered pairs are tried in order of their number of submissions and
+    /// the first one which unmasks the aggregated model and scalar to valid values is used.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [pet]
+    /// mask_quorum = 0.5
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_PET__MASK_QUORUM=0.5
+    /// ```
+    pub mask_quorum: f64,
+
+    #[validate]
+    /// Settings for the distributed differential privacy of the global models. It is disabled if
+    /// the section is missing.
+    pub dp: Option<DpSettings>,
 }
 
 impl Default for PetSettings {
@@ -245,8 +432,42 @@ impl Default for PetSettings {
             min_update_time: 0_u64,
             max_sum_time: 604800_u64,
             max_update_time: 604800_u64,
+            min_idle_time: 0_u64,
+            round_period: 0_u64,
+            max_rounds: None,
             sum: 0.01_f64,
             update: 0.1_f64,
+            adapt_fractions: false,
+            fraction_headroom: 0.5_f64,
+            mask_quorum: 0_f64,
+            dp: None,
+        }
+    }
+}
+
+impl PetSettings {
+    /// Checks that the noise of the differential privacy fits into the bounds of the model masking
+    /// configuration for at least [`min_update_count`] models.
+    ///
+    /// Otherwise the aggregated noise could wrap around the finite group of the masking
+    /// configuration, which would corrupt the global model without being detected.
+    ///
+    /// [`min_update_count`]: #structfield.min_update_count
+    pub fn validate_dp_bounds(&self, config: &MaskConfig) -> Result<(), ValidationError> {
+        let dp = match self.dp {
+            Some(dp) => dp,
+            None => return Ok(()),
+        };
+        let noise =
+            NoiseParameters::calibrate(dp.clip_norm, dp.epsilon, dp.delta, self.min_update_count);
+        if self.min_update_count <= config.model_type.max_nb_models()
+            && noise.fits_bounds(config, self.min_update_count)
+        {
+            Ok(())
+        } else {
+            Err(ValidationError::new(
+                "differential privacy noise exceeds the model mask bounds",
+            ))
         }
     }
 }
@@ -254,7 +475,8 @@ impl Default for PetSettings {
 /// Checks PET settings.
 fn validate_pet(s: &PetSettings) -> Result<(), ValidationError> {
     validate_phase_times(s)?;
-    validate_fractions(s)
+    validate_fractions(s)?;
+    validate_mask_quorum(s)
 }
 
 /// Checks validity of phase time ranges.
@@ -268,227 +490,1078 @@ fn validate_phase_times(s: &PetSettings) -> Result<(), ValidationError> {
 
 /// Checks pathological cases of deadlocks.
 fn validate_fractions(s: &PetSettings) -> Result<(), ValidationError> {
-    if 0. < s.sum
-        && s.sum < 1.
-        && 0. < s.update
-        && s.update < 1.
-        && 0. < s.sum + s.update - s.sum * s.update
-        && s.sum + s.update - s.sum * s.update < 1.
-    {
+    if are_fractions_valid(s.sum, s.update) {
         Ok(())
     } else {
         Err(ValidationError::new("starvation"))
     }
 }
 
-#[derive(Debug, Validate, Deserialize, Clone, Copy)]
-/// REST API settings.
-pub struct ApiSettings {
-    /// The address to which the REST API should be bound.
+/// Checks whether the fractions of sum and update participants avoid pathological cases of
+/// deadlocks.
+pub(crate) fn are_fractions_valid(sum: f64, update: f64) -> bool {
+    0. < sum
+        && sum < 1.
+        && 0. < update
+        && update < 1.
+        && 0. < sum + update - sum * update
+        && sum + update - sum * update < 1.
+}
+
+/// Checks that masks can reach the quorum.
+fn validate_mask_quorum(s: &PetSettings) -> Result<(), ValidationError> {
+    if 0. <= s.mask_quorum && s.mask_quorum < 1. {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid mask quorum"))
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[validate(schema(function = "validate_dp"))]
+/// Distributed differential privacy settings.
+///
+/// Each update participant clips its scaled local model and adds a share of Gaussian noise to its
+/// masked model. The shares are calibrated such that the aggregate of at least
+/// [`PetSettings::min_update_count`] models is `(epsilon, delta)`-differentially private.
+///
+/// The aggregated noise must fit into the bounds of the model masking configuration, hence the
+/// settings are rejected unless the bound of the [`MaskSettings::bound_type`] exceeds the
+/// `clip_norm` by several standard deviations of the aggregated noise per model. For example,
+/// `clip_norm = 1.0`, `epsilon = 0.5` and `delta = 1e-5` require at least `bound_type = "B2"`.
+pub struct DpSettings {
+    /// The bound of the L2 norm to which the scaled local models are clipped. The value must be
+    /// positive (i.e. `clip_norm > 0`).
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [api]
-    /// bind_address = "0.0.0.0:8081"
-    /// # or
-    /// bind_address = "127.0.0.1:8081"
+    /// [pet.dp]
+    /// clip_norm = 1.0
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_API__BIND_ADDRESS=127.0.0.1:8081
+    /// XAYNET_PET__DP__CLIP_NORM=1.0
     /// ```
-    pub bind_address: std::net::SocketAddr,
-}
+    pub clip_norm: f64,
 
-#[derive(Debug, Validate, Deserialize, Clone, Copy)]
-/// Masking settings.
-pub struct MaskSettings {
-    /// The order of the finite group.
+    /// The privacy budget spent per round. The value must be between `0` and `1` (i.e.
+    /// `0 < epsilon < 1`).
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [mask]
-    /// group_type = "Integer"
+    /// [pet.dp]
+    /// epsilon = 0.5
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_MASK__GROUP_TYPE=Integer
+    /// XAYNET_PET__DP__EPSILON=0.5
     /// ```
-    pub group_type: GroupType,
+    pub epsilon: f64,
 
-    /// The data type of the numbers to be masked.
+    /// The probability per round with which the privacy guarantee may fail. The value must be
+    /// between `0` and `1` (i.e. `0 < delta < 1`).
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [mask]
-    /// data_type = "F32"
+    /// [pet.dp]
+    /// delta = 0.00001
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_MASK__DATA_TYPE=F32
+    /// XAYNET_PET__DP__DELTA=0.00001
     /// ```
-    pub data_type: DataType,
+    pub delta: f64,
 
-    /// The bounds of the numbers to be masked.
+    /// The total privacy budget after which the coordinator shuts down. The budget spent by the
+    /// rounds adds up.
+    ///
+    /// Defaults to no maximum i.e. the coordinator runs rounds until it is stopped.
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [mask]
-    /// bound_type = "B0"
+    /// [pet.dp]
+    /// max_epsilon = 10.0
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_MASK__BOUND_TYPE=B0
+    /// XAYNET_PET__DP__MAX_EPSILON=10.0
     /// ```
-    pub bound_type: BoundType,
+    pub max_epsilon: Option<f64>,
+}
 
-    /// The maximum number of models to be aggregated.
+/// Checks the ranges of the differential privacy parameters.
+fn validate_dp(s: &DpSettings) -> Result<(), ValidationError> {
+    if 0. < s.clip_norm
+        && s.clip_norm.is_finite()
+        && 0. < s.epsilon
+        && s.epsilon < 1.
+        && 0. < s.delta
+        && s.delta < 1.
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new(
+            "invalid differential privacy parameters",
+        ))
+    }
+}
+
+#[derive(Validate, Deserialize, Clone)]
+/// REST API settings.
+pub struct ApiSettings {
+    /// The address to which the REST API should be bound.
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [mask]
-    /// model_type = "M3"
+    /// [api]
+    /// bind_address = "0.0.0.0:8081"
+    /// # or
+    /// bind_address = "127.0.0.1:8081"
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_MASK__MODEL_TYPE=M3
+    /// XAYNET_API__BIND_ADDRESS=127.0.0.1:8081
     /// ```
-    pub model_type: ModelType,
-}
+    pub bind_address: std::net::SocketAddr,
 
-impl Default for MaskSettings {
-    fn default() -> Self {
-        Self {
-            group_type: GroupType::Prime,
-            data_type: DataType::F32,
-            bound_type: BoundType::B0,
-            model_type: ModelType::M3,
-        }
-    }
-}
+    #[validate(length(min = 16))]
+    /// The bearer token which authenticates requests to the admin API. The admin API is disabled
+    /// if no token is set.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [api]
+    /// admin_token = "change-me-to-a-long-random-token"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_API__ADMIN_TOKEN=change-me-to-a-long-random-token
+    /// ```
+    pub admin_token: Option<String>,
 
-impl From<MaskSettings> for MaskConfig {
-    fn from(
-        MaskSettings {
-            group_type,
-            data_type,
-            bound_type,
-            model_type,
-        }: MaskSettings,
-    ) -> MaskConfig {
-        MaskConfig {
-            group_type,
-            data_type,
-            bound_type,
-            model_type,
-        }
-    }
-}
+    /// The address to which the admin API should be bound. The admin API is served on this address
+    /// and not on the `bind_address`, hence it should only be reachable from a trusted network. It
+    /// is served over TLS if TLS is configured, just like the public API. The admin API is served
+    /// next to the public API on the `bind_address` if no address is set.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [api]
+    /// admin_bind_address = "127.0.0.1:8082"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_API__ADMIN_BIND_ADDRESS=127.0.0.1:8082
+    /// ```
+    pub admin_bind_address: Option<std::net::SocketAddr>,
 
-#[derive(Debug, Deserialize)]
-/// Model settings.
-pub struct ModelSettings {
-    /// The expected size of the model. The model size corresponds to the number of elements.
-    /// This value is used to validate the uniform length of the submitted models/masks.
+    #[validate(range(min = 1))]
+    /// The maximum size of a request body, in bytes. Larger requests are rejected with
+    /// `413 Payload Too Large`. Messages which exceed this size must be split into multipart
+    /// messages by the participants.
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [model]
-    /// size = 100
+    /// [api]
+    /// max_body_size = 104857600
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_MODEL__SIZE=100
+    /// XAYNET_API__MAX_BODY_SIZE=104857600
     /// ```
-    pub size: usize,
+    pub max_body_size: u64,
+
+    /// Settings for serving the REST API over TLS. The REST API is served over plain HTTP if the
+    /// section is missing.
+    pub tls: Option<TlsSettings>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
-/// Metrics settings.
-pub struct MetricsSettings {
-    #[validate]
-    /// Settings for the InfluxDB backend.
-    pub influxdb: InfluxSettings,
+impl fmt::Debug for ApiSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ApiSettings")
+            .field("bind_address", &self.bind_address)
+            .field("admin_token", &self.admin_token.as_ref().map(|_| REDACTED))
+            .field("admin_bind_address", &self.admin_bind_address)
+            .field("max_body_size", &self.max_body_size)
+            .field("tls", &self.tls)
+            .finish()
+    }
 }
 
-#[derive(Debug, Deserialize, Validate)]
-/// InfluxDB settings.
-pub struct InfluxSettings {
-    #[validate(url)]
-    /// The URL where InfluxDB is running.
+#[derive(Debug, Deserialize, Clone)]
+/// TLS settings of the REST API.
+///
+/// The files are checked for changes every minute. Changed certificates and keys are used for new
+/// connections without restarting the coordinator.
+pub struct TlsSettings {
+    /// The path of the PEM encoded certificate chain of the coordinator, starting with the
+    /// certificate of the coordinator itself.
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [metrics.influxdb]
-    /// url = "http://localhost:8086"
+    /// [api.tls]
+    /// certificate = "/etc/xaynet/tls/coordinator.pem"
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_METRICS__INFLUXDB__URL=http://localhost:8086
+    /// XAYNET_API__TLS__CERTIFICATE=/etc/xaynet/tls/coordinator.pem
     /// ```
-    pub url: String,
+    pub certificate: PathBuf,
 
-    /// The InfluxDB database name.
+    /// The path of the PEM encoded PKCS#8 or RSA private key of the coordinator.
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [metrics.influxdb]
-    /// db = "test"
+    /// [api.tls]
+    /// key = "/etc/xaynet/tls/coordinator.key"
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_METRICS__INFLUXDB__DB=test
+    /// XAYNET_API__TLS__KEY=/etc/xaynet/tls/coordinator.key
     /// ```
-    pub db: String,
-}
+    pub key: PathBuf,
 
-#[derive(Debug, Deserialize)]
-/// Redis settings.
-pub struct RedisSettings {
-    /// The URL where Redis is running.
-    ///
-    /// The format of the URL is `redis://[<username>][:<passwd>@]<hostname>[:port][/<db>]`.
+    /// The path of the PEM encoded certificates of the authorities which issue client
+    /// certificates. If it is set, clients must authenticate with a certificate issued by one of
+    /// these authorities. Otherwise, client certificates are not requested.
     ///
     /// # Examples
     ///
     /// **TOML**
     /// ```text
-    /// [redis]
-    /// url = "redis://127.0.0.1/"
+    /// [api.tls]
+    /// client_ca = "/etc/xaynet/tls/client_ca.pem"
     /// ```
     ///
     /// **Environment variable**
     /// ```text
-    /// XAYNET_REDIS__URL=redis://127.0.0.1/
+    /// XAYNET_API__TLS__CLIENT_CA=/etc/xaynet/tls/client_ca.pem
     /// ```
-    #[serde(deserialize_with = "deserialize_redis_url")]
+    pub client_ca: Option<PathBuf>,
+}
+
+#[derive(Debug, Validate, Deserialize, Clone, Copy)]
+/// Multipart message settings.
+///
+/// Large messages can be split by the participants into several chunks. The chunks are buffered
+/// by the coordinator until the message is complete, which is bounded by these settings.
+pub struct MultipartSettings {
+    #[validate(range(min = 1))]
+    /// The maximum number of incomplete multipart messages a single participant can have at any
+    /// time. Chunks of additional messages are rejected.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [multipart]
+    /// max_pending_messages = 1
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MULTIPART__MAX_PENDING_MESSAGES=1
+    /// ```
+    pub max_pending_messages: usize,
+
+    /// The maximum size of the payload of a multipart message, in bytes. Messages that exceed
+    /// this size are discarded.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [multipart]
+    /// max_message_size = 104857600
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MULTIPART__MAX_MESSAGE_SIZE=104857600
+    /// ```
+    pub max_message_size: usize,
+
+    /// The maximum size of all incomplete multipart messages together, in bytes. Chunks which
+    /// would exceed this size are rejected until other messages are complete or expired.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [multipart]
+    /// max_buffer_size = 1073741824
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MULTIPART__MAX_BUFFER_SIZE=1073741824
+    /// ```
+    pub max_buffer_size: usize,
+
+    #[validate(range(min = 1))]
+    /// The amount of time after which an incomplete multipart message is discarded if no new
+    /// chunk has been received for it, in seconds.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [multipart]
+    /// expiry = 300
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MULTIPART__EXPIRY=300
+    /// ```
+    pub expiry: u64,
+}
+
+impl Default for MultipartSettings {
+    fn default() -> Self {
+        Self {
+            max_pending_messages: 1_usize,
+            max_message_size: 104857600_usize,
+            max_buffer_size: 1073741824_usize,
+            expiry: 300_u64,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Deserialize, Clone, Copy)]
+/// Request queue settings.
+///
+/// The PET messages are queued until the state machine processes them. Messages which arrive
+/// while the queue is full are rejected with `503 Service Unavailable`.
+pub struct RequestSettings {
+    #[validate(range(min = 1))]
+    /// The maximum number of messages in the queue.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [requests]
+    /// queue_depth = 100
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_REQUESTS__QUEUE_DEPTH=100
+    /// ```
+    pub queue_depth: usize,
+
+    /// The amount of time after which a participant should resend a message that was rejected
+    /// because the queue was full, in seconds. It is sent in the `Retry-After` header.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [requests]
+    /// retry_after = 5
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_REQUESTS__RETRY_AFTER=5
+    /// ```
+    pub retry_after: u64,
+}
+
+impl Default for RequestSettings {
+    fn default() -> Self {
+        Self {
+            queue_depth: 100_usize,
+            retry_after: 5_u64,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Deserialize, Clone, Copy)]
+/// Message processing settings.
+///
+/// The PET messages are decrypted and parsed on a dedicated thread pool before they are handed to
+/// the state machine.
+pub struct ProcessingSettings {
+    /// The number of threads of the thread pool which decrypts and parses the messages. If set to
+    /// `0`, the number of threads is equal to the number of logical CPUs.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [processing]
+    /// threads = 4
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_PROCESSING__THREADS=4
+    /// ```
+    pub threads: usize,
+
+    #[validate(range(min = 1))]
+    /// The maximum number of messages which are processed concurrently. Further messages wait
+    /// until the processing of an earlier message has been completed, before their bodies are
+    /// received. Hence, this also bounds the memory of the buffered message bodies.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [processing]
+    /// max_in_flight = 64
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_PROCESSING__MAX_IN_FLIGHT=64
+    /// ```
+    pub max_in_flight: usize,
+}
+
+impl Default for ProcessingSettings {
+    fn default() -> Self {
+        Self {
+            threads: 0_usize,
+            max_in_flight: 64_usize,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Deserialize, Clone, Copy)]
+/// Masking settings.
+pub struct MaskSettings {
+    /// The order of the finite group.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [mask]
+    /// group_type = "Integer"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MASK__GROUP_TYPE=Integer
+    /// ```
+    pub group_type: GroupType,
+
+    /// The data type of the numbers to be masked.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [mask]
+    /// data_type = "F32"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MASK__DATA_TYPE=F32
+    /// ```
+    pub data_type: DataType,
+
+    /// The bounds of the numbers to be masked.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [mask]
+    /// bound_type = "B0"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MASK__BOUND_TYPE=B0
+    /// ```
+    pub bound_type: BoundType,
+
+    /// The maximum number of models to be aggregated.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [mask]
+    /// model_type = "M3"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MASK__MODEL_TYPE=M3
+    /// ```
+    pub model_type: ModelType,
+
+    #[validate]
+    /// The masking settings of the scalar.
+    pub scalar: ScalarMaskSettings,
+}
+
+impl Default for MaskSettings {
+    fn default() -> Self {
+        Self {
+            group_type: GroupType::Prime,
+            data_type: DataType::F32,
+            bound_type: BoundType::B0,
+            model_type: ModelType::M3,
+            scalar: ScalarMaskSettings::default(),
+        }
+    }
+}
+
+impl From<MaskSettings> for MaskConfigPair {
+    fn from(
+        MaskSettings {
+            group_type,
+            data_type,
+            bound_type,
+            model_type,
+            scalar,
+        }: MaskSettings,
+    ) -> MaskConfigPair {
+        MaskConfigPair {
+            model: MaskConfig {
+                group_type,
+                data_type,
+                bound_type,
+                model_type,
+            },
+            scalar: scalar.into(),
+        }
+    }
+}
+
+#[derive(Debug, Validate, Deserialize, Clone, Copy)]
+/// Masking settings of the scalar.
+///
+/// The scalar is masked independently of the model weights, hence it may use a smaller group and a
+/// tighter bound. The scalar is clamped to the non-negative numbers bounded by the bound type.
+pub struct ScalarMaskSettings {
+    /// The order of the finite group.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [mask.scalar]
+    /// group_type = "Integer"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MASK__SCALAR__GROUP_TYPE=Integer
+    /// ```
+    pub group_type: GroupType,
+
+    /// The data type of the scalar to be masked.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [mask.scalar]
+    /// data_type = "F32"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MASK__SCALAR__DATA_TYPE=F32
+    /// ```
+    pub data_type: DataType,
+
+    /// The bounds of the scalar to be masked.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [mask.scalar]
+    /// bound_type = "B0"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MASK__SCALAR__BOUND_TYPE=B0
+    /// ```
+    pub bound_type: BoundType,
+
+    /// The maximum number of scalars to be aggregated.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [mask.scalar]
+    /// model_type = "M3"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MASK__SCALAR__MODEL_TYPE=M3
+    /// ```
+    pub model_type: ModelType,
+}
+
+impl Default for ScalarMaskSettings {
+    fn default() -> Self {
+        Self {
+            group_type: GroupType::Prime,
+            data_type: DataType::F32,
+            bound_type: BoundType::B0,
+            model_type: ModelType::M3,
+        }
+    }
+}
+
+impl From<ScalarMaskSettings> for MaskConfig {
+    fn from(
+        ScalarMaskSettings {
+            group_type,
+            data_type,
+            bound_type,
+            model_type,
+        }: ScalarMaskSettings,
+    ) -> MaskConfig {
+        MaskConfig {
+            group_type,
+            data_type,
+            bound_type,
+            model_type,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+/// Model settings.
+pub struct ModelSettings {
+    /// The expected size of the model. The model size corresponds to the number of elements.
+    /// This value is used to validate the uniform length of the submitted models/masks.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model]
+    /// size = 100
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__SIZE=100
+    /// ```
+    pub size: usize,
+
+    /// The path of a file with the initial global model, which is served until the global model
+    /// of the first round has been aggregated. The file contains a JSON array of the model
+    /// weights, which must be numbers of the data type of the model masking configuration. The
+    /// number of weights must be equal to the model size.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model]
+    /// initial = "/etc/xaynet/initial_model.json"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__INITIAL=/etc/xaynet/initial_model.json
+    /// ```
+    pub initial: Option<PathBuf>,
+
+    #[validate]
+    /// Settings for the history of the global models. The history is disabled if the section is
+    /// missing.
+    pub history: Option<ModelHistorySettings>,
+
+    #[validate]
+    /// Settings for the server-side optimization of the global model. The averaged model is
+    /// published as the global model as-is if the section is missing.
+    pub optimizer: Option<OptimizerSettings>,
+}
+
+#[derive(Debug, Deserialize, Validate, Clone)]
+#[validate(schema(function = "validate_model_history"))]
+/// Model history settings.
+///
+/// The history keeps the global model of every round together with its metadata.
+pub struct ModelHistorySettings {
+    /// The store of the model history. The global models are either stored in Redis or as files
+    /// in a local directory.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model.history]
+    /// store = "Redis"
+    /// # or
+    /// store = "Directory"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__HISTORY__STORE=Redis
+    /// ```
+    pub store: ModelHistoryStore,
+
+    /// The directory in which the global models are stored. It is created if it doesn't exist
+    /// yet. Required if the store is `"Directory"`.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model.history]
+    /// directory = "/var/lib/xaynet/models"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__HISTORY__DIRECTORY=/var/lib/xaynet/models
+    /// ```
+    pub directory: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+/// The store of the model history.
+pub enum ModelHistoryStore {
+    /// Stores the global models in Redis.
+    Redis,
+    /// Stores the global models as files in a local directory.
+    Directory,
+}
+
+#[derive(Debug, Deserialize, Validate, Serialize, Clone, Copy, PartialEq)]
+#[validate(schema(function = "validate_optimizer"))]
+/// Server optimizer settings.
+///
+/// The server optimizer treats the difference between the averaged model of a round and the
+/// previous global model as a pseudo-gradient and applies it to the previous global model with
+/// the configured algorithm.
+pub struct OptimizerSettings {
+    /// The optimization algorithm.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model.optimizer]
+    /// algorithm = "Momentum"
+    /// # or
+    /// algorithm = "Adam"
+    /// # or
+    /// algorithm = "Yogi"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__OPTIMIZER__ALGORITHM=Adam
+    /// ```
+    pub algorithm: OptimizerAlgorithm,
+
+    #[validate(range(min = 0.0))]
+    /// The server learning rate. The value must be greater or equal to `0` (i.e.
+    /// `learning_rate >= 0`).
+    ///
+    /// With the `Momentum` algorithm, a learning rate of `1` and a `beta1` of `0` publish the
+    /// averaged model as-is.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model.optimizer]
+    /// learning_rate = 1.0
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__OPTIMIZER__LEARNING_RATE=1.0
+    /// ```
+    pub learning_rate: f64,
+
+    /// The decay rate of the momentum, i.e. of the first moment of the pseudo-gradients. The
+    /// value must be between `0` and `1` (i.e. `0 <= beta1 < 1`).
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model.optimizer]
+    /// beta1 = 0.9
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__OPTIMIZER__BETA1=0.9
+    /// ```
+    pub beta1: f64,
+
+    /// The decay rate of the second moment of the pseudo-gradients. The value must be between `0`
+    /// and `1` (i.e. `0 <= beta2 < 1`). This is only used by the `Adam` and `Yogi` algorithms.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model.optimizer]
+    /// beta2 = 0.99
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__OPTIMIZER__BETA2=0.99
+    /// ```
+    pub beta2: f64,
+
+    /// The degree of adaptivity, which also bounds the adaptive learning rates. The value must be
+    /// positive (i.e. `tau > 0`). This is only used by the `Adam` and `Yogi` algorithms.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [model.optimizer]
+    /// tau = 0.001
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_MODEL__OPTIMIZER__TAU=0.001
+    /// ```
+    pub tau: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+/// The algorithm of the server optimizer.
+pub enum OptimizerAlgorithm {
+    /// Server momentum (FedAvgM).
+    Momentum,
+    /// Adaptive moment estimation (FedAdam).
+    Adam,
+    /// Adam with additive updates of the second moment (FedYogi).
+    Yogi,
+}
+
+/// Checks the ranges of the decay rates and the adaptivity of the server optimizer.
+fn validate_optimizer(s: &OptimizerSettings) -> Result<(), ValidationError> {
+    if 0. <= s.beta1 && s.beta1 < 1. && 0. <= s.beta2 && s.beta2 < 1. && 0. < s.tau {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid optimizer parameters"))
+    }
+}
+
+/// Checks that a directory is set if the model history is stored in a directory.
+fn validate_model_history(s: &ModelHistorySettings) -> Result<(), ValidationError> {
+    match (s.store, &s.directory) {
+        (ModelHistoryStore::Directory, None) => {
+            Err(ValidationError::new("missing model history directory"))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, Clone)]
+#[validate(schema(function = "validate_allowlist"))]
+/// Participant allowlist settings.
+///
+/// If the allowlist is enabled, only the participants whose public keys are on the allowlist may
+/// take part in the PET protocol.
+pub struct AllowlistSettings {
+    /// The store of the allowlist. The public keys are either stored in Redis or in a local file.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [allowlist]
+    /// store = "Redis"
+    /// # or
+    /// store = "File"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_ALLOWLIST__STORE=Redis
+    /// ```
+    pub store: AllowlistStore,
+
+    /// The file in which the public keys are stored, one base64 encoded key per line. It is
+    /// created if it doesn't exist yet. Required if the store is `"File"`.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [allowlist]
+    /// file = "/var/lib/xaynet/allowlist"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_ALLOWLIST__FILE=/var/lib/xaynet/allowlist
+    /// ```
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+/// The store of the participant allowlist.
+pub enum AllowlistStore {
+    /// Stores the public keys in Redis.
+    Redis,
+    /// Stores the public keys in a local file.
+    File,
+}
+
+/// Checks that a file is set if the allowlist is stored in a file.
+fn validate_allowlist(s: &AllowlistSettings) -> Result<(), ValidationError> {
+    match (s.store, &s.file) {
+        (AllowlistStore::File, None) => Err(ValidationError::new("missing allowlist file")),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+/// Metrics settings.
+///
+/// Each backend is enabled by its own section. The metrics are recorded with all enabled
+/// backends, or dropped if none is enabled.
+pub struct MetricsSettings {
+    #[validate]
+    /// Settings for the InfluxDB backend.
+    pub influxdb: Option<InfluxSettings>,
+    /// Settings for the Prometheus backend.
+    pub prometheus: Option<PrometheusSettings>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+/// InfluxDB settings.
+pub struct InfluxSettings {
+    #[validate(url)]
+    /// The URL where InfluxDB is running.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [metrics.influxdb]
+    /// url = "http://localhost:8086"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_METRICS__INFLUXDB__URL=http://localhost:8086
+    /// ```
+    pub url: String,
+
+    /// The InfluxDB database name.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [metrics.influxdb]
+    /// db = "test"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_METRICS__INFLUXDB__DB=test
+    /// ```
+    pub db: String,
+}
+
+#[derive(Debug, Deserialize)]
+/// Prometheus settings.
+pub struct PrometheusSettings {
+    /// The address to which the Prometheus `/metrics` endpoint should be bound.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [metrics.prometheus]
+    /// bind_address = "0.0.0.0:9090"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_METRICS__PROMETHEUS__BIND_ADDRESS=0.0.0.0:9090
+    /// ```
+    pub bind_address: std::net::SocketAddr,
+}
+
+#[derive(Deserialize)]
+/// Redis settings.
+pub struct RedisSettings {
+    /// The URL where Redis is running.
+    ///
+    /// The format of the URL is `redis://[<username>][:<passwd>@]<hostname>[:port][/<db>]`.
+    ///
+    /// # Examples
+    ///
+    /// **TOML**
+    /// ```text
+    /// [redis]
+    /// url = "redis://127.0.0.1/"
+    /// ```
+    ///
+    /// **Environment variable**
+    /// ```text
+    /// XAYNET_REDIS__URL=redis://127.0.0.1/
+    /// ```
+    #[serde(deserialize_with = "deserialize_redis_url")]
     pub url: ConnectionInfo,
 }
 
+impl fmt::Debug for RedisSettings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut url = self.url.clone();
+        if url.passwd.is_some() {
+            url.passwd = Some(REDACTED.to_string());
+        }
+        f.debug_struct("RedisSettings").field("url", &url).finish()
+    }
+}
+
 fn deserialize_redis_url<'de, D>(deserializer: D) -> Result<ConnectionInfo, D::Error>
 where
     D: Deserializer<'de>,
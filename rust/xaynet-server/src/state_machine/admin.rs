@@ -0,0 +1,262 @@
+//! This module provides the [`StateMachine`]'s `AdminCommand`, `AdminSender` and `AdminReceiver`
+//! types.
+//!
+//! Admin commands allow an operator to control the execution of the [`StateMachine`]. Pausing and
+//! shutting down take effect once the current round has been completed, whereas aborting and
+//! restarting interrupt the current phase. New PET settings are applied when the next round
+//! starts.
+//!
+//! Only the sum, update and sum2 phases and the paused state can be interrupted. Aborting and
+//! restarting commands which arrive in any other phase are dropped when the phase ends, while the
+//! other commands are applied then.
+//!
+//! [`StateMachine`]: crate::state_machine::StateMachine
+use std::str::FromStr;
+
+use derive_more::From;
+use futures::future;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use validator::{Validate, ValidationErrors};
+
+use crate::settings::PetSettings;
+
+/// A command sent by an operator to control the [`StateMachine`].
+///
+/// [`StateMachine`]: crate::state_machine::StateMachine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdminCommand {
+    /// Pauses the [`StateMachine`] before the next round starts.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    Pause,
+    /// Resumes a paused [`StateMachine`].
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    Resume,
+    /// Aborts the current round. The [`StateMachine`] continues as after an error.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    Abort,
+    /// Completes the current round and shuts the [`StateMachine`] down afterwards.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    Shutdown,
+    /// Aborts the current round and immediately starts a new round with new keys, even if the
+    /// [`StateMachine`] is paused. The pause is lifted.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    Restart,
+    /// Replaces the PET settings of the [`StateMachine`] when the next round starts. The current
+    /// round continues with the previous settings.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    UpdatePetSettings(PetSettings),
+}
+
+/// Error that occurs when an [`AdminSender`] tries to send a command on a closed admin channel.
+#[derive(Debug, Error)]
+#[error("the AdminSender cannot be used because the state machine shut down")]
+pub struct AdminChannelClosed;
+
+/// Error that occurs when an [`AdminSender`] tries to update the PET settings.
+#[derive(Debug, Error)]
+pub enum UpdatePetSettingsError {
+    #[error("invalid PET settings: {0}")]
+    Invalid(#[from] ValidationErrors),
+    #[error(transparent)]
+    Closed(#[from] AdminChannelClosed),
+}
+
+/// Error that occurs when parsing an unknown admin command.
+#[derive(Debug, Error)]
+#[error("unknown admin command: {0}")]
+pub struct UnknownAdminCommand(String);
+
+impl FromStr for AdminCommand {
+    type Err = UnknownAdminCommand;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pause" => Ok(Self::Pause),
+            "resume" => Ok(Self::Resume),
+            "abort" => Ok(Self::Abort),
+            "shutdown" => Ok(Self::Shutdown),
+            "restart" => Ok(Self::Restart),
+            _ => Err(UnknownAdminCommand(s.to_string())),
+        }
+    }
+}
+
+/// A handle to send admin commands to the [`StateMachine`].
+///
+/// [`StateMachine`]: crate::state_machine::StateMachine
+#[derive(Clone, From, Debug)]
+pub struct AdminSender(mpsc::UnboundedSender<AdminCommand>);
+
+impl AdminSender {
+    /// Sends an admin command to the [`StateMachine`].
+    ///
+    /// # Errors
+    /// Fails if the [`StateMachine`] has already shut down.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    pub fn send(&self, command: AdminCommand) -> Result<(), AdminChannelClosed> {
+        self.0.send(command).map_err(|_| {
+            warn!(
+                "failed to send admin command to the state machine: state machine is shutting down"
+            );
+            AdminChannelClosed
+        })
+    }
+
+    /// Sends new PET settings to the [`StateMachine`], which applies them when the next round
+    /// starts.
+    ///
+    /// # Errors
+    /// Fails if the settings are invalid or if the [`StateMachine`] has already shut down.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    pub fn update_pet_settings(&self, settings: PetSettings) -> Result<(), UpdatePetSettingsError> {
+        settings.validate()?;
+        self.send(AdminCommand::UpdatePetSettings(settings))?;
+        Ok(())
+    }
+}
+
+/// The receiver half of the admin channel that is used by the [`StateMachine`] to receive admin
+/// commands.
+///
+/// It also keeps track of the pending pause and shutdown commands and of the pending PET settings,
+/// which only take effect at the end of the current round.
+///
+/// [`StateMachine`]: crate::state_machine::StateMachine
+#[derive(Debug)]
+pub struct AdminReceiver {
+    rx: mpsc::UnboundedReceiver<AdminCommand>,
+    paused: bool,
+    shutdown: bool,
+    pet_settings: Option<PetSettings>,
+}
+
+impl AdminReceiver {
+    /// Creates a new admin channel and returns the [`AdminReceiver`] as well as the
+    /// [`AdminSender`] half.
+    pub fn new() -> (Self, AdminSender) {
+        let (tx, rx) = mpsc::unbounded_channel::<AdminCommand>();
+        let receiver = Self {
+            rx,
+            paused: false,
+            shutdown: false,
+            pet_settings: None,
+        };
+        (receiver, AdminSender::from(tx))
+    }
+
+    /// Receives the next admin command.
+    ///
+    /// Never resolves once all [`AdminSender`]s have been dropped, because the [`StateMachine`]
+    /// keeps running without admin commands.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    pub async fn recv(&mut self) -> AdminCommand {
+        match self.rx.recv().await {
+            Some(command) => command,
+            None => future::pending().await,
+        }
+    }
+
+    /// Receives the next admin command if one is pending.
+    pub fn try_recv(&mut self) -> Option<AdminCommand> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Checks whether the [`StateMachine`] should pause before the next round.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Checks whether the [`StateMachine`] should shut down before the next round.
+    ///
+    /// [`StateMachine`]: crate::state_machine::StateMachine
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown
+    }
+
+    pub(in crate::state_machine) fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub(in crate::state_machine) fn set_shutdown(&mut self) {
+        self.shutdown = true;
+    }
+
+    pub(in crate::state_machine) fn set_pet_settings(&mut self, settings: PetSettings) {
+        self.pet_settings = Some(settings);
+    }
+
+    /// Takes the PET settings which are pending for the next round, if any.
+    pub(in crate::state_machine) fn take_pet_settings(&mut self) -> Option<PetSettings> {
+        self.pet_settings.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_admin_command() {
+        assert_eq!(
+            "pause".parse::<AdminCommand>().unwrap(),
+            AdminCommand::Pause
+        );
+        assert_eq!(
+            "resume".parse::<AdminCommand>().unwrap(),
+            AdminCommand::Resume
+        );
+        assert_eq!(
+            "abort".parse::<AdminCommand>().unwrap(),
+            AdminCommand::Abort
+        );
+        assert_eq!(
+            "shutdown".parse::<AdminCommand>().unwrap(),
+            AdminCommand::Shutdown
+        );
+        assert_eq!(
+            "restart".parse::<AdminCommand>().unwrap(),
+            AdminCommand::Restart
+        );
+        assert!("Pause".parse::<AdminCommand>().is_err());
+        assert!("stop".parse::<AdminCommand>().is_err());
+    }
+
+    #[tokio::test]
+    async fn update_pet_settings() {
+        let (mut receiver, sender) = AdminReceiver::new();
+
+        let mut settings = PetSettings {
+            min_update_count: 1,
+            ..PetSettings::default()
+        };
+        assert!(matches!(
+            sender.update_pet_settings(settings),
+            Err(UpdatePetSettingsError::Invalid(_))
+        ));
+
+        settings.min_update_count = 5;
+        sender.update_pet_settings(settings).unwrap();
+        assert_eq!(
+            receiver.recv().await,
+            AdminCommand::UpdatePetSettings(settings)
+        );
+
+        drop(receiver);
+        assert!(matches!(
+            sender.update_pet_settings(settings),
+            Err(UpdatePetSettingsError::Closed(_))
+        ));
+    }
+}
@@ -1,13 +1,19 @@
 //! Coordinator state and round parameter types.
 use std::collections::HashMap;
 
+use sodiumoxide::crypto::hash::sha256;
 use xaynet_core::{
     common::{RoundParameters, RoundSeed},
-    crypto::{ByteObject, EncryptKeyPair},
-    mask::{MaskConfig, MaskObject},
+    crypto::{ByteObject, EncryptKeyPair, Sha256},
+    mask::{MaskConfigPair, MaskObject},
+    message::ToBytes,
+    SumParticipantPublicKey,
 };
 
-use crate::settings::{MaskSettings, ModelSettings, PetSettings};
+use crate::{
+    settings::{DpSettings, MaskSettings, ModelSettings, PetSettings},
+    state_machine::phases::PhaseName,
+};
 
 /// The coordinator state.
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -30,10 +36,44 @@ pub struct CoordinatorState {
     pub max_sum_time: u64,
     /// The maximum time (in seconds) permitted for processing update messages.
     pub max_update_time: u64,
-    /// The masking configuration.
-    pub mask_config: MaskConfig,
+    /// The minimum time (in seconds) reserved for the idle phase.
+    pub min_idle_time: u64,
+    /// The period (in seconds) of the round schedule, or `0` if rounds are not scheduled.
+    pub round_period: u64,
+    /// The maximum number of completed rounds, or `None` if the number of rounds is unlimited.
+    pub max_rounds: Option<u64>,
+    /// The number of rounds which have been completed, i.e. which published a global model.
+    pub completed_rounds: u64,
+    /// Whether the sum and update fractions are adapted between rounds.
+    pub adapt_fractions: bool,
+    /// The headroom on top of the minimum message counts targeted by adapted fractions.
+    pub fraction_headroom: f64,
+    /// The sum fraction of the PET settings, which bounds the adapted sum fractions.
+    pub configured_sum: f64,
+    /// The update fraction of the PET settings, which bounds the adapted update fractions.
+    pub configured_update: f64,
+    /// The number of sum messages accepted in the sum phase of the previous round, or `None` if
+    /// the phase didn't run.
+    pub last_sum_count: Option<usize>,
+    /// The number of update messages accepted in the update phase of the previous round, or
+    /// `None` if the phase didn't run.
+    pub last_update_count: Option<usize>,
+    /// The fraction of sum2 participants which must agree on the masks for them to be
+    /// considered for unmasking.
+    pub mask_quorum: f64,
+    /// The distributed differential privacy settings, or `None` if it is disabled.
+    pub dp: Option<DpSettings>,
+    /// The privacy budget `epsilon` spent by the completed rounds with differential privacy.
+    pub epsilon_spent: f64,
+    /// The privacy budget `delta` spent by the completed rounds with differential privacy.
+    pub delta_spent: f64,
+    /// The masking configurations of the model weights and of the scalar.
+    pub mask_config: MaskConfigPair,
     /// The size of the model.
     pub model_size: usize,
+    /// The last phase of the PET protocol the coordinator entered. It is used to resume the
+    /// round after a restart.
+    pub phase: PhaseName,
 }
 
 impl CoordinatorState {
@@ -43,11 +83,15 @@ impl CoordinatorState {
         model_settings: ModelSettings,
     ) -> Self {
         let keys = EncryptKeyPair::generate();
+        let mask_config = mask_settings.into();
         let round_params = RoundParameters {
             pk: keys.public,
             sum: pet_settings.sum,
             update: pet_settings.update,
             seed: RoundSeed::zeroed(),
+            start_time: 0,
+            mask_config,
+            noise: None,
         };
         let round_id = 0;
         Self {
@@ -60,8 +104,64 @@ impl CoordinatorState {
             min_update_time: pet_settings.min_update_time,
             max_sum_time: pet_settings.max_sum_time,
             max_update_time: pet_settings.max_update_time,
-            mask_config: mask_settings.into(),
+            min_idle_time: pet_settings.min_idle_time,
+            round_period: pet_settings.round_period,
+            max_rounds: pet_settings.max_rounds,
+            completed_rounds: 0,
+            adapt_fractions: pet_settings.adapt_fractions,
+            fraction_headroom: pet_settings.fraction_headroom,
+            configured_sum: pet_settings.sum,
+            configured_update: pet_settings.update,
+            last_sum_count: None,
+            last_update_count: None,
+            mask_quorum: pet_settings.mask_quorum,
+            dp: pet_settings.dp,
+            epsilon_spent: 0.,
+            delta_spent: 0.,
+            mask_config,
             model_size: model_settings.size,
+            phase: PhaseName::Idle,
+        }
+    }
+
+    /// Replaces the PET settings of the coordinator.
+    ///
+    /// The sum and update fractions are reset to the configured fractions, which are then adapted
+    /// again in the following rounds if enabled.
+    pub fn apply_pet_settings(&mut self, pet_settings: PetSettings) {
+        self.round_params.sum = pet_settings.sum;
+        self.round_params.update = pet_settings.update;
+        self.min_sum_count = pet_settings.min_sum_count;
+        self.min_update_count = pet_settings.min_update_count;
+        self.min_sum_time = pet_settings.min_sum_time;
+        self.min_update_time = pet_settings.min_update_time;
+        self.max_sum_time = pet_settings.max_sum_time;
+        self.max_update_time = pet_settings.max_update_time;
+        self.min_idle_time = pet_settings.min_idle_time;
+        self.round_period = pet_settings.round_period;
+        self.max_rounds = pet_settings.max_rounds;
+        self.adapt_fractions = pet_settings.adapt_fractions;
+        self.fraction_headroom = pet_settings.fraction_headroom;
+        self.configured_sum = pet_settings.sum;
+        self.configured_update = pet_settings.update;
+        self.last_sum_count = None;
+        self.last_update_count = None;
+        self.mask_quorum = pet_settings.mask_quorum;
+        self.dp = pet_settings.dp;
+    }
+
+    /// Checks whether another round with differential privacy would exceed the total privacy
+    /// budget.
+    ///
+    /// The privacy budgets of the rounds add up by the basic composition theorem.
+    pub fn is_privacy_budget_exhausted(&self) -> bool {
+        match self.dp {
+            Some(DpSettings {
+                epsilon,
+                max_epsilon: Some(max_epsilon),
+                ..
+            }) => self.epsilon_spent + epsilon > max_epsilon,
+            _ => false,
         }
     }
 }
@@ -69,3 +169,40 @@ impl CoordinatorState {
 /// A dictionary created during the sum2 phase of the protocol. It counts the model masks
 /// represented by their hashes.
 pub type MaskDict = HashMap<MaskObject, usize>;
+
+/// A dictionary created during the sum2 phase of the protocol. It maps the sum participants to
+/// the digests of the masks they submitted.
+pub type MaskSubmissions = HashMap<SumParticipantPublicKey, Sha256>;
+
+/// Computes the digest of a model mask and a scalar mask submitted by a sum participant.
+pub fn mask_digest(model_mask: &MaskObject, scalar_mask: &MaskObject) -> Sha256 {
+    PartialMaskDigest::new(model_mask).finalize(scalar_mask)
+}
+
+/// The digest of a model mask which is completed by a scalar mask to a [`mask_digest()`].
+///
+/// A model mask can be paired with several scalar masks without hashing it again.
+#[derive(Clone, Copy)]
+pub struct PartialMaskDigest(sha256::State);
+
+impl PartialMaskDigest {
+    /// Starts the digest of the given model mask.
+    pub fn new(model_mask: &MaskObject) -> Self {
+        let mut state = sha256::State::new();
+        state.update(&mask_bytes(model_mask));
+        Self(state)
+    }
+
+    /// Completes the digest with the given scalar mask.
+    pub fn finalize(mut self, scalar_mask: &MaskObject) -> Sha256 {
+        self.0.update(&mask_bytes(scalar_mask));
+        Sha256::from(self.0.finalize())
+    }
+}
+
+/// Serializes a mask.
+fn mask_bytes(mask: &MaskObject) -> Vec<u8> {
+    let mut bytes = vec![0; mask.buffer_length()];
+    mask.to_bytes(&mut bytes);
+    bytes
+}
@@ -9,7 +9,7 @@ use std::{
     task::{Context, Poll},
 };
 
-use futures::Stream;
+use futures::{FutureExt, Stream, StreamExt};
 use tokio::sync::watch;
 use xaynet_core::{
     common::RoundParameters,
@@ -53,11 +53,42 @@ pub enum DictionaryUpdate<D> {
     New(Arc<D>),
 }
 
+/// Progress of a phase which accepts messages.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct MessageProgress {
+    /// The number of messages accepted during the phase.
+    pub count: usize,
+    /// The minimum number of messages the phase requires.
+    pub min_count: usize,
+    /// The maximum duration of the phase in seconds.
+    pub max_time: u64,
+}
+
+/// Phase progress event.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PhaseProgress {
+    /// The time at which the phase started, as a Unix timestamp in seconds.
+    pub start_time: u64,
+    /// The progress of the accepted messages, if the phase accepts messages.
+    pub messages: Option<MessageProgress>,
+}
+
+/// Round outcome event.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RoundOutcome {
+    /// The round completed with a new global model.
+    Completed,
+    /// The round failed for the given reason.
+    Failed(String),
+}
+
 /// A convenience type to emit any coordinator event.
 #[derive(Debug)]
 pub struct EventPublisher {
     /// Round ID that is attached to all the requests.
     round_id: u64,
+    /// Start time of the current phase that is attached to the phase progress events.
+    phase_start_time: u64,
     keys_tx: EventBroadcaster<EncryptKeyPair>,
     params_tx: EventBroadcaster<RoundParameters>,
     phase_tx: EventBroadcaster<PhaseName>,
@@ -65,11 +96,14 @@ pub struct EventPublisher {
     mask_length_tx: EventBroadcaster<MaskLengthUpdate>,
     sum_dict_tx: EventBroadcaster<DictionaryUpdate<SumDict>>,
     seed_dict_tx: EventBroadcaster<DictionaryUpdate<SeedDict>>,
+    progress_tx: EventBroadcaster<PhaseProgress>,
+    outcome_tx: EventBroadcaster<Option<RoundOutcome>>,
+    storage_failures_tx: EventBroadcaster<u32>,
 }
 
 /// The `EventSubscriber` hands out `EventListener`s for any
 /// coordinator event.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EventSubscriber {
     keys_rx: EventListener<EncryptKeyPair>,
     params_rx: EventListener<RoundParameters>,
@@ -78,6 +112,9 @@ pub struct EventSubscriber {
     mask_length_rx: EventListener<MaskLengthUpdate>,
     sum_dict_rx: EventListener<DictionaryUpdate<SumDict>>,
     seed_dict_rx: EventListener<DictionaryUpdate<SeedDict>>,
+    progress_rx: EventListener<PhaseProgress>,
+    outcome_rx: EventListener<Option<RoundOutcome>>,
+    storage_failures_rx: EventListener<u32>,
 }
 
 impl EventPublisher {
@@ -125,8 +162,25 @@ impl EventPublisher {
             event: params,
         });
 
+        let (progress_tx, progress_rx) = watch::channel::<Event<PhaseProgress>>(Event {
+            round_id,
+            event: PhaseProgress {
+                start_time: 0,
+                messages: None,
+            },
+        });
+
+        let (outcome_tx, outcome_rx) = watch::channel::<Event<Option<RoundOutcome>>>(Event {
+            round_id,
+            event: None,
+        });
+
+        let (storage_failures_tx, storage_failures_rx) =
+            watch::channel::<Event<u32>>(Event { round_id, event: 0 });
+
         let publisher = EventPublisher {
             round_id,
+            phase_start_time: 0,
             keys_tx: keys_tx.into(),
             params_tx: params_tx.into(),
             phase_tx: phase_tx.into(),
@@ -134,6 +188,9 @@ impl EventPublisher {
             mask_length_tx: mask_length_tx.into(),
             sum_dict_tx: sum_dict_tx.into(),
             seed_dict_tx: seed_dict_tx.into(),
+            progress_tx: progress_tx.into(),
+            outcome_tx: outcome_tx.into(),
+            storage_failures_tx: storage_failures_tx.into(),
         };
 
         let subscriber = EventSubscriber {
@@ -144,6 +201,9 @@ impl EventPublisher {
             mask_length_rx: mask_length_rx.into(),
             sum_dict_rx: sum_dict_rx.into(),
             seed_dict_rx: seed_dict_rx.into(),
+            progress_rx: progress_rx.into(),
+            outcome_rx: outcome_rx.into(),
+            storage_failures_rx: storage_failures_rx.into(),
         };
 
         (publisher, subscriber)
@@ -195,6 +255,35 @@ impl EventPublisher {
     pub fn broadcast_seed_dict(&mut self, update: DictionaryUpdate<SeedDict>) {
         let _ = self.seed_dict_tx.broadcast(self.event(update));
     }
+
+    /// Emit a phase progress event for a phase which started at the given Unix time
+    pub fn broadcast_phase_start(&mut self, start_time: u64, messages: Option<MessageProgress>) {
+        self.phase_start_time = start_time;
+        let progress = PhaseProgress {
+            start_time,
+            messages,
+        };
+        self.progress_tx.broadcast(self.event(progress));
+    }
+
+    /// Emit a phase progress event for the current phase
+    pub fn broadcast_message_progress(&mut self, messages: MessageProgress) {
+        let progress = PhaseProgress {
+            start_time: self.phase_start_time,
+            messages: Some(messages),
+        };
+        self.progress_tx.broadcast(self.event(progress));
+    }
+
+    /// Emit a round outcome event
+    pub fn broadcast_round_outcome(&mut self, outcome: RoundOutcome) {
+        self.outcome_tx.broadcast(self.event(Some(outcome)));
+    }
+
+    /// Emit the number of consecutive rounds which failed because of storage errors
+    pub fn broadcast_storage_failures(&mut self, failures: u32) {
+        self.storage_failures_tx.broadcast(self.event(failures));
+    }
 }
 
 impl EventSubscriber {
@@ -233,6 +322,23 @@ impl EventSubscriber {
     pub fn seed_dict_listener(&self) -> EventListener<DictionaryUpdate<SeedDict>> {
         self.seed_dict_rx.clone()
     }
+
+    /// Get a listener for phase progress events
+    pub fn progress_listener(&self) -> EventListener<PhaseProgress> {
+        self.progress_rx.clone()
+    }
+
+    /// Get a listener for round outcome events. The outcome is `None`
+    /// until the first round ended.
+    pub fn outcome_listener(&self) -> EventListener<Option<RoundOutcome>> {
+        self.outcome_rx.clone()
+    }
+
+    /// Get a listener for the number of consecutive rounds which
+    /// failed because of storage errors
+    pub fn storage_failures_listener(&self) -> EventListener<u32> {
+        self.storage_failures_rx.clone()
+    }
 }
 
 /// A listener for coordinator events. It can be used to either
@@ -255,6 +361,20 @@ where
     pub fn get_latest(&self) -> Event<E> {
         self.0.borrow().clone()
     }
+
+    /// Checks whether the coordinator stopped emitting events, which is the case once the
+    /// `EventPublisher` has been dropped.
+    pub fn is_closed(&self) -> bool {
+        let mut listener = self.clone();
+        // skip the events the clone hasn't seen yet until the channel is either pending or closed
+        loop {
+            match listener.next().now_or_never() {
+                Some(Some(_)) => continue,
+                Some(None) => return true,
+                None => return false,
+            }
+        }
+    }
 }
 
 impl<E: Clone> Stream for EventListener<E> {
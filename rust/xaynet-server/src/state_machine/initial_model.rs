@@ -0,0 +1,131 @@
+//! Loading of the initial global model.
+//!
+//! The initial model is published as the global model when the [`StateMachine`] is created, so
+//! that the participants of the first round have a model to train from. It is read from a file
+//! which contains a JSON array of the model weights, e.g. `[0.5, -1.0, 0.25]`. The weights must be
+//! numbers of the data type of the model masking configuration.
+//!
+//! [`StateMachine`]: crate::state_machine::StateMachine
+use std::{fmt::Debug, fs, io, path::Path};
+
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use xaynet_core::mask::{DataType, FromPrimitives, Model};
+
+/// Errors which can occur while loading the initial model.
+#[derive(Debug, Error)]
+pub enum InitialModelError {
+    #[error("failed to read the initial model: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("the initial model is not a JSON array of {0:?} numbers: {1}")]
+    Format(DataType, serde_json::Error),
+
+    #[error("the initial model contains an invalid weight: {0}")]
+    InvalidWeight(String),
+
+    #[error("the initial model has {actual} weights, but the model size is {expected}")]
+    InvalidSize { expected: usize, actual: usize },
+}
+
+/// Loads the initial model from the file at the given `path`.
+///
+/// # Errors
+/// Fails if the file cannot be read, if the weights are not finite numbers of the given
+/// `data_type` or if the number of weights differs from the model `size`.
+pub fn load_initial_model(
+    path: &Path,
+    data_type: DataType,
+    size: usize,
+) -> Result<Model, InitialModelError> {
+    let bytes = fs::read(path)?;
+    let model = match data_type {
+        DataType::F32 => parse_model::<f32>(&bytes, data_type)?,
+        DataType::F64 => parse_model::<f64>(&bytes, data_type)?,
+        DataType::I32 => parse_model::<i32>(&bytes, data_type)?,
+        DataType::I64 => parse_model::<i64>(&bytes, data_type)?,
+    };
+    if model.len() != size {
+        return Err(InitialModelError::InvalidSize {
+            expected: size,
+            actual: model.len(),
+        });
+    }
+    Ok(model)
+}
+
+/// Parses a model from a JSON array of primitive weights.
+fn parse_model<P>(bytes: &[u8], data_type: DataType) -> Result<Model, InitialModelError>
+where
+    P: Debug + DeserializeOwned,
+    Model: FromPrimitives<P>,
+{
+    let weights = serde_json::from_slice::<Vec<P>>(bytes)
+        .map_err(|err| InitialModelError::Format(data_type, err))?;
+    Model::from_primitives(weights.into_iter())
+        .map_err(|err| InitialModelError::InvalidWeight(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::utils::TempPath;
+    use num::{bigint::BigInt, rational::Ratio};
+
+    /// Writes the contents to a new temporary file.
+    fn model_file(contents: &str) -> TempPath {
+        let path = TempPath::with_extension("json");
+        fs::write(&*path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_valid_initial_model() {
+        let path = model_file("[0.5, -1, 0.25]");
+        let model = load_initial_model(&path, DataType::F32, 3).unwrap();
+        let expected = vec![
+            Ratio::new(BigInt::from(1), BigInt::from(2)),
+            Ratio::from(BigInt::from(-1)),
+            Ratio::new(BigInt::from(1), BigInt::from(4)),
+        ]
+        .into_iter()
+        .collect::<Model>();
+        assert_eq!(model, expected);
+    }
+
+    #[test]
+    fn load_initial_model_of_wrong_size() {
+        let path = model_file("[1, 2, 3]");
+        assert!(matches!(
+            load_initial_model(&path, DataType::I64, 4),
+            Err(InitialModelError::InvalidSize {
+                expected: 4,
+                actual: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn load_initial_model_of_wrong_data_type() {
+        let path = model_file("[1, 2.5, 3]");
+        assert!(matches!(
+            load_initial_model(&path, DataType::I32, 3),
+            Err(InitialModelError::Format(DataType::I32, _))
+        ));
+
+        let path = model_file("[1, 1e300]");
+        assert!(matches!(
+            load_initial_model(&path, DataType::F32, 2),
+            Err(InitialModelError::InvalidWeight(_))
+        ));
+    }
+
+    #[test]
+    fn load_missing_initial_model() {
+        let path = TempPath::with_extension("json");
+        assert!(matches!(
+            load_initial_model(&path, DataType::F32, 3),
+            Err(InitialModelError::Io(_))
+        ));
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! # Overview
 //!
-//! ![](https://mermaid.ink/svg/eyJjb2RlIjoic3RhdGVEaWFncmFtXG5cdFsqXSAtLT4gSWRsZVxuXG4gIElkbGUgLS0-IFN1bVxuICBTdW0gLS0-IFVwZGF0ZVxuICBVcGRhdGUgLS0-IFN1bTJcbiAgU3VtMiAtLT4gVW5tYXNrXG4gIFVubWFzayAtLT4gSWRsZVxuXG4gIFN1bSAtLT4gRXJyb3JcbiAgVXBkYXRlIC0tPiBFcnJvclxuICBTdW0yIC0tPiBFcnJvclxuICBVbm1hc2sgLS0-IEVycm9yXG4gIEVycm9yIC0tPiBJZGxlXG4gIEVycm9yIC0tPiBTaHV0ZG93blxuXG4gIFNodXRkb3duIC0tPiBbKl1cblxuXG5cblxuXG5cblxuICAiLCJtZXJtYWlkIjp7InRoZW1lIjoibmV1dHJhbCJ9fQ)
+//! ![](https://mermaid.ink/svg/eyJjb2RlIjoic3RhdGVEaWFncmFtXG5cdFsqXSAtLT4gSWRsZVxuXG4gIElkbGUgLS0-IFN1bVxuICBTdW0gLS0-IFVwZGF0ZVxuICBVcGRhdGUgLS0-IFN1bTJcbiAgU3VtMiAtLT4gVW5tYXNrXG4gIFVubWFzayAtLT4gSWRsZVxuXG4gIFN1bSAtLT4gRXJyb3JcbiAgVXBkYXRlIC0tPiBFcnJvclxuICBTdW0yIC0tPiBFcnJvclxuICBVbm1hc2sgLS0-IEVycm9yXG4gIEVycm9yIC0tPiBJZGxlXG4gIEVycm9yIC0tPiBTaHV0ZG93blxuICBVbm1hc2sgLS0-IFNodXRkb3duXG5cbiAgVW5tYXNrIC0tPiBQYXVzZWRcbiAgRXJyb3IgLS0-IFBhdXNlZFxuICBQYXVzZWQgLS0-IElkbGVcbiAgUGF1c2VkIC0tPiBTaHV0ZG93blxuXG4gIFNodXRkb3duIC0tPiBbKl1cblxuXG5cblxuXG5cblxuICAiLCJtZXJtYWlkIjp7InRoZW1lIjoibmV1dHJhbCJ9fQ)
 //!
 //! The [`StateMachine`] is responsible for executing the individual tasks of the PET protocol.
 //! The main tasks include: building the sum and seed dictionaries, aggregating the masked
@@ -19,8 +19,9 @@
 //!
 //! Publishes [`PhaseName::Idle`], increments the `round id` by `1`, invalidates the
 //! [`SumDict`], [`SeedDict`], `scalar` and `mask length`, updates the [`EncryptKeyPair`],
-//! `thresholds` as well as the `seed` and publishes the [`EncryptKeyPair`] and the
-//! [`RoundParameters`].
+//! `thresholds` as well as the `seed`, schedules the start of the round and publishes the
+//! [`EncryptKeyPair`] and the [`RoundParameters`]. Afterwards, it waits until the scheduled start
+//! of the round.
 //!
 //! **Sum**
 //!
@@ -41,14 +42,27 @@
 //!
 //! **Unmask**
 //!
-//! Publishes [`PhaseName::Unmask`], unmasks the global masked model and publishes the global
-//! model.
+//! Publishes [`PhaseName::Unmask`], selects the masks which reach the configured quorum and
+//! verifiably unmask the global masked model, unmasks it, records an [`UnmaskAudit`] of the sum
+//! participants whose masks were not used and publishes the global model. If a [`ModelHistory`]
+//! is configured, the global model is added to it together with its [`ModelMetadata`].
 //!
 //! **Error**
 //!
 //! Publishes [`PhaseName::Error`] and handles [`StateError`]s that can occur during the
 //! execution of the [`StateMachine`]. In most cases, the error is handled by restarting the round.
-//! However, if a [`StateError::ChannelError`] occurs, the [`StateMachine`] will shut down.
+//! However, if a [`StateError::ChannelError`] occurs, the [`StateMachine`] will shut down. After a
+//! [`StateError::StorageError`], the next round is delayed, increasingly so for consecutive
+//! storage errors, such that an unreachable Redis doesn't cause a tight loop of failing rounds.
+//!
+//! After the unmask phase or an error, the [`StateMachine`] also shuts down if the configured
+//! maximum number of rounds has been reached or if an admin requested a shutdown. If an admin
+//! paused the [`StateMachine`], it moves to the paused state instead of starting the next round.
+//!
+//! **Paused**
+//!
+//! Publishes [`PhaseName::Paused`] and rejects all requests until an admin resumes the
+//! [`StateMachine`], restarts a round or requests a shutdown.
 //!
 //! **Shutdown**
 //!
@@ -56,16 +70,45 @@
 //! the [`StateMachine`] performs a clean shutdown of the [Request][requests_idx] channel by
 //! closing it and consuming all remaining messages.
 //!
+//! # Persistence
+//!
+//! A [`StateMachine`] created via [`StateMachine::restore()`] writes the coordinator state, the
+//! [`SumDict`], the [`SeedDict`], the masked models and the [`MaskDict`]s through to Redis. The
+//! coordinator state is stored at the beginning of every phase of the PET protocol and the
+//! dictionaries are updated with every accepted message. The masked models of the update phase
+//! are stored individually and replaced by their aggregation at the end of the phase. When the
+//! coordinator restarts, [`StateMachine::restore()`] rebuilds the phase in which the coordinator
+//! stopped from the stored data, so that the messages which have already been processed are not
+//! lost. A restored phase starts its timers from the beginning and a restored idle phase keeps
+//! its round id.
+//!
 //! # Requests
 //!
 //! By initiating a new [`StateMachine`] via [`StateMachine::new()`], a new
 //! [StateMachineRequest][requests_idx] channel is created, the function of which is to send
 //! [`StateMachineRequest`]s to the [`StateMachine`]. The sender half of that channel
 //! ([`RequestSender`]) is returned back to the caller of [`StateMachine::new()`], whereas the
-//! receiver half ([`RequestReceiver`]) is used by the [`StateMachine`].
+//! receiver half ([`RequestReceiver`]) is used by the [`StateMachine`]. The channel holds a
+//! bounded number of requests: once it is full, further requests are rejected with
+//! [`StateMachineError::RequestQueueFull`] until the [`StateMachine`] catches up.
 //!
 //! See [here][requests] for more details.
 //!
+//! # Admin commands
+//!
+//! Besides the [`RequestSender`], [`StateMachine::new()`] and [`StateMachine::restore()`] return an
+//! [`AdminSender`], which allows an operator to control the execution of the [`StateMachine`]:
+//! - **pause**: moves to the paused state once the current round has been completed.
+//! - **resume**: resumes a paused [`StateMachine`].
+//! - **abort**: aborts the current round with [`StateError::Aborted`], after which the
+//!   [`StateMachine`] proceeds as after any other error.
+//! - **shutdown**: moves to the shutdown state once the current round has been completed.
+//! - **restart**: aborts the current round with [`StateError::Restarted`] and immediately starts a
+//!   new round with new keys, even if the [`StateMachine`] is paused.
+//!
+//! A phase is interrupted by an abort or restart while it waits for requests. All commands are
+//! reflected in the published phase events.
+//!
 //! # Events
 //!
 //! During the execution of the PET protocol, the [`StateMachine`] will publish various events
@@ -73,6 +116,11 @@
 //! events via the [`EventSubscriber`]. An [`EventSubscriber`] is automatically created when a new
 //! [`StateMachine`] is created through [`StateMachine::new()`].
 //!
+//! If an initial model is configured in the model settings, it is published as the global model
+//! when the [`StateMachine`] is created. It is served until the global model of the first
+//! completed round replaces it, so that the participants of the first round have a model to train
+//! from.
+//!
 //! See [here][events] for more details.
 //!
 //! [settings]: ../settings/index.html
@@ -83,25 +131,37 @@
 //! [`PhaseName::Unmask`]: crate::state_machine::phases::PhaseName::Unmask
 //! [`PhaseName::Error`]: crate::state_machine::phases::PhaseName::Error
 //! [`PhaseName::Shutdown`]: crate::state_machine::phases::PhaseName::Shutdown
+//! [`PhaseName::Paused`]: crate::state_machine::phases::PhaseName::Paused
 //! [`SumDict`]: xaynet_core::SumDict
 //! [`SeedDict`]: xaynet_core::SeedDict
 //! [`EncryptKeyPair`]: xaynet_core::crypto::EncryptKeyPair
 //! [`RoundParameters`]: xaynet_core::common::RoundParameters
 //! [`MaskDict`]: crate::state_machine::coordinator::MaskDict
+//! [`UnmaskAudit`]: crate::state_machine::phases::UnmaskAudit
+//! [`ModelHistory`]: crate::storage::history::ModelHistory
+//! [`ModelMetadata`]: crate::storage::history::ModelMetadata
 //! [`StateMachineRequest`]: crate::state_machine::requests::StateMachineRequest
+//! [`AdminSender`]: crate::state_machine::admin::AdminSender
 //! [requests_idx]: ./requests/index.html
 //! [events]: ./events/index.html
 
+pub mod admin;
 pub mod coordinator;
 pub mod events;
+pub mod initial_model;
+pub mod optimizer;
 pub mod phases;
 pub mod requests;
 
 use self::{
+    admin::{AdminReceiver, AdminSender},
     coordinator::CoordinatorState,
-    events::{EventPublisher, EventSubscriber},
+    events::{EventPublisher, EventSubscriber, ModelUpdate},
+    initial_model::{load_initial_model, InitialModelError},
+    optimizer::ServerOptimizer,
     phases::{
         Idle,
+        Paused,
         Phase,
         PhaseName,
         PhaseState,
@@ -116,11 +176,24 @@ use self::{
     requests::{RequestReceiver, RequestSender},
 };
 
+use std::{path::Path, sync::Arc};
+
 use derive_more::From;
+use redis::RedisError;
 use thiserror::Error;
-use xaynet_core::{mask::UnmaskingError, InitError};
+use xaynet_core::{
+    common::MessageErrorKind,
+    mask::{Model, UnmaskingError},
+    InitError,
+};
 
-use crate::settings::{MaskSettings, ModelSettings, PetSettings};
+use crate::{
+    settings::{MaskSettings, ModelSettings, PetSettings, RequestSettings},
+    storage::{
+        history::{ModelHistory, ModelHistoryError},
+        redis::Client,
+    },
+};
 
 #[cfg(feature = "metrics")]
 use crate::metrics::MetricsSender;
@@ -137,12 +210,57 @@ pub enum StateMachineError {
     #[error("invalid update: the seed dictionary sent by the participant is invalid")]
     InvalidLocalSeedDict,
 
+    #[error("the request queue of the state machine is full")]
+    RequestQueueFull {
+        /// The time in seconds after which the request should be resent.
+        retry_after: u64,
+    },
+
     #[error("the request could not be processed due to an internal error")]
     InternalError,
 }
 
+impl StateMachineError {
+    /// Gets the machine readable kind of this error.
+    pub fn kind(&self) -> MessageErrorKind {
+        match self {
+            Self::MessageRejected => MessageErrorKind::MessageRejected,
+            Self::AggregationFailed => MessageErrorKind::AggregationFailed,
+            Self::InvalidLocalSeedDict => MessageErrorKind::InvalidLocalSeedDict,
+            Self::RequestQueueFull { .. } => MessageErrorKind::RequestQueueFull,
+            Self::InternalError => MessageErrorKind::InternalError,
+        }
+    }
+}
+
 pub type StateMachineResult = Result<(), StateMachineError>;
 
+/// Error returned when the state machine cannot be created.
+#[derive(Debug, Error)]
+pub enum StateMachineInitError {
+    #[error("{0}")]
+    Init(#[from] InitError),
+
+    #[error("{0}")]
+    InitialModel(#[from] InitialModelError),
+}
+
+/// Error returned when the state machine cannot be restored.
+#[derive(Debug, Error)]
+pub enum RestoreError {
+    #[error("{0}")]
+    Init(#[from] InitError),
+
+    #[error("{0}")]
+    InitialModel(#[from] InitialModelError),
+
+    #[error("failed to read the coordinator state from Redis: {0}")]
+    Redis(#[from] RedisError),
+
+    #[error("failed to read from the model history: {0}")]
+    ModelHistory(#[from] ModelHistoryError),
+}
+
 /// Error that occurs when unmasking of the global model fails.
 #[derive(Error, Debug, Eq, PartialEq)]
 pub enum RoundFailed {
@@ -150,6 +268,8 @@ pub enum RoundFailed {
     AmbiguousMasks,
     #[error("no mask found")]
     NoMask,
+    #[error("no mask was submitted by a quorum of the sum participants")]
+    NoQuorum,
     #[error("unmasking error: {0}")]
     Unmasking(#[from] UnmaskingError),
 }
@@ -164,6 +284,7 @@ pub enum StateMachine {
     Unmask(PhaseState<Unmask>),
     Error(PhaseState<StateError>),
     Shutdown(PhaseState<Shutdown>),
+    Paused(PhaseState<Paused>),
 }
 
 impl StateMachine
@@ -175,12 +296,16 @@ where
     PhaseState<Unmask>: Phase,
     PhaseState<StateError>: Phase,
     PhaseState<Shutdown>: Phase,
+    PhaseState<Paused>: Phase,
 {
     /// Creates a new state machine with the initial state [`Idle`].
     ///
+    /// If an initial model is configured, it is published as the global model.
+    ///
     /// # Errors
     ///
-    /// Fails if there is insufficient system entropy to generate secrets.
+    /// Fails if there is insufficient system entropy to generate secrets or if the initial model
+    /// cannot be loaded.
     ///
     /// <div class="information">
     ///     <div class="tooltip ignore" style="">ⓘ<span class="tooltiptext">Note</span></div>
@@ -203,30 +328,155 @@ where
         pet_settings: PetSettings,
         mask_settings: MaskSettings,
         model_settings: ModelSettings,
+        request_settings: RequestSettings,
+        model_history: Option<ModelHistory>,
         #[cfg(feature = "metrics")] metrics_tx: MetricsSender,
-    ) -> Result<(Self, RequestSender, EventSubscriber), InitError> {
+    ) -> Result<(Self, RequestSender, AdminSender, EventSubscriber), StateMachineInitError> {
         // crucial: init must be called before anything else in this module
         sodiumoxide::init().or(Err(InitError))?;
 
+        let initial_model = model_settings.initial.clone();
+        let optimizer_settings = model_settings.optimizer;
         let coordinator_state = CoordinatorState::new(pet_settings, mask_settings, model_settings);
-        let (event_publisher, event_subscriber) = EventPublisher::init(
+        let (mut event_publisher, event_subscriber) = EventPublisher::init(
             coordinator_state.round_id,
             coordinator_state.keys.clone(),
             coordinator_state.round_params.clone(),
             PhaseName::Idle,
         );
-        let (req_receiver, handle) = RequestReceiver::new();
+        let initial_model = publish_initial_model(
+            &mut event_publisher,
+            initial_model.as_deref(),
+            &coordinator_state,
+        )?;
+        let optimizer = optimizer_settings.map(|settings| {
+            ServerOptimizer::new(
+                settings,
+                &coordinator_state.mask_config.model,
+                None,
+                initial_model.as_ref(),
+            )
+        });
+        let (req_receiver, handle) = RequestReceiver::new(request_settings);
+        let (admin_receiver, admin_handle) = AdminReceiver::new();
 
         let shared = Shared::new(
             coordinator_state,
             event_publisher,
             req_receiver,
+            admin_receiver,
+            None,
+            model_history,
+            optimizer,
             #[cfg(feature = "metrics")]
             metrics_tx,
         );
 
         let state_machine = StateMachine::from(PhaseState::<Idle>::new(shared));
-        Ok((state_machine, handle, event_subscriber))
+        Ok((state_machine, handle, admin_handle, event_subscriber))
+    }
+
+    /// Creates a state machine that writes its state through to Redis.
+    ///
+    /// If Redis holds the state of a previous coordinator, the phase in which that coordinator
+    /// stopped is restored from it. The settings then only take effect from the next round on.
+    /// Otherwise, a new state machine with the initial state [`Idle`] is created, like with
+    /// [`StateMachine::new()`], and the initial model is published as the global model if one is
+    /// configured. A restored state machine instead publishes the latest global model of the
+    /// model history if there is one and restores the state of the server optimizer from Redis.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is insufficient system entropy to generate secrets, if the stored state
+    /// cannot be read or if the initial model cannot be loaded.
+    pub async fn restore(
+        pet_settings: PetSettings,
+        mask_settings: MaskSettings,
+        model_settings: ModelSettings,
+        request_settings: RequestSettings,
+        redis: Client,
+        model_history: Option<ModelHistory>,
+        #[cfg(feature = "metrics")] metrics_tx: MetricsSender,
+    ) -> Result<(Self, RequestSender, AdminSender, EventSubscriber), RestoreError> {
+        // crucial: init must be called before anything else in this module
+        sodiumoxide::init().or(Err(InitError))?;
+
+        let initial_model = model_settings.initial.clone();
+        let optimizer_settings = model_settings.optimizer;
+        let (mut admin_receiver, admin_handle) = AdminReceiver::new();
+        let (coordinator_state, restored) =
+            match redis.connection().await.get_coordinator_state().await? {
+                Some(coordinator_state) => {
+                    info!(
+                        "restoring the coordinator state of round {} in phase {:?}",
+                        coordinator_state.round_id, coordinator_state.phase
+                    );
+                    admin_receiver.set_pet_settings(pet_settings);
+                    (coordinator_state, true)
+                }
+                None => {
+                    info!("no coordinator state found: starting from scratch");
+                    let state = CoordinatorState::new(pet_settings, mask_settings, model_settings);
+                    (state, false)
+                }
+            };
+        let phase = coordinator_state.phase;
+        let (mut event_publisher, event_subscriber) = EventPublisher::init(
+            coordinator_state.round_id,
+            coordinator_state.keys.clone(),
+            coordinator_state.round_params.clone(),
+            phase,
+        );
+        let initial_model = if restored {
+            publish_latest_model(&mut event_publisher, model_history.as_ref()).await?
+        } else {
+            publish_initial_model(
+                &mut event_publisher,
+                initial_model.as_deref(),
+                &coordinator_state,
+            )?
+        };
+        let mask_config = coordinator_state.mask_config.model;
+        let optimizer = match optimizer_settings {
+            // a restored optimizer continues from its stored state or starts over from the
+            // averaged model of the next round, since the initial model is outdated
+            Some(settings) if restored => {
+                let state = redis.connection().await.get_optimizer_state().await?;
+                Some(ServerOptimizer::new(settings, &mask_config, state, None))
+            }
+            Some(settings) => Some(ServerOptimizer::new(
+                settings,
+                &mask_config,
+                None,
+                initial_model.as_ref(),
+            )),
+            None => None,
+        };
+        let (req_receiver, handle) = RequestReceiver::new(request_settings);
+
+        let shared = Shared::new(
+            coordinator_state,
+            event_publisher,
+            req_receiver,
+            admin_receiver,
+            Some(redis.clone()),
+            model_history,
+            optimizer,
+            #[cfg(feature = "metrics")]
+            metrics_tx,
+        );
+
+        let state_machine = match phase {
+            PhaseName::Sum => PhaseState::<Sum>::restore(shared, &redis).await?.into(),
+            PhaseName::Update => PhaseState::<Update>::restore(shared, &redis).await?.into(),
+            PhaseName::Sum2 => PhaseState::<Sum2>::restore(shared, &redis).await?.into(),
+            PhaseName::Unmask => PhaseState::<Unmask>::restore(shared, &redis).await?.into(),
+            PhaseName::Idle if restored => PhaseState::<Idle>::restore(shared).into(),
+            PhaseName::Idle | PhaseName::Error | PhaseName::Shutdown | PhaseName::Paused => {
+                PhaseState::<Idle>::new(shared).into()
+            }
+        };
+        Ok((state_machine, handle, admin_handle, event_subscriber))
     }
 
     /// Moves the [`StateMachine`] to the next state and consumes the current one.
@@ -240,6 +490,7 @@ where
             StateMachine::Unmask(state) => state.run_phase().await,
             StateMachine::Error(state) => state.run_phase().await,
             StateMachine::Shutdown(state) => state.run_phase().await,
+            StateMachine::Paused(state) => state.run_phase().await,
         }
     }
 
@@ -252,5 +503,48 @@ where
     }
 }
 
+/// Publishes the latest global model of the model history of a restored coordinator.
+///
+/// The initial model isn't published, since it would replace the global model of the previous
+/// rounds. The published model is returned.
+async fn publish_latest_model(
+    publisher: &mut EventPublisher,
+    model_history: Option<&ModelHistory>,
+) -> Result<Option<Model>, ModelHistoryError> {
+    let model = match model_history {
+        Some(history) => history.get_latest_model().await?,
+        None => None,
+    };
+    match &model {
+        Some(model) => {
+            info!("publishing the latest global model of the model history");
+            publisher.broadcast_model(ModelUpdate::New(Arc::new(model.clone())));
+        }
+        None => warn!("no global model to restore: waiting for the current round to complete"),
+    }
+    Ok(model)
+}
+
+/// Loads the initial model from the given `path` and publishes it as the global model.
+///
+/// The initial model is validated against the model size and the data type of the model masking
+/// configuration of the coordinator state. The published model is returned.
+fn publish_initial_model(
+    publisher: &mut EventPublisher,
+    path: Option<&Path>,
+    state: &CoordinatorState,
+) -> Result<Option<Model>, InitialModelError> {
+    match path {
+        Some(path) => {
+            let model =
+                load_initial_model(path, state.mask_config.model.data_type, state.model_size)?;
+            info!("publishing the initial model from {}", path.display());
+            publisher.broadcast_model(ModelUpdate::New(Arc::new(model.clone())));
+            Ok(Some(model))
+        }
+        None => Ok(None),
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests;
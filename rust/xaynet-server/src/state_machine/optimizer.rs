@@ -0,0 +1,261 @@
+//! Server-side optimization of the global model.
+//!
+//! Plain averaging publishes the averaged model of a round as the new global model. The
+//! [`ServerOptimizer`] instead treats the difference between the averaged model and the previous
+//! global model as a pseudo-gradient and applies it to the previous global model with momentum
+//! (FedAvgM), Adam (FedAdam) or Yogi (FedYogi), see [Adaptive Federated Optimization].
+//!
+//! The [`OptimizerState`] is kept across rounds and stored in Redis next to the coordinator state,
+//! so that a restarted coordinator continues with the same moments.
+//!
+//! [Adaptive Federated Optimization]: https://arxiv.org/abs/2003.00295
+use num::traits::ToPrimitive;
+use xaynet_core::mask::{FromPrimitives, IntoPrimitives, MaskConfig, Model};
+
+use crate::settings::{OptimizerAlgorithm, OptimizerSettings};
+
+/// The state of the server optimizer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptimizerState {
+    /// The weights of the previous global model.
+    pub global_model: Vec<f64>,
+    /// The first moment of the pseudo-gradients.
+    pub momentum: Vec<f64>,
+    /// The second moment of the pseudo-gradients. It is only used by the adaptive algorithms.
+    pub variance: Vec<f64>,
+}
+
+impl OptimizerState {
+    /// Creates the state of an optimizer which starts from the given global model.
+    ///
+    /// The second moments start at `tau^2`, which bounds the first adaptive learning rates.
+    pub fn new(global_model: Vec<f64>, tau: f64) -> Self {
+        let len = global_model.len();
+        Self {
+            global_model,
+            momentum: vec![0.; len],
+            variance: vec![tau * tau; len],
+        }
+    }
+}
+
+/// An optimizer for the global model.
+#[derive(Debug)]
+pub struct ServerOptimizer {
+    settings: OptimizerSettings,
+    state: Option<OptimizerState>,
+    /// The bound of the weights of the model masking configuration.
+    bound: f64,
+}
+
+impl ServerOptimizer {
+    /// Creates a server optimizer.
+    ///
+    /// If there is no state yet, the state is initialized from the initial global model if there
+    /// is one, or from the averaged model of the next round otherwise. The optimized weights are
+    /// clamped to the bounds of the model masking configuration `mask_config`.
+    pub fn new(
+        settings: OptimizerSettings,
+        mask_config: &MaskConfig,
+        state: Option<OptimizerState>,
+        initial_model: Option<&Model>,
+    ) -> Self {
+        let state = state.or_else(|| {
+            initial_model
+                .and_then(|model| to_weights(model.clone()))
+                .map(|weights| OptimizerState::new(weights, settings.tau))
+        });
+        let bound = mask_config.add_shift().to_f64().unwrap_or(f64::MAX);
+        Self {
+            settings,
+            state,
+            bound,
+        }
+    }
+
+    /// Gets the state of the optimizer or `None` if no global model has been optimized yet.
+    pub fn state(&self) -> Option<&OptimizerState> {
+        self.state.as_ref()
+    }
+
+    /// Computes the new global model from the averaged model of a round.
+    ///
+    /// The averaged model is published as-is and the state is reset to it if there is no previous
+    /// global model of the same length or if the weights can't be represented as floats.
+    pub fn step(&mut self, averaged_model: Model) -> Model {
+        let averaged = match to_weights(averaged_model.clone()) {
+            Some(averaged) => averaged,
+            None => {
+                warn!("skipping the server optimizer: the averaged model is not representable");
+                return averaged_model;
+            }
+        };
+        let state = match self.state.as_mut() {
+            Some(state) if state.global_model.len() == averaged.len() => state,
+            _ => {
+                info!("initializing the server optimizer from the averaged model");
+                self.state = Some(OptimizerState::new(averaged, self.settings.tau));
+                return averaged_model;
+            }
+        };
+
+        let OptimizerSettings {
+            algorithm,
+            learning_rate,
+            beta1,
+            beta2,
+            tau,
+        } = self.settings;
+        let weights = state
+            .global_model
+            .iter_mut()
+            .zip(state.momentum.iter_mut())
+            .zip(state.variance.iter_mut())
+            .zip(averaged);
+        for (((weight, momentum), variance), averaged) in weights {
+            let gradient = averaged - *weight;
+            let squared = gradient * gradient;
+            *weight += match algorithm {
+                OptimizerAlgorithm::Momentum => {
+                    *momentum = beta1 * *momentum + gradient;
+                    learning_rate * *momentum
+                }
+                OptimizerAlgorithm::Adam | OptimizerAlgorithm::Yogi => {
+                    *momentum = beta1 * *momentum + (1. - beta1) * gradient;
+                    *variance = if algorithm == OptimizerAlgorithm::Adam {
+                        beta2 * *variance + (1. - beta2) * squared
+                    } else {
+                        *variance - (1. - beta2) * squared * (*variance - squared).signum()
+                    };
+                    learning_rate * *momentum / (variance.sqrt() + tau)
+                }
+            };
+            // the global model must be maskable by the participants of the next round
+            *weight = weight.max(-self.bound).min(self.bound);
+        }
+        Model::from_primitives_bounded(state.global_model.iter().copied())
+    }
+}
+
+/// Converts a model into its weights or `None` if a weight can't be represented as a float.
+fn to_weights(model: Model) -> Option<Vec<f64>> {
+    model.into_primitives().collect::<Result<_, _>>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use xaynet_core::mask::{BoundType, DataType, GroupType, ModelType};
+
+    use super::*;
+
+    fn mask_config(bound_type: BoundType) -> MaskConfig {
+        MaskConfig {
+            group_type: GroupType::Prime,
+            data_type: DataType::F64,
+            bound_type,
+            model_type: ModelType::M3,
+        }
+    }
+
+    fn optimizer(
+        settings: OptimizerSettings,
+        state: Option<OptimizerState>,
+        initial_model: Option<&Model>,
+    ) -> ServerOptimizer {
+        ServerOptimizer::new(
+            settings,
+            &mask_config(BoundType::Bmax),
+            state,
+            initial_model,
+        )
+    }
+
+    fn settings(
+        algorithm: OptimizerAlgorithm,
+        learning_rate: f64,
+        beta1: f64,
+    ) -> OptimizerSettings {
+        OptimizerSettings {
+            algorithm,
+            learning_rate,
+            beta1,
+            beta2: 0.99,
+            tau: 1e-3,
+        }
+    }
+
+    fn model(weights: &[f64]) -> Model {
+        Model::from_primitives(weights.iter().copied()).unwrap()
+    }
+
+    fn weights(model: Model) -> Vec<f64> {
+        to_weights(model).unwrap()
+    }
+
+    #[test]
+    fn first_averaged_model_is_published() {
+        let mut optimizer = optimizer(settings(OptimizerAlgorithm::Adam, 0.1, 0.9), None, None);
+        assert!(optimizer.state().is_none());
+        assert_eq!(optimizer.step(model(&[1., 2.])), model(&[1., 2.]));
+        assert_eq!(optimizer.state().unwrap().global_model, vec![1., 2.]);
+    }
+
+    #[test]
+    fn momentum_without_decay_is_averaging() {
+        let initial = model(&[0., 0.]);
+        let settings = settings(OptimizerAlgorithm::Momentum, 1., 0.);
+        let mut optimizer = optimizer(settings, None, Some(&initial));
+        assert_eq!(optimizer.step(model(&[1., -2.])), model(&[1., -2.]));
+        assert_eq!(optimizer.step(model(&[0.5, 4.])), model(&[0.5, 4.]));
+    }
+
+    #[test]
+    fn momentum_accumulates() {
+        let initial = model(&[0.]);
+        let settings = settings(OptimizerAlgorithm::Momentum, 0.5, 0.5);
+        let mut optimizer = optimizer(settings, None, Some(&initial));
+        // m = 1, x = 0.5
+        assert_eq!(weights(optimizer.step(model(&[1.]))), vec![0.5]);
+        // m = 0.5 * 1 + 0.5, x = 0.5 + 0.5 * 1
+        assert_eq!(weights(optimizer.step(model(&[1.]))), vec![1.]);
+    }
+
+    #[test]
+    fn adaptive_steps_are_bounded() {
+        let initial = model(&[0., 0.]);
+        for algorithm in &[OptimizerAlgorithm::Adam, OptimizerAlgorithm::Yogi] {
+            let mut optimizer = optimizer(settings(*algorithm, 0.1, 0.9), None, Some(&initial));
+            let global = weights(optimizer.step(model(&[100., -0.01])));
+            // the adaptive step is about the learning rate times (1 - beta1) / sqrt(1 - beta2)
+            // regardless of the scale of the pseudo-gradient
+            assert!(global[0] > 0. && global[0] < 0.11);
+            assert!(global[1] < 0. && global[1] > -0.11);
+        }
+    }
+
+    #[test]
+    fn state_is_restored() {
+        let state = OptimizerState {
+            global_model: vec![1.],
+            momentum: vec![1.],
+            variance: vec![0.],
+        };
+        let settings = settings(OptimizerAlgorithm::Momentum, 1., 1.);
+        let mut optimizer = optimizer(settings, Some(state), Some(&model(&[7.])));
+        // m = 1 + 1, x = 1 + 2
+        assert_eq!(weights(optimizer.step(model(&[2.]))), vec![3.]);
+    }
+
+    #[test]
+    fn weights_are_clamped_to_the_mask_bound() {
+        let initial = model(&[0.5, -0.5]);
+        let settings = settings(OptimizerAlgorithm::Momentum, 1., 0.5);
+        let mut optimizer =
+            ServerOptimizer::new(settings, &mask_config(BoundType::B0), None, Some(&initial));
+        // m = 0.5, x = 0.5 + 0.5
+        assert_eq!(weights(optimizer.step(model(&[1., -1.]))), vec![1., -1.]);
+        // m = 0.25 + 0, x = 1 + 0.25 clamped to the bound of 1
+        assert_eq!(weights(optimizer.step(model(&[1., -1.]))), vec![1., -1.]);
+        assert_eq!(optimizer.state().unwrap().global_model, vec![1., -1.]);
+    }
+}
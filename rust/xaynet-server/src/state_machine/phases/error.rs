@@ -1,4 +1,5 @@
 use crate::state_machine::{
+    events::RoundOutcome,
     phases::{Idle, Phase, PhaseName, PhaseState, Shared, Shutdown},
     RoundFailed,
     StateMachine,
@@ -7,7 +8,15 @@ use crate::state_machine::{
 #[cfg(feature = "metrics")]
 use crate::metrics;
 
+use redis::RedisError;
 use thiserror::Error;
+use tokio::time::{self, Duration};
+
+/// The delay of the next round after the first of consecutive storage errors.
+const STORAGE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The maximum delay of the next round after consecutive storage errors.
+const MAX_STORAGE_BACKOFF: Duration = Duration::from_secs(60);
 
 /// Error that can occur during the execution of the [`StateMachine`].
 #[derive(Error, Debug)]
@@ -18,6 +27,26 @@ pub enum StateError {
     RoundError(#[from] RoundFailed),
     #[error("state failed: phase timeout: {0}")]
     TimeoutError(#[from] tokio::time::Elapsed),
+    #[error("state failed: storage error: {0}")]
+    StorageError(#[from] RedisError),
+    #[error("state failed: round aborted by an admin")]
+    Aborted,
+    #[error("state failed: round restarted by an admin")]
+    Restarted,
+}
+
+impl StateError {
+    /// Gets the kind of the error, which doesn't depend on the details of the error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ChannelError(_) => "channel",
+            Self::RoundError(_) => "round",
+            Self::TimeoutError(_) => "timeout",
+            Self::StorageError(_) => "storage",
+            Self::Aborted => "aborted",
+            Self::Restarted => "restarted",
+        }
+    }
 }
 
 impl PhaseState<StateError> {
@@ -45,6 +74,24 @@ impl Phase for PhaseState<StateError> {
 
         info!("broadcasting error phase event");
         self.shared.io.events.broadcast_phase(PhaseName::Error);
+        self.shared
+            .io
+            .events
+            .broadcast_round_outcome(RoundOutcome::Failed(self.inner.to_string()));
+
+        if let StateError::StorageError(_) = self.inner {
+            self.shared.storage_failures = self.shared.storage_failures.saturating_add(1);
+            self.shared
+                .io
+                .events
+                .broadcast_storage_failures(self.shared.storage_failures);
+            let backoff = storage_backoff(self.shared.storage_failures);
+            warn!(
+                "delaying the next round by {:?} after {} consecutive storage errors",
+                backoff, self.shared.storage_failures
+            );
+            time::delay_for(backoff).await;
+        }
 
         Ok(())
     }
@@ -55,7 +102,29 @@ impl Phase for PhaseState<StateError> {
     fn next(self) -> Option<StateMachine> {
         Some(match self.inner {
             StateError::ChannelError(_) => PhaseState::<Shutdown>::new(self.shared).into(),
-            _ => PhaseState::<Idle>::new(self.shared).into(),
+            StateError::Restarted => PhaseState::<Idle>::new(self.shared).into(),
+            _ => PhaseState::<Idle>::next_round(self.shared),
         })
     }
 }
+
+/// Gets the delay of the next round after the given number of consecutive storage errors, which
+/// doubles with every error up to the [`MAX_STORAGE_BACKOFF`].
+fn storage_backoff(failures: u32) -> Duration {
+    let factor = 2_u32.pow(failures.saturating_sub(1).min(16));
+    (STORAGE_BACKOFF * factor).min(MAX_STORAGE_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_backoff_doubles_up_to_the_maximum() {
+        assert_eq!(storage_backoff(1), Duration::from_secs(1));
+        assert_eq!(storage_backoff(2), Duration::from_secs(2));
+        assert_eq!(storage_backoff(6), Duration::from_secs(32));
+        assert_eq!(storage_backoff(7), MAX_STORAGE_BACKOFF);
+        assert_eq!(storage_backoff(u32::MAX), MAX_STORAGE_BACKOFF);
+    }
+}
@@ -1,15 +1,20 @@
+use tokio::time::Duration;
 use xaynet_core::{
     common::RoundSeed,
     crypto::{ByteObject, EncryptKeyPair, SigningKeySeed},
+    mask::NoiseParameters,
 };
 
-use crate::state_machine::{
-    events::{DictionaryUpdate, MaskLengthUpdate},
-    phases::{Handler, Phase, PhaseName, PhaseState, Shared, Sum},
-    requests::StateMachineRequest,
-    StateError,
-    StateMachine,
-    StateMachineError,
+use crate::{
+    settings::are_fractions_valid,
+    state_machine::{
+        events::{DictionaryUpdate, MaskLengthUpdate},
+        phases::{unix_time, Handler, Paused, Phase, PhaseName, PhaseState, Shared, Shutdown, Sum},
+        requests::StateMachineRequest,
+        StateError,
+        StateMachine,
+        StateMachineError,
+    },
 };
 
 #[cfg(feature = "metrics")]
@@ -17,13 +22,27 @@ use crate::metrics;
 
 use sodiumoxide::crypto::hash::sha256;
 
+/// The maximum factor by which the sum and update fractions are adapted between two rounds.
+const MAX_ADAPTATION_FACTOR: f64 = 10.;
+
+/// The minimum of adapted sum and update fractions.
+const MIN_FRACTION: f64 = 1e-6;
+
+/// The maximum of adapted sum and update fractions.
+const MAX_FRACTION: f64 = 0.999;
+
+/// The maximum factor by which adapted sum and update fractions deviate from the configured
+/// fractions.
+const MAX_FRACTION_DEVIATION: f64 = 4.;
+
 /// Idle state
 #[derive(Debug)]
 pub struct Idle;
 
+#[async_trait]
 impl Handler for PhaseState<Idle> {
     /// Reject the request with a [`StateMachineError::MessageRejected`]
-    fn handle_request(&mut self, _req: StateMachineRequest) -> Result<(), StateMachineError> {
+    async fn handle_request(&mut self, _req: StateMachineRequest) -> Result<(), StateMachineError> {
         Err(StateMachineError::MessageRejected)
     }
 }
@@ -36,15 +55,37 @@ impl Phase for PhaseState<Idle> {
     ///
     /// See the [module level documentation](../index.html) for more details.
     async fn run(&mut self) -> Result<(), StateError> {
+        if let Some(pet_settings) = self.shared.io.admin.take_pet_settings() {
+            match pet_settings.validate_dp_bounds(&self.shared.state.mask_config.model) {
+                Ok(()) => {
+                    info!("applying the updated PET settings");
+                    self.shared.state.apply_pet_settings(pet_settings);
+                }
+                Err(err) => warn!("discarding the updated PET settings: {}", err),
+            }
+        }
+
         info!("updating the keys");
         self.gen_round_keypair();
 
         info!("updating round thresholds");
         self.update_round_thresholds();
 
+        info!("updating noise parameters");
+        self.update_noise_params();
+
         info!("updating round seeds");
         self.update_round_seed();
 
+        let now = unix_time();
+        let start_time = next_start_time(
+            now,
+            self.shared.state.min_idle_time,
+            self.shared.state.round_period,
+        );
+        info!("scheduling the round to start at {}", start_time);
+        self.shared.state.round_params.start_time = start_time;
+
         let events = &mut self.shared.io.events;
 
         info!("broadcasting new keys");
@@ -77,7 +118,16 @@ impl Phase for PhaseState<Idle> {
             )
         );
 
-        // TODO: add a delay to prolongate the idle phase
+        let idle_time = start_time.saturating_sub(now);
+        if idle_time > 0 {
+            debug!("in idle phase for {} seconds", idle_time);
+            self.process_during(Duration::from_secs(idle_time)).await?;
+        }
+
+        if let Some(redis) = self.shared.redis().await {
+            info!("flushing the dictionaries of the previous round");
+            redis.flush_dicts().await?;
+        }
         Ok(())
     }
 
@@ -88,6 +138,16 @@ impl Phase for PhaseState<Idle> {
 }
 
 impl PhaseState<Idle> {
+    /// Restores an idle state without starting a new round, since the round id has already been
+    /// incremented when the restored idle phase was entered.
+    pub fn restore(shared: Shared) -> Self {
+        info!("restoring idle phase");
+        Self {
+            inner: Idle,
+            shared,
+        }
+    }
+
     /// Creates a new idle state.
     pub fn new(mut shared: Shared) -> Self {
         // Since some events are emitted very early, the round id must
@@ -101,7 +161,89 @@ impl PhaseState<Idle> {
         }
     }
 
-    fn update_round_thresholds(&mut self) {}
+    /// Creates the idle state of the next round, a shutdown state if an admin requested a
+    /// shutdown, the maximum number of completed rounds has been reached or the privacy budget is
+    /// exhausted, or a paused state if an admin paused the coordinator.
+    pub fn next_round(shared: Shared) -> StateMachine {
+        if shared.io.admin.is_shutdown() {
+            info!("shutdown requested by an admin");
+            return PhaseState::<Shutdown>::new(shared).into();
+        }
+        match shared.state.max_rounds {
+            Some(max_rounds) if shared.state.completed_rounds >= max_rounds => {
+                info!("maximum number of {} completed rounds reached", max_rounds);
+                PhaseState::<Shutdown>::new(shared).into()
+            }
+            _ if shared.state.is_privacy_budget_exhausted() => {
+                info!(
+                    "privacy budget exhausted after spending epsilon {}",
+                    shared.state.epsilon_spent
+                );
+                PhaseState::<Shutdown>::new(shared).into()
+            }
+            _ if shared.io.admin.is_paused() => PhaseState::<Paused>::new(shared).into(),
+            _ => PhaseState::<Idle>::new(shared).into(),
+        }
+    }
+
+    /// Adapts the sum and update fractions to the numbers of messages of the previous round.
+    ///
+    /// The fractions are only adapted if enabled and if the respective phase of the previous
+    /// round ran and received any messages, since a round without participants says nothing about
+    /// the number of participants. The adapted fractions deviate from the configured fractions by
+    /// at most the [`MAX_FRACTION_DEVIATION`]. The fractions are kept if the adapted fractions
+    /// would cause a deadlock.
+    fn update_round_thresholds(&mut self) {
+        let state = &mut self.shared.state;
+        let last_sum_count = state.last_sum_count.take().filter(|count| *count > 0);
+        let last_update_count = state.last_update_count.take().filter(|count| *count > 0);
+        if !state.adapt_fractions {
+            return;
+        }
+
+        let headroom = 1. + state.fraction_headroom;
+        let params = &state.round_params;
+        let sum = match last_sum_count {
+            Some(count) => {
+                let target = state.min_sum_count as f64 * headroom;
+                let sum = params.sum * adaptation_factor(count, target);
+                clamp_fraction(sum, state.configured_sum)
+            }
+            None => params.sum,
+        };
+        let update = match last_update_count {
+            Some(count) => {
+                // update participants are only selected among the participants which are not
+                // selected for the sum task
+                let target = state.min_update_count as f64 * headroom;
+                let correction = (1. - params.sum) / (1. - sum);
+                let update = params.update * adaptation_factor(count, target) * correction;
+                clamp_fraction(update, state.configured_update)
+            }
+            None => params.update,
+        };
+
+        if are_fractions_valid(sum, update) {
+            info!("adapted fractions: sum {}, update {}", sum, update);
+            state.round_params.sum = sum;
+            state.round_params.update = update;
+        } else {
+            warn!(
+                "keeping fractions, adapted fractions would cause a deadlock: sum {}, update {}",
+                sum, update
+            );
+        }
+    }
+
+    /// Calibrates the noise parameters to the differential privacy settings and the minimum number
+    /// of update participants.
+    fn update_noise_params(&mut self) {
+        let state = &mut self.shared.state;
+        let min_update_count = state.min_update_count;
+        state.round_params.noise = state.dp.map(|dp| {
+            NoiseParameters::calibrate(dp.clip_norm, dp.epsilon, dp.delta, min_update_count)
+        });
+    }
 
     /// Updates the seed round parameter.
     fn update_round_seed(&mut self) {
@@ -129,12 +271,49 @@ impl PhaseState<Idle> {
     }
 }
 
+/// Computes the factor by which a fraction is adapted such that the `observed` number of messages
+/// is expected to reach the `target` number of messages.
+///
+/// The factor is bounded by the [`MAX_ADAPTATION_FACTOR`].
+fn adaptation_factor(observed: usize, target: f64) -> f64 {
+    if observed == 0 {
+        return MAX_ADAPTATION_FACTOR;
+    }
+    (target / observed as f64).clamp(1. / MAX_ADAPTATION_FACTOR, MAX_ADAPTATION_FACTOR)
+}
+
+/// Clamps an adapted fraction to the range from [`MIN_FRACTION`] to [`MAX_FRACTION`] and to at
+/// most the [`MAX_FRACTION_DEVIATION`] from the `configured` fraction.
+fn clamp_fraction(fraction: f64, configured: f64) -> f64 {
+    let min = (configured / MAX_FRACTION_DEVIATION).max(MIN_FRACTION);
+    let max = (configured * MAX_FRACTION_DEVIATION).min(MAX_FRACTION);
+    fraction.clamp(min.min(max), max)
+}
+
+/// Computes the start time of the next round.
+///
+/// The round starts after the `min_idle_time` has passed. If the `round_period` is not `0`, the
+/// start is delayed to the next multiple of the `round_period`.
+fn next_start_time(now: u64, min_idle_time: u64, round_period: u64) -> u64 {
+    let earliest = now + min_idle_time;
+    if round_period == 0 {
+        return earliest;
+    }
+    match earliest % round_period {
+        0 => earliest,
+        rem => earliest + (round_period - rem),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::state_machine::{
-        events::Event,
-        tests::{builder::StateMachineBuilder, utils},
+    use crate::{
+        settings::{DpSettings, PetSettings},
+        state_machine::{
+            events::Event,
+            tests::{builder::StateMachineBuilder, utils},
+        },
     };
 
     #[tokio::test]
@@ -216,4 +395,232 @@ mod test {
             expected_event(MaskLengthUpdate::Invalidate)
         );
     }
+
+    #[test]
+    fn start_time_without_schedule() {
+        assert_eq!(next_start_time(1000, 0, 0), 1000);
+        assert_eq!(next_start_time(1000, 60, 0), 1060);
+    }
+
+    #[test]
+    fn start_time_with_schedule() {
+        assert_eq!(next_start_time(3600, 0, 3600), 3600);
+        assert_eq!(next_start_time(3601, 0, 3600), 7200);
+        assert_eq!(next_start_time(3000, 600, 3600), 3600);
+        assert_eq!(next_start_time(3001, 600, 3600), 7200);
+    }
+
+    #[tokio::test]
+    async fn start_time_is_published() {
+        let (state_machine, _request_tx, events) = StateMachineBuilder::new().build();
+        let before = unix_time();
+        let state_machine = state_machine.next().await.unwrap();
+        assert!(state_machine.is_sum());
+
+        let start_time = events.params_listener().get_latest().event.start_time;
+        assert!(before <= start_time && start_time <= unix_time());
+    }
+
+    #[tokio::test]
+    async fn pending_pet_settings_are_applied() {
+        let (mut shared, event_subscriber, ..) = utils::init_shared();
+        shared.state.last_sum_count = Some(10);
+        let pet_settings = PetSettings {
+            sum: 0.2,
+            update: 0.5,
+            min_update_count: 5,
+            max_update_time: 120,
+            ..utils::pet_settings()
+        };
+        shared.io.admin.set_pet_settings(pet_settings);
+
+        let mut idle_phase = PhaseState::<Idle>::new(shared);
+        idle_phase.run().await.unwrap();
+
+        let state = &idle_phase.shared.state;
+        assert_eq!(state.min_update_count, 5);
+        assert_eq!(state.max_update_time, 120);
+        assert_eq!(state.last_sum_count, None);
+        let round_params = event_subscriber.params_listener().get_latest().event;
+        assert_eq!((round_params.sum, round_params.update), (0.2, 0.5));
+        assert!(idle_phase.shared.io.admin.take_pet_settings().is_none());
+    }
+
+    #[tokio::test]
+    async fn pending_pet_settings_exceeding_mask_bounds_are_discarded() {
+        let (mut shared, ..) = utils::init_shared();
+        let pet_settings = PetSettings {
+            min_update_count: 5,
+            dp: Some(DpSettings {
+                clip_norm: 1.,
+                epsilon: 0.5,
+                delta: 1e-5,
+                max_epsilon: None,
+            }),
+            ..utils::pet_settings()
+        };
+        shared.io.admin.set_pet_settings(pet_settings);
+
+        let mut idle_phase = PhaseState::<Idle>::new(shared);
+        idle_phase.run().await.unwrap();
+
+        let state = &idle_phase.shared.state;
+        assert_eq!(
+            state.min_update_count,
+            utils::pet_settings().min_update_count
+        );
+        assert!(state.dp.is_none());
+    }
+
+    #[test]
+    fn shutdown_after_max_rounds() {
+        let (mut shared, ..) = utils::init_shared();
+        shared.state.max_rounds = Some(2);
+        shared.state.completed_rounds = 1;
+        // failed rounds don't count towards the maximum
+        shared.set_round_id(4);
+        let state_machine = PhaseState::<Idle>::next_round(shared);
+        assert!(state_machine.is_idle());
+
+        let PhaseState { mut shared, .. } = state_machine.into_idle_phase_state();
+        shared.state.completed_rounds = 2;
+        assert!(PhaseState::<Idle>::next_round(shared).is_shutdown());
+    }
+
+    #[test]
+    fn shutdown_after_privacy_budget() {
+        let (mut shared, ..) = utils::init_shared();
+        shared.state.dp = Some(DpSettings {
+            clip_norm: 1.,
+            epsilon: 0.5,
+            delta: 1e-5,
+            max_epsilon: Some(1.),
+        });
+        shared.state.epsilon_spent = 0.5;
+        let state_machine = PhaseState::<Idle>::next_round(shared);
+        assert!(state_machine.is_idle());
+
+        let PhaseState { mut shared, .. } = state_machine.into_idle_phase_state();
+        shared.state.epsilon_spent = 1.;
+        assert!(PhaseState::<Idle>::next_round(shared).is_shutdown());
+    }
+
+    #[tokio::test]
+    async fn noise_params_are_published() {
+        let (mut shared, event_subscriber, ..) = utils::init_shared();
+        let dp = DpSettings {
+            clip_norm: 1.,
+            epsilon: 0.5,
+            delta: 1e-5,
+            max_epsilon: None,
+        };
+        shared.state.dp = Some(dp);
+        let min_update_count = shared.state.min_update_count;
+
+        let mut idle_phase = PhaseState::<Idle>::new(shared);
+        idle_phase.run().await.unwrap();
+
+        let round_params = event_subscriber.params_listener().get_latest().event;
+        assert_eq!(
+            round_params.noise,
+            Some(NoiseParameters::calibrate(1., 0.5, 1e-5, min_update_count))
+        );
+    }
+
+    #[test]
+    fn adaptation_factor_is_bounded() {
+        assert_eq!(adaptation_factor(10, 15.), 1.5);
+        assert_eq!(adaptation_factor(30, 15.), 0.5);
+        assert_eq!(adaptation_factor(0, 15.), MAX_ADAPTATION_FACTOR);
+        assert_eq!(adaptation_factor(1, 100.), MAX_ADAPTATION_FACTOR);
+        assert_eq!(adaptation_factor(1000, 1.), 1. / MAX_ADAPTATION_FACTOR);
+    }
+
+    fn idle_phase(
+        adapt_fractions: bool,
+        last_sum_count: Option<usize>,
+        last_update_count: Option<usize>,
+    ) -> PhaseState<Idle> {
+        let (mut shared, ..) = utils::init_shared();
+        shared.state.round_params.sum = 0.2;
+        shared.state.round_params.update = 0.5;
+        shared.state.configured_sum = 0.2;
+        shared.state.configured_update = 0.5;
+        shared.state.min_sum_count = 10;
+        shared.state.min_update_count = 10;
+        shared.state.adapt_fractions = adapt_fractions;
+        shared.state.fraction_headroom = 0.5;
+        shared.state.last_sum_count = last_sum_count;
+        shared.state.last_update_count = last_update_count;
+        PhaseState::<Idle>::new(shared)
+    }
+
+    #[test]
+    fn fractions_are_constant_by_default() {
+        let mut idle_phase = idle_phase(false, Some(30), Some(30));
+        idle_phase.update_round_thresholds();
+
+        let state = &idle_phase.shared.state;
+        assert_eq!(state.round_params.sum, 0.2);
+        assert_eq!(state.round_params.update, 0.5);
+        assert_eq!(state.last_sum_count, None);
+        assert_eq!(state.last_update_count, None);
+    }
+
+    #[test]
+    fn fractions_are_adapted() {
+        // 30 sum messages, but only 15 are targeted
+        let mut idle_phase = idle_phase(true, Some(30), Some(10));
+        idle_phase.update_round_thresholds();
+
+        let state = &idle_phase.shared.state;
+        assert!((state.round_params.sum - 0.1).abs() < 1e-9);
+        // 10 update messages, but 15 are targeted among the remaining 90% instead of 80%
+        assert!((state.round_params.update - 0.5 * 1.5 * 0.8 / 0.9).abs() < 1e-9);
+        assert_eq!(state.last_sum_count, None);
+        assert_eq!(state.last_update_count, None);
+    }
+
+    #[test]
+    fn fractions_are_adapted_without_update_phase() {
+        let mut idle_phase = idle_phase(true, Some(5), None);
+        idle_phase.update_round_thresholds();
+
+        let state = &idle_phase.shared.state;
+        assert!((state.round_params.sum - 0.6).abs() < 1e-9);
+        assert_eq!(state.round_params.update, 0.5);
+    }
+
+    #[test]
+    fn fractions_are_kept_without_participants() {
+        let mut idle_phase = idle_phase(true, Some(0), Some(0));
+        idle_phase.update_round_thresholds();
+
+        let state = &idle_phase.shared.state;
+        assert_eq!(state.round_params.sum, 0.2);
+        assert_eq!(state.round_params.update, 0.5);
+        assert_eq!(state.last_sum_count, None);
+        assert_eq!(state.last_update_count, None);
+    }
+
+    #[test]
+    fn fractions_are_bounded_by_the_configured_fractions() {
+        let mut phase = idle_phase(true, Some(1), Some(1000));
+        phase.shared.state.round_params.sum = 0.05;
+        phase.shared.state.configured_sum = 0.02;
+        phase.update_round_thresholds();
+
+        let state = &phase.shared.state;
+        // 1 sum message, but 15 are targeted
+        assert!((state.round_params.sum - 0.02 * MAX_FRACTION_DEVIATION).abs() < 1e-9);
+        // 1000 update messages, but only 15 are targeted
+        assert!((state.round_params.update - 0.5 / MAX_FRACTION_DEVIATION).abs() < 1e-9);
+
+        // the adapted fractions never exceed the maximum fraction
+        let mut phase = idle_phase(true, Some(1), None);
+        phase.shared.state.round_params.sum = 0.5;
+        phase.shared.state.configured_sum = 0.5;
+        phase.update_round_thresholds();
+        assert_eq!(phase.shared.state.round_params.sum, MAX_FRACTION);
+    }
 }
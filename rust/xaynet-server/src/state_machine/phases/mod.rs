@@ -2,6 +2,7 @@
 
 mod error;
 mod idle;
+mod paused;
 mod shutdown;
 mod sum;
 mod sum2;
@@ -11,30 +12,42 @@ mod update;
 pub use self::{
     error::StateError,
     idle::Idle,
+    paused::Paused,
     shutdown::Shutdown,
     sum::Sum,
     sum2::Sum2,
-    unmask::Unmask,
+    unmask::{Unmask, UnmaskAudit},
     update::Update,
 };
 
-use crate::state_machine::{
-    coordinator::CoordinatorState,
-    events::EventPublisher,
-    requests::{RequestReceiver, ResponseSender, StateMachineRequest},
-    StateMachine,
-    StateMachineError,
+use crate::{
+    state_machine::{
+        admin::{AdminCommand, AdminReceiver},
+        coordinator::CoordinatorState,
+        events::{EventPublisher, MessageProgress},
+        optimizer::ServerOptimizer,
+        requests::{RequestReceiver, ResponseSender, StateMachineRequest},
+        StateMachine,
+        StateMachineError,
+    },
+    storage::{
+        history::ModelHistory,
+        redis::{Client, Connection},
+    },
 };
 
 #[cfg(feature = "metrics")]
 use crate::{metrics, metrics::MetricsSender};
 
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use futures::StreamExt;
+use redis::RedisResult;
 use tracing::Span;
 use tracing_futures::Instrument;
 
 /// Name of the current phase
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 pub enum PhaseName {
     Idle,
     Sum,
@@ -43,6 +56,7 @@ pub enum PhaseName {
     Unmask,
     Error,
     Shutdown,
+    Paused,
 }
 
 /// A trait that must be implemented by a state in order to move to a next state.
@@ -54,14 +68,20 @@ pub trait Phase {
     /// Run this phase to completion
     async fn run(&mut self) -> Result<(), StateError>;
 
+    /// Progress of the accepted messages, if this phase accepts messages.
+    fn message_progress(&self) -> Option<MessageProgress> {
+        None
+    }
+
     /// Moves from this state to the next state.
     fn next(self) -> Option<StateMachine>;
 }
 
 /// A trait that must be implemented by a state to handle a request.
+#[async_trait]
 pub trait Handler {
     /// Handles a request.
-    fn handle_request(&mut self, req: StateMachineRequest) -> Result<(), StateMachineError>;
+    async fn handle_request(&mut self, req: StateMachineRequest) -> Result<(), StateMachineError>;
 }
 
 /// I/O interfaces.
@@ -69,8 +89,15 @@ pub trait Handler {
 pub struct IO {
     /// The request receiver half.
     pub(in crate::state_machine) request_rx: RequestReceiver,
+    /// The admin command receiver half.
+    pub(in crate::state_machine) admin: AdminReceiver,
     /// The event publisher.
     pub(in crate::state_machine) events: EventPublisher,
+    /// The Redis client the coordinator state is written through to, or `None` if the state
+    /// is only kept in memory.
+    pub(in crate::state_machine) redis: Option<Client>,
+    /// The history the global models are stored in, or `None` if the history is disabled.
+    pub(in crate::state_machine) model_history: Option<ModelHistory>,
     #[cfg(feature = "metrics")]
     /// The metrics sender half.
     pub(in crate::state_machine) metrics_tx: MetricsSender,
@@ -82,22 +109,38 @@ pub struct IO {
 pub struct Shared {
     /// The coordinator state.
     pub(in crate::state_machine) state: CoordinatorState,
+    /// The server optimizer of the global model, or `None` if the averaged model is published
+    /// as-is.
+    pub(in crate::state_machine) optimizer: Option<ServerOptimizer>,
+    /// The number of consecutive storage errors, by which the next round is increasingly
+    /// delayed.
+    pub(in crate::state_machine) storage_failures: u32,
     /// I/O interfaces.
     pub(in crate::state_machine) io: IO,
 }
 
 impl Shared {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         coordinator_state: CoordinatorState,
         publisher: EventPublisher,
         request_rx: RequestReceiver,
+        admin: AdminReceiver,
+        redis: Option<Client>,
+        model_history: Option<ModelHistory>,
+        optimizer: Option<ServerOptimizer>,
         #[cfg(feature = "metrics")] metrics_tx: MetricsSender,
     ) -> Self {
         Self {
             state: coordinator_state,
+            optimizer,
+            storage_failures: 0,
             io: IO {
                 request_rx,
+                admin,
                 events: publisher,
+                redis,
+                model_history,
                 #[cfg(feature = "metrics")]
                 metrics_tx,
             },
@@ -114,6 +157,15 @@ impl Shared {
     pub fn round_id(&self) -> u64 {
         self.state.round_id
     }
+
+    /// Acquires a Redis connection, or returns `None` if the coordinator state is only kept in
+    /// memory.
+    pub(in crate::state_machine) async fn redis(&self) -> Option<Connection> {
+        match &self.io.redis {
+            Some(client) => Some(client.connection().await),
+            None => None,
+        }
+    }
 }
 
 /// The state corresponding to a phase of the PET protocol.
@@ -156,20 +208,29 @@ where
     /// Processes the next available request.
     async fn process_single(&mut self) -> Result<(), StateError> {
         let (req, span, resp_tx) = self.next_request().await?;
-        let _span_guard = span.enter();
-        let res = self.handle_request(req);
+        metrics!(
+            self.shared.io.metrics_tx,
+            metrics::request_queue::depth::update(self.shared.io.request_rx.queue_depth())
+        );
+        async move {
+            let res = self.handle_request(req).await;
+
+            if res.is_ok() {
+                self.broadcast_message_progress();
+            } else {
+                metrics!(
+                    self.shared.io.metrics_tx,
+                    metrics::message::rejected::increment(self.shared.state.round_id, Self::NAME)
+                );
+            }
 
-        if res.is_err() {
-            metrics!(
-                self.shared.io.metrics_tx,
-                metrics::message::rejected::increment(self.shared.state.round_id, Self::NAME)
-            );
+            // This may error out if the receiver has already be dropped but
+            // it doesn't matter for us.
+            let _ = resp_tx.send(res.map_err(Into::into));
+            Ok(())
         }
-
-        // This may error out if the receiver has already be dropped but
-        // it doesn't matter for us.
-        let _ = resp_tx.send(res.map_err(Into::into));
-        Ok(())
+        .instrument(span)
+        .await
     }
 }
 
@@ -189,9 +250,23 @@ where
             self.shared.io.events.broadcast_phase(
                 phase,
             );
+            let messages = self.message_progress();
+            self.shared.io.events.broadcast_phase_start(unix_time(), messages);
 
             metrics!(self.shared.io.metrics_tx, metrics::phase::update(phase));
 
+            // The error, shutdown and paused phases are not persisted: a restarted coordinator
+            // resumes from the last phase of the PET protocol instead.
+            match phase {
+                PhaseName::Error | PhaseName::Shutdown | PhaseName::Paused => {}
+                _ => {
+                    if let Err(err) = self.persist_state(phase).await {
+                        warn!("failed to persist the coordinator state: {}", err);
+                        return Some(self.into_error_state(err.into()));
+                    }
+                }
+            }
+
             if let Err(err) = self.run().await {
                 warn!("phase failed: {:?}", err);
                 return Some(self.into_error_state(err));
@@ -212,11 +287,33 @@ where
                 }
             }
 
+            self.drain_admin_commands();
+
             info!("transitioning to the next phase");
             self.next()
         }.instrument(span).await
     }
 
+    /// Broadcasts the progress of the accepted messages.
+    fn broadcast_message_progress(&mut self) {
+        if let Some(messages) = self.message_progress() {
+            self.shared.io.events.broadcast_message_progress(messages);
+        }
+    }
+
+    /// Records the given phase in the coordinator state and writes the state through to Redis.
+    async fn persist_state(&mut self, phase: PhaseName) -> RedisResult<()> {
+        self.shared.state.phase = phase;
+        if let Some(redis) = self.shared.redis().await {
+            redis.set_coordinator_state(&self.shared.state).await?;
+            if self.shared.storage_failures != 0 {
+                self.shared.storage_failures = 0;
+                self.shared.io.events.broadcast_storage_failures(0);
+            }
+        }
+        Ok(())
+    }
+
     /// Process all the pending requests that are now considered
     /// outdated. This happens at the end of each phase, before
     /// transitioning to the next phase.
@@ -244,18 +341,70 @@ where
 
 // Functions that are available to all states
 impl<S> PhaseState<S> {
-    /// Receives the next [`Request`].
+    /// Receives the next [`Request`]. Admin commands which arrive in the meantime are applied.
     ///
     /// # Errors
-    /// Returns [`StateError::ChannelError`] when all sender halves have been dropped.
+    /// Returns [`StateError::ChannelError`] when all sender halves have been dropped and
+    /// [`StateError::Aborted`] or [`StateError::Restarted`] when an admin command interrupts the
+    /// current phase.
     async fn next_request(
         &mut self,
     ) -> Result<(StateMachineRequest, Span, ResponseSender), StateError> {
         debug!("waiting for the next incoming request");
-        self.shared.io.request_rx.next().await.ok_or_else(|| {
-            error!("request receiver broken: senders have been dropped");
-            StateError::ChannelError("all message senders have been dropped!")
-        })
+        loop {
+            let io = &mut self.shared.io;
+            tokio::select! {
+                req = io.request_rx.next() => {
+                    return req.ok_or_else(|| {
+                        error!("request receiver broken: senders have been dropped");
+                        StateError::ChannelError("all message senders have been dropped!")
+                    });
+                }
+                command = io.admin.recv() => self.apply_admin_command(command)?,
+            }
+        }
+    }
+
+    /// Applies an admin command.
+    ///
+    /// Pausing, resuming, shutting down and updating the PET settings only take effect at the end
+    /// of the current round.
+    ///
+    /// # Errors
+    /// Returns [`StateError::Aborted`] or [`StateError::Restarted`] if the command interrupts the
+    /// current phase.
+    fn apply_admin_command(&mut self, command: AdminCommand) -> Result<(), StateError> {
+        info!("received admin command: {:?}", command);
+        let admin = &mut self.shared.io.admin;
+        match command {
+            AdminCommand::Pause => admin.set_paused(true),
+            AdminCommand::Resume => admin.set_paused(false),
+            AdminCommand::Shutdown => admin.set_shutdown(),
+            AdminCommand::Abort => return Err(StateError::Aborted),
+            AdminCommand::Restart => {
+                admin.set_paused(false);
+                return Err(StateError::Restarted);
+            }
+            AdminCommand::UpdatePetSettings(settings) => admin.set_pet_settings(settings),
+        }
+        Ok(())
+    }
+
+    /// Applies the admin commands which arrived during a phase that didn't receive them.
+    ///
+    /// Aborting and restarting commands are dropped, since the phase is already over.
+    fn drain_admin_commands(&mut self) {
+        while let Some(command) = self.shared.io.admin.try_recv() {
+            match command {
+                AdminCommand::Abort | AdminCommand::Restart => {
+                    warn!("dropping admin command {:?}: the phase is over", command)
+                }
+                command => {
+                    // only aborting and restarting commands fail
+                    let _ = self.apply_admin_command(command);
+                }
+            }
+        }
     }
 
     fn try_next_request(
@@ -281,6 +430,14 @@ impl<S> PhaseState<S> {
     }
 }
 
+/// Gets the current Unix time in seconds.
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
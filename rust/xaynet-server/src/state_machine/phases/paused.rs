@@ -0,0 +1,92 @@
+use futures::StreamExt;
+
+use crate::state_machine::{
+    admin::AdminCommand,
+    phases::{Idle, Phase, PhaseName, PhaseState, Shared, Shutdown},
+    StateError,
+    StateMachine,
+    StateMachineError,
+};
+
+#[cfg(feature = "metrics")]
+use crate::metrics;
+
+/// Paused state
+#[derive(Debug)]
+pub struct Paused;
+
+#[async_trait]
+impl Phase for PhaseState<Paused> {
+    const NAME: PhaseName = PhaseName::Paused;
+
+    /// Waits until an admin resumes the coordinator, restarts a round or requests a shutdown.
+    /// Requests which arrive in the meantime are rejected.
+    ///
+    /// See the [module level documentation](../index.html) for more details.
+    async fn run(&mut self) -> Result<(), StateError> {
+        info!("paused before the next round");
+        self.wait_for_resume().await
+    }
+
+    /// Moves from the paused state to the next state.
+    ///
+    /// See the [module level documentation](../index.html) for more details.
+    fn next(self) -> Option<StateMachine> {
+        Some(if self.shared.io.admin.is_shutdown() {
+            info!("shutdown requested by an admin");
+            PhaseState::<Shutdown>::new(self.shared).into()
+        } else {
+            PhaseState::<Idle>::new(self.shared).into()
+        })
+    }
+}
+
+impl PhaseState<Paused> {
+    /// Creates a new paused state.
+    pub fn new(shared: Shared) -> Self {
+        info!("state transition");
+        Self {
+            inner: Paused,
+            shared,
+        }
+    }
+
+    /// Rejects requests and applies admin commands until the coordinator is resumed, a round is
+    /// restarted or a shutdown is requested.
+    async fn wait_for_resume(&mut self) -> Result<(), StateError> {
+        while self.shared.io.admin.is_paused() && !self.shared.io.admin.is_shutdown() {
+            let io = &mut self.shared.io;
+            tokio::select! {
+                req = io.request_rx.next() => {
+                    let (_req, span, resp_tx) = req.ok_or_else(|| {
+                        error!("request receiver broken: senders have been dropped");
+                        StateError::ChannelError("all message senders have been dropped!")
+                    })?;
+                    let _span_guard = span.enter();
+                    info!("rejecting request");
+                    let _ = resp_tx.send(Err(StateMachineError::MessageRejected));
+
+                    metrics!(
+                        self.shared.io.metrics_tx,
+                        metrics::message::rejected::increment(
+                            self.shared.state.round_id,
+                            Self::NAME
+                        )
+                    );
+                }
+                command = io.admin.recv() => {
+                    match command {
+                        AdminCommand::Restart => {
+                            info!("starting a round despite the pause");
+                            self.shared.io.admin.set_paused(false);
+                            return Ok(());
+                        }
+                        AdminCommand::Abort => info!("no round to abort while paused"),
+                        other => self.apply_admin_command(other)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,13 +1,17 @@
 use std::sync::Arc;
 
+use redis::RedisResult;
 use xaynet_core::{LocalSeedDict, SeedDict, SumDict};
 
-use crate::state_machine::{
-    events::DictionaryUpdate,
-    phases::{Handler, Phase, PhaseName, PhaseState, Shared, StateError, Update},
-    requests::{StateMachineRequest, SumRequest},
-    StateMachine,
-    StateMachineError,
+use crate::{
+    state_machine::{
+        events::{DictionaryUpdate, MessageProgress},
+        phases::{Handler, Phase, PhaseName, PhaseState, Shared, StateError, Update},
+        requests::{StateMachineRequest, SumRequest},
+        StateMachine,
+        StateMachineError,
+    },
+    storage::{redis::Client, AddSumParticipant},
 };
 
 #[cfg(feature = "metrics")]
@@ -31,21 +35,21 @@ impl Sum {
     }
 }
 
+#[async_trait]
 impl Handler for PhaseState<Sum> {
     /// Handles a [`StateMachineRequest`].
     ///
     /// If the request is a [`StateMachineRequest::Update`] or
     /// [`StateMachineRequest::Sum2`] request, the request sender will receive a
     /// [`StateMachineError::MessageRejected`].
-    fn handle_request(&mut self, req: StateMachineRequest) -> Result<(), StateMachineError> {
+    async fn handle_request(&mut self, req: StateMachineRequest) -> Result<(), StateMachineError> {
         match req {
             StateMachineRequest::Sum(sum_req) => {
                 metrics!(
                     self.shared.io.metrics_tx,
                     metrics::message::sum::increment(self.shared.state.round_id, Self::NAME)
                 );
-                self.handle_sum(sum_req);
-                Ok(())
+                self.handle_sum(sum_req).await
             }
             _ => Err(StateMachineError::MessageRejected),
         }
@@ -68,7 +72,11 @@ where
         self.process_during(Duration::from_secs(min_time)).await?;
 
         let time_left = self.shared.state.max_sum_time - min_time;
-        timeout(Duration::from_secs(time_left), self.process_until_enough()).await??;
+        let processed = timeout(Duration::from_secs(time_left), self.process_until_enough()).await;
+        // the count is also recorded if the phase timed out, to adapt the fractions of the next
+        // round accordingly
+        self.shared.state.last_sum_count = Some(self.inner.sum_dict.len());
+        processed??;
 
         info!(
             "{} sum messages handled (min {} required)",
@@ -79,6 +87,14 @@ where
         Ok(())
     }
 
+    fn message_progress(&self) -> Option<MessageProgress> {
+        Some(MessageProgress {
+            count: self.inner.sum_dict.len(),
+            min_count: self.shared.state.min_sum_count,
+            max_time: self.shared.state.max_sum_time,
+        })
+    }
+
     fn next(self) -> Option<StateMachine> {
         let Self {
             inner: Sum {
@@ -133,13 +149,51 @@ impl PhaseState<Sum> {
         }
     }
 
+    /// Restores a sum state from the sum dictionary stored in Redis.
+    pub async fn restore(shared: Shared, redis: &Client) -> RedisResult<Self> {
+        info!("restoring sum phase");
+        let sum_dict = redis.connection().await.get_sum_dict().await?;
+        Ok(Self {
+            inner: Sum {
+                sum_dict,
+                seed_dict: None,
+            },
+            shared,
+        })
+    }
+
     /// Handles a sum request.
-    fn handle_sum(&mut self, req: SumRequest) {
+    ///
+    /// # Errors
+    /// Fails if the participant already sent a sum message or if the sum participant could not
+    /// be written to Redis.
+    async fn handle_sum(&mut self, req: SumRequest) -> Result<(), StateMachineError> {
         let SumRequest {
             participant_pk,
             ephm_pk,
         } = req;
+
+        if self.inner.sum_dict.contains_key(&participant_pk) {
+            warn!("sum participant already registered, ignoring sum message");
+            return Err(StateMachineError::MessageRejected);
+        }
+
+        if let Some(redis) = self.shared.redis().await {
+            match redis.add_sum_participant(&participant_pk, &ephm_pk).await {
+                Ok(AddSumParticipant::Ok) => {}
+                Ok(AddSumParticipant::AlreadyExists) => {
+                    warn!("sum participant already stored, ignoring sum message");
+                    return Err(StateMachineError::MessageRejected);
+                }
+                Err(err) => {
+                    warn!("failed to store sum participant: {}", err);
+                    return Err(StateMachineError::InternalError);
+                }
+            }
+        }
+
         self.inner.sum_dict.insert(participant_pk, ephm_pk);
+        Ok(())
     }
 
     /// Freezes the sum dictionary.
@@ -242,4 +296,59 @@ mod test {
             }
         );
     }
+    #[tokio::test]
+    pub async fn sum_twice() {
+        let sum = Sum {
+            sum_dict: SumDict::new(),
+            seed_dict: None,
+        };
+        let (state_machine, request_tx, events) = StateMachineBuilder::new()
+            .with_phase(sum)
+            .with_sum_ratio(1.0)
+            .with_update_ratio(0.0)
+            .with_min_sum(2)
+            .with_model_size(4)
+            .build();
+        assert!(state_machine.is_sum());
+
+        let round_params = events.params_listener().get_latest().event;
+        let seed = round_params.seed.clone();
+
+        // A participant that sends a second sum message is rejected
+        // and the phase only ends once another participant has sent
+        // its sum message
+        let mut summer_1 = utils::generate_summer(&seed, 1.0, 0.0);
+        let mut summer_2 = utils::generate_summer(&seed, 1.0, 0.0);
+        let msg_1 = summer_1.compose_sum_message(round_params.pk);
+        let msg_2 = summer_2.compose_sum_message(round_params.pk);
+        let requests_fut = async {
+            request_tx.msg(&msg_1).await.unwrap();
+            match request_tx.msg(&msg_1).await {
+                Err(StateMachineError::MessageRejected) => {}
+                res => panic!("expected the message to be rejected, got {:?}", res),
+            }
+            request_tx.msg(&msg_2).await.unwrap();
+        };
+        let transition_fut = async { state_machine.next().await.unwrap() };
+
+        let ((), state_machine) = tokio::join!(requests_fut, transition_fut);
+        let PhaseState {
+            inner: update_state,
+            ..
+        } = state_machine.into_update_phase_state();
+        assert_eq!(update_state.frozen_sum_dict().len(), 2);
+        assert_eq!(
+            update_state.frozen_sum_dict().get(&summer_1.pk),
+            Some(&utils::ephm_pk(&msg_1))
+        );
+
+        // only the accepted messages are counted in the progress of the phase
+        let progress = events.progress_listener().get_latest().event;
+        assert_eq!(
+            progress
+                .messages
+                .map(|messages| (messages.count, messages.min_count)),
+            Some((2, 2))
+        );
+    }
 }
@@ -1,15 +1,22 @@
+use std::sync::Arc;
+
+use redis::RedisResult;
 use xaynet_core::{
     mask::{Aggregation, MaskObject},
     SumDict,
     SumParticipantPublicKey,
 };
 
-use crate::state_machine::{
-    coordinator::MaskDict,
-    phases::{Handler, Phase, PhaseName, PhaseState, Shared, StateError, Unmask},
-    requests::{StateMachineRequest, Sum2Request},
-    StateMachine,
-    StateMachineError,
+use crate::{
+    state_machine::{
+        coordinator::{mask_digest, MaskDict, MaskSubmissions},
+        events::{DictionaryUpdate, MaskLengthUpdate, MessageProgress},
+        phases::{Handler, Phase, PhaseName, PhaseState, Shared, StateError, Unmask},
+        requests::{StateMachineRequest, Sum2Request},
+        StateMachine,
+        StateMachineError,
+    },
+    storage::redis::Client,
 };
 
 #[cfg(feature = "metrics")]
@@ -34,6 +41,9 @@ pub struct Sum2 {
 
     /// The scalar mask dictionary built during the sum2 phase.
     scalar_mask_dict: MaskDict,
+
+    /// The digests of the masks submitted by the sum participants during the sum2 phase.
+    mask_submissions: MaskSubmissions,
 }
 
 #[cfg(test)]
@@ -57,6 +67,10 @@ impl Sum2 {
     pub fn scalar_mask_dict(&self) -> &MaskDict {
         &self.scalar_mask_dict
     }
+
+    pub fn mask_submissions(&self) -> &MaskSubmissions {
+        &self.mask_submissions
+    }
 }
 
 #[async_trait]
@@ -85,6 +99,14 @@ where
         Ok(())
     }
 
+    fn message_progress(&self) -> Option<MessageProgress> {
+        Some(MessageProgress {
+            count: self.mask_count(),
+            min_count: self.shared.state.min_sum_count,
+            max_time: self.shared.state.max_sum_time,
+        })
+    }
+
     /// Moves from the sum2 state to the next state.
     ///
     /// See the [module level documentation](../index.html) for more details.
@@ -96,6 +118,7 @@ where
                 self.inner.scalar_agg,
                 self.inner.model_mask_dict,
                 self.inner.scalar_mask_dict,
+                self.inner.mask_submissions,
             )
             .into(),
         )
@@ -120,20 +143,21 @@ where
     }
 }
 
+#[async_trait]
 impl Handler for PhaseState<Sum2> {
     /// Handles a [`StateMachineRequest`],
     ///
     /// If the request is a [`StateMachineRequest::Sum`] or
     /// [`StateMachineRequest::Update`] request, the request sender
     /// will receive a [`StateMachineError::MessageRejected`].
-    fn handle_request(&mut self, req: StateMachineRequest) -> Result<(), StateMachineError> {
+    async fn handle_request(&mut self, req: StateMachineRequest) -> Result<(), StateMachineError> {
         match req {
             StateMachineRequest::Sum2(sum2_req) => {
                 metrics!(
                     self.shared.io.metrics_tx,
                     metrics::message::sum2::increment(self.shared.state.round_id, Self::NAME)
                 );
-                self.handle_sum2(sum2_req)
+                self.handle_sum2(sum2_req).await
             }
             _ => Err(StateMachineError::MessageRejected),
         }
@@ -156,38 +180,95 @@ impl PhaseState<Sum2> {
                 scalar_agg,
                 model_mask_dict: MaskDict::new(),
                 scalar_mask_dict: MaskDict::new(),
+                mask_submissions: MaskSubmissions::new(),
             },
             shared,
         }
     }
 
+    /// Restores a sum2 state from the sum dictionary, aggregations and mask dictionaries stored
+    /// in Redis.
+    ///
+    /// The sum participants that already sent their masks are removed from the sum dictionary.
+    pub async fn restore(mut shared: Shared, redis: &Client) -> RedisResult<Self> {
+        info!("restoring sum2 phase");
+        let mut sum_dict = redis.connection().await.get_sum_dict().await?;
+        let mask_submissions = redis.connection().await.get_mask_submissions().await?;
+        for pk in mask_submissions.keys() {
+            sum_dict.remove(pk);
+        }
+        let (model_agg, scalar_agg) = match redis.connection().await.get_aggregations().await? {
+            Some(aggregations) => aggregations,
+            None => (
+                Aggregation::new(shared.state.mask_config.model, shared.state.model_size),
+                Aggregation::new(shared.state.mask_config.scalar, 1),
+            ),
+        };
+        let (model_mask_dict, scalar_mask_dict) = redis.connection().await.get_mask_dicts().await?;
+        let seed_dict = redis.connection().await.get_seed_dict().await?;
+
+        info!("broadcasting the restored mask length");
+        shared
+            .io
+            .events
+            .broadcast_mask_length(MaskLengthUpdate::New(model_agg.len()));
+
+        info!("broadcasting the restored global seed dictionary");
+        shared
+            .io
+            .events
+            .broadcast_seed_dict(DictionaryUpdate::New(Arc::new(seed_dict)));
+
+        let mut sum2 = Self::new(shared, sum_dict, model_agg, scalar_agg);
+        sum2.inner.model_mask_dict = model_mask_dict;
+        sum2.inner.scalar_mask_dict = scalar_mask_dict;
+        sum2.inner.mask_submissions = mask_submissions;
+        Ok(sum2)
+    }
+
     /// Handles a sum2 request.
     /// If the handling of the sum2 message fails, an error is returned to the request sender.
-    fn handle_sum2(&mut self, req: Sum2Request) -> Result<(), StateMachineError> {
+    async fn handle_sum2(&mut self, req: Sum2Request) -> Result<(), StateMachineError> {
         let Sum2Request {
             participant_pk,
             model_mask,
             scalar_mask,
         } = req;
         self.add_mask(&participant_pk, model_mask, scalar_mask)
+            .await
     }
 
     /// Adds a mask to the mask dictionary.
     ///
     /// # Errors
-    /// Fails if the sum participant didn't register in the sum phase or it is a repetition.
-    fn add_mask(
+    /// Fails if the sum participant didn't register in the sum phase, it is a repetition or the
+    /// masks could not be written to Redis.
+    async fn add_mask(
         &mut self,
         pk: &SumParticipantPublicKey,
         model_mask: MaskObject,
         scalar_mask: MaskObject,
     ) -> Result<(), StateMachineError> {
-        // We remove the participant key here to make sure a participant
-        // cannot submit a mask multiple times
-        if self.inner.sum_dict.remove(pk).is_none() {
+        if !self.inner.sum_dict.contains_key(pk) {
             return Err(StateMachineError::MessageRejected);
         }
 
+        let digest = mask_digest(&model_mask, &scalar_mask);
+        if let Some(redis) = self.shared.redis().await {
+            redis
+                .add_masks(pk, &model_mask, &scalar_mask, &digest)
+                .await
+                .map_err(|err| {
+                    warn!("failed to store the masks: {}", err);
+                    StateMachineError::InternalError
+                })?;
+        }
+
+        // We remove the participant key here to make sure a participant
+        // cannot submit a mask multiple times
+        self.inner.sum_dict.remove(pk);
+        self.inner.mask_submissions.insert(*pk, digest);
+
         if let Some(count) = self.inner.model_mask_dict.get_mut(&model_mask) {
             *count += 1;
         } else {
@@ -252,17 +333,18 @@ mod test {
         sum_dict.insert(summer.pk, ephm_pk);
 
         // Generate a new masked model, seed dictionary and aggregration
-        let updater = utils::generate_updater(&seed, sum_ratio, update_ratio);
+        let mut updater = utils::generate_updater(&seed, sum_ratio, update_ratio);
         let scalar = 1.0 / (n_updaters as f64 * update_ratio);
         let model = Model::from_primitives(vec![0; model_size].into_iter()).unwrap();
-        let msg =
-            updater.compose_update_message(coord_keys.public, &sum_dict, scalar, model.clone());
+        let msg = updater
+            .compose_update_message(coord_keys.public, &sum_dict, scalar, model.clone())
+            .unwrap();
         let masked_model = utils::masked_model(&msg);
         let masked_scalar = utils::masked_scalar(&msg);
         let local_seed_dict = utils::local_seed_dict(&msg);
-        let mut aggregation = Aggregation::new(utils::mask_settings().into(), model_size);
+        let mut aggregation = Aggregation::new(utils::mask_config().model, model_size);
         aggregation.aggregate(masked_model.clone());
-        let mut scalar_agg = Aggregation::new(utils::mask_settings().into(), 1);
+        let mut scalar_agg = Aggregation::new(utils::mask_config().scalar, 1);
         scalar_agg.aggregate(masked_scalar.clone());
 
         // Create the state machine
@@ -272,6 +354,7 @@ mod test {
             scalar_agg,
             model_mask_dict: MaskDict::new(),
             scalar_mask_dict: MaskDict::new(),
+            mask_submissions: MaskSubmissions::new(),
         };
 
         let (state_machine, request_tx, events) = StateMachineBuilder::new()
@@ -281,7 +364,7 @@ mod test {
             .with_update_ratio(update_ratio)
             .with_min_sum(n_summers)
             .with_min_update(n_updaters)
-            .with_mask_config(utils::mask_settings().into())
+            .with_mask_config(utils::mask_config())
             .build();
         assert!(state_machine.is_sum2());
 
@@ -307,6 +390,8 @@ mod test {
         assert_eq!(unmask_state.mask_dict().len(), 1);
         let (mask, count) = unmask_state.mask_dict().iter().next().unwrap().clone();
         assert_eq!(*count, 1);
+        assert_eq!(unmask_state.mask_submissions().len(), 1);
+        assert!(unmask_state.mask_submissions().contains_key(&summer.pk));
 
         let unmasked_model = unmask_state
             .aggregation()
@@ -1,13 +1,21 @@
-use std::{cmp::Ordering, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 
-use xaynet_core::mask::{Aggregation, MaskObject, Model};
+use redis::RedisResult;
+use xaynet_core::{
+    crypto::{ByteObject, Sha256},
+    mask::{Aggregation, MaskObject, Model},
+    SumParticipantPublicKey,
+};
 
-use crate::state_machine::{
-    coordinator::MaskDict,
-    events::ModelUpdate,
-    phases::{Idle, Phase, PhaseName, PhaseState, Shared, StateError},
-    RoundFailed,
-    StateMachine,
+use crate::{
+    state_machine::{
+        coordinator::{mask_digest, MaskDict, MaskSubmissions, PartialMaskDigest},
+        events::{ModelUpdate, RoundOutcome},
+        phases::{Idle, Phase, PhaseName, PhaseState, Shared, StateError},
+        RoundFailed,
+        StateMachine,
+    },
+    storage::{history::ModelMetadata, redis::Client},
 };
 
 #[cfg(feature = "metrics")]
@@ -27,6 +35,43 @@ pub struct Unmask {
 
     /// The scalar mask dictionary built during the sum2 phase.
     scalar_mask_dict: MaskDict,
+
+    /// The digests of the masks submitted by the sum participants during the sum2 phase.
+    mask_submissions: MaskSubmissions,
+
+    /// The audit of the masks used for unmasking.
+    audit: Option<UnmaskAudit>,
+}
+
+/// An audit of the masks which were used for unmasking the global model of a round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnmaskAudit {
+    /// The round in which the masks were submitted.
+    pub round_id: u64,
+
+    /// The number of sum participants which submitted masks.
+    pub nb_submissions: usize,
+
+    /// The sum participants whose masks were not used for unmasking.
+    pub rejected: Vec<SumParticipantPublicKey>,
+}
+
+impl UnmaskAudit {
+    /// Creates an audit from the mask submissions and the digest of the masks used for
+    /// unmasking.
+    pub fn new(round_id: u64, submissions: &MaskSubmissions, used: &Sha256) -> Self {
+        let mut rejected = submissions
+            .iter()
+            .filter(|(_, digest)| *digest != used)
+            .map(|(pk, _)| *pk)
+            .collect::<Vec<_>>();
+        rejected.sort_unstable();
+        Self {
+            round_id,
+            nb_submissions: submissions.len(),
+            rejected,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -37,6 +82,9 @@ impl Unmask {
     pub fn mask_dict(&self) -> &MaskDict {
         &self.model_mask_dict
     }
+    pub fn mask_submissions(&self) -> &MaskSubmissions {
+        &self.mask_submissions
+    }
 }
 
 #[async_trait]
@@ -55,12 +103,22 @@ impl Phase for PhaseState<Unmask> {
         );
 
         let global_model = self.end_round()?;
+        let global_model = self.optimize(global_model);
+        self.store_optimizer_state().await?;
+        self.record_audit().await?;
+        self.add_to_history(&global_model).await;
+        self.spend_privacy_budget();
+        self.shared.state.completed_rounds += 1;
 
         info!("broadcasting the new global model");
         self.shared
             .io
             .events
             .broadcast_model(ModelUpdate::New(Arc::new(global_model)));
+        self.shared
+            .io
+            .events
+            .broadcast_round_outcome(RoundOutcome::Completed);
 
         Ok(())
     }
@@ -70,7 +128,7 @@ impl Phase for PhaseState<Unmask> {
     /// See the [module level documentation](../index.html) for more details.
     fn next(self) -> Option<StateMachine> {
         info!("going back to idle phase");
-        Some(PhaseState::<Idle>::new(self.shared).into())
+        Some(PhaseState::<Idle>::next_round(self.shared))
     }
 }
 
@@ -82,6 +140,7 @@ impl PhaseState<Unmask> {
         scalar_agg: Aggregation,
         model_mask_dict: MaskDict,
         scalar_mask_dict: MaskDict,
+        mask_submissions: MaskSubmissions,
     ) -> Self {
         info!("state transition");
         Self {
@@ -90,68 +149,427 @@ impl PhaseState<Unmask> {
                 scalar_agg: Some(scalar_agg),
                 model_mask_dict,
                 scalar_mask_dict,
+                mask_submissions,
+                audit: None,
             },
             shared,
         }
     }
 
-    /// Freezes the mask dictionary.
-    fn freeze_mask_dict(&mut self) -> Result<(MaskObject, MaskObject), RoundFailed> {
+    /// Restores an unmask state from the aggregations, mask dictionaries and mask submissions
+    /// stored in Redis.
+    pub async fn restore(shared: Shared, redis: &Client) -> RedisResult<Self> {
+        info!("restoring unmask phase");
+        let (model_agg, scalar_agg) = match redis.connection().await.get_aggregations().await? {
+            Some(aggregations) => aggregations,
+            None => (
+                Aggregation::new(shared.state.mask_config.model, shared.state.model_size),
+                Aggregation::new(shared.state.mask_config.scalar, 1),
+            ),
+        };
+        let (model_mask_dict, scalar_mask_dict) = redis.connection().await.get_mask_dicts().await?;
+        let mask_submissions = redis.connection().await.get_mask_submissions().await?;
+        Ok(Self::new(
+            shared,
+            model_agg,
+            scalar_agg,
+            model_mask_dict,
+            scalar_mask_dict,
+            mask_submissions,
+        ))
+    }
+
+    /// Freezes the mask dictionaries.
+    ///
+    /// Selects the pair of a model mask and a scalar mask which unmasks the aggregations, see
+    /// [`select_masks()`] for details.
+    fn freeze_mask_dict(
+        &mut self,
+        model_agg: &Aggregation,
+        scalar_agg: &Aggregation,
+    ) -> Result<(MaskObject, MaskObject), RoundFailed> {
         if self.inner.model_mask_dict.is_empty() {
             return Err(RoundFailed::NoMask);
         }
 
-        let model_mask = self
-            .inner
-            .model_mask_dict
-            .drain()
-            .fold(
-                (None, 0_usize),
-                |(unique_mask, unique_count), (mask, count)| match unique_count.cmp(&count) {
-                    Ordering::Less => (Some(mask), count),
-                    Ordering::Greater => (unique_mask, unique_count),
-                    Ordering::Equal => (None, unique_count),
-                },
-            )
-            .0
-            .ok_or(RoundFailed::AmbiguousMasks)?;
-
-        // TODO remove duplication
-        let scalar_mask = self
-            .inner
-            .scalar_mask_dict
-            .drain()
-            .fold(
-                (None, 0_usize),
-                |(unique_mask, unique_count), (mask, count)| match unique_count.cmp(&count) {
-                    Ordering::Less => (Some(mask), count),
-                    Ordering::Greater => (unique_mask, unique_count),
-                    Ordering::Equal => (None, unique_count),
-                },
-            )
-            .0
-            .ok_or(RoundFailed::AmbiguousMasks)?;
-
-        Ok((model_mask, scalar_mask))
+        select_masks(
+            self.inner.model_mask_dict.drain().collect(),
+            self.inner.scalar_mask_dict.drain().collect(),
+            &self.inner.mask_submissions,
+            (model_agg, scalar_agg),
+            self.shared.state.mask_quorum,
+        )
     }
 
     fn end_round(&mut self) -> Result<Model, RoundFailed> {
-        let (model_mask, scalar_mask) = self.freeze_mask_dict()?;
-
         // Safe unwrap: State::<Unmask>::new always creates Some(aggregation)
         let model_agg = self.inner.model_agg.take().unwrap();
         let scalar_agg = self.inner.scalar_agg.take().unwrap();
 
-        model_agg
-            .validate_unmasking(&model_mask)
-            .map_err(RoundFailed::from)?;
-        scalar_agg
-            .validate_unmasking(&scalar_mask)
-            .map_err(RoundFailed::from)?;
+        let (model_mask, scalar_mask) = self.freeze_mask_dict(&model_agg, &scalar_agg)?;
+
+        self.inner.audit = Some(UnmaskAudit::new(
+            self.shared.state.round_id,
+            &self.inner.mask_submissions,
+            &mask_digest(&model_mask, &scalar_mask),
+        ));
 
         let model = model_agg.unmask(model_mask);
         let scalar = scalar_agg.unmask(scalar_mask);
 
         Ok(Aggregation::correct(model, scalar))
     }
+
+    /// Logs the unmask audit and stores it in Redis.
+    async fn record_audit(&mut self) -> Result<(), StateError> {
+        let audit = match self.inner.audit.take() {
+            Some(audit) => audit,
+            None => return Ok(()),
+        };
+
+        if audit.rejected.is_empty() {
+            info!(
+                "the masks of all {} sum2 participants were used for unmasking",
+                audit.nb_submissions
+            );
+        } else {
+            warn!(
+                "the masks of {} out of {} sum2 participants were not used for unmasking: {:?}",
+                audit.rejected.len(),
+                audit.nb_submissions,
+                audit.rejected
+            );
+        }
+
+        if let Some(redis) = self.shared.redis().await {
+            redis.add_unmask_audit(&audit).await?;
+        }
+        Ok(())
+    }
+
+    /// Adds the privacy budget of the round to the spent budget, if differential privacy is
+    /// enabled.
+    fn spend_privacy_budget(&mut self) {
+        let state = &mut self.shared.state;
+        if let (Some(dp), Some(_)) = (state.dp, state.round_params.noise) {
+            state.epsilon_spent += dp.epsilon;
+            state.delta_spent += dp.delta;
+            info!(
+                "spent privacy budget: epsilon {}, delta {}",
+                state.epsilon_spent, state.delta_spent
+            );
+        }
+    }
+
+    /// Applies the server optimizer to the averaged model, if the optimizer is enabled.
+    fn optimize(&mut self, averaged_model: Model) -> Model {
+        match self.shared.optimizer.as_mut() {
+            Some(optimizer) => {
+                info!("applying the server optimizer");
+                optimizer.step(averaged_model)
+            }
+            None => averaged_model,
+        }
+    }
+
+    /// Stores the state of the server optimizer in Redis, if the optimizer is enabled.
+    async fn store_optimizer_state(&self) -> Result<(), StateError> {
+        let optimizer_state = match self.shared.optimizer.as_ref().and_then(|o| o.state()) {
+            Some(optimizer_state) => optimizer_state,
+            None => return Ok(()),
+        };
+        if let Some(redis) = self.shared.redis().await {
+            redis.set_optimizer_state(optimizer_state).await?;
+        }
+        Ok(())
+    }
+
+    /// Adds the global model to the model history, if the history is enabled.
+    ///
+    /// A failure to store the model is only logged, because the round has been completed
+    /// successfully anyway.
+    async fn add_to_history(&self, global_model: &Model) {
+        let history = match &self.shared.io.model_history {
+            Some(history) => history,
+            None => return,
+        };
+
+        let state = &self.shared.state;
+        let metadata = ModelMetadata {
+            round_id: state.round_id,
+            round_seed_hash: Sha256::hash(state.round_params.seed.as_slice()),
+            nb_sum: state.last_sum_count.unwrap_or_default(),
+            nb_update: state.last_update_count.unwrap_or_default(),
+            nb_sum2: self.inner.mask_submissions.len(),
+        };
+        match history.add_model(&metadata, global_model).await {
+            Ok(()) => info!("added the global model to the model history"),
+            Err(err) => error!(
+                "failed to add the global model of round {} to the model history: {}",
+                state.round_id, err
+            ),
+        }
+    }
+}
+
+/// Selects the pair of a model mask and a scalar mask which unmasks the aggregations.
+///
+/// Each sum participant submits a model mask together with a scalar mask, hence the masks are
+/// selected as pairs, which are identified by the digests of the `submissions`. Only pairs which
+/// were submitted by strictly more than the `quorum` fraction of the sum2 participants are
+/// considered. The considered pairs are verified against the aggregations in descending order of
+/// their number of submissions and the first pair whose masks are both verified is selected.
+///
+/// # Errors
+/// Fails if no pair reaches the quorum, if none of the considered pairs can be verified or if
+/// several verified pairs have the same number of submissions.
+fn select_masks(
+    model_mask_dict: MaskDict,
+    scalar_mask_dict: MaskDict,
+    submissions: &MaskSubmissions,
+    (model_agg, scalar_agg): (&Aggregation, &Aggregation),
+    quorum: f64,
+) -> Result<(MaskObject, MaskObject), RoundFailed> {
+    let nb_submissions = submissions.len() as f64;
+    let reaches_quorum = |count: usize| count as f64 > quorum * nb_submissions;
+    let mut pair_counts = HashMap::<Sha256, usize>::new();
+    for digest in submissions.values() {
+        *pair_counts.entry(*digest).or_default() += 1;
+    }
+
+    // a pair is submitted at most as often as each of its masks
+    let scalar_masks = scalar_mask_dict
+        .into_iter()
+        .filter(|(_, count)| reaches_quorum(*count))
+        .map(|(mask, _)| mask)
+        .collect::<Vec<_>>();
+    let mut candidates = Vec::new();
+    for (model_mask, _) in model_mask_dict
+        .into_iter()
+        .filter(|(_, count)| reaches_quorum(*count))
+    {
+        let partial_digest = PartialMaskDigest::new(&model_mask);
+        for scalar_mask in &scalar_masks {
+            match pair_counts.get(&partial_digest.finalize(scalar_mask)) {
+                Some(count) if reaches_quorum(*count) => {
+                    candidates.push((model_mask.clone(), scalar_mask.clone(), *count))
+                }
+                _ => {}
+            }
+        }
+    }
+    candidates.sort_unstable_by(|(_, _, count1), (_, _, count2)| count2.cmp(count1));
+
+    let mut selected: Option<(MaskObject, MaskObject, usize)> = None;
+    let mut first_error = None;
+    for (model_mask, scalar_mask, count) in candidates {
+        if let Some((_, _, selected_count)) = selected {
+            if count < selected_count {
+                break;
+            }
+        }
+
+        let verified = model_agg
+            .verify_unmasking(&model_mask)
+            .and_then(|_| scalar_agg.verify_unmasking(&scalar_mask));
+        match verified {
+            Ok(()) if selected.is_some() => return Err(RoundFailed::AmbiguousMasks),
+            Ok(()) => selected = Some((model_mask, scalar_mask, count)),
+            Err(err) => {
+                warn!(
+                    "masks submitted {} times failed verification: {}",
+                    count, err
+                );
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+
+    match (selected, first_error) {
+        (Some((model_mask, scalar_mask, _)), _) => Ok((model_mask, scalar_mask)),
+        (None, Some(err)) => Err(err.into()),
+        (None, None) => Err(RoundFailed::NoQuorum),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state_machine::tests::utils;
+    use num::{bigint::BigUint, traits::One};
+    use xaynet_core::{
+        crypto::{ByteObject, SigningKeyPair},
+        mask::{FromPrimitives, MaskSeed, Masker, UnmaskingError},
+    };
+
+    type Masks = (MaskObject, MaskObject);
+
+    /// Creates the aggregations of a single masked model and masked scalar together with their
+    /// masks.
+    fn masked_model() -> ((Aggregation, Aggregation), Masks) {
+        let config = utils::mask_config();
+        let seed = MaskSeed::generate();
+        let model = Model::from_primitives(vec![1_i32, 0, -1, 0].into_iter()).unwrap();
+        let (_, masked_model, masked_scalar) =
+            Masker::with_seed(config, seed.clone()).mask(0.5, model);
+        let aggregations = (
+            Aggregation::from(masked_model),
+            Aggregation::from(masked_scalar),
+        );
+        (aggregations, seed.derive_mask(4, config))
+    }
+
+    /// Creates masks that don't unmask the aggregations of [`masked_model()`].
+    fn wrong_masks() -> Masks {
+        MaskSeed::generate().derive_mask(4, utils::mask_config())
+    }
+
+    /// Shifts a mask by half of the group order, such that it never unmasks to valid values.
+    fn shifted(mask: &MaskObject) -> MaskObject {
+        let mut shifted = mask.clone();
+        let order = shifted.config.order();
+        shifted.data[0] = (&shifted.data[0] + &order / BigUint::from(2_u8)) % order;
+        shifted
+    }
+
+    /// Selects the masks from pairs of masks which were each submitted the given number of times.
+    fn select(
+        submitted: &[(Masks, usize)],
+        (model_agg, scalar_agg): &(Aggregation, Aggregation),
+        quorum: f64,
+    ) -> Result<Masks, RoundFailed> {
+        let mut model_mask_dict = MaskDict::new();
+        let mut scalar_mask_dict = MaskDict::new();
+        let mut submissions = MaskSubmissions::new();
+        for ((model_mask, scalar_mask), count) in submitted {
+            *model_mask_dict.entry(model_mask.clone()).or_default() += count;
+            *scalar_mask_dict.entry(scalar_mask.clone()).or_default() += count;
+            for _ in 0..*count {
+                let pk = SigningKeyPair::generate().public;
+                submissions.insert(pk, mask_digest(model_mask, scalar_mask));
+            }
+        }
+        select_masks(
+            model_mask_dict,
+            scalar_mask_dict,
+            &submissions,
+            (model_agg, scalar_agg),
+            quorum,
+        )
+    }
+
+    #[test]
+    fn select_masks_despite_tie() {
+        let (aggregations, masks) = masked_model();
+        let submitted = [(masks.clone(), 1), (wrong_masks(), 1)];
+
+        assert_eq!(select(&submitted, &aggregations, 0.).unwrap(), masks);
+    }
+
+    #[test]
+    fn select_masks_with_fewer_submissions() {
+        let (aggregations, masks) = masked_model();
+        let submitted = [(masks.clone(), 1), (wrong_masks(), 2)];
+
+        assert_eq!(select(&submitted, &aggregations, 0.).unwrap(), masks);
+    }
+
+    #[test]
+    fn select_masks_with_majority() {
+        let (aggregations, masks) = masked_model();
+        let mut submitted = vec![(masks.clone(), 2), (wrong_masks(), 1)];
+        assert_eq!(select(&submitted, &aggregations, 0.5).unwrap(), masks);
+
+        // without a strict majority, the masks aren't considered
+        submitted.push((wrong_masks(), 1));
+        assert_eq!(
+            select(&submitted, &aggregations, 0.5).unwrap_err(),
+            RoundFailed::NoQuorum
+        );
+    }
+
+    #[test]
+    fn select_masks_as_pairs() {
+        let (aggregations, (model_mask, scalar_mask)) = masked_model();
+        let wrong_model_mask = shifted(&model_mask);
+        let wrong_scalar_mask = shifted(&scalar_mask);
+        let mut submitted = vec![
+            ((model_mask.clone(), wrong_scalar_mask.clone()), 1),
+            ((wrong_model_mask.clone(), scalar_mask.clone()), 1),
+            ((model_mask.clone(), scalar_mask.clone()), 1),
+        ];
+
+        // each mask reaches the majority on its own, but the pair doesn't
+        assert_eq!(
+            select(&submitted, &aggregations, 0.5).unwrap_err(),
+            RoundFailed::NoQuorum
+        );
+
+        // the pairs with a wrong mask aren't selected even though they were submitted more often
+        submitted[0].1 = 2;
+        submitted[1].1 = 2;
+        assert_eq!(
+            select(&submitted, &aggregations, 0.).unwrap(),
+            (model_mask, scalar_mask)
+        );
+    }
+
+    #[test]
+    fn select_masks_without_verified_masks() {
+        let (aggregations, masks) = masked_model();
+        let submitted = [(masks, 1), (wrong_masks(), 2)];
+
+        // the only masks that reach the quorum are wrong
+        assert_eq!(
+            select(&submitted, &aggregations, 0.5).unwrap_err(),
+            RoundFailed::Unmasking(UnmaskingError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn select_masks_ambiguous() {
+        let (aggregations, (model_mask, scalar_mask)) = masked_model();
+        // a slightly different mask still unmasks the aggregation to valid values
+        let mut other_mask = model_mask.clone();
+        other_mask.data[0] = (&other_mask.data[0] + BigUint::one()) % other_mask.config.order();
+        let submitted = [
+            ((model_mask, scalar_mask.clone()), 1),
+            ((other_mask, scalar_mask), 1),
+        ];
+
+        assert_eq!(
+            select(&submitted, &aggregations, 0.).unwrap_err(),
+            RoundFailed::AmbiguousMasks
+        );
+    }
+
+    #[test]
+    fn select_no_masks() {
+        let (aggregations, _) = masked_model();
+        assert_eq!(
+            select(&[], &aggregations, 0.).unwrap_err(),
+            RoundFailed::NoQuorum
+        );
+    }
+
+    #[test]
+    fn audit_rejected_masks() {
+        let (_, (model_mask, scalar_mask)) = masked_model();
+        let used = mask_digest(&model_mask, &scalar_mask);
+        let rejected = SigningKeyPair::generate().public;
+        let mut submissions = MaskSubmissions::new();
+        submissions.insert(SigningKeyPair::generate().public, used);
+        submissions.insert(SigningKeyPair::generate().public, used);
+        submissions.insert(rejected, mask_digest(&model_mask, &wrong_masks().1));
+
+        let audit = UnmaskAudit::new(7, &submissions, &used);
+        assert_eq!(
+            audit,
+            UnmaskAudit {
+                round_id: 7,
+                nb_submissions: 3,
+                rejected: vec![rejected],
+            }
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use redis::RedisResult;
 use xaynet_core::{
     mask::{Aggregation, MaskObject},
     LocalSeedDict,
@@ -8,12 +9,15 @@ use xaynet_core::{
     UpdateParticipantPublicKey,
 };
 
-use crate::state_machine::{
-    events::{DictionaryUpdate, MaskLengthUpdate},
-    phases::{Handler, Phase, PhaseName, PhaseState, Shared, StateError, Sum2},
-    requests::{StateMachineRequest, UpdateRequest},
-    StateMachine,
-    StateMachineError,
+use crate::{
+    state_machine::{
+        events::{DictionaryUpdate, MaskLengthUpdate, MessageProgress},
+        phases::{Handler, Phase, PhaseName, PhaseState, Shared, StateError, Sum2},
+        requests::{StateMachineRequest, UpdateRequest},
+        StateMachine,
+        StateMachineError,
+    },
+    storage::redis::Client,
 };
 
 #[cfg(feature = "metrics")]
@@ -66,16 +70,35 @@ where
         self.process_during(Duration::from_secs(min_time)).await?;
 
         let time_left = self.shared.state.max_update_time - min_time;
-        timeout(Duration::from_secs(time_left), self.process_until_enough()).await??;
+        let processed = timeout(Duration::from_secs(time_left), self.process_until_enough()).await;
+        // the count is also recorded if the phase timed out, to adapt the fractions of the next
+        // round accordingly
+        self.shared.state.last_update_count = Some(self.updater_count());
+        processed??;
 
         info!(
             "{} update messages handled (min {} required)",
             self.updater_count(),
             self.shared.state.min_update_count
         );
+
+        if let Some(redis) = self.shared.redis().await {
+            info!("storing the aggregations");
+            redis
+                .set_aggregations(&self.inner.model_agg, &self.inner.scalar_agg)
+                .await?;
+        }
         Ok(())
     }
 
+    fn message_progress(&self) -> Option<MessageProgress> {
+        Some(MessageProgress {
+            count: self.updater_count(),
+            min_count: self.shared.state.min_update_count,
+            max_time: self.shared.state.max_update_time,
+        })
+    }
+
     fn next(self) -> Option<StateMachine> {
         let PhaseState {
             inner:
@@ -122,20 +145,21 @@ where
     }
 }
 
+#[async_trait]
 impl Handler for PhaseState<Update> {
     /// Handles a [`StateMachineRequest`].
     ///
     /// If the request is a [`StateMachineRequest::Sum`] or
     /// [`StateMachineRequest::Sum2`] request, the request sender will
     /// receive a [`StateMachineError::MessageRejected`].
-    fn handle_request(&mut self, req: StateMachineRequest) -> Result<(), StateMachineError> {
+    async fn handle_request(&mut self, req: StateMachineRequest) -> Result<(), StateMachineError> {
         match req {
             StateMachineRequest::Update(update_req) => {
                 metrics!(
                     self.shared.io.metrics_tx,
                     metrics::message::update::increment(self.shared.state.round_id, Self::NAME)
                 );
-                self.handle_update(update_req)
+                self.handle_update(update_req).await
             }
             _ => Err(StateMachineError::MessageRejected),
         }
@@ -150,17 +174,58 @@ impl PhaseState<Update> {
             inner: Update {
                 frozen_sum_dict,
                 seed_dict,
-                model_agg: Aggregation::new(shared.state.mask_config, shared.state.model_size),
-                // TODO separate config for scalars
-                scalar_agg: Aggregation::new(shared.state.mask_config, 1),
+                model_agg: Aggregation::new(
+                    shared.state.mask_config.model,
+                    shared.state.model_size,
+                ),
+                scalar_agg: Aggregation::new(shared.state.mask_config.scalar, 1),
             },
             shared,
         }
     }
 
+    /// Restores an update state from the sum dictionary, seed dictionary and masked objects stored
+    /// in Redis.
+    pub async fn restore(mut shared: Shared, redis: &Client) -> RedisResult<Self> {
+        info!("restoring update phase");
+        let frozen_sum_dict = redis.connection().await.get_sum_dict().await?;
+        let seed_dict = redis.connection().await.get_seed_dict().await?;
+
+        info!("broadcasting the restored sum dictionary");
+        shared
+            .io
+            .events
+            .broadcast_sum_dict(DictionaryUpdate::New(Arc::new(frozen_sum_dict.clone())));
+
+        let mut update = Self::new(shared, frozen_sum_dict, seed_dict);
+        // the aggregations are only stored if the coordinator stopped right after the update phase
+        if let Some((model_agg, scalar_agg)) = redis.connection().await.get_aggregations().await? {
+            update.inner.model_agg = model_agg;
+            update.inner.scalar_agg = scalar_agg;
+        }
+        info!("aggregating the restored masked models and scalars");
+        for (masked_model, masked_scalar) in redis.connection().await.get_masked_objects().await? {
+            let model_agg = &mut update.inner.model_agg;
+            let scalar_agg = &mut update.inner.scalar_agg;
+            match (
+                model_agg.validate_aggregation(&masked_model),
+                scalar_agg.validate_aggregation(&masked_scalar),
+            ) {
+                (Ok(()), Ok(())) => {
+                    model_agg.aggregate(masked_model);
+                    scalar_agg.aggregate(masked_scalar);
+                }
+                (Err(err), _) | (_, Err(err)) => {
+                    warn!("skipping a restored masked object: {}", err);
+                }
+            }
+        }
+        Ok(update)
+    }
+
     /// Handles an update request.
     /// If the handling of the update message fails, an error is returned to the request sender.
-    fn handle_update(&mut self, req: UpdateRequest) -> Result<(), StateMachineError> {
+    async fn handle_update(&mut self, req: UpdateRequest) -> Result<(), StateMachineError> {
         let UpdateRequest {
             participant_pk,
             local_seed_dict,
@@ -173,10 +238,11 @@ impl PhaseState<Update> {
             masked_model,
             masked_scalar,
         )
+        .await
     }
 
     /// Updates the local seed dict and aggregates the masked model.
-    fn update_seed_dict_and_aggregate_mask(
+    async fn update_seed_dict_and_aggregate_mask(
         &mut self,
         pk: &UpdateParticipantPublicKey,
         local_seed_dict: &LocalSeedDict,
@@ -205,27 +271,47 @@ impl PhaseState<Update> {
                 StateMachineError::AggregationFailed
             })?;
 
-        // Try to update local seed dict first. If this fail, we do
+        // Check the local seed dict first. If it is invalid, we do
         // not want to aggregate the model.
-        info!("updating the global seed dictionary");
-        self.add_local_seed_dict(pk, local_seed_dict)
+        self.validate_local_seed_dict(pk, local_seed_dict)
             .map_err(|err| {
                 warn!("invalid local seed dictionary, ignoring update message");
                 err
             })?;
 
+        if let Some(redis) = self.shared.redis().await {
+            // Only the masked objects of this update are stored. They are aggregated into the
+            // stored aggregations at the end of the update phase.
+            info!("storing the local seed dictionary and the masked objects");
+            redis
+                .update_seed_dict_and_masked_objects(
+                    pk,
+                    local_seed_dict,
+                    &masked_model,
+                    &masked_scalar,
+                )
+                .await
+                .map_err(|err| {
+                    warn!("failed to store the update: {}", err);
+                    StateMachineError::InternalError
+                })?;
+        }
+
         info!("aggregating the masked model and scalar");
         self.inner.model_agg.aggregate(masked_model);
         self.inner.scalar_agg.aggregate(masked_scalar);
+
+        info!("updating the global seed dictionary");
+        self.add_local_seed_dict(pk, local_seed_dict);
         Ok(())
     }
 
-    /// Adds a local seed dictionary to the seed dictionary.
+    /// Checks whether a local seed dictionary can be added to the seed dictionary.
     ///
     /// # Error
     /// Fails if it contains invalid keys or it is a repetition.
-    fn add_local_seed_dict(
-        &mut self,
+    fn validate_local_seed_dict(
+        &self,
         pk: &UpdateParticipantPublicKey,
         local_seed_dict: &LocalSeedDict,
     ) -> Result<(), StateMachineError> {
@@ -240,17 +326,6 @@ impl PhaseState<Update> {
                 .next()
                 .map_or(true, |dict| !dict.contains_key(pk))
         {
-            debug!("adding local seed dictionary");
-            for (sum_pk, seed) in local_seed_dict {
-                self.inner
-                    .seed_dict
-                    .get_mut(sum_pk)
-                    // FIXME: the error is not very adapted here, it's
-                    // more an internal error. Could we not unwrap
-                    // here per the checks above?
-                    .ok_or(StateMachineError::InvalidLocalSeedDict)?
-                    .insert(*pk, seed.clone());
-            }
             Ok(())
         } else {
             warn!("invalid seed dictionary");
@@ -258,6 +333,28 @@ impl PhaseState<Update> {
         }
     }
 
+    /// Adds a local seed dictionary to the seed dictionary.
+    ///
+    /// The local seed dictionary must have been checked with
+    /// [`validate_local_seed_dict()`](#method.validate_local_seed_dict) beforehand.
+    fn add_local_seed_dict(
+        &mut self,
+        pk: &UpdateParticipantPublicKey,
+        local_seed_dict: &LocalSeedDict,
+    ) {
+        debug!("adding local seed dictionary");
+        for (sum_pk, seed) in local_seed_dict {
+            // Safe unwrap: the keys of a valid local seed dictionary
+            // are the keys of the sum dictionary, which are also the
+            // keys of the seed dictionary.
+            self.inner
+                .seed_dict
+                .get_mut(sum_pk)
+                .unwrap()
+                .insert(*pk, seed.clone());
+        }
+    }
+
     /// Returns the number of update participants that sent a valid update message.
     fn updater_count(&self) -> usize {
         self.inner
@@ -303,7 +400,7 @@ mod test {
         // Find a sum participant and an update participant for the
         // given seed and ratios.
         let mut summer = utils::generate_summer(&seed, sum_ratio, update_ratio);
-        let updater = utils::generate_updater(&seed, sum_ratio, update_ratio);
+        let mut updater = utils::generate_updater(&seed, sum_ratio, update_ratio);
 
         // Initialize the update phase state
         let sum_msg = summer.compose_sum_message(coord_keys.public);
@@ -314,8 +411,8 @@ mod test {
 
         let mut seed_dict = SeedDict::new();
         seed_dict.insert(summer.pk, HashMap::new());
-        let aggregation = Aggregation::new(utils::mask_settings().into(), model_size);
-        let scalar_agg = Aggregation::new(utils::mask_settings().into(), 1);
+        let aggregation = Aggregation::new(utils::mask_config().model, model_size);
+        let scalar_agg = Aggregation::new(utils::mask_config().scalar, 1);
         let update = Update {
             frozen_sum_dict: frozen_sum_dict.clone(),
             seed_dict: seed_dict.clone(),
@@ -331,7 +428,7 @@ mod test {
             .with_update_ratio(update_ratio)
             .with_min_sum(n_summers)
             .with_min_update(n_updaters)
-            .with_mask_config(utils::mask_settings().into())
+            .with_mask_config(utils::mask_config())
             .build();
 
         assert!(state_machine.is_update());
@@ -339,12 +436,9 @@ mod test {
         // Create an update request.
         let scalar = 1.0 / (n_updaters as f64 * update_ratio);
         let model = Model::from_primitives(vec![0; model_size].into_iter()).unwrap();
-        let update_msg = updater.compose_update_message(
-            coord_keys.public,
-            &frozen_sum_dict,
-            scalar,
-            model.clone(),
-        );
+        let update_msg = updater
+            .compose_update_message(coord_keys.public, &frozen_sum_dict, scalar, model.clone())
+            .unwrap();
         let masked_model = utils::masked_model(&update_msg);
         let request_fut = async { request_tx.msg(&update_msg).await.unwrap() };
 
@@ -4,13 +4,20 @@
 //! [`StateMachine`]: crate::state_machine::StateMachine
 use std::{
     pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
 };
 
 use derive_more::From;
 use futures::Stream;
 use thiserror::Error;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
 use tracing::Span;
 use xaynet_core::{
     mask::MaskObject,
@@ -27,7 +34,10 @@ use xaynet_core::{
 #[error("the RequestSender cannot be used because the state machine shut down")]
 pub struct StateMachineShutdown;
 
-use crate::state_machine::{StateMachineError, StateMachineResult};
+use crate::{
+    settings::RequestSettings,
+    state_machine::{StateMachineError, StateMachineResult},
+};
 
 /// A sum request.
 #[derive(Debug)]
@@ -107,23 +117,42 @@ impl From<Message> for StateMachineRequest {
 /// A handle to send requests to the [`StateMachine`].
 ///
 /// [`StateMachine`]: crate::state_machine
-#[derive(Clone, From, Debug)]
-pub struct RequestSender(mpsc::UnboundedSender<(StateMachineRequest, Span, ResponseSender)>);
+#[derive(Clone, Debug)]
+pub struct RequestSender {
+    tx: mpsc::Sender<(StateMachineRequest, Span, ResponseSender)>,
+    queue: Arc<RequestQueue>,
+}
 
 impl RequestSender {
     /// Sends a request to the [`StateMachine`].
     ///
     /// # Errors
-    /// Fails if the [`StateMachine`] has already shut down and the `Request` channel has been
-    /// closed as a result.
+    /// Fails if the `Request` channel is full or if the [`StateMachine`] has already shut down
+    /// and the `Request` channel has been closed as a result.
     ///
     /// [`StateMachine`]: crate::state_machine
     pub async fn request(&self, req: StateMachineRequest, span: Span) -> StateMachineResult {
         let (resp_tx, resp_rx) = oneshot::channel::<StateMachineResult>();
-        self.0.send((req, span, resp_tx)).map_err(|_| {
-            warn!("failed to send request to the state machine: state machine is shutting down");
-            StateMachineError::InternalError
-        })?;
+        // the depth is incremented beforehand, so that it doesn't underflow if the request is
+        // received before the sending returns
+        self.queue.depth.fetch_add(1, Ordering::SeqCst);
+        if let Err(err) = self.tx.clone().try_send((req, span, resp_tx)) {
+            self.queue.depth.fetch_sub(1, Ordering::SeqCst);
+            return Err(match err {
+                TrySendError::Full(_) => {
+                    warn!("failed to send request to the state machine: request queue is full");
+                    StateMachineError::RequestQueueFull {
+                        retry_after: self.queue.retry_after,
+                    }
+                }
+                TrySendError::Closed(_) => {
+                    warn!(
+                        "failed to send request to the state machine: state machine is shutting down"
+                    );
+                    StateMachineError::InternalError
+                }
+            });
+        }
         resp_rx.await.map_err(|_| {
             warn!(
                 "failed to receive response from the state machine: state machine is shutting down"
@@ -131,6 +160,41 @@ impl RequestSender {
             StateMachineError::InternalError
         })?
     }
+
+    /// Checks whether the `Request` channel is full, in which case further requests are
+    /// rejected until the [`StateMachine`] catches up.
+    ///
+    /// [`StateMachine`]: crate::state_machine
+    pub fn is_full(&self) -> bool {
+        self.queue.depth.load(Ordering::SeqCst) >= self.queue.capacity
+    }
+
+    /// Gets the time in seconds after which a participant should resend a request which was
+    /// rejected because the `Request` channel was full.
+    pub fn retry_after(&self) -> u64 {
+        self.queue.retry_after
+    }
+
+    /// Checks whether the `Request` channel has been closed, in which case the [`StateMachine`]
+    /// doesn't receive any further requests.
+    ///
+    /// [`StateMachine`]: crate::state_machine
+    pub fn is_closed(&self) -> bool {
+        self.queue.closed.load(Ordering::SeqCst)
+    }
+}
+
+/// The state of the `Request` channel which is shared by its halves.
+#[derive(Debug)]
+struct RequestQueue {
+    /// The number of requests in the channel.
+    depth: AtomicUsize,
+    /// The maximum number of requests in the channel.
+    capacity: usize,
+    /// The time in seconds after which rejected requests should be resent.
+    retry_after: u64,
+    /// Whether the receiver half closed the channel or has been dropped.
+    closed: AtomicBool,
 }
 
 /// A channel for sending the state machine to send the response to a
@@ -141,52 +205,148 @@ pub(in crate::state_machine) type ResponseSender = oneshot::Sender<StateMachineR
 /// requests.
 ///
 /// [`StateMachine`]: crate::state_machine
-#[derive(From, Debug)]
-pub struct RequestReceiver(mpsc::UnboundedReceiver<(StateMachineRequest, Span, ResponseSender)>);
+#[derive(Debug)]
+pub struct RequestReceiver {
+    rx: mpsc::Receiver<(StateMachineRequest, Span, ResponseSender)>,
+    queue: Arc<RequestQueue>,
+}
 
 impl Stream for RequestReceiver {
     type Item = (StateMachineRequest, Span, ResponseSender);
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
         trace!("RequestReceiver: polling");
-        Pin::new(&mut self.get_mut().0).poll_next(cx)
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.rx).poll_next(cx);
+        if let Poll::Ready(Some(_)) = poll {
+            this.queue.depth.fetch_sub(1, Ordering::SeqCst);
+        }
+        poll
+    }
+}
+
+impl Drop for RequestReceiver {
+    fn drop(&mut self) {
+        self.queue.closed.store(true, Ordering::SeqCst);
     }
 }
 
 impl RequestReceiver {
     /// Creates a new `Request` channel and returns the [`RequestReceiver`] as well as the
     /// [`RequestSender`] half.
-    pub fn new() -> (Self, RequestSender) {
-        let (tx, rx) = mpsc::unbounded_channel::<(StateMachineRequest, Span, ResponseSender)>();
-        let receiver = RequestReceiver::from(rx);
-        let handle = RequestSender::from(tx);
+    ///
+    /// The channel holds at most `settings.queue_depth` requests.
+    pub fn new(settings: RequestSettings) -> (Self, RequestSender) {
+        let (tx, rx) =
+            mpsc::channel::<(StateMachineRequest, Span, ResponseSender)>(settings.queue_depth);
+        let queue = Arc::new(RequestQueue {
+            depth: AtomicUsize::new(0),
+            capacity: settings.queue_depth,
+            retry_after: settings.retry_after,
+            closed: AtomicBool::new(false),
+        });
+        let receiver = RequestReceiver {
+            rx,
+            queue: queue.clone(),
+        };
+        let handle = RequestSender { tx, queue };
         (receiver, handle)
     }
 
+    /// Gets the number of requests in the `Request` channel.
+    pub fn queue_depth(&self) -> usize {
+        self.queue.depth.load(Ordering::SeqCst)
+    }
+
     /// Closes the `Request` channel.
     /// See [the `tokio` documentation][close] for more information.
     ///
-    /// [close]: https://docs.rs/tokio/0.2.21/tokio/sync/mpsc/struct.UnboundedReceiver.html#method.close
+    /// [close]: https://docs.rs/tokio/0.2.21/tokio/sync/mpsc/struct.Receiver.html#method.close
     pub fn close(&mut self) {
-        self.0.close()
+        self.queue.closed.store(true, Ordering::SeqCst);
+        self.rx.close()
     }
 
     /// Receives the next request.
     /// See [the `tokio` documentation][receive] for more information.
     ///
-    /// [receive]: https://docs.rs/tokio/0.2.21/tokio/sync/mpsc/struct.UnboundedReceiver.html#method.recv
+    /// [receive]: https://docs.rs/tokio/0.2.21/tokio/sync/mpsc/struct.Receiver.html#method.recv
     pub async fn recv(&mut self) -> Option<(StateMachineRequest, Span, ResponseSender)> {
-        self.0.recv().await
+        let req = self.rx.recv().await;
+        if req.is_some() {
+            self.queue.depth.fetch_sub(1, Ordering::SeqCst);
+        }
+        req
     }
 
     /// Try to retrieve the next request without blocked
     /// See [the `tokio` documentation][try_receive] for more information.
     ///
-    /// [try_receive]: https://docs.rs/tokio/0.2.21/tokio/sync/mpsc/struct.UnboundedReceiver.html#method.try_recv
+    /// [try_receive]: https://docs.rs/tokio/0.2.21/tokio/sync/mpsc/struct.Receiver.html#method.try_recv
     pub fn try_recv(
         &mut self,
     ) -> Result<(StateMachineRequest, Span, ResponseSender), tokio::sync::mpsc::error::TryRecvError>
     {
-        self.0.try_recv()
+        let req = self.rx.try_recv()?;
+        self.queue.depth.fetch_sub(1, Ordering::SeqCst);
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::{assert_pending, assert_ready_eq, task};
+    use xaynet_core::crypto::{EncryptKeyPair, SigningKeyPair};
+
+    fn sum_request() -> StateMachineRequest {
+        StateMachineRequest::Sum(SumRequest {
+            participant_pk: SigningKeyPair::generate().public,
+            ephm_pk: EncryptKeyPair::generate().public,
+        })
+    }
+
+    #[tokio::test]
+    async fn full_request_queue() {
+        let settings = RequestSettings {
+            queue_depth: 2,
+            retry_after: 3,
+        };
+        let (mut receiver, sender) = RequestReceiver::new(settings);
+
+        // the requests stay in the queue until the state machine receives them
+        let mut first = task::spawn(sender.request(sum_request(), Span::none()));
+        let mut second = task::spawn(sender.request(sum_request(), Span::none()));
+        assert_pending!(first.poll());
+        assert_pending!(second.poll());
+        assert_eq!(receiver.queue_depth(), 2);
+        assert!(sender.is_full());
+
+        let err = sender
+            .request(sum_request(), Span::none())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            StateMachineError::RequestQueueFull { retry_after: 3 }
+        ));
+        assert_eq!(receiver.queue_depth(), 2);
+
+        let (_, _, resp_tx) = receiver.recv().await.unwrap();
+        assert_eq!(receiver.queue_depth(), 1);
+        assert!(!sender.is_full());
+        resp_tx.send(Ok(())).unwrap();
+        assert_ready_eq!(first.poll().map(|res| res.is_ok()), true);
+    }
+
+    #[test]
+    fn closed_request_queue() {
+        let (receiver, sender) = RequestReceiver::new(RequestSettings {
+            queue_depth: 1,
+            retry_after: 3,
+        });
+        assert!(!sender.is_closed());
+        drop(receiver);
+        assert!(sender.is_closed());
     }
 }
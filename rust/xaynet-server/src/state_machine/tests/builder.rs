@@ -1,29 +1,35 @@
-use xaynet_core::{common::RoundSeed, crypto::EncryptKeyPair, mask::MaskConfig};
-
-use crate::state_machine::{
-    events::EventSubscriber,
-    phases::{self, Handler, Phase, PhaseState, Shared},
-    requests::RequestSender,
-    tests::utils,
-    StateMachine,
+use xaynet_core::{common::RoundSeed, crypto::EncryptKeyPair, mask::MaskConfigPair};
+
+use crate::{
+    state_machine::{
+        admin::AdminSender,
+        events::EventSubscriber,
+        phases::{self, Handler, Phase, PhaseState, Shared},
+        requests::RequestSender,
+        tests::utils,
+        StateMachine,
+    },
+    storage::history::ModelHistory,
 };
 
 #[derive(Debug)]
 pub struct StateMachineBuilder<P> {
     shared: Shared,
     request_tx: RequestSender,
+    admin_tx: AdminSender,
     event_subscriber: EventSubscriber,
     phase_state: P,
 }
 
 impl StateMachineBuilder<phases::Idle> {
     pub fn new() -> Self {
-        let (shared, event_subscriber, request_tx) = utils::init_shared();
+        let (shared, event_subscriber, request_tx, admin_tx) = utils::init_shared();
 
         let phase_state = phases::Idle;
         StateMachineBuilder {
             shared,
             request_tx,
+            admin_tx,
             event_subscriber,
             phase_state,
         }
@@ -41,6 +47,7 @@ where
             request_tx,
             event_subscriber,
             phase_state,
+            ..
         } = self;
 
         // Make sure the events that the listeners have are up to date
@@ -68,6 +75,11 @@ where
         (state_machine, request_tx, event_subscriber)
     }
 
+    /// Gets a handle to send admin commands to the state machine which is built.
+    pub fn admin_sender(&self) -> AdminSender {
+        self.admin_tx.clone()
+    }
+
     #[allow(dead_code)]
     pub fn with_keys(mut self, keys: EncryptKeyPair) -> Self {
         self.shared.state.round_params.pk = keys.public.clone();
@@ -100,8 +112,9 @@ where
         self
     }
 
-    pub fn with_mask_config(mut self, mask_config: MaskConfig) -> Self {
+    pub fn with_mask_config(mut self, mask_config: MaskConfigPair) -> Self {
         self.shared.state.mask_config = mask_config;
+        self.shared.state.round_params.mask_config = mask_config;
         self
     }
 
@@ -115,16 +128,23 @@ where
         self
     }
 
+    pub fn with_model_history(mut self, model_history: ModelHistory) -> Self {
+        self.shared.io.model_history = Some(model_history);
+        self
+    }
+
     pub fn with_phase<S>(self, phase_state: S) -> StateMachineBuilder<S> {
         let Self {
             shared,
             request_tx,
+            admin_tx,
             event_subscriber,
             ..
         } = self;
         StateMachineBuilder {
             shared,
             request_tx,
+            admin_tx,
             event_subscriber,
             phase_state,
         }
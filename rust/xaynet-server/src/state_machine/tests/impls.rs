@@ -113,6 +113,13 @@ impl StateMachine {
             _ => panic!("not in shutdown state"),
         }
     }
+
+    pub fn is_paused(&self) -> bool {
+        match self {
+            StateMachine::Paused(_) => true,
+            _ => false,
+        }
+    }
 }
 
 impl<D> DictionaryUpdate<D> {
@@ -2,21 +2,48 @@ pub mod builder;
 pub mod impls;
 pub mod utils;
 
+use std::sync::Arc;
+
 use xaynet_core::{
     common::RoundSeed,
-    crypto::{ByteObject, EncryptKeyPair},
+    crypto::{ByteObject, EncryptKeyPair, Sha256},
     mask::{FromPrimitives, Model},
 };
 
-use crate::state_machine::{
-    events::Event,
-    phases::PhaseName,
-    tests::{
-        builder::StateMachineBuilder,
-        utils::{enable_logging, generate_summer, generate_updater},
+use serial_test::serial;
+
+use crate::{
+    state_machine::{
+        admin::AdminCommand,
+        events::{Event, ModelUpdate},
+        initial_model::InitialModelError,
+        phases::{Paused, PhaseName, PhaseState, StateError},
+        tests::{
+            builder::StateMachineBuilder,
+            utils::{
+                enable_logging,
+                generate_summer,
+                generate_updater,
+                mask_settings,
+                model_settings,
+                pet_settings,
+                request_settings,
+            },
+        },
+        StateMachine,
+        StateMachineError,
+        StateMachineInitError,
+    },
+    storage::{
+        history::{ModelHistory, ModelMetadata},
+        redis::Client,
+        tests::utils::TempPath,
     },
 };
 
+#[cfg(feature = "metrics")]
+use crate::metrics::MetricsSender;
+
 #[tokio::test]
 async fn full_round() {
     enable_logging();
@@ -28,6 +55,8 @@ async fn full_round() {
     let coord_keys = EncryptKeyPair::generate();
     let coord_pk = coord_keys.public;
     let model_size = 4;
+    let history_dir = TempPath::new();
+    let model_history = ModelHistory::Directory(history_dir.to_path_buf());
 
     let (state_machine, requests, events) = StateMachineBuilder::new()
         .with_round_id(42)
@@ -37,6 +66,7 @@ async fn full_round() {
         .with_min_sum(n_summers)
         .with_min_update(n_updaters)
         .with_model_size(model_size)
+        .with_model_history(model_history.clone())
         .build();
 
     assert!(state_machine.is_idle());
@@ -62,8 +92,10 @@ async fn full_round() {
     let scalar = 1.0 / (n_updaters as f64 * update_ratio);
     let model = Model::from_primitives(vec![0; model_size].into_iter()).unwrap();
     for _ in 0..3 {
-        let updater = generate_updater(&seed, sum_ratio, update_ratio);
-        let msg = updater.compose_update_message(coord_pk, &sum_dict, scalar, model.clone());
+        let mut updater = generate_updater(&seed, sum_ratio, update_ratio);
+        let msg = updater
+            .compose_update_message(coord_pk, &sum_dict, scalar, model.clone())
+            .unwrap();
         requests.msg(&msg).await.unwrap();
     }
     let state_machine = transition_task.await.unwrap();
@@ -85,9 +117,30 @@ async fn full_round() {
     assert!(state_machine.is_unmask());
 
     // Unmask phase
+    let round_seed = events.params_listener().get_latest().event.seed;
     let state_machine = state_machine.next().await.unwrap();
     assert!(state_machine.is_idle());
 
+    // The global model has been added to the model history
+    assert_eq!(
+        model_history.list_models().await.unwrap(),
+        vec![ModelMetadata {
+            round_id: 42,
+            round_seed_hash: Sha256::hash(round_seed.as_slice()),
+            nb_sum: n_summers,
+            nb_update: n_updaters,
+            nb_sum2: n_summers,
+        }]
+    );
+    let global_model = match events.model_listener().get_latest().event {
+        ModelUpdate::New(model) => model,
+        ModelUpdate::Invalidate => panic!("no global model has been published"),
+    };
+    assert_eq!(
+        model_history.get_model(42).await.unwrap().as_ref(),
+        Some(global_model.as_ref())
+    );
+
     // New idle phase
     let state_machine = state_machine.next().await.unwrap();
     // During the idle phase, a new phase event with an updated round
@@ -111,3 +164,324 @@ async fn full_round() {
     assert!(state_machine.is_shutdown());
     assert!(state_machine.next().await.is_none())
 }
+
+#[tokio::test]
+async fn initial_model_is_published() {
+    let path = TempPath::with_extension("json");
+    std::fs::write(&*path, "[0.5, -2, 0]").unwrap();
+    let mut model_settings = model_settings();
+    model_settings.size = 3;
+    model_settings.initial = Some(path.to_path_buf());
+
+    let (_state_machine, _requests, _admin, events) = StateMachine::new(
+        pet_settings(),
+        mask_settings(),
+        model_settings,
+        request_settings(),
+        None,
+        #[cfg(feature = "metrics")]
+        MetricsSender(),
+    )
+    .unwrap();
+
+    let expected = Model::from_primitives(vec![0.5_f32, -2., 0.].into_iter()).unwrap();
+    match events.model_listener().get_latest().event {
+        ModelUpdate::New(model) => assert_eq!(model.as_ref(), &expected),
+        ModelUpdate::Invalidate => panic!("the initial model has not been published"),
+    }
+}
+
+#[tokio::test]
+async fn invalid_initial_model_is_rejected() {
+    let path = TempPath::with_extension("json");
+    std::fs::write(&*path, "[0.5, -2]").unwrap();
+    let mut model_settings = model_settings();
+    model_settings.size = 3;
+    model_settings.initial = Some(path.to_path_buf());
+
+    let result = StateMachine::new(
+        pet_settings(),
+        mask_settings(),
+        model_settings,
+        request_settings(),
+        None,
+        #[cfg(feature = "metrics")]
+        MetricsSender(),
+    );
+    assert!(matches!(
+        result,
+        Err(StateMachineInitError::InitialModel(
+            InitialModelError::InvalidSize {
+                expected: 3,
+                actual: 2
+            }
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn admin_commands() {
+    let builder = StateMachineBuilder::new().with_round_id(1);
+    let admin = builder.admin_sender();
+    let (state_machine, requests, events) = builder.build();
+
+    // Idle phase
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_sum());
+
+    // Pause the coordinator and abort the sum phase
+    admin.send(AdminCommand::Pause).unwrap();
+    admin.send(AdminCommand::Abort).unwrap();
+    let state_machine = state_machine.next().await.unwrap();
+    let error_state = state_machine.into_error_phase_state();
+    assert!(matches!(error_state.inner, StateError::Aborted));
+    let state_machine = StateMachine::from(error_state);
+
+    // Error phase
+    let state_machine = state_machine.next().await.unwrap();
+    assert_eq!(events.phase_listener().get_latest().event, PhaseName::Error);
+    assert!(state_machine.is_paused());
+
+    // Paused phase: requests are rejected until the coordinator is resumed
+    let round_params = events.params_listener().get_latest().event;
+    let mut summer = generate_summer(&round_params.seed, round_params.sum, round_params.update);
+    let msg = summer.compose_sum_message(round_params.pk);
+    let req = async {
+        let res = requests.msg(&msg).await;
+        assert_eq!(
+            events.phase_listener().get_latest().event,
+            PhaseName::Paused
+        );
+        admin.send(AdminCommand::Resume).unwrap();
+        res
+    };
+    let transition = async { state_machine.next().await.unwrap() };
+    let (res, state_machine) = tokio::join!(req, transition);
+    assert!(matches!(res, Err(StateMachineError::MessageRejected)));
+    assert!(state_machine.is_idle());
+
+    // Idle phase
+    let state_machine = state_machine.next().await.unwrap();
+    assert_eq!(events.phase_listener().get_latest().round_id, 2);
+    assert!(state_machine.is_sum());
+
+    // Restart the round despite a pause
+    let keys = events.keys_listener().get_latest().event;
+    admin.send(AdminCommand::Pause).unwrap();
+    admin.send(AdminCommand::Restart).unwrap();
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_error());
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_idle());
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_sum());
+    assert_eq!(events.phase_listener().get_latest().round_id, 3);
+    assert_ne!(events.keys_listener().get_latest().event, keys);
+
+    // Shut down after the round
+    admin.send(AdminCommand::Shutdown).unwrap();
+    admin.send(AdminCommand::Abort).unwrap();
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_error());
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_shutdown());
+    assert!(state_machine.next().await.is_none());
+    assert_eq!(
+        events.phase_listener().get_latest().event,
+        PhaseName::Shutdown
+    );
+}
+
+#[tokio::test]
+async fn admin_commands_are_drained_at_transitions() {
+    let builder = StateMachineBuilder::new().with_round_id(1);
+    let admin = builder.admin_sender();
+    let (state_machine, _requests, _events) = builder.build();
+
+    // Idle phase: the abort is dropped while the pause is applied
+    admin.send(AdminCommand::Pause).unwrap();
+    admin.send(AdminCommand::Abort).unwrap();
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_sum());
+
+    // Sum phase: the restart lifts the pause
+    admin.send(AdminCommand::Restart).unwrap();
+    let state_machine = state_machine.next().await.unwrap();
+    let error_state = state_machine.into_error_phase_state();
+    assert!(matches!(error_state.inner, StateError::Restarted));
+    assert!(!error_state.shared.io.admin.is_paused());
+    let state_machine = StateMachine::from(error_state);
+
+    // Error phase
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_idle());
+
+    // Paused phase: the restart lifts the pause
+    let mut idle_state = state_machine.into_idle_phase_state();
+    idle_state.shared.io.admin.set_paused(true);
+    let state_machine = StateMachine::from(PhaseState::<Paused>::new(idle_state.shared));
+    admin.send(AdminCommand::Restart).unwrap();
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_idle());
+    assert!(!state_machine
+        .into_idle_phase_state()
+        .shared
+        .io
+        .admin
+        .is_paused());
+}
+
+#[tokio::test]
+#[serial]
+async fn integration_restore_sum_phase() {
+    let redis = Client::new("redis://127.0.0.1/", 10).await.unwrap();
+    redis.connection().await.flush_db().await.unwrap();
+
+    let (state_machine, requests, _admin, events) = StateMachine::restore(
+        pet_settings(),
+        mask_settings(),
+        model_settings(),
+        request_settings(),
+        redis.clone(),
+        None,
+        #[cfg(feature = "metrics")]
+        MetricsSender(),
+    )
+    .await
+    .unwrap();
+    assert!(state_machine.is_idle());
+
+    // Idle phase
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_sum());
+
+    // Sum phase
+    let round_params = events.params_listener().get_latest().event;
+    let keys = events.keys_listener().get_latest().event;
+    let mut summer = generate_summer(&round_params.seed, round_params.sum, round_params.update);
+    let msg = summer.compose_sum_message(round_params.pk);
+    let req = async { requests.msg(&msg).await.unwrap() };
+    let transition = async { state_machine.next().await.unwrap() };
+    let ((), state_machine) = tokio::join!(req, transition);
+    assert!(state_machine.is_update());
+
+    // Simulate a restart of the coordinator before the update phase
+    // has been entered
+    drop(state_machine);
+    let (state_machine, _requests, _admin, events) = StateMachine::restore(
+        pet_settings(),
+        mask_settings(),
+        model_settings(),
+        request_settings(),
+        redis,
+        None,
+        #[cfg(feature = "metrics")]
+        MetricsSender(),
+    )
+    .await
+    .unwrap();
+    assert!(state_machine.is_sum());
+    assert_eq!(events.phase_listener().get_latest().event, PhaseName::Sum);
+    assert_eq!(events.params_listener().get_latest().event, round_params);
+    assert_eq!(events.keys_listener().get_latest().event, keys);
+
+    let sum_state = state_machine.into_sum_phase_state();
+    assert_eq!(sum_state.inner.sum_dict().len(), 1);
+    assert!(sum_state.inner.sum_dict().contains_key(&summer.pk));
+}
+
+#[tokio::test]
+#[serial]
+async fn integration_restore_idle_phase_keeps_round_id() {
+    let redis = Client::new("redis://127.0.0.1/", 10).await.unwrap();
+    redis.connection().await.flush_db().await.unwrap();
+    let restore = || {
+        StateMachine::restore(
+            pet_settings(),
+            mask_settings(),
+            model_settings(),
+            request_settings(),
+            redis.clone(),
+            None,
+            #[cfg(feature = "metrics")]
+            MetricsSender(),
+        )
+    };
+
+    // The idle phase of the first round is stored when it's entered
+    let (state_machine, _requests, _admin, _events) = restore().await.unwrap();
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_sum());
+    assert_eq!(state_machine.into_sum_phase_state().shared.round_id(), 1);
+
+    // Simulate a restart of the coordinator in the idle phase
+    let (state_machine, _requests, _admin, _events) = restore().await.unwrap();
+    assert!(state_machine.is_idle());
+    assert_eq!(state_machine.into_idle_phase_state().shared.round_id(), 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn integration_restore_publishes_latest_model() {
+    let redis = Client::new("redis://127.0.0.1/", 10).await.unwrap();
+    redis.connection().await.flush_db().await.unwrap();
+    let path = TempPath::with_extension("json");
+    std::fs::write(&*path, "[0.5, -2, 0]").unwrap();
+    let history_dir = TempPath::new();
+    let model_history = ModelHistory::Directory(history_dir.to_path_buf());
+    let model_settings = || {
+        let mut model_settings = model_settings();
+        model_settings.size = 3;
+        model_settings.initial = Some(path.to_path_buf());
+        model_settings
+    };
+
+    // A fresh coordinator publishes the initial model
+    let (state_machine, _requests, _admin, events) = StateMachine::restore(
+        pet_settings(),
+        mask_settings(),
+        model_settings(),
+        request_settings(),
+        redis.clone(),
+        Some(model_history.clone()),
+        #[cfg(feature = "metrics")]
+        MetricsSender(),
+    )
+    .await
+    .unwrap();
+    let initial = Model::from_primitives(vec![0.5_f32, -2., 0.].into_iter()).unwrap();
+    assert_eq!(
+        events.model_listener().get_latest().event,
+        ModelUpdate::New(Arc::new(initial))
+    );
+    let state_machine = state_machine.next().await.unwrap();
+    assert!(state_machine.is_sum());
+
+    // Simulate a restart of the coordinator after a global model has been stored in the history
+    drop(state_machine);
+    let latest = Model::from_primitives(vec![1_f32, 2., 3.].into_iter()).unwrap();
+    let metadata = ModelMetadata {
+        round_id: 1,
+        round_seed_hash: Sha256::hash(&[]),
+        nb_sum: 1,
+        nb_update: 3,
+        nb_sum2: 1,
+    };
+    model_history.add_model(&metadata, &latest).await.unwrap();
+    let (_state_machine, _requests, _admin, events) = StateMachine::restore(
+        pet_settings(),
+        mask_settings(),
+        model_settings(),
+        request_settings(),
+        redis,
+        Some(model_history),
+        #[cfg(feature = "metrics")]
+        MetricsSender(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        events.model_listener().get_latest().event,
+        ModelUpdate::New(Arc::new(latest))
+    );
+}
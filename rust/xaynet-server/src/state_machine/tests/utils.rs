@@ -1,15 +1,16 @@
 use xaynet_core::{
     common::RoundSeed,
     crypto::ByteObject,
-    mask::{BoundType, DataType, GroupType, MaskObject, ModelType},
+    mask::{BoundType, DataType, GroupType, MaskConfigPair, MaskObject, ModelType},
     message::{Message, Payload, Sum, Update},
     LocalSeedDict,
     SumParticipantEphemeralPublicKey,
 };
 
 use crate::{
-    settings::{MaskSettings, ModelSettings, PetSettings},
+    settings::{MaskSettings, ModelSettings, PetSettings, RequestSettings, ScalarMaskSettings},
     state_machine::{
+        admin::{AdminReceiver, AdminSender},
         coordinator::CoordinatorState,
         events::{EventPublisher, EventSubscriber},
         phases::{PhaseName, Shared},
@@ -58,9 +59,19 @@ pub fn mask_settings() -> MaskSettings {
         data_type: DataType::F32,
         bound_type: BoundType::B0,
         model_type: ModelType::M3,
+        scalar: ScalarMaskSettings {
+            group_type: GroupType::Prime,
+            data_type: DataType::F32,
+            bound_type: BoundType::B0,
+            model_type: ModelType::M3,
+        },
     }
 }
 
+pub fn mask_config() -> MaskConfigPair {
+    mask_settings().into()
+}
+
 pub fn pet_settings() -> PetSettings {
     PetSettings {
         sum: 0.4,
@@ -72,10 +83,22 @@ pub fn pet_settings() -> PetSettings {
 }
 
 pub fn model_settings() -> ModelSettings {
-    ModelSettings { size: 1 }
+    ModelSettings {
+        size: 1,
+        initial: None,
+        history: None,
+        optimizer: None,
+    }
+}
+
+pub fn request_settings() -> RequestSettings {
+    RequestSettings {
+        queue_depth: 100,
+        retry_after: 1,
+    }
 }
 
-pub fn init_shared() -> (Shared, EventSubscriber, RequestSender) {
+pub fn init_shared() -> (Shared, EventSubscriber, RequestSender, AdminSender) {
     let coordinator_state =
         CoordinatorState::new(pet_settings(), mask_settings(), model_settings());
 
@@ -86,17 +109,23 @@ pub fn init_shared() -> (Shared, EventSubscriber, RequestSender) {
         PhaseName::Idle,
     );
 
-    let (request_rx, request_tx) = RequestReceiver::new();
+    let (request_rx, request_tx) = RequestReceiver::new(request_settings());
+    let (admin_rx, admin_tx) = AdminReceiver::new();
     (
         Shared::new(
             coordinator_state,
             event_publisher,
             request_rx,
+            admin_rx,
+            None,
+            None,
+            None,
             #[cfg(feature = "metrics")]
             MetricsSender(),
         ),
         event_subscriber,
         request_tx,
+        admin_tx,
     )
 }
 
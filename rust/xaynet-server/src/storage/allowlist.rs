@@ -0,0 +1,236 @@
+//! An allowlist of participants for closed federations.
+//!
+//! If the [`Allowlist`] is enabled, only the participants whose public keys are on the allowlist
+//! may take part in the PET protocol. The public keys are stored either in Redis or in a local
+//! file, which contains one base64 encoded public key per line.
+//!
+//! The file is read once when the allowlist is created. Afterwards, the allowlist should only be
+//! modified through the allowlist methods, which write the changes back to the file.
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use redis::RedisError;
+use thiserror::Error;
+use tokio::{fs, sync::RwLock};
+use xaynet_core::{crypto::ByteObject, ParticipantPublicKey};
+
+use crate::{
+    settings::{AllowlistSettings, AllowlistStore},
+    storage::redis::Client,
+};
+
+/// Errors which can occur while accessing the allowlist.
+#[derive(Debug, Error)]
+pub enum AllowlistError {
+    #[error("Redis failed: {0}")]
+    Redis(#[from] RedisError),
+
+    #[error("IO failed: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("invalid public key in line {0} of the allowlist file")]
+    InvalidPublicKey(usize),
+
+    #[error("the allowlist is stored in Redis, but Redis is not configured")]
+    MissingRedis,
+}
+
+/// The store of the participant allowlist.
+#[derive(Debug, Clone)]
+pub enum Allowlist {
+    /// Stores the public keys in Redis.
+    Redis(Client),
+    /// Stores the public keys in the given file and keeps a copy of them in memory.
+    File {
+        path: PathBuf,
+        pks: Arc<RwLock<HashSet<ParticipantPublicKey>>>,
+    },
+}
+
+impl Allowlist {
+    /// Creates an allowlist from the settings.
+    ///
+    /// The `redis` client is only used if the settings select Redis as the store.
+    ///
+    /// # Errors
+    /// Fails if the allowlist file exists but cannot be read or contains invalid public keys, or if
+    /// the settings select Redis as the store but no `redis` client is given.
+    pub async fn new(
+        settings: &AllowlistSettings,
+        redis: Option<&Client>,
+    ) -> Result<Self, AllowlistError> {
+        match (settings.store, &settings.file) {
+            (AllowlistStore::File, Some(path)) => Self::from_file(path.clone()).await,
+            // the settings validation ensures that a file store has a file
+            _ => redis
+                .map(|redis| Self::Redis(redis.clone()))
+                .ok_or(AllowlistError::MissingRedis),
+        }
+    }
+
+    /// Creates an allowlist which is stored in the given file.
+    ///
+    /// The file is created when the first participant is added if it doesn't exist yet.
+    pub async fn from_file(path: PathBuf) -> Result<Self, AllowlistError> {
+        let contents = match fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let pks = contents
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(line_number, line)| {
+                base64::decode(line)
+                    .ok()
+                    .and_then(|bytes| ParticipantPublicKey::from_slice(&bytes))
+                    .ok_or(AllowlistError::InvalidPublicKey(line_number))
+            })
+            .collect::<Result<HashSet<_>, _>>()?;
+        Ok(Self::File {
+            path,
+            pks: Arc::new(RwLock::new(pks)),
+        })
+    }
+
+    /// Checks whether a participant is on the allowlist.
+    pub async fn contains(&self, pk: &ParticipantPublicKey) -> Result<bool, AllowlistError> {
+        match self {
+            Self::Redis(client) => client
+                .connection()
+                .await
+                .is_allowed_participant(pk)
+                .await
+                .map_err(Into::into),
+            Self::File { pks, .. } => Ok(pks.read().await.contains(pk)),
+        }
+    }
+
+    /// Adds a participant to the allowlist.
+    ///
+    /// Returns `false` if the participant was already on the allowlist.
+    pub async fn add(&self, pk: &ParticipantPublicKey) -> Result<bool, AllowlistError> {
+        match self {
+            Self::Redis(client) => client
+                .connection()
+                .await
+                .add_allowed_participant(pk)
+                .await
+                .map_err(Into::into),
+            Self::File { path, pks } => {
+                let mut pks = pks.write().await;
+                if !pks.insert(*pk) {
+                    return Ok(false);
+                }
+                if let Err(err) = write_file(path, &pks).await {
+                    pks.remove(pk);
+                    return Err(err);
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    /// Removes a participant from the allowlist.
+    ///
+    /// Returns `false` if the participant was not on the allowlist.
+    pub async fn remove(&self, pk: &ParticipantPublicKey) -> Result<bool, AllowlistError> {
+        match self {
+            Self::Redis(client) => client
+                .connection()
+                .await
+                .remove_allowed_participant(pk)
+                .await
+                .map_err(Into::into),
+            Self::File { path, pks } => {
+                let mut pks = pks.write().await;
+                if !pks.remove(pk) {
+                    return Ok(false);
+                }
+                if let Err(err) = write_file(path, &pks).await {
+                    pks.insert(*pk);
+                    return Err(err);
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    /// Retrieves all participants on the allowlist.
+    pub async fn list(&self) -> Result<HashSet<ParticipantPublicKey>, AllowlistError> {
+        match self {
+            Self::Redis(client) => client
+                .connection()
+                .await
+                .get_allowlist()
+                .await
+                .map_err(Into::into),
+            Self::File { pks, .. } => Ok(pks.read().await.clone()),
+        }
+    }
+}
+
+/// Writes the public keys to the allowlist file, one base64 encoded key per line.
+async fn write_file(
+    path: &Path,
+    pks: &HashSet<ParticipantPublicKey>,
+) -> Result<(), AllowlistError> {
+    let mut lines = pks
+        .iter()
+        .map(|pk| base64::encode(pk.as_slice()))
+        .collect::<Vec<_>>();
+    lines.sort_unstable();
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    fs::write(path, contents).await.map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::utils::TempPath;
+    use xaynet_core::crypto::SigningKeyPair;
+
+    #[tokio::test]
+    async fn file_allowlist() {
+        let path = TempPath::new();
+        let allowlist = Allowlist::from_file(path.to_path_buf()).await.unwrap();
+        assert!(allowlist.list().await.unwrap().is_empty());
+
+        let pk_1 = SigningKeyPair::generate().public;
+        let pk_2 = SigningKeyPair::generate().public;
+        assert!(allowlist.add(&pk_1).await.unwrap());
+        assert!(allowlist.add(&pk_2).await.unwrap());
+        assert!(!allowlist.add(&pk_1).await.unwrap());
+        assert!(allowlist.contains(&pk_1).await.unwrap());
+
+        assert!(allowlist.remove(&pk_1).await.unwrap());
+        assert!(!allowlist.remove(&pk_1).await.unwrap());
+        assert!(!allowlist.contains(&pk_1).await.unwrap());
+
+        // the changes are written to the file
+        let allowlist = Allowlist::from_file(path.to_path_buf()).await.unwrap();
+        assert_eq!(
+            allowlist.list().await.unwrap(),
+            [pk_2].iter().cloned().collect()
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_allowlist_file() {
+        let path = TempPath::new();
+        let pk = SigningKeyPair::generate().public;
+        let contents = format!("{}\n\nnot a key\n", base64::encode(pk.as_slice()));
+        std::fs::write(&*path, contents).unwrap();
+        assert!(matches!(
+            Allowlist::from_file(path.to_path_buf()).await,
+            Err(AllowlistError::InvalidPublicKey(3))
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+//! A history of the global models.
+//!
+//! The [`ModelHistory`] keeps the global model of every round together with its
+//! [`ModelMetadata`], so that the models of earlier rounds can be retrieved for comparisons,
+//! audits or rollbacks. The models are stored either in Redis or as files in a local directory.
+//!
+//! # Directory Layout
+//!
+//! ```text
+//! <directory>/
+//!     1.model // bincode encoded global model of round 1
+//!     1.meta  // bincode encoded metadata of the global model of round 1
+//!     2.model
+//!     2.meta
+//! ```
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use redis::RedisError;
+use thiserror::Error;
+use tokio::fs;
+use xaynet_core::{crypto::Sha256, mask::Model};
+
+use crate::{
+    settings::{ModelHistorySettings, ModelHistoryStore},
+    storage::redis::Client,
+};
+
+/// The metadata of a global model in the model history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelMetadata {
+    /// The round in which the global model was aggregated.
+    pub round_id: u64,
+
+    /// The SHA-256 hash of the seed of that round.
+    pub round_seed_hash: Sha256,
+
+    /// The number of sum participants.
+    pub nb_sum: usize,
+
+    /// The number of update participants whose models were aggregated.
+    pub nb_update: usize,
+
+    /// The number of sum participants which submitted masks.
+    pub nb_sum2: usize,
+}
+
+/// Errors which can occur while accessing the model history.
+#[derive(Debug, Error)]
+pub enum ModelHistoryError {
+    #[error("Redis failed: {0}")]
+    Redis(#[from] RedisError),
+
+    #[error("IO failed: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("(de)serialization failed: {0}")]
+    Serialization(#[from] bincode::Error),
+
+    #[error("the model history is stored in Redis, but Redis is not configured")]
+    MissingRedis,
+}
+
+/// The store of the global model history.
+#[derive(Debug, Clone)]
+pub enum ModelHistory {
+    /// Stores the global models in Redis.
+    Redis(Client),
+    /// Stores the global models as files in the given directory.
+    Directory(PathBuf),
+}
+
+impl ModelHistory {
+    /// Creates a model history from the settings.
+    ///
+    /// The `redis` client is only used if the settings select Redis as the store.
+    ///
+    /// # Errors
+    /// Fails if the settings select Redis as the store but no `redis` client is given.
+    pub fn new(
+        settings: &ModelHistorySettings,
+        redis: Option<&Client>,
+    ) -> Result<Self, ModelHistoryError> {
+        match (settings.store, &settings.directory) {
+            (ModelHistoryStore::Directory, Some(directory)) => {
+                Ok(Self::Directory(directory.clone()))
+            }
+            // the settings validation ensures that a directory store has a directory
+            _ => redis
+                .map(|redis| Self::Redis(redis.clone()))
+                .ok_or(ModelHistoryError::MissingRedis),
+        }
+    }
+
+    /// Adds a global model together with its metadata to the history.
+    ///
+    /// A global model which was stored before for the same round is overwritten.
+    pub async fn add_model(
+        &self,
+        metadata: &ModelMetadata,
+        model: &Model,
+    ) -> Result<(), ModelHistoryError> {
+        match self {
+            Self::Redis(client) => client
+                .connection()
+                .await
+                .add_global_model(metadata, model)
+                .await
+                .map_err(Into::into),
+            Self::Directory(directory) => {
+                fs::create_dir_all(directory).await?;
+                // the metadata is written last, so that only complete entries are listed
+                let model_path = entry_path(directory, metadata.round_id, "model");
+                fs::write(model_path, bincode::serialize(model)?).await?;
+                let metadata_path = entry_path(directory, metadata.round_id, "meta");
+                fs::write(metadata_path, bincode::serialize(metadata)?).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Retrieves the global model of the given round or `None` if the history doesn't contain
+    /// that round.
+    pub async fn get_model(&self, round_id: u64) -> Result<Option<Model>, ModelHistoryError> {
+        match self {
+            Self::Redis(client) => client
+                .connection()
+                .await
+                .get_global_model(round_id)
+                .await
+                .map_err(Into::into),
+            Self::Directory(directory) => {
+                match fs::read(entry_path(directory, round_id, "model")).await {
+                    Ok(bytes) => Ok(Some(bincode::deserialize(&bytes)?)),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                    Err(err) => Err(err.into()),
+                }
+            }
+        }
+    }
+
+    /// Retrieves the global model of the latest round in the history or `None` if the history is
+    /// empty.
+    pub async fn get_latest_model(&self) -> Result<Option<Model>, ModelHistoryError> {
+        match self.list_models().await?.last() {
+            Some(metadata) => self.get_model(metadata.round_id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieves the metadata of all global models in the history, sorted by round.
+    pub async fn list_models(&self) -> Result<Vec<ModelMetadata>, ModelHistoryError> {
+        match self {
+            Self::Redis(client) => client
+                .connection()
+                .await
+                .get_model_history()
+                .await
+                .map_err(Into::into),
+            Self::Directory(directory) => {
+                let mut entries = match fs::read_dir(directory).await {
+                    Ok(entries) => entries,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+                    Err(err) => return Err(err.into()),
+                };
+                let mut history = Vec::new();
+                while let Some(entry) = entries.next_entry().await? {
+                    let path = entry.path();
+                    if path.extension().and_then(|ext| ext.to_str()) == Some("meta") {
+                        let bytes = fs::read(path).await?;
+                        history.push(bincode::deserialize::<ModelMetadata>(&bytes)?);
+                    }
+                }
+                history.sort_unstable_by_key(|metadata| metadata.round_id);
+                Ok(history)
+            }
+        }
+    }
+}
+
+/// Gets the path of the file with the given extension for the entry of the given round.
+fn entry_path(directory: &Path, round_id: u64, extension: &str) -> PathBuf {
+    directory.join(format!("{}.{}", round_id, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::utils::TempPath;
+    use xaynet_core::mask::FromPrimitives;
+
+    fn metadata(round_id: u64) -> ModelMetadata {
+        ModelMetadata {
+            round_id,
+            round_seed_hash: Sha256::hash(&round_id.to_le_bytes()),
+            nb_sum: 1,
+            nb_update: 3,
+            nb_sum2: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn directory_model_history() {
+        let directory = TempPath::new();
+        let history = ModelHistory::Directory(directory.to_path_buf());
+        assert!(history.list_models().await.unwrap().is_empty());
+        assert!(history.get_model(1).await.unwrap().is_none());
+
+        let models = [
+            Model::from_primitives(vec![1_i32, 2, 3].into_iter()).unwrap(),
+            Model::from_primitives(vec![4_i32, 5, 6].into_iter()).unwrap(),
+            Model::from_primitives(vec![7_i32, 8, 9].into_iter()).unwrap(),
+        ];
+        for (round_id, model) in [10, 2, 1].iter().zip(models.iter()) {
+            history
+                .add_model(&metadata(*round_id), model)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            history.list_models().await.unwrap(),
+            vec![metadata(1), metadata(2), metadata(10)]
+        );
+        assert_eq!(
+            history.get_model(10).await.unwrap().as_ref(),
+            Some(&models[0])
+        );
+        assert_eq!(
+            history.get_model(1).await.unwrap().as_ref(),
+            Some(&models[2])
+        );
+        assert!(history.get_model(3).await.unwrap().is_none());
+
+        // a model of the same round is overwritten
+        history.add_model(&metadata(2), &models[0]).await.unwrap();
+        assert_eq!(history.list_models().await.unwrap().len(), 3);
+        assert_eq!(
+            history.get_model(2).await.unwrap().as_ref(),
+            Some(&models[0])
+        );
+    }
+}
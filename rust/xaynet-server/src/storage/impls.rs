@@ -1,10 +1,17 @@
-use crate::state_machine::coordinator::CoordinatorState;
+use crate::{
+    state_machine::{
+        coordinator::CoordinatorState,
+        optimizer::OptimizerState,
+        phases::UnmaskAudit,
+    },
+    storage::history::ModelMetadata,
+};
 use derive_more::{From, Into};
 use paste::paste;
 use redis::{ErrorKind, FromRedisValue, RedisError, RedisResult, RedisWrite, ToRedisArgs, Value};
 use xaynet_core::{
-    crypto::{ByteObject, PublicEncryptKey, PublicSigningKey},
-    mask::{EncryptedMaskSeed, MaskObject},
+    crypto::{ByteObject, PublicEncryptKey, PublicSigningKey, Sha256},
+    mask::{Aggregation, EncryptedMaskSeed, MaskObject, Model},
 };
 
 fn redis_type_error(desc: &'static str, details: Option<String>) -> RedisError {
@@ -105,6 +112,7 @@ macro_rules! impl_byte_object_redis_traits {
 impl_byte_object_redis_traits!(PublicEncryptKey);
 impl_byte_object_redis_traits!(PublicSigningKey);
 impl_byte_object_redis_traits!(EncryptedMaskSeed);
+impl_byte_object_redis_traits!(Sha256);
 
 /// Implements ['FromRedisValue'] and ['ToRedisArgs'] for types that implement
 /// ['Serialize`] and [`Deserialize']. The data is de/serialized via bincode.
@@ -160,6 +168,16 @@ macro_rules! impl_bincode_redis_traits {
 // so bincode will not panic.
 impl_bincode_redis_traits!(CoordinatorState);
 
+// UnmaskAudit only contains fixed-size types and a sequence of known length, so bincode will
+// not panic.
+impl_bincode_redis_traits!(UnmaskAudit);
+
+// ModelMetadata only contains fixed-size types, so bincode will not panic.
+impl_bincode_redis_traits!(ModelMetadata);
+
+// OptimizerState only contains sequences of known length, so bincode will not panic.
+impl_bincode_redis_traits!(OptimizerState);
+
 #[derive(From, Into, Serialize, Deserialize)]
 pub(crate) struct MaskObjectRead(MaskObject);
 
@@ -187,6 +205,60 @@ impl<'a> ToRedisArgs for &'a MaskObjectWrite<'a> {
     }
 }
 
+#[derive(From, Into, Serialize, Deserialize)]
+pub(crate) struct AggregationRead(Aggregation);
+
+impl_bincode_redis_traits!(AggregationRead);
+
+#[derive(From, Serialize)]
+pub(crate) struct AggregationWrite<'a>(&'a Aggregation);
+
+impl ToRedisArgs for AggregationWrite<'_> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let data = bincode::serialize(self).unwrap();
+        data.write_redis_args(out)
+    }
+}
+
+impl<'a> ToRedisArgs for &'a AggregationWrite<'a> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        (*self).write_redis_args(out)
+    }
+}
+
+#[derive(From, Into, Serialize, Deserialize)]
+pub(crate) struct ModelRead(Model);
+
+impl_bincode_redis_traits!(ModelRead);
+
+#[derive(From, Serialize)]
+pub(crate) struct ModelWrite<'a>(&'a Model);
+
+impl ToRedisArgs for ModelWrite<'_> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let data = bincode::serialize(self).unwrap();
+        data.write_redis_args(out)
+    }
+}
+
+impl<'a> ToRedisArgs for &'a ModelWrite<'a> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        (*self).write_redis_args(out)
+    }
+}
+
 #[derive(Ord, PartialOrd, Eq, PartialEq, Debug)]
 pub enum AddSumParticipant {
     Ok,
@@ -1,4 +1,9 @@
+pub mod allowlist;
+pub mod history;
 pub(crate) mod impls;
 pub mod redis;
 
+#[cfg(test)]
+pub(crate) mod tests;
+
 pub use self::impls::AddSumParticipant;
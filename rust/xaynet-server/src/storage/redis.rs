@@ -22,37 +22,90 @@
 //!         "UpdateParticipantPublicKey_1": EncryptedMaskSeed,
 //!         "UpdateParticipantPublicKey_2": EncryptedMaskSeed
 //!     }
+//!     // Masked objects of the update phase
+//!     "masked_models": { // hash
+//!         "UpdateParticipantPublicKey_1": "...", // bincode encoded string
+//!         "UpdateParticipantPublicKey_2": "..."
+//!     },
+//!     "masked_scalars": { // hash
+//!         "UpdateParticipantPublicKey_1": "...", // bincode encoded string
+//!         "UpdateParticipantPublicKey_2": "..."
+//!     },
+//!     // Aggregations, stored at the end of the update phase
+//!     "model_agg": "...", // bincode encoded string
+//!     "scalar_agg": "...", // bincode encoded string
 //!     // Mask dict
+//!     "sum2_participants": { // hash
+//!         "SumParticipantPublicKey_1": Sha256, // digest of the submitted masks
+//!         "SumParticipantPublicKey_2": Sha256
+//!     },
 //!     "mask_dict": [ // sorted set
 //!         (mask_object_1, 12341), // (mask: bincode encoded string, score/counter: number)
 //!         (mask_object_2, 1)
+//!     ],
+//!     "scalar_mask_dict": [ // sorted set
+//!         (mask_object_1, 12341),
+//!         (mask_object_2, 1)
+//!     ],
+//!     // Unmask audits
+//!     "unmask_audits": [ // list
+//!         unmask_audit_1, // bincode encoded string
+//!         unmask_audit_2
+//!     ],
+//!     // Model history
+//!     "model_history": { // hash
+//!         "1": model_metadata_1, // (round id, metadata: bincode encoded string)
+//!         "2": model_metadata_2
+//!     },
+//!     "global_model:1": "...", // bincode encoded string
+//!     "global_model:2": "...",
+//!     // Participant allowlist
+//!     "allowlist": [ // set
+//!         ParticipantPublicKey_1,
+//!         ParticipantPublicKey_2
 //!     ]
 //! }
 //! ```
 use crate::{
-    state_machine::coordinator::CoordinatorState,
-    storage::impls::{
-        AddSumParticipant,
-        DeleteSumParticipant,
-        EncryptedMaskSeedRead,
-        EncryptedMaskSeedWrite,
-        MaskObjectRead,
-        MaskObjectWrite,
-        PublicEncryptKeyRead,
-        PublicEncryptKeyWrite,
-        PublicSigningKeyRead,
-        PublicSigningKeyWrite,
+    state_machine::{
+        coordinator::{CoordinatorState, MaskDict, MaskSubmissions},
+        optimizer::OptimizerState,
+        phases::UnmaskAudit,
+    },
+    storage::{
+        history::ModelMetadata,
+        impls::{
+            AddSumParticipant,
+            AggregationRead,
+            AggregationWrite,
+            DeleteSumParticipant,
+            EncryptedMaskSeedRead,
+            EncryptedMaskSeedWrite,
+            MaskObjectRead,
+            MaskObjectWrite,
+            ModelRead,
+            ModelWrite,
+            PublicEncryptKeyRead,
+            PublicEncryptKeyWrite,
+            PublicSigningKeyRead,
+            PublicSigningKeyWrite,
+            Sha256Read,
+            Sha256Write,
+        },
     },
 };
 use redis::{aio::ConnectionManager, AsyncCommands, IntoConnectionInfo, RedisError, RedisResult};
 use std::{
     collections::{HashMap, HashSet},
+    fmt,
     sync::Arc,
 };
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use xaynet_core::{
-    mask::{EncryptedMaskSeed, MaskObject},
+    crypto::Sha256,
+    mask::{Aggregation, EncryptedMaskSeed, MaskObject, Model},
     LocalSeedDict,
+    ParticipantPublicKey,
     SeedDict,
     SumDict,
     SumParticipantEphemeralPublicKey,
@@ -66,9 +119,6 @@ pub struct Client {
     semaphore: Arc<Semaphore>,
 }
 
-#[cfg(test)]
-use std::fmt;
-#[cfg(test)]
 impl fmt::Debug for Client {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Client")
@@ -120,9 +170,7 @@ impl Client {
 
 impl Connection {
     /// Retrieves a [`CoordinatorState`] or `None` when the [`CoordinatorState`] does not exist.
-    // currently only used for testing but later required for restoring the coordinator
-    #[allow(dead_code)]
-    async fn get_coordinator_state(mut self) -> RedisResult<Option<CoordinatorState>> {
+    pub async fn get_coordinator_state(mut self) -> RedisResult<Option<CoordinatorState>> {
         debug!("get coordinator state");
         // https://redis.io/commands/get
         // > Get the value of key. If the key does not exist the special value nil is returned.
@@ -369,6 +417,215 @@ impl Connection {
         pipe.atomic().query_async(&mut self.connection).await
     }
 
+    /// Updates the [`SeedDict`] with the seeds from the given ['UpdateParticipantPublicKey'] and
+    /// stores its masked model and masked scalar.
+    ///
+    /// The seeds and the masked objects are written in a single transaction, so that the stored
+    /// masked objects always belong to exactly the stored update participants. Only the masked
+    /// objects of the participant are written, the aggregations are stored once at the end of the
+    /// update phase via [`Connection::set_aggregations`]. See [`Connection::update_seed_dict`] for
+    /// the caveats of Redis transactions.
+    pub async fn update_seed_dict_and_masked_objects(
+        mut self,
+        update_pk: &UpdateParticipantPublicKey,
+        update: &LocalSeedDict,
+        masked_model: &MaskObject,
+        masked_scalar: &MaskObject,
+    ) -> RedisResult<()> {
+        debug!(
+            "update seed dictionary and masked objects for update participant with pk {:?}",
+            update_pk
+        );
+        let mut pipe = redis::pipe();
+
+        pipe.sadd(
+            "update_participants",
+            PublicSigningKeyWrite::from(update_pk),
+        )
+        .ignore();
+        for (sum_pk, encr_seed) in update {
+            pipe.hset_nx(
+                PublicSigningKeyWrite::from(sum_pk),
+                PublicSigningKeyWrite::from(update_pk),
+                EncryptedMaskSeedWrite::from(encr_seed),
+            )
+            .ignore();
+        }
+
+        // https://redis.io/commands/hset
+        // > If the key does not exist, a new key holding a hash is created.
+        //   If field already exists in the hash, it is overwritten.
+        pipe.hset(
+            "masked_models",
+            PublicSigningKeyWrite::from(update_pk),
+            MaskObjectWrite::from(masked_model),
+        )
+        .ignore();
+        pipe.hset(
+            "masked_scalars",
+            PublicSigningKeyWrite::from(update_pk),
+            MaskObjectWrite::from(masked_scalar),
+        )
+        .ignore();
+        pipe.atomic().query_async(&mut self.connection).await
+    }
+
+    /// Retrieves the masked models and masked scalars of the update participants which have not
+    /// been aggregated into the stored aggregations yet.
+    pub async fn get_masked_objects(mut self) -> RedisResult<Vec<(MaskObject, MaskObject)>> {
+        debug!("get masked objects");
+        // https://redis.io/commands/hgetall
+        // > Return value
+        //   Array reply: list of fields and their values stored in the hash, or an empty
+        //   list when key does not exist.
+        let masked_models: HashMap<PublicSigningKeyRead, MaskObjectRead> =
+            self.connection.hgetall("masked_models").await?;
+        let mut masked_scalars: HashMap<PublicSigningKeyRead, MaskObjectRead> =
+            self.connection.hgetall("masked_scalars").await?;
+
+        // both hashes are written in the same transaction, hence they have the same fields
+        Ok(masked_models
+            .into_iter()
+            .filter_map(|(pk, masked_model)| {
+                masked_scalars
+                    .remove(&pk)
+                    .map(|masked_scalar| (masked_model.into(), masked_scalar.into()))
+            })
+            .collect())
+    }
+
+    /// Stores the model and scalar aggregations and deletes the masked objects which they
+    /// aggregate.
+    pub async fn set_aggregations(
+        mut self,
+        model_agg: &Aggregation,
+        scalar_agg: &Aggregation,
+    ) -> RedisResult<()> {
+        debug!("set aggregations");
+        // https://redis.io/commands/set
+        // > Set key to hold the string value. If key already holds a value,
+        //   it is overwritten, regardless of its type.
+        redis::pipe()
+            .set("model_agg", AggregationWrite::from(model_agg))
+            .ignore()
+            .set("scalar_agg", AggregationWrite::from(scalar_agg))
+            .ignore()
+            .del("masked_models")
+            .ignore()
+            .del("masked_scalars")
+            .ignore()
+            .atomic()
+            .query_async(&mut self.connection)
+            .await
+    }
+
+    /// Retrieves the model and scalar aggregations or `None` when no update has been stored yet.
+    pub async fn get_aggregations(mut self) -> RedisResult<Option<(Aggregation, Aggregation)>> {
+        debug!("get aggregations");
+        // https://redis.io/commands/mget
+        // > Return value
+        //   Array reply: list of values at the specified keys.
+        //   For every key that does not hold a string value or does not exist,
+        //   the special value nil is returned.
+        let result: (Option<AggregationRead>, Option<AggregationRead>) =
+            self.connection.get(&["model_agg", "scalar_agg"]).await?;
+
+        Ok(match result {
+            (Some(model_agg), Some(scalar_agg)) => Some((model_agg.into(), scalar_agg.into())),
+            _ => None,
+        })
+    }
+
+    /// Records the masks sent by the given sum participant.
+    ///
+    /// The participant is added to the sum2 participants together with the `digest` of its masks
+    /// and the scores/counters of the given model mask and scalar mask are incremented by `1`.
+    /// All of this happens in a single transaction.
+    pub async fn add_masks(
+        mut self,
+        sum_pk: &SumParticipantPublicKey,
+        model_mask: &MaskObject,
+        scalar_mask: &MaskObject,
+        digest: &Sha256,
+    ) -> RedisResult<()> {
+        debug!("add masks of sum participant with pk {:?}", sum_pk);
+        let mut pipe = redis::pipe();
+        pipe.hset(
+            "sum2_participants",
+            PublicSigningKeyWrite::from(sum_pk),
+            Sha256Write::from(digest),
+        )
+        .ignore();
+        pipe.zincr("mask_dict", MaskObjectWrite::from(model_mask), 1_usize)
+            .ignore();
+        pipe.zincr(
+            "scalar_mask_dict",
+            MaskObjectWrite::from(scalar_mask),
+            1_usize,
+        )
+        .ignore();
+        pipe.atomic().query_async(&mut self.connection).await
+    }
+
+    /// Retrieves the [`SumParticipantPublicKey`]s of the sum participants that already sent
+    /// their masks.
+    pub async fn get_sum2_pks(mut self) -> RedisResult<HashSet<SumParticipantPublicKey>> {
+        debug!("get public keys of all sum2 participants");
+        // https://redis.io/commands/hkeys
+        // > Return value:
+        //   Array reply: list of fields in the hash, or an empty list when key does not exist.
+        let result: HashSet<PublicSigningKeyRead> =
+            self.connection.hkeys("sum2_participants").await?;
+        let sum2_pks = result.into_iter().map(|pk| pk.into()).collect();
+
+        Ok(sum2_pks)
+    }
+
+    /// Retrieves the digests of the masks submitted by the sum2 participants.
+    pub async fn get_mask_submissions(mut self) -> RedisResult<MaskSubmissions> {
+        debug!("get mask submissions");
+        // https://redis.io/commands/hgetall
+        // > Return value
+        //   Array reply: list of fields and their values stored in the hash, or an empty
+        //   list when key does not exist.
+        let result: Vec<(PublicSigningKeyRead, Sha256Read)> =
+            self.connection.hgetall("sum2_participants").await?;
+        let submissions = result
+            .into_iter()
+            .map(|(pk, digest)| (pk.into(), digest.into()))
+            .collect();
+
+        Ok(submissions)
+    }
+
+    /// Retrieves the model mask dictionary and the scalar mask dictionary.
+    pub async fn get_mask_dicts(mut self) -> RedisResult<(MaskDict, MaskDict)> {
+        debug!("get mask dictionaries");
+        // https://redis.io/commands/zrange
+        // > Return value
+        //   Array reply: list of elements in the specified range (optionally with their scores,
+        //   in case the WITHSCORES option is given).
+        let model_masks: Vec<(MaskObjectRead, usize)> = self
+            .connection
+            .zrange_withscores("mask_dict", 0, -1)
+            .await?;
+        let scalar_masks: Vec<(MaskObjectRead, usize)> = self
+            .connection
+            .zrange_withscores("scalar_mask_dict", 0, -1)
+            .await?;
+
+        Ok((
+            model_masks
+                .into_iter()
+                .map(|(mask, count)| (mask.into(), count))
+                .collect(),
+            scalar_masks
+                .into_iter()
+                .map(|(mask, count)| (mask.into(), count))
+                .collect(),
+        ))
+    }
+
     /// Updates the mask dictionary with the given [`MaskObject`].
     ///
     /// The score/counter of the given mask is incremented by `1`.
@@ -405,6 +662,152 @@ impl Connection {
             .collect())
     }
 
+    /// Appends an unmask audit to the list of unmask audits.
+    ///
+    /// The audits are kept across rounds, i.e. they are not deleted by
+    /// [`Connection::flush_dicts()`].
+    pub async fn add_unmask_audit(mut self, audit: &UnmaskAudit) -> RedisResult<()> {
+        debug!("add unmask audit of round {}", audit.round_id);
+        // https://redis.io/commands/rpush
+        // > Return value
+        //   Integer reply: the length of the list after the push operation.
+        //
+        // We ignore the return value because we are not interested in it.
+        let _: usize = self.connection.rpush("unmask_audits", audit).await?;
+        Ok(())
+    }
+
+    /// Retrieves all unmask audits in the order in which they were added.
+    pub async fn get_unmask_audits(mut self) -> RedisResult<Vec<UnmaskAudit>> {
+        debug!("get unmask audits");
+        // https://redis.io/commands/lrange
+        // > Return value
+        //   Array reply: list of elements in the specified range.
+        self.connection.lrange("unmask_audits", 0, -1).await
+    }
+
+    /// Adds a global model together with its metadata to the model history.
+    ///
+    /// A global model which was stored before for the same round is overwritten.
+    pub async fn add_global_model(
+        mut self,
+        metadata: &ModelMetadata,
+        model: &Model,
+    ) -> RedisResult<()> {
+        debug!("add global model of round {}", metadata.round_id);
+        let mut pipe = redis::pipe();
+        // https://redis.io/commands/set
+        // > Set key to hold the string value. If key already holds a value,
+        //   it is overwritten, regardless of its type.
+        pipe.set(global_model_key(metadata.round_id), ModelWrite::from(model))
+            .ignore();
+        // https://redis.io/commands/hset
+        // > Sets field in the hash stored at key to value. If field already exists in the hash,
+        //   it is overwritten.
+        pipe.hset("model_history", metadata.round_id, metadata)
+            .ignore();
+        pipe.atomic().query_async(&mut self.connection).await
+    }
+
+    /// Retrieves the global model of the given round or `None` when the model history doesn't
+    /// contain that round.
+    pub async fn get_global_model(mut self, round_id: u64) -> RedisResult<Option<Model>> {
+        debug!("get global model of round {}", round_id);
+        // https://redis.io/commands/get
+        // > Return value
+        //   Bulk string reply: the value of key, or nil when key does not exist.
+        let model: Option<ModelRead> = self.connection.get(global_model_key(round_id)).await?;
+        Ok(model.map(Into::into))
+    }
+
+    /// Retrieves the metadata of all global models in the model history, sorted by round.
+    pub async fn get_model_history(mut self) -> RedisResult<Vec<ModelMetadata>> {
+        debug!("get model history");
+        // https://redis.io/commands/hvals
+        // > Return value
+        //   Array reply: list of values in the hash, or an empty list when key does not exist.
+        let mut history: Vec<ModelMetadata> = self.connection.hvals("model_history").await?;
+        history.sort_unstable_by_key(|metadata| metadata.round_id);
+        Ok(history)
+    }
+
+    /// Stores the state of the server optimizer.
+    ///
+    /// If the state already exists, it is overwritten.
+    pub async fn set_optimizer_state(mut self, state: &OptimizerState) -> RedisResult<()> {
+        debug!("set optimizer state");
+        // https://redis.io/commands/set
+        // > Set key to hold the string value. If key already holds a value,
+        //   it is overwritten, regardless of its type.
+        self.connection.set("optimizer_state", state).await
+    }
+
+    /// Retrieves the state of the server optimizer or `None` when it does not exist.
+    pub async fn get_optimizer_state(mut self) -> RedisResult<Option<OptimizerState>> {
+        debug!("get optimizer state");
+        // https://redis.io/commands/get
+        // > Return value
+        //   Bulk string reply: the value of key, or nil when key does not exist.
+        self.connection.get("optimizer_state").await
+    }
+
+    /// Adds a participant to the allowlist.
+    ///
+    /// Returns `false` if the participant was already on the allowlist.
+    pub async fn add_allowed_participant(mut self, pk: &ParticipantPublicKey) -> RedisResult<bool> {
+        debug!("add participant to the allowlist");
+        // https://redis.io/commands/sadd
+        // > Return value
+        //   Integer reply: the number of elements that were added to the set,
+        //   not including all the elements already present into the set.
+        let added: usize = self
+            .connection
+            .sadd("allowlist", PublicSigningKeyWrite::from(pk))
+            .await?;
+        Ok(added == 1)
+    }
+
+    /// Removes a participant from the allowlist.
+    ///
+    /// Returns `false` if the participant was not on the allowlist.
+    pub async fn remove_allowed_participant(
+        mut self,
+        pk: &ParticipantPublicKey,
+    ) -> RedisResult<bool> {
+        debug!("remove participant from the allowlist");
+        // https://redis.io/commands/srem
+        // > Return value
+        //   Integer reply: the number of members that were removed from the set,
+        //   not including non existing members.
+        let removed: usize = self
+            .connection
+            .srem("allowlist", PublicSigningKeyWrite::from(pk))
+            .await?;
+        Ok(removed == 1)
+    }
+
+    /// Checks whether a participant is on the allowlist.
+    pub async fn is_allowed_participant(mut self, pk: &ParticipantPublicKey) -> RedisResult<bool> {
+        debug!("check whether participant is on the allowlist");
+        // https://redis.io/commands/sismember
+        // > Return value
+        //   Integer reply: 1 if the element is a member of the set.
+        //   0 if the element is not a member of the set, or if key does not exist.
+        self.connection
+            .sismember("allowlist", PublicSigningKeyWrite::from(pk))
+            .await
+    }
+
+    /// Retrieves all participants on the allowlist.
+    pub async fn get_allowlist(mut self) -> RedisResult<HashSet<ParticipantPublicKey>> {
+        debug!("get allowlist");
+        // https://redis.io/commands/smembers
+        // > Return value
+        //   Array reply: all elements of the set.
+        let pks: Vec<PublicSigningKeyRead> = self.connection.smembers("allowlist").await?;
+        Ok(pks.into_iter().map(Into::into).collect())
+    }
+
     /// Deletes all data in the current database.
     pub async fn flush_db(mut self) -> RedisResult<()> {
         debug!("flush current database");
@@ -416,7 +819,8 @@ impl Connection {
             .await
     }
 
-    /// Deletes the dictionaries [`SumDict`], [`SeedDict`] and mask dictionary.
+    /// Deletes the dictionaries [`SumDict`], [`SeedDict`] and mask dictionaries as well as the
+    /// aggregations.
     pub async fn flush_dicts(mut self) -> RedisResult<()> {
         debug!("flush all dictionaries");
         // https://redis.io/commands/hkeys
@@ -441,8 +845,16 @@ impl Connection {
             pipe.del(sum_pk).ignore();
         }
 
-        //delete mask dict
+        //delete aggregations
+        pipe.del("model_agg").ignore();
+        pipe.del("scalar_agg").ignore();
+        pipe.del("masked_models").ignore();
+        pipe.del("masked_scalars").ignore();
+
+        //delete mask dicts
+        pipe.del("sum2_participants").ignore();
         pipe.del("mask_dict").ignore();
+        pipe.del("scalar_mask_dict").ignore();
         pipe.atomic().query_async(&mut self.connection).await
     }
 
@@ -454,15 +866,23 @@ impl Connection {
     }
 }
 
+/// Gets the key under which the global model of the given round is stored.
+fn global_model_key(round_id: u64) -> String {
+    format!("global_model:{}", round_id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::state_machine::tests::utils::{mask_settings, model_settings, pet_settings};
+    use crate::state_machine::{
+        coordinator::mask_digest,
+        tests::utils::{mask_settings, model_settings, pet_settings},
+    };
     use num::{bigint::BigUint, traits::identities::Zero};
     use serial_test::serial;
     use xaynet_core::{
-        crypto::{EncryptKeyPair, SigningKeyPair},
-        mask::{BoundType, DataType, GroupType, MaskConfig, MaskObject, ModelType},
+        crypto::{ByteObject, EncryptKeyPair, SigningKeyPair},
+        mask::{BoundType, DataType, FromPrimitives, GroupType, MaskConfig, MaskObject, ModelType},
     };
 
     fn create_mask(byte_size: usize) -> MaskObject {
@@ -658,6 +1078,340 @@ mod tests {
         assert_eq!(sum_dict.len(), 0);
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn integration_aggregations() {
+        // test the writing and reading of the masked objects together with the seed dict and
+        // their replacement by the aggregations
+        let client = init_client().await;
+
+        let aggregations = client.connection().await.get_aggregations().await.unwrap();
+        assert!(aggregations.is_none());
+
+        let SigningKeyPair { public: sum_pk, .. } = SigningKeyPair::generate();
+        let EncryptKeyPair { public: epk, .. } = EncryptKeyPair::generate();
+        client
+            .connection()
+            .await
+            .add_sum_participant(&sum_pk, &epk)
+            .await
+            .unwrap();
+
+        let SigningKeyPair {
+            public: update_pk, ..
+        } = SigningKeyPair::generate();
+        let mut local_seed_dict = LocalSeedDict::new();
+        local_seed_dict.insert(sum_pk, EncryptedMaskSeed::zeroed());
+
+        let masked_model = create_mask(10);
+        let masked_scalar = create_mask(1);
+        client
+            .connection()
+            .await
+            .update_seed_dict_and_masked_objects(
+                &update_pk,
+                &local_seed_dict,
+                &masked_model,
+                &masked_scalar,
+            )
+            .await
+            .unwrap();
+
+        let seed_dict = client.connection().await.get_seed_dict().await.unwrap();
+        assert_eq!(
+            seed_dict.get(&sum_pk).unwrap().get(&update_pk).unwrap(),
+            &EncryptedMaskSeed::zeroed()
+        );
+        let masked_objects = client
+            .connection()
+            .await
+            .get_masked_objects()
+            .await
+            .unwrap();
+        assert_eq!(
+            masked_objects,
+            vec![(masked_model.clone(), masked_scalar.clone())]
+        );
+
+        let mut model_agg = Aggregation::new(masked_model.config, 10);
+        model_agg.aggregate(masked_model);
+        let mut scalar_agg = Aggregation::new(masked_scalar.config, 1);
+        scalar_agg.aggregate(masked_scalar);
+        client
+            .connection()
+            .await
+            .set_aggregations(&model_agg, &scalar_agg)
+            .await
+            .unwrap();
+
+        // the aggregated masked objects are deleted
+        let masked_objects = client
+            .connection()
+            .await
+            .get_masked_objects()
+            .await
+            .unwrap();
+        assert!(masked_objects.is_empty());
+        let (get_model_agg, get_scalar_agg) = client
+            .connection()
+            .await
+            .get_aggregations()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Into::<MaskObject>::into(get_model_agg),
+            Into::<MaskObject>::into(model_agg)
+        );
+        assert_eq!(
+            Into::<MaskObject>::into(get_scalar_agg),
+            Into::<MaskObject>::into(scalar_agg)
+        );
+
+        // ensure that flush_dicts removes the aggregations
+        client.connection().await.flush_dicts().await.unwrap();
+        let aggregations = client.connection().await.get_aggregations().await.unwrap();
+        assert!(aggregations.is_none());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn integration_add_masks() {
+        // test the writing and reading of the mask dictionaries
+        let client = init_client().await;
+
+        let model_mask = create_mask(10);
+        let scalar_mask = create_mask(1);
+        let digest = mask_digest(&model_mask, &scalar_mask);
+        let mut sum_pks = HashSet::new();
+        for _ in 0..2 {
+            let SigningKeyPair { public: pk, .. } = SigningKeyPair::generate();
+            sum_pks.insert(pk);
+            client
+                .connection()
+                .await
+                .add_masks(&pk, &model_mask, &scalar_mask, &digest)
+                .await
+                .unwrap();
+        }
+
+        let sum2_pks = client.connection().await.get_sum2_pks().await.unwrap();
+        assert_eq!(sum2_pks, sum_pks);
+
+        let submissions = client
+            .connection()
+            .await
+            .get_mask_submissions()
+            .await
+            .unwrap();
+        assert_eq!(submissions.len(), 2);
+        assert!(submissions
+            .iter()
+            .all(|(pk, submitted)| sum_pks.contains(pk) && submitted == &digest));
+
+        let (model_mask_dict, scalar_mask_dict) =
+            client.connection().await.get_mask_dicts().await.unwrap();
+        assert_eq!(model_mask_dict.len(), 1);
+        assert_eq!(model_mask_dict.get(&model_mask), Some(&2));
+        assert_eq!(scalar_mask_dict.len(), 1);
+        assert_eq!(scalar_mask_dict.get(&scalar_mask), Some(&2));
+
+        // ensure that flush_dicts removes the mask dictionaries
+        client.connection().await.flush_dicts().await.unwrap();
+        let sum2_pks = client.connection().await.get_sum2_pks().await.unwrap();
+        assert!(sum2_pks.is_empty());
+        let (model_mask_dict, scalar_mask_dict) =
+            client.connection().await.get_mask_dicts().await.unwrap();
+        assert!(model_mask_dict.is_empty());
+        assert!(scalar_mask_dict.is_empty());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn integration_add_unmask_audits() {
+        // test the writing and reading of unmask audits
+        let client = init_client().await;
+
+        let audits = vec![
+            UnmaskAudit {
+                round_id: 1,
+                nb_submissions: 3,
+                rejected: vec![SigningKeyPair::generate().public],
+            },
+            UnmaskAudit {
+                round_id: 2,
+                nb_submissions: 2,
+                rejected: Vec::new(),
+            },
+        ];
+        for audit in audits.iter() {
+            client
+                .connection()
+                .await
+                .add_unmask_audit(audit)
+                .await
+                .unwrap();
+        }
+
+        let get_audits = client.connection().await.get_unmask_audits().await.unwrap();
+        assert_eq!(get_audits, audits);
+
+        // ensure that flush_dicts keeps the audits
+        client.connection().await.flush_dicts().await.unwrap();
+        let get_audits = client.connection().await.get_unmask_audits().await.unwrap();
+        assert_eq!(get_audits, audits);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn integration_model_history() {
+        // test the writing and reading of global models and their metadata
+        let client = init_client().await;
+
+        let history = client.connection().await.get_model_history().await.unwrap();
+        assert!(history.is_empty());
+        let model = client.connection().await.get_global_model(1).await.unwrap();
+        assert!(model.is_none());
+
+        let models = [
+            Model::from_primitives(vec![1_i32, 2, 3].into_iter()).unwrap(),
+            Model::from_primitives(vec![4_i32, 5, 6].into_iter()).unwrap(),
+        ];
+        let history = vec![
+            ModelMetadata {
+                round_id: 1,
+                round_seed_hash: Sha256::hash(&[1]),
+                nb_sum: 1,
+                nb_update: 3,
+                nb_sum2: 1,
+            },
+            ModelMetadata {
+                round_id: 2,
+                round_seed_hash: Sha256::hash(&[2]),
+                nb_sum: 2,
+                nb_update: 4,
+                nb_sum2: 2,
+            },
+        ];
+        for (metadata, model) in history.iter().zip(models.iter()).rev() {
+            client
+                .connection()
+                .await
+                .add_global_model(metadata, model)
+                .await
+                .unwrap();
+        }
+
+        let get_history = client.connection().await.get_model_history().await.unwrap();
+        assert_eq!(get_history, history);
+        let get_model = client.connection().await.get_global_model(1).await.unwrap();
+        assert_eq!(get_model.as_ref(), Some(&models[0]));
+
+        // ensure that flush_dicts keeps the model history
+        client.connection().await.flush_dicts().await.unwrap();
+        let get_history = client.connection().await.get_model_history().await.unwrap();
+        assert_eq!(get_history, history);
+        let get_model = client.connection().await.get_global_model(2).await.unwrap();
+        assert_eq!(get_model.as_ref(), Some(&models[1]));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn integration_optimizer_state() {
+        // test the writing and reading of the optimizer state
+        let client = init_client().await;
+
+        let state = client
+            .connection()
+            .await
+            .get_optimizer_state()
+            .await
+            .unwrap();
+        assert!(state.is_none());
+
+        let state = OptimizerState::new(vec![1., 2., 3.], 0.1);
+        client
+            .connection()
+            .await
+            .set_optimizer_state(&state)
+            .await
+            .unwrap();
+
+        // ensure that flush_dicts keeps the optimizer state
+        client.connection().await.flush_dicts().await.unwrap();
+        let get_state = client
+            .connection()
+            .await
+            .get_optimizer_state()
+            .await
+            .unwrap();
+        assert_eq!(get_state, Some(state));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn integration_allowlist() {
+        // test the adding, checking and removing of allowed participants
+        let client = init_client().await;
+
+        let allowlist = client.connection().await.get_allowlist().await.unwrap();
+        assert!(allowlist.is_empty());
+
+        let pk_1 = SigningKeyPair::generate().public;
+        let pk_2 = SigningKeyPair::generate().public;
+        for pk in [pk_1, pk_2].iter() {
+            let added = client
+                .connection()
+                .await
+                .add_allowed_participant(pk)
+                .await
+                .unwrap();
+            assert!(added);
+        }
+        let added = client
+            .connection()
+            .await
+            .add_allowed_participant(&pk_1)
+            .await
+            .unwrap();
+        assert!(!added);
+
+        let allowlist = client.connection().await.get_allowlist().await.unwrap();
+        assert_eq!(allowlist, [pk_1, pk_2].iter().cloned().collect());
+
+        // ensure that flush_dicts keeps the allowlist
+        client.connection().await.flush_dicts().await.unwrap();
+        let allowed = client
+            .connection()
+            .await
+            .is_allowed_participant(&pk_1)
+            .await
+            .unwrap();
+        assert!(allowed);
+
+        let removed = client
+            .connection()
+            .await
+            .remove_allowed_participant(&pk_1)
+            .await
+            .unwrap();
+        assert!(removed);
+        let removed = client
+            .connection()
+            .await
+            .remove_allowed_participant(&pk_1)
+            .await
+            .unwrap();
+        assert!(!removed);
+        let allowed = client
+            .connection()
+            .await
+            .is_allowed_participant(&pk_1)
+            .await
+            .unwrap();
+        assert!(!allowed);
+    }
+
     #[tokio::test]
     #[serial]
     async fn integration_flush_dicts_return() {
@@ -0,0 +1,39 @@
+use std::{fs, ops::Deref, path::PathBuf};
+
+/// A unique path in the temporary directory, which is removed when it's dropped.
+///
+/// Neither a file nor a directory is created at the path. Whatever is created there by a test is
+/// removed even if the test fails.
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    /// Creates a unique temporary path.
+    pub fn new() -> Self {
+        Self(std::env::temp_dir().join(uuid::Uuid::new_v4().to_string()))
+    }
+
+    /// Creates a unique temporary path with the given extension.
+    pub fn with_extension(extension: &str) -> Self {
+        let mut path = Self::new();
+        path.0.set_extension(extension);
+        path
+    }
+}
+
+impl Deref for TempPath {
+    type Target = PathBuf;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = fs::remove_dir_all(&self.0);
+        } else {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+}